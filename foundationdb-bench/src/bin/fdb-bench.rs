@@ -39,7 +39,7 @@ impl Counter {
 
 #[derive(Clone)]
 struct Bench {
-    db: Arc<Database>,
+    db: Database,
     opt: Opt,
 }
 
@@ -147,10 +147,8 @@ fn main() {
     info!("opt: {:?}", opt);
 
     let _guard = unsafe { foundationdb::boot() };
-    let db = Arc::new(
-        futures::executor::block_on(fdb::Database::new_compat(None))
-            .expect("failed to get database"),
-    );
+    let db = futures::executor::block_on(fdb::Database::new_compat(None))
+        .expect("failed to get database");
 
     let bench = Bench { db, opt };
     bench.run();
@@ -2,21 +2,26 @@
 extern crate log;
 
 use foundationdb as fdb;
-use foundationdb_sys as fdb_sys;
 
 use std::borrow::Cow;
 use std::collections::HashMap;
 use std::convert::TryFrom;
 use std::io::Write;
+use std::panic::AssertUnwindSafe;
 use std::pin::Pin;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::Arc;
 use std::thread;
 
+use fdb::directory::{Directory, DirectoryError, DirectoryLayer, DirectoryOutput};
 use fdb::options::{ConflictRangeType, DatabaseOption, TransactionOption};
 use fdb::tuple::{pack, pack_into, unpack, Bytes, Element, Subspace, TuplePack};
 use fdb::*;
 use futures::future;
 use futures::prelude::*;
+use structopt::clap;
+use structopt::StructOpt;
 
 static WAITED_FOR_EMPTY: Element = Element::Bytes(Bytes(Cow::Borrowed(b"WAITED_FOR_EMPTY")));
 static RESULT_NOT_PRESENT: Element = Element::Bytes(Bytes(Cow::Borrowed(b"RESULT_NOT_PRESENT")));
@@ -27,50 +32,38 @@ static GOT_COMMITTED_VERSION: Element =
 static ERROR_NONE: Element = Element::Bytes(Bytes(Cow::Borrowed(b"ERROR: NONE")));
 static ERROR_MULTIPLE: Element = Element::Bytes(Bytes(Cow::Borrowed(b"ERROR: MULTIPLE")));
 static OK: Element = Element::Bytes(Bytes(Cow::Borrowed(b"OK")));
+static DIRECTORY_ERROR: Element = Element::Bytes(Bytes(Cow::Borrowed(b"DIRECTORY_ERROR")));
 
-#[cfg(feature = "fdb-6_2")]
+#[cfg(any(feature = "fdb-6_2", feature = "fdb-7_1"))]
 static GOT_APPROXIMATE_SIZE: Element =
     Element::Bytes(Bytes(Cow::Borrowed(b"GOT_APPROXIMATE_SIZE")));
+#[cfg(feature = "fdb-7_1")]
+static GOT_ESTIMATED_RANGE_SIZE: Element =
+    Element::Bytes(Bytes(Cow::Borrowed(b"GOT_ESTIMATED_RANGE_SIZE")));
+#[cfg(feature = "fdb-7_1")]
+static GOT_RANGE_SPLIT_POINTS: Element =
+    Element::Bytes(Bytes(Cow::Borrowed(b"GOT_RANGE_SPLIT_POINTS")));
+
+/// Error code pushed by the operations that are not available at the compiled API version
+const API_VERSION_NOT_SUPPORTED: i32 = 2203;
+
+/// Number of sets committed by each transaction of LOG_STACK
+const LOG_STACK_BATCH: usize = 100;
+
+/// Number of stack items printed by `StackMachine::dump`
+const DUMP_STACK_ITEMS: usize = 10;
+
+/// Returns an error to inject instead of running an attempt of the LOG_STACK retry loop, see
+/// `RUST_BINDINGTESTER_BUGGIFY`.
+type FaultInjector = Arc<dyn Fn() -> Option<FdbError> + Send + Sync>;
 
 use crate::fdb::options::{MutationType, StreamingMode};
 use tuple::VersionstampOffset;
-fn mutation_from_str(s: &str) -> MutationType {
-    match s {
-        "ADD" => MutationType::Add,
-        "AND" => MutationType::And,
-        "BIT_AND" => MutationType::BitAnd,
-        "OR" => MutationType::Or,
-        "BIT_OR" => MutationType::BitOr,
-        "XOR" => MutationType::Xor,
-        "BIT_XOR" => MutationType::BitXor,
-        "APPEND_IF_FITS" => MutationType::AppendIfFits,
-        "MAX" => MutationType::Max,
-        "MIN" => MutationType::Min,
-        "SET_VERSIONSTAMPED_KEY" => MutationType::SetVersionstampedKey,
-        "SET_VERSIONSTAMPED_VALUE" => MutationType::SetVersionstampedValue,
-        "BYTE_MIN" => MutationType::ByteMin,
-        "BYTE_MAX" => MutationType::ByteMax,
-        "COMPARE_AND_CLEAR" => MutationType::CompareAndClear,
-        _ => unimplemented!("mutation_from_str({})", s),
-    }
-}
-
-pub fn streaming_from_value(val: i32) -> StreamingMode {
-    match val {
-        fdb_sys::FDBStreamingMode_FDB_STREAMING_MODE_WANT_ALL => StreamingMode::WantAll,
-        fdb_sys::FDBStreamingMode_FDB_STREAMING_MODE_ITERATOR => StreamingMode::Iterator,
-        fdb_sys::FDBStreamingMode_FDB_STREAMING_MODE_EXACT => StreamingMode::Exact,
-        fdb_sys::FDBStreamingMode_FDB_STREAMING_MODE_SMALL => StreamingMode::Small,
-        fdb_sys::FDBStreamingMode_FDB_STREAMING_MODE_MEDIUM => StreamingMode::Medium,
-        fdb_sys::FDBStreamingMode_FDB_STREAMING_MODE_LARGE => StreamingMode::Large,
-        fdb_sys::FDBStreamingMode_FDB_STREAMING_MODE_SERIAL => StreamingMode::Serial,
-        _ => unimplemented!("streaming_from_value({})", val),
-    }
-}
 
 struct Instr {
     code: InstrCode,
     database: bool,
+    tenant: bool,
     snapshot: bool,
     starts_with: bool,
     selector: bool,
@@ -82,6 +75,9 @@ impl std::fmt::Debug for Instr {
         if self.database {
             write!(fmt, " db")?;
         }
+        if self.tenant {
+            write!(fmt, " tenant")?;
+        }
         if self.snapshot {
             write!(fmt, " snapshot")?;
         }
@@ -104,6 +100,14 @@ impl Instr {
             false
         }
     }
+    fn pop_tenant(&mut self) -> bool {
+        if self.tenant {
+            self.tenant = false;
+            true
+        } else {
+            false
+        }
+    }
     fn pop_snapshot(&mut self) -> bool {
         if self.snapshot {
             self.snapshot = false;
@@ -130,7 +134,7 @@ impl Instr {
     }
 
     fn has_flags(&self) -> bool {
-        self.database || self.snapshot || self.starts_with || self.selector
+        self.database || self.tenant || self.snapshot || self.starts_with || self.selector
     }
 }
 
@@ -170,6 +174,8 @@ enum InstrCode {
     Cancel,
     GetCommittedVersion,
     GetApproximateSize,
+    GetEstimatedRangeSize,
+    GetRangeSplitPoints,
     WaitFuture,
 
     TuplePack,
@@ -182,12 +188,61 @@ enum InstrCode {
     DecodeFloat,
     DecodeDouble,
 
+    // tenant operations
+    TenantCreate,
+    TenantDelete,
+    TenantSetActive,
+    TenantClearActive,
+    TenantList,
+
     // Thread Operations
     StartThread,
     WaitEmpty,
 
     // misc
     UnitTests,
+
+    // directory operations
+    DirectoryCreateSubspace,
+    DirectoryCreateLayer,
+    DirectoryCreateOrOpen,
+    DirectoryCreate,
+    DirectoryOpen,
+    DirectoryChange,
+    DirectorySetErrorIndex,
+    DirectoryMove,
+    DirectoryMoveTo,
+    DirectoryRemove,
+    DirectoryRemoveIfExists,
+    DirectoryList,
+    DirectoryExists,
+    DirectoryPackKey,
+    DirectoryUnpackKey,
+    DirectoryRange,
+    DirectoryContains,
+    DirectoryOpenSubspace,
+    DirectoryLogSubspace,
+    DirectoryLogDirectory,
+    DirectoryStripPrefix,
+}
+
+impl InstrCode {
+    /// Instructions that append an item to the directory list, even when they fail
+    fn creates_directory(&self) -> bool {
+        use crate::InstrCode::*;
+
+        matches!(
+            self,
+            DirectoryCreateSubspace
+                | DirectoryCreateLayer
+                | DirectoryCreateOrOpen
+                | DirectoryCreate
+                | DirectoryOpen
+                | DirectoryMove
+                | DirectoryMoveTo
+                | DirectoryOpenSubspace
+        )
+    }
 }
 
 fn has_opt<'a>(cmd: &'a str, opt: &'static str) -> (&'a str, bool) {
@@ -208,6 +263,7 @@ impl Instr {
         let cmd = tup[0].as_str().unwrap();
 
         let (cmd, database) = has_opt(cmd, "_DATABASE");
+        let (cmd, tenant) = has_opt(cmd, "_TENANT");
         let (cmd, snapshot) = has_opt(cmd, "_SNAPSHOT");
         let (cmd, starts_with) = has_opt(cmd, "_STARTS_WITH");
         let (cmd, selector) = has_opt(cmd, "_SELECTOR");
@@ -246,6 +302,8 @@ impl Instr {
             "CANCEL" => Cancel,
             "GET_COMMITTED_VERSION" => GetCommittedVersion,
             "GET_APPROXIMATE_SIZE" => GetApproximateSize,
+            "GET_ESTIMATED_RANGE_SIZE" => GetEstimatedRangeSize,
+            "GET_RANGE_SPLIT_POINTS" => GetRangeSplitPoints,
             "WAIT_FUTURE" => WaitFuture,
 
             "TUPLE_PACK" => TuplePack,
@@ -258,16 +316,45 @@ impl Instr {
             "DECODE_FLOAT" => DecodeFloat,
             "DECODE_DOUBLE" => DecodeDouble,
 
+            "TENANT_CREATE" => TenantCreate,
+            "TENANT_DELETE" => TenantDelete,
+            "TENANT_SET_ACTIVE" => TenantSetActive,
+            "TENANT_CLEAR_ACTIVE" => TenantClearActive,
+            "TENANT_LIST" => TenantList,
+
             "START_THREAD" => StartThread,
             "WAIT_EMPTY" => WaitEmpty,
 
             "UNIT_TESTS" => UnitTests,
 
+            "DIRECTORY_CREATE_SUBSPACE" => DirectoryCreateSubspace,
+            "DIRECTORY_CREATE_LAYER" => DirectoryCreateLayer,
+            "DIRECTORY_CREATE_OR_OPEN" => DirectoryCreateOrOpen,
+            "DIRECTORY_CREATE" => DirectoryCreate,
+            "DIRECTORY_OPEN" => DirectoryOpen,
+            "DIRECTORY_CHANGE" => DirectoryChange,
+            "DIRECTORY_SET_ERROR_INDEX" => DirectorySetErrorIndex,
+            "DIRECTORY_MOVE" => DirectoryMove,
+            "DIRECTORY_MOVE_TO" => DirectoryMoveTo,
+            "DIRECTORY_REMOVE" => DirectoryRemove,
+            "DIRECTORY_REMOVE_IF_EXISTS" => DirectoryRemoveIfExists,
+            "DIRECTORY_LIST" => DirectoryList,
+            "DIRECTORY_EXISTS" => DirectoryExists,
+            "DIRECTORY_PACK_KEY" => DirectoryPackKey,
+            "DIRECTORY_UNPACK_KEY" => DirectoryUnpackKey,
+            "DIRECTORY_RANGE" => DirectoryRange,
+            "DIRECTORY_CONTAINS" => DirectoryContains,
+            "DIRECTORY_OPEN_SUBSPACE" => DirectoryOpenSubspace,
+            "DIRECTORY_LOG_SUBSPACE" => DirectoryLogSubspace,
+            "DIRECTORY_LOG_DIRECTORY" => DirectoryLogDirectory,
+            "DIRECTORY_STRIP_PREFIX" => DirectoryStripPrefix,
+
             name => unimplemented!("inimplemented instr: {}", name),
         };
         Instr {
             code,
             database,
+            tenant,
             snapshot,
             starts_with,
             selector,
@@ -439,6 +526,174 @@ impl TransactionState {
     }
 }
 
+/// An item of the directory list
+#[derive(Clone)]
+enum DirectoryStackItem {
+    DirectoryLayer(DirectoryLayer),
+    DirectoryOutput(DirectoryOutput),
+    Subspace(Subspace),
+    Null,
+}
+
+impl std::fmt::Debug for DirectoryStackItem {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        use DirectoryStackItem as D;
+
+        match self {
+            D::DirectoryLayer(d) => write!(f, "DirectoryLayer({:?})", d.get_path()),
+            D::DirectoryOutput(DirectoryOutput::DirectorySubspace(d)) => {
+                write!(f, "DirectorySubspace({:?})", d.get_path())
+            }
+            D::DirectoryOutput(DirectoryOutput::DirectoryPartition(d)) => {
+                write!(f, "DirectoryPartition({:?})", d.get_path())
+            }
+            D::Subspace(s) => write!(f, "Subspace({:?})", Bytes::from(s.bytes())),
+            D::Null => write!(f, "Null"),
+        }
+    }
+}
+
+impl DirectoryStackItem {
+    fn as_directory(&self) -> Option<&(dyn Directory + Send + Sync)> {
+        use DirectoryStackItem as D;
+
+        match self {
+            D::DirectoryLayer(d) => Some(d),
+            D::DirectoryOutput(d) => Some(d),
+            _ => None,
+        }
+    }
+
+    /// The subspace of the item, for subspace operations
+    fn as_subspace(&self) -> Option<Subspace> {
+        use DirectoryStackItem as D;
+
+        match self {
+            D::Subspace(s) => Some(s.clone()),
+            D::DirectoryOutput(DirectoryOutput::DirectorySubspace(d)) => {
+                Some(Subspace::from_bytes(d.bytes()))
+            }
+            _ => None,
+        }
+    }
+
+    fn get_path(&self) -> Option<&[String]> {
+        use DirectoryStackItem as D;
+
+        match self {
+            D::DirectoryLayer(d) => Some(d.get_path()),
+            D::DirectoryOutput(d) => Some(d.get_path()),
+            _ => None,
+        }
+    }
+
+    fn get_layer(&self) -> Option<&[u8]> {
+        use DirectoryStackItem as D;
+
+        match self {
+            D::DirectoryLayer(d) => Some(d.get_layer()),
+            D::DirectoryOutput(d) => Some(d.get_layer()),
+            _ => None,
+        }
+    }
+}
+
+/// A directory operation, that can be run with the current transaction or with the database
+#[derive(Debug)]
+enum DirectoryOp {
+    CreateOrOpen {
+        path: Vec<String>,
+        layer: Option<Vec<u8>>,
+    },
+    Create {
+        path: Vec<String>,
+        layer: Option<Vec<u8>>,
+        prefix: Option<Vec<u8>>,
+    },
+    Open {
+        path: Vec<String>,
+        layer: Option<Vec<u8>>,
+    },
+    Move {
+        old_path: Vec<String>,
+        new_path: Vec<String>,
+    },
+    MoveTo {
+        new_path: Vec<String>,
+    },
+    Remove {
+        path: Vec<String>,
+    },
+    RemoveIfExists {
+        path: Vec<String>,
+    },
+    List {
+        path: Vec<String>,
+    },
+    Exists {
+        path: Vec<String>,
+    },
+}
+
+enum DirectoryOpResult {
+    Directory(Box<DirectoryOutput>),
+    Removed,
+    List(Vec<String>),
+    Exists(bool),
+}
+
+async fn run_directory_op(
+    directory: &DirectoryStackItem,
+    trx: &Transaction,
+    op: &DirectoryOp,
+) -> Result<DirectoryOpResult, DirectoryError> {
+    use DirectoryOp as O;
+    use DirectoryOpResult as R;
+
+    let directory = directory
+        .as_directory()
+        .expect("directory operations to be called on directories");
+    Ok(match op {
+        O::CreateOrOpen { path, layer } => R::Directory(Box::new(
+            directory
+                .create_or_open(trx, path, None, layer.as_deref())
+                .await?,
+        )),
+        O::Create {
+            path,
+            layer,
+            prefix,
+        } => R::Directory(Box::new(
+            directory
+                .create(trx, path, prefix.as_deref(), layer.as_deref())
+                .await?,
+        )),
+        O::Open { path, layer } => {
+            R::Directory(Box::new(directory.open(trx, path, layer.as_deref()).await?))
+        }
+        O::Move { old_path, new_path } => {
+            R::Directory(Box::new(directory.move_to(trx, old_path, new_path).await?))
+        }
+        O::MoveTo { new_path } => {
+            R::Directory(Box::new(directory.move_directory(trx, new_path).await?))
+        }
+        O::Remove { path } => {
+            directory.remove(trx, path).await?;
+            R::Removed
+        }
+        O::RemoveIfExists { path } => {
+            directory.remove_if_exists(trx, path).await?;
+            R::Removed
+        }
+        O::List { path } => R::List(directory.list(trx, path).await?),
+        O::Exists { path } => R::Exists(directory.exists(trx, path).await?),
+    })
+}
+
+fn check_dir<T>(r: Result<T, DirectoryError>) -> Result<T, ()> {
+    r.map_err(|err| debug!("DIRECTORY_ERROR {:?}", err))
+}
+
 struct StackMachine {
     prefix: Bytes<'static>,
 
@@ -462,17 +717,30 @@ struct StackMachine {
     threads: Vec<thread::JoinHandle<()>>,
 
     trx_counter: usize,
+
+    // The directory list, it can contain subspaces, directories and directory layers. It is
+    // initialized with the default directory layer.
+    directory_list: Vec<DirectoryStackItem>,
+
+    // The index in the directory list of the currently active directory.
+    directory_index: usize,
+
+    // The index to use when the directory at directory_index is not present.
+    error_index: usize,
+
+    // The active tenant, used by NEW_TRANSACTION and the instructions suffixed with _TENANT.
+    #[cfg(feature = "fdb-7_1")]
+    tenant: Option<Tenant>,
+
+    // Injects retryable errors in the retry loops of the tester, to check they are retried.
+    fault_injector: Option<FaultInjector>,
+
+    // The number of the instruction before which the machine stops, see `--stop-at`.
+    stop_at: Option<usize>,
 }
 
-fn strinc(key: Bytes) -> Bytes {
-    let mut key = key.into_owned();
-    for i in (0..key.len()).rev() {
-        if key[i] != 0xff {
-            key[i] += 1;
-            return Bytes::from(key);
-        }
-    }
-    panic!("failed to strinc");
+fn strinc(key: Bytes) -> Bytes<'static> {
+    Bytes::from(fdb::key::strinc(&key).expect("failed to strinc"))
 }
 
 impl StackMachine {
@@ -492,27 +760,60 @@ impl StackMachine {
             last_version: 0,
             threads: Vec::new(),
             trx_counter: 0,
+            directory_list: vec![DirectoryStackItem::DirectoryLayer(DirectoryLayer::default())],
+            directory_index: 0,
+            error_index: 0,
+            #[cfg(feature = "fdb-7_1")]
+            tenant: None,
+            fault_injector: None,
+            stop_at: None,
         }
     }
 
+    /// Creates a transaction on the active tenant if `use_tenant` is set and a tenant is active,
+    /// on the database otherwise.
+    #[cfg_attr(not(feature = "fdb-7_1"), allow(unused_variables))]
+    fn create_trx(&self, db: &Database, use_tenant: bool) -> FdbResult<Transaction> {
+        #[cfg(feature = "fdb-7_1")]
+        {
+            if let (true, Some(tenant)) = (use_tenant, &self.tenant) {
+                return tenant.create_trx();
+            }
+        }
+        db.create_trx()
+    }
+
     fn next_trx_id(&mut self) -> usize {
         self.trx_counter += 1;
         self.trx_counter
     }
 
-    async fn fetch_instr(&self, trx: &Transaction) -> FdbResult<Vec<Instr>> {
-        let opt = RangeOption::from(&Subspace::from(&self.prefix));
+    /// Reads the instructions of this stack machine, starting over with the backoff of
+    /// `Transaction::on_error` after a retryable error.
+    async fn fetch_instr(&self, db: &Database) -> FdbResult<Vec<Instr>> {
+        let opt = Subspace::from(&self.prefix).range_option();
         debug!("opt = {:?}", opt);
-        let instrs = Vec::new();
-        trx.get_ranges(opt, false)
-            .try_fold(instrs, |mut instrs, res| {
-                for kv in res.iter() {
-                    let instr = Instr::from(kv.value());
-                    instrs.push(instr);
+        let mut trx = db.create_trx()?;
+        loop {
+            let res = trx
+                .get_ranges(opt.clone(), false)
+                .try_fold(Vec::new(), |mut instrs, res| {
+                    for kv in res.iter() {
+                        let instr = Instr::from(kv.value());
+                        instrs.push(instr);
+                    }
+                    future::ok(instrs)
+                })
+                .await;
+            match res {
+                Ok(instrs) => return Ok(instrs),
+                Err(err) => {
+                    debug!("instructions read failed: {:?}", err);
+                    // fails if the error is not retryable
+                    trx = trx.on_error(err).await?;
                 }
-                future::ok(instrs)
-            })
-            .await
+            }
+        }
     }
 
     async fn pop(&mut self) -> StackItem {
@@ -612,6 +913,11 @@ impl StackMachine {
         self.push(number, Element::Bytes(packed.into()));
     }
 
+    /// Pushes the error of an operation that is not available at the compiled API version.
+    fn push_unsupported(&mut self, number: usize) {
+        self.push_err(number, FdbError::from_code(API_VERSION_NOT_SUPPORTED));
+    }
+
     fn check<T>(&mut self, number: usize, r: FdbResult<T>) -> Result<T, ()> {
         match r {
             Ok(v) => Ok(v),
@@ -622,21 +928,320 @@ impl StackMachine {
         }
     }
 
+    async fn pop_tuple(&mut self) -> Vec<Element<'static>> {
+        let count = self.pop_usize().await;
+        let mut tuple = Vec::with_capacity(count);
+        for _ in 0..count {
+            tuple.push(self.pop_element().await);
+        }
+        tuple
+    }
+
+    async fn pop_path(&mut self) -> Result<Vec<String>, ()> {
+        self.pop_tuple()
+            .await
+            .into_iter()
+            .map(|element| match element {
+                Element::String(s) => Ok(s.into_owned()),
+                element => {
+                    debug!("path element must be a string, found {:?}", element);
+                    Err(())
+                }
+            })
+            .collect()
+    }
+
+    async fn pop_optional_bytes(&mut self) -> Result<Option<Vec<u8>>, ()> {
+        match self.pop_element().await {
+            Element::Nil => Ok(None),
+            Element::Bytes(b) => Ok(Some(b.into_owned())),
+            element => {
+                debug!("bytes were expected, found {:?}", element);
+                Err(())
+            }
+        }
+    }
+
+    fn current_directory(&self) -> &DirectoryStackItem {
+        &self.directory_list[self.directory_index]
+    }
+
+    fn current_subspace(&self) -> Result<Subspace, ()> {
+        self.current_directory().as_subspace().ok_or_else(|| {
+            debug!(
+                "subspace operation on a non subspace {:?}",
+                self.current_directory()
+            )
+        })
+    }
+
     #[allow(clippy::cognitive_complexity)]
-    async fn run_step(
+    async fn run_directory_step(
         &mut self,
-        db: Arc<Database>,
+        db: &Database,
         number: usize,
-        mut instr: Instr,
+        is_db: bool,
+        trx: &Transaction,
+        code: &InstrCode,
     ) -> Result<(), ()> {
         use crate::InstrCode::*;
 
-        let is_db = instr.pop_database();
+        let op = match code {
+            // Pops the top two items off the stack as PATH and RAW_PREFIX, and appends
+            // a new subspace with the given path and raw prefix to the directory list.
+            DirectoryCreateSubspace => {
+                let path = self.pop_tuple().await;
+                let raw_prefix = self.pop_bytes().await;
+                debug!("directory_create_subspace {:?} {:?}", path, raw_prefix);
+                let subspace = Subspace::from_bytes(&raw_prefix).subspace(&path);
+                self.directory_list
+                    .push(DirectoryStackItem::Subspace(subspace));
+                return Ok(());
+            }
+            // Pops the top three items off the stack as INDEX1, INDEX2, and
+            // ALLOW_MANUAL_PREFIXES, and appends a new directory layer using the
+            // subspaces at INDEX1 and INDEX2 as its node and content subspaces.
+            DirectoryCreateLayer => {
+                let index1 = self.pop_usize().await;
+                let index2 = self.pop_usize().await;
+                let allow_manual_prefixes = self.pop_i64().await != 0;
+                debug!(
+                    "directory_create_layer {} {} {}",
+                    index1, index2, allow_manual_prefixes
+                );
+                let node_subspace = self.directory_list.get(index1).ok_or(())?;
+                let content_subspace = self.directory_list.get(index2).ok_or(())?;
+                let item = match (node_subspace.as_subspace(), content_subspace.as_subspace()) {
+                    (Some(node_subspace), Some(content_subspace)) => {
+                        DirectoryStackItem::DirectoryLayer(DirectoryLayer::new(
+                            node_subspace,
+                            content_subspace,
+                            allow_manual_prefixes,
+                        ))
+                    }
+                    _ => DirectoryStackItem::Null,
+                };
+                self.directory_list.push(item);
+                return Ok(());
+            }
+            // Pops the top item off the stack as INDEX, and sets the current directory
+            // to the one at INDEX, or to the one at the error index if it is null.
+            DirectoryChange => {
+                let index = self.pop_usize().await;
+                debug!("directory_change {}", index);
+                self.directory_index = match self.directory_list.get(index) {
+                    Some(DirectoryStackItem::Null) => self.error_index,
+                    Some(_) => index,
+                    None => return Err(()),
+                };
+                return Ok(());
+            }
+            // Pops the top item off the stack as ERROR_INDEX, and sets the error index.
+            DirectorySetErrorIndex => {
+                self.error_index = self.pop_usize().await;
+                debug!("directory_set_error_index {}", self.error_index);
+                return Ok(());
+            }
+            // Pops the top item off the stack as KEY_TUPLE, and pushes the key packed
+            // by the current subspace.
+            DirectoryPackKey => {
+                let tuple = self.pop_tuple().await;
+                debug!("directory_pack_key {:?}", tuple);
+                let key = self.current_subspace()?.pack(&tuple);
+                self.push(number, Element::Bytes(key.into()));
+                return Ok(());
+            }
+            // Pops the top item off the stack as KEY, unpacks it with the current subspace
+            // and pushes each element of the resulting tuple onto the stack.
+            DirectoryUnpackKey => {
+                let key = self.pop_bytes().await;
+                debug!("directory_unpack_key {:?}", key);
+                let tuple: Vec<Element> = self
+                    .current_subspace()?
+                    .unpack(&key)
+                    .map_err(|err| debug!("unpack error {:?}", err))?;
+                for element in tuple {
+                    self.push(number, element.into_owned());
+                }
+                return Ok(());
+            }
+            // Pops the top item off the stack as TUPLE, and pushes the beginning and the
+            // end of the range of the subspace for TUPLE in the current subspace.
+            DirectoryRange => {
+                let tuple = self.pop_tuple().await;
+                debug!("directory_range {:?}", tuple);
+                let (begin, end) = self.current_subspace()?.subspace(&tuple).range();
+                self.push(number, Element::Bytes(begin.into()));
+                self.push(number, Element::Bytes(end.into()));
+                return Ok(());
+            }
+            // Pops the top item off the stack as KEY, and pushes 1 if the current subspace
+            // contains KEY, 0 otherwise.
+            DirectoryContains => {
+                let key = self.pop_bytes().await;
+                debug!("directory_contains {:?}", key);
+                let contains = self.current_subspace()?.is_start_of(&key);
+                self.push(number, Element::Int(contains as i64));
+                return Ok(());
+            }
+            // Pops the top item off the stack as TUPLE, and appends the subspace of the
+            // current subspace for TUPLE to the directory list.
+            DirectoryOpenSubspace => {
+                let tuple = self.pop_tuple().await;
+                debug!("directory_open_subspace {:?}", tuple);
+                let subspace = self.current_subspace()?.subspace(&tuple);
+                self.directory_list
+                    .push(DirectoryStackItem::Subspace(subspace));
+                return Ok(());
+            }
+            // Pops the top item off the stack as PREFIX, and logs the raw prefix of the
+            // current subspace at PREFIX + pack((directory_index,)).
+            DirectoryLogSubspace => {
+                let prefix = self.pop_bytes().await;
+                debug!("directory_log_subspace {:?}", prefix);
+                let subspace = self.current_subspace()?;
+                let mut key = prefix.into_owned();
+                key.extend_from_slice(&pack(&(self.directory_index,)));
+                trx.set(&key, subspace.bytes());
+                return Ok(());
+            }
+            // Pops the top item off the stack as PREFIX, and logs the path, layer, existence
+            // and children of the current directory in the subspace
+            // Subspace((directory_index,), PREFIX).
+            DirectoryLogDirectory => {
+                let prefix = self.pop_bytes().await;
+                debug!("directory_log_directory {:?}", prefix);
+                let directory = self.current_directory().clone();
+                let log_subspace = Subspace::from_bytes(&prefix).subspace(&(self.directory_index,));
+                let exists = {
+                    let directory = directory.as_directory().ok_or(())?;
+                    check_dir(directory.exists(trx, &[]).await)?
+                };
+                let children = if exists {
+                    let directory = directory.as_directory().ok_or(())?;
+                    check_dir(directory.list(trx, &[]).await)?
+                } else {
+                    Vec::new()
+                };
+                let path = directory.get_path().ok_or(())?;
+                let layer = directory.get_layer().ok_or(())?;
+
+                trx.set(&log_subspace.pack(&"path"), &pack(&path));
+                trx.set(&log_subspace.pack(&"layer"), &pack(&(Bytes::from(layer),)));
+                trx.set(&log_subspace.pack(&"exists"), &pack(&(exists as i64,)));
+                trx.set(&log_subspace.pack(&"children"), &pack(&children));
+                return Ok(());
+            }
+            // Pops the top item off the stack as BYTE_ARRAY, and pushes the result of
+            // stripping the prefix of the current subspace from BYTE_ARRAY.
+            DirectoryStripPrefix => {
+                let bytes = self.pop_bytes().await;
+                debug!("directory_strip_prefix {:?}", bytes);
+                let subspace = self.current_subspace()?;
+                if !subspace.is_start_of(&bytes) {
+                    debug!(
+                        "{:?} does not start with {:?}",
+                        bytes,
+                        RedactedBytes(subspace.bytes())
+                    );
+                    return Err(());
+                }
+                let stripped = bytes[subspace.bytes().len()..].to_vec();
+                self.push(number, Element::Bytes(stripped.into()));
+                return Ok(());
+            }
+
+            // Directory operations that must be run within a transaction
+            DirectoryCreateOrOpen => {
+                let path = self.pop_path().await?;
+                let layer = self.pop_optional_bytes().await?;
+                DirectoryOp::CreateOrOpen { path, layer }
+            }
+            DirectoryCreate => {
+                let path = self.pop_path().await?;
+                let layer = self.pop_optional_bytes().await?;
+                let prefix = self.pop_optional_bytes().await?;
+                DirectoryOp::Create {
+                    path,
+                    layer,
+                    prefix,
+                }
+            }
+            DirectoryOpen => {
+                let path = self.pop_path().await?;
+                let layer = self.pop_optional_bytes().await?;
+                DirectoryOp::Open { path, layer }
+            }
+            DirectoryMove => {
+                let old_path = self.pop_path().await?;
+                let new_path = self.pop_path().await?;
+                DirectoryOp::Move { old_path, new_path }
+            }
+            DirectoryMoveTo => {
+                let new_path = self.pop_path().await?;
+                DirectoryOp::MoveTo { new_path }
+            }
+            DirectoryRemove | DirectoryRemoveIfExists | DirectoryList | DirectoryExists => {
+                let count = self.pop_usize().await;
+                let path = if count == 0 {
+                    Vec::new()
+                } else {
+                    self.pop_path().await?
+                };
+                match code {
+                    DirectoryRemove => DirectoryOp::Remove { path },
+                    DirectoryRemoveIfExists => DirectoryOp::RemoveIfExists { path },
+                    DirectoryList => DirectoryOp::List { path },
+                    _ => DirectoryOp::Exists { path },
+                }
+            }
+            _ => unreachable!("not a directory instruction: {:?}", code),
+        };
+
+        let directory = self.current_directory().clone();
+        if directory.as_directory().is_none() {
+            debug!("directory operation on a non directory {:?}", directory);
+            return Err(());
+        }
+        debug!("directory {:?} {:?} (is_db: {})", directory, op, is_db);
+
+        let r = if is_db {
+            db.transact_boxed_local(
+                (directory, op),
+                |trx, (directory, op)| run_directory_op(directory, trx, op).boxed_local(),
+                TransactOption::default(),
+            )
+            .await
+        } else {
+            run_directory_op(&directory, trx, &op).await
+        };
+
+        match check_dir(r)? {
+            DirectoryOpResult::Directory(output) => self
+                .directory_list
+                .push(DirectoryStackItem::DirectoryOutput(*output)),
+            DirectoryOpResult::Removed => {}
+            DirectoryOpResult::List(children) => {
+                self.push(number, Element::Bytes(pack(&children).into()))
+            }
+            DirectoryOpResult::Exists(exists) => self.push(number, Element::Int(exists as i64)),
+        }
+
+        Ok(())
+    }
+
+    #[allow(clippy::cognitive_complexity)]
+    async fn run_step(&mut self, db: Database, number: usize, mut instr: Instr) -> Result<(), ()> {
+        use crate::InstrCode::*;
+
+        let is_tenant = instr.pop_tenant();
+        // instructions suffixed with _TENANT behave like the _DATABASE ones on the active tenant
+        let is_db = instr.pop_database() || is_tenant;
         let mut mutation = false;
         let mut pending = false;
         let (mut trx, trx_name) = if is_db {
             (
-                TransactionState::Transaction(self.check(number, db.create_trx())?),
+                TransactionState::Transaction(self.check(number, self.create_trx(&db, is_tenant))?),
                 None,
             )
         } else {
@@ -758,25 +1363,21 @@ impl StackMachine {
             LogStack => {
                 let prefix: Bytes = self.pop_bytes().await;
                 let mut stack_idx = self.stack.len();
-                let trx_id = self.next_trx_id();
-                let mut t = trx.take(trx_id);
-                let mut n = 0;
+                let mut entries = Vec::with_capacity(stack_idx);
                 while let Some(stack_item) = self.maybe_pop().await {
                     stack_idx -= 1;
                     let mut key = prefix.clone().into_owned();
                     stack_idx.pack_into_vec(&mut key);
                     stack_item.number.pack_into_vec(&mut key);
 
-                    let value = pack(&stack_item.data.unwrap());
-                    t.set(&key, &value[..value.len().min(40000)]);
-                    n += 1;
-                    if n == 100 {
-                        t = t.commit().await.unwrap().reset();
-                        n = 0;
-                    }
+                    let mut value = pack(&stack_item.data.expect("stack item to be resolved"));
+                    value.truncate(40000);
+                    entries.push((key, value));
+                }
+                // the current transaction is not used, it is put back in the map below
+                if let Err(err) = self.log_entries(&db, &entries).await {
+                    self.push_err(number, err);
                 }
-                t = t.commit().await.unwrap().reset();
-                trx = TransactionState::Transaction(t);
             }
 
             // Creates a new transaction and stores it in the global transaction map
@@ -784,15 +1385,17 @@ impl StackMachine {
             NewTransaction => {
                 let name = self.cur_transaction.clone();
                 debug!("create_trx {:?}", name);
-                let trx = self.check(number, db.create_trx())?;
-                trx.set_option(fdb::options::TransactionOption::DebugTransactionIdentifier(
-                    "RUST".to_string(),
-                ))
-                .unwrap();
-                trx.set_option(fdb::options::TransactionOption::LogTransaction)
-                    .unwrap();
+                let trx = self.check(number, self.create_trx(&db, true))?;
+                let r = trx
+                    .set_option(fdb::options::TransactionOption::DebugTransactionIdentifier(
+                        "RUST".to_string(),
+                    ))
+                    .and_then(|()| trx.set_option(fdb::options::TransactionOption::LogTransaction));
                 self.transactions
                     .insert(name, TransactionState::Transaction(trx));
+                if let Err(err) = r {
+                    self.push_err(number, err);
+                }
             }
 
             // Pop the top item off of the stack as TRANSACTION_NAME. Begin using the
@@ -803,7 +1406,7 @@ impl StackMachine {
                 let name: Bytes = self.pop_bytes().await;
                 debug!("use_transaction {:?}", name);
                 if !self.transactions.contains_key(&name) {
-                    let trx = self.check(number, db.create_trx())?;
+                    let trx = self.check(number, self.create_trx(&db, true))?;
                     self.transactions
                         .insert(name.clone(), TransactionState::Transaction(trx));
                 }
@@ -935,12 +1538,12 @@ impl StackMachine {
                 let limit: i64 = self.pop_i64().await;
                 let reverse: i64 = self.pop_i64().await;
                 let streaming_mode: i32 = self.pop_i32().await;
-                let mode = streaming_from_value(streaming_mode);
+                let mode = StreamingMode::try_from(streaming_mode).expect("unknown streaming mode");
                 debug!(
                     "get_range begin={:?}\n, begin={:?}\n, end={:?}\n,end={:?}\n, limit={:?}, rev={:?}, mode={:?}",
-                    begin,
+                    RedactedBytes(begin.key()),
                     unpack::<Element>(&begin.key()),
-                    end,
+                    RedactedBytes(end.key()),
                     unpack::<Element>(&end.key()),
                     limit,
                     reverse,
@@ -1078,7 +1681,7 @@ impl StackMachine {
                 let value: Bytes = self.pop_bytes().await;
                 debug!("atomic_op {:?} {:?} {:?}", key, value, optype);
 
-                let op = mutation_from_str(&optype);
+                let op = MutationType::from_str(&optype).expect("unknown mutation type");
                 trx.as_mut().atomic_op(&key, &value, op);
                 mutation = true;
             }
@@ -1207,7 +1810,7 @@ impl StackMachine {
             // limits, so these bindings can obtain different sizes back.
             GetApproximateSize => {
                 debug!("get_approximate_size");
-                #[cfg(feature = "fdb-6_2")]
+                #[cfg(any(feature = "fdb-6_2", feature = "fdb-7_1"))]
                 {
                     trx.as_mut()
                         .get_approximate_size()
@@ -1215,12 +1818,64 @@ impl StackMachine {
                         .expect("failed to get approximate size");
                     self.push(number, GOT_APPROXIMATE_SIZE.clone().into_owned());
                 }
-                #[cfg(not(feature = "fdb-6_2"))]
+                #[cfg(not(any(feature = "fdb-6_2", feature = "fdb-7_1")))]
                 {
                     unimplemented!("get_approximate_size requires fdb620+");
                 }
             }
 
+            // Pops the top two items off of the stack as BEGIN_KEY and END_KEY. Calls
+            // get_estimated_range_size_bytes and pushes the byte string
+            // "GOT_ESTIMATED_RANGE_SIZE" onto the stack.
+            GetEstimatedRangeSize => {
+                let begin = self.pop_bytes().await;
+                let end = self.pop_bytes().await;
+                debug!("get_estimated_range_size {:?} {:?}", begin, end);
+                #[cfg(feature = "fdb-7_1")]
+                {
+                    let r = trx
+                        .as_mut()
+                        .get_estimated_range_size_bytes(&begin, &end)
+                        .await;
+                    match r {
+                        Ok(..) => self.push(number, GOT_ESTIMATED_RANGE_SIZE.clone().into_owned()),
+                        Err(err) => self.push_err(number, err),
+                    }
+                }
+                #[cfg(not(feature = "fdb-7_1"))]
+                {
+                    self.push_unsupported(number);
+                }
+            }
+
+            // Pops the top three items off of the stack as BEGIN_KEY, END_KEY and
+            // CHUNK_SIZE. Calls get_range_split_points and pushes the byte string
+            // "GOT_RANGE_SPLIT_POINTS" onto the stack.
+            GetRangeSplitPoints => {
+                let begin = self.pop_bytes().await;
+                let end = self.pop_bytes().await;
+                let chunk_size = self.pop_i64().await;
+                debug!(
+                    "get_range_split_points {:?} {:?} {}",
+                    begin, end, chunk_size
+                );
+                #[cfg(feature = "fdb-7_1")]
+                {
+                    let r = trx
+                        .as_mut()
+                        .get_range_split_points(&begin, &end, chunk_size)
+                        .await;
+                    match r {
+                        Ok(..) => self.push(number, GOT_RANGE_SPLIT_POINTS.clone().into_owned()),
+                        Err(err) => self.push_err(number, err),
+                    }
+                }
+                #[cfg(not(feature = "fdb-7_1"))]
+                {
+                    self.push_unsupported(number);
+                }
+            }
+
             // Pops the top item off the stack and pushes it back on. If the top item on
             // the stack is a future, this will have the side effect of waiting on the
             // result of the future and pushing the result on the stack. Does not change
@@ -1230,6 +1885,82 @@ impl StackMachine {
                 let item = self.pop().await;
                 self.stack.push(item);
             }
+            // Pops the top item off of the stack as TENANT_NAME. Creates a new tenant in the
+            // database with the name TENANT_NAME. May optionally push a future onto the stack.
+            TenantCreate => {
+                let name = self.pop_bytes().await;
+                debug!("tenant_create {:?}", name);
+                #[cfg(feature = "fdb-7_1")]
+                {
+                    let r = TenantManagement::create_tenant(&db, &name).await;
+                    self.push_res(number, r, b"RESULT_NOT_PRESENT");
+                }
+                #[cfg(not(feature = "fdb-7_1"))]
+                {
+                    self.push_unsupported(number);
+                }
+            }
+            // Pops the top item off of the stack as TENANT_NAME. Removes the tenant with the
+            // name TENANT_NAME from the database. May optionally push a future onto the stack.
+            TenantDelete => {
+                let name = self.pop_bytes().await;
+                debug!("tenant_delete {:?}", name);
+                #[cfg(feature = "fdb-7_1")]
+                {
+                    let r = TenantManagement::delete_tenant(&db, &name).await;
+                    self.push_res(number, r, b"RESULT_NOT_PRESENT");
+                }
+                #[cfg(not(feature = "fdb-7_1"))]
+                {
+                    self.push_unsupported(number);
+                }
+            }
+            // Pops the top item off of the stack as TENANT_NAME. Opens the tenant with name
+            // TENANT_NAME and stores it as the active tenant.
+            TenantSetActive => {
+                let name = self.pop_bytes().await;
+                debug!("tenant_set_active {:?}", name);
+                #[cfg(feature = "fdb-7_1")]
+                {
+                    match db.open_tenant(&name) {
+                        Ok(tenant) => self.tenant = Some(tenant),
+                        Err(err) => self.push_err(number, err),
+                    }
+                }
+                #[cfg(not(feature = "fdb-7_1"))]
+                {
+                    self.push_unsupported(number);
+                }
+            }
+            // Unsets the active tenant.
+            TenantClearActive => {
+                debug!("tenant_clear_active");
+                #[cfg(feature = "fdb-7_1")]
+                {
+                    self.tenant = None;
+                }
+            }
+            // Pops the top three items off of the stack as BEGIN, END and LIMIT. Lists the
+            // tenants with names in the range [BEGIN, END), returning at most LIMIT tenants,
+            // and pushes the names packed as a tuple onto the stack.
+            TenantList => {
+                let begin = self.pop_bytes().await;
+                let end = self.pop_bytes().await;
+                let limit = self.pop_usize().await;
+                debug!("tenant_list {:?} {:?} {}", begin, end, limit);
+                #[cfg(feature = "fdb-7_1")]
+                {
+                    match TenantManagement::list_tenants(&db, &begin, &end, limit).await {
+                        Ok(names) => self.push(number, Element::Bytes(pack(&names).into())),
+                        Err(err) => self.push_err(number, err),
+                    }
+                }
+                #[cfg(not(feature = "fdb-7_1"))]
+                {
+                    self.push_unsupported(number);
+                }
+            }
+
             // Pops the top item off of the stack as N. Pops the next N items off of the
             // stack and packs them as the tuple [item0,item1,...,itemN], and then pushes
             // this single packed value onto the stack.
@@ -1405,18 +2136,11 @@ impl StackMachine {
             StartThread => {
                 let prefix = self.pop_bytes().await;
                 debug!("start_thread {:?}", prefix);
-                let db = db.clone();
-                self.threads.push(
-                    thread::Builder::new()
-                        .name(format!("{:?}", prefix))
-                        .spawn(move || {
-                            let mut sm = StackMachine::new(&db, prefix.clone());
-                            futures::executor::block_on(sm.run(db)).unwrap();
-                            sm.join();
-                            debug!("thread {:?} exit", prefix);
-                        })
-                        .unwrap(),
-                );
+                self.threads.push(Self::start_thread(
+                    db.clone(),
+                    prefix,
+                    self.fault_injector.clone(),
+                ));
             }
 
             // Pops the top item off of the stack as PREFIX. Blocks execution until the
@@ -1506,6 +2230,41 @@ impl StackMachine {
                 // test_locality(db)
                 // test_predicates()
             }
+
+            DirectoryCreateSubspace
+            | DirectoryCreateLayer
+            | DirectoryCreateOrOpen
+            | DirectoryCreate
+            | DirectoryOpen
+            | DirectoryChange
+            | DirectorySetErrorIndex
+            | DirectoryMove
+            | DirectoryMoveTo
+            | DirectoryRemove
+            | DirectoryRemoveIfExists
+            | DirectoryList
+            | DirectoryExists
+            | DirectoryPackKey
+            | DirectoryUnpackKey
+            | DirectoryRange
+            | DirectoryContains
+            | DirectoryOpenSubspace
+            | DirectoryLogSubspace
+            | DirectoryLogDirectory
+            | DirectoryStripPrefix => {
+                // directory operations always read their metadata without snapshot isolation
+                let _snapshot = instr.pop_snapshot();
+                let code = &instr.code;
+                let r = self
+                    .run_directory_step(&db, number, is_db, trx.as_mut(), code)
+                    .await;
+                if r.is_err() {
+                    if code.creates_directory() {
+                        self.directory_list.push(DirectoryStackItem::Null);
+                    }
+                    self.push(number, DIRECTORY_ERROR.clone().into_owned());
+                }
+            }
         }
 
         if is_db && pending {
@@ -1528,19 +2287,128 @@ impl StackMachine {
         Ok(())
     }
 
-    async fn run(&mut self, db: Arc<Database>) -> FdbResult<()> {
+    /// Writes the entries of LOG_STACK with the standard retry loop, committing every
+    /// `LOG_STACK_BATCH` sets to avoid past_version errors.
+    async fn log_entries(&self, db: &Database, entries: &[(Vec<u8>, Vec<u8>)]) -> FdbResult<()> {
+        for batch in entries.chunks(LOG_STACK_BATCH) {
+            db.transact_boxed(
+                (batch, self.fault_injector.clone()),
+                |trx, (batch, fault_injector)| {
+                    if let Some(err) = fault_injector.as_ref().and_then(|inject| inject()) {
+                        debug!("LOG_STACK injected error {:?}", err);
+                        return future::err(err).boxed();
+                    }
+                    for (key, value) in batch.iter() {
+                        trx.set(key, value);
+                    }
+                    future::ok(()).boxed()
+                },
+                TransactOption::idempotent(),
+            )
+            .await?;
+        }
+        Ok(())
+    }
+
+    async fn run(&mut self, db: Database) -> FdbResult<()> {
         info!("Fetching instructions...");
-        let instrs = self.fetch_instr(&db.create_trx()?).await?;
+        let instrs = self.fetch_instr(&db).await?;
         info!("{} instructions found", instrs.len());
 
         for (i, instr) in instrs.into_iter().enumerate() {
+            if self.stop_at == Some(i) {
+                eprintln!("{}", self.dump(i, &format!("{:?}", instr), "stopped"));
+                break;
+            }
             trace!("{}/{}, {:?}", i, self.stack.len(), instr);
-            let _ = self.run_step(db.clone(), i, instr).await;
+            if let Err(dump) = self.run_step_or_dump(db.clone(), i, instr).await {
+                eprintln!("{}", dump);
+                std::process::exit(1);
+            }
         }
 
         Ok(())
     }
 
+    /// Runs `instr`, returning the dump of the machine if it panics, as the panic would
+    /// otherwise be reported deep in the stack of the machine, without the instruction it failed
+    /// on.
+    async fn run_step_or_dump(
+        &mut self,
+        db: Database,
+        number: usize,
+        instr: Instr,
+    ) -> Result<(), String> {
+        let decoded = format!("{:?}", instr);
+        match AssertUnwindSafe(self.run_step(db, number, instr))
+            .catch_unwind()
+            .await
+        {
+            // errors of the operations are pushed on the stack, they are expected results
+            Ok(_) => Ok(()),
+            Err(panic) => {
+                let reason = if let Some(msg) = panic.downcast_ref::<&str>() {
+                    *msg
+                } else if let Some(msg) = panic.downcast_ref::<String>() {
+                    msg.as_str()
+                } else {
+                    "panicked"
+                };
+                Err(self.dump(number, &decoded, reason))
+            }
+        }
+    }
+
+    /// Describes the state of the machine at instruction `number`: the instruction, `reason`
+    /// and the items at the top of the stack.
+    fn dump(&self, number: usize, instr: &str, reason: &str) -> String {
+        let mut dump = format!(
+            "thread {:?}, instruction {} {}: {}\nstack ({} items):",
+            self.prefix,
+            number,
+            instr,
+            reason,
+            self.stack.len()
+        );
+        for (depth, item) in self.stack.iter().rev().take(DUMP_STACK_ITEMS).enumerate() {
+            if item.fut.is_some() {
+                dump.push_str(&format!(
+                    "\n  {}: [item num={}, pending]",
+                    depth, item.number
+                ));
+            } else {
+                dump.push_str(&format!("\n  {}: {:?}", depth, item));
+            }
+        }
+        if self.stack.len() > DUMP_STACK_ITEMS {
+            dump.push_str("\n  ...");
+        }
+        dump
+    }
+
+    /// Starts a new stack machine on `prefix` in its own thread.
+    fn start_thread(
+        db: Database,
+        prefix: Bytes<'static>,
+        fault_injector: Option<FaultInjector>,
+    ) -> thread::JoinHandle<()> {
+        thread::Builder::new()
+            .name(format!("{:?}", prefix))
+            .spawn(move || {
+                let mut sm = StackMachine::new(&db, prefix.clone());
+                sm.fault_injector = fault_injector;
+                if let Err(err) = futures::executor::block_on(sm.run(db)) {
+                    panic!(
+                        "thread {:?} failed to read its instructions: {}",
+                        prefix, err
+                    );
+                }
+                sm.join();
+                debug!("thread {:?} exit", prefix);
+            })
+            .unwrap()
+    }
+
     fn join(&mut self) {
         for handle in self.threads.drain(0..) {
             handle.join().expect("joined thread to not panic");
@@ -1548,9 +2416,84 @@ impl StackMachine {
     }
 }
 
+/// Makes every Nth attempt of the LOG_STACK retry loop fail with `not_committed` (1020), where N
+/// is the value of `RUST_BINDINGTESTER_BUGGIFY`, at least 2 so that the retries can succeed.
+fn buggify_from_env() -> Option<FaultInjector> {
+    let period = std::env::var("RUST_BINDINGTESTER_BUGGIFY").ok()?;
+    let period = period
+        .parse::<usize>()
+        .ok()
+        .filter(|&period| period >= 2)
+        .expect("RUST_BINDINGTESTER_BUGGIFY to be an integer greater than 1");
+    info!("injecting a retryable error every {} attempts", period);
+    let attempts = AtomicUsize::new(0);
+    Some(Arc::new(move || {
+        if (attempts.fetch_add(1, Ordering::SeqCst) + 1) % period == 0 {
+            Some(FdbError::from_code(1020))
+        } else {
+            None
+        }
+    }))
+}
+
+/// The command line of the tester.
+///
+/// bindingtester.py runs it as `bindingtester PREFIX API_VERSION [CLUSTER_FILE]`, the options
+/// take precedence over these positional arguments.
+#[derive(StructOpt, Debug)]
+#[structopt(name = "bindingtester")]
+struct Opt {
+    /// The prefix of the keys of the instructions to run
+    #[structopt(long = "prefix", value_name = "PREFIX")]
+    prefix_opt: Option<String>,
+
+    /// The API version the instructions are run with
+    #[structopt(long = "api-version", value_name = "API_VERSION")]
+    api_version_opt: Option<i32>,
+
+    /// The cluster file of the database, the default one if not set
+    #[structopt(long = "cluster-file", value_name = "CLUSTER_FILE")]
+    cluster_file_opt: Option<String>,
+
+    /// Logs every instruction and operation, overriding the level of RUST_LOG
+    #[structopt(short = "v", long = "verbose")]
+    verbose: bool,
+
+    /// Stops before running the instruction N of the main thread, printing the stack
+    #[structopt(long = "stop-at", value_name = "N")]
+    stop_at: Option<usize>,
+
+    #[structopt(name = "PREFIX")]
+    prefix: Option<String>,
+
+    #[structopt(name = "API_VERSION")]
+    api_version: Option<i32>,
+
+    #[structopt(name = "CLUSTER_FILE")]
+    cluster_file: Option<String>,
+}
+
+/// Returns the value given by the option, or else by the positional argument, exiting with a
+/// usage error if there is none.
+fn required<T>(opt: Option<T>, positional: Option<T>, name: &str) -> T {
+    opt.or(positional).unwrap_or_else(|| {
+        clap::Error::with_description(
+            &format!("the {} is required", name),
+            clap::ErrorKind::MissingRequiredArgument,
+        )
+        .exit()
+    })
+}
+
 fn main() {
+    let opt = Opt::from_args();
+    let prefix = required(opt.prefix_opt, opt.prefix, "prefix");
+    let api_version = required(opt.api_version_opt, opt.api_version, "api version");
+    let cluster_path = opt.cluster_file_opt.or(opt.cluster_file);
+
     let now = std::time::Instant::now();
-    env_logger::Builder::from_default_env()
+    let mut logger = env_logger::Builder::from_default_env();
+    logger
         .format(move |buf, record| {
             let current_thread = thread::current();
             let thread_name = current_thread.name().unwrap_or("?");
@@ -1564,19 +2507,11 @@ fn main() {
             )
         })
         .format_timestamp_millis()
-        .target(env_logger::Target::Stdout)
-        .init();
-
-    let args = std::env::args().collect::<Vec<_>>();
-    let prefix = &args[1];
-
-    let cluster_path = if args.len() > 3 {
-        Some(args[3].as_str())
-    } else {
-        None
-    };
-
-    let api_version = args[2].parse::<i32>().expect("failed to parse api version");
+        .target(env_logger::Target::Stdout);
+    if opt.verbose {
+        logger.filter_level(log::LevelFilter::Trace);
+    }
+    logger.init();
 
     info!(
         "Starting rust bindingtester with api_version {}",
@@ -1588,16 +2523,97 @@ fn main() {
         .expect("failed to initialize FoundationDB API");
     let _network = unsafe { builder.boot() };
 
-    let db = Arc::new(
-        futures::executor::block_on(fdb::Database::new_compat(cluster_path))
-            .expect("failed to get database"),
-    );
+    let db = futures::executor::block_on(fdb::Database::new_compat(cluster_path.as_deref()))
+        .expect("failed to get database");
 
     let mut sm = StackMachine::new(&db, Bytes::from(prefix.to_owned().into_bytes()));
-    futures::executor::block_on(sm.run(db)).unwrap();
+    sm.fault_injector = buggify_from_env();
+    sm.stop_at = opt.stop_at;
+    futures::executor::block_on(sm.run(db)).expect("failed to read instructions");
     sm.join();
 
     info!("Closing...");
 
     info!("Done.");
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_stack_machine() {
+        let _guard = unsafe { fdb::boot() };
+        futures::executor::block_on(test_log_stack_retry_async()).expect("failed to run");
+        futures::executor::block_on(test_dump_on_panic_async()).expect("failed to run");
+    }
+
+    async fn test_log_stack_retry_async() -> FdbResult<()> {
+        let db = Database::default()?;
+        let prefix = b"test_bindingtester_log_stack".to_vec();
+        let (begin, end) = Subspace::from_bytes(&prefix).range();
+        let trx = db.create_trx()?;
+        trx.clear_range(&begin, &end);
+        trx.commit().await?;
+
+        let mut sm = StackMachine::new(&db, Bytes::from(prefix.clone()));
+        // the first attempt of every batch fails with a retryable error
+        let injected = Arc::new(AtomicUsize::new(0));
+        let injected0 = injected.clone();
+        let attempts = AtomicUsize::new(0);
+        sm.fault_injector = Some(Arc::new(move || {
+            if attempts.fetch_add(1, Ordering::SeqCst) % 2 == 0 {
+                injected0.fetch_add(1, Ordering::SeqCst);
+                Some(FdbError::from_code(1020))
+            } else {
+                None
+            }
+        }));
+
+        let count = LOG_STACK_BATCH + 50;
+        for number in 0..count {
+            sm.push(number, Element::Int(number as i64));
+        }
+        sm.push(count, Element::Bytes(Bytes::from(prefix.clone())));
+        let instr = Instr::from(&pack(&("LOG_STACK",)));
+        assert_eq!(sm.run_step(db.clone(), count + 1, instr).await, Ok(()));
+        assert!(sm.stack.is_empty());
+        assert_eq!(injected.load(Ordering::SeqCst), 2);
+
+        let trx = db.create_trx()?;
+        let opt = RangeOption::from((begin.as_slice(), end.as_slice()));
+        let logged: Vec<_> = trx.get_ranges_keyvalues(opt, false).try_collect().await?;
+        assert_eq!(logged.len(), count);
+
+        let trx = db.create_trx()?;
+        trx.clear_range(&begin, &end);
+        trx.commit().await?;
+        Ok(())
+    }
+
+    async fn test_dump_on_panic_async() -> FdbResult<()> {
+        let db = Database::default()?;
+        let mut sm = StackMachine::new(&db, Bytes::from(b"test_bindingtester_dump".to_vec()));
+
+        // SUB panics on elements that are not integers
+        let instrs = vec![
+            pack(&("PUSH", 1)),
+            pack(&("PUSH", "not an integer")),
+            pack(&("SUB",)),
+        ];
+        let mut dump = None;
+        for (number, instr) in instrs.iter().enumerate() {
+            if let Err(err) = sm
+                .run_step_or_dump(db.clone(), number, Instr::from(instr))
+                .await
+            {
+                dump = Some(err);
+                break;
+            }
+        }
+        let dump = dump.expect("SUB to panic");
+        assert!(dump.contains("instruction 2 [Sub]"), "{}", dump);
+        assert!(dump.contains("sub between invalid elements"), "{}", dump);
+        Ok(())
+    }
+}
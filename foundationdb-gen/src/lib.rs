@@ -1,6 +1,10 @@
 extern crate xml;
 
+use std::borrow::Cow;
 use std::fmt;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
 use xml::attribute::OwnedAttribute;
 use xml::reader::{EventReader, XmlEvent};
 
@@ -35,11 +39,216 @@ impl FdbScope {
 
     fn gen_impl<W: fmt::Write>(&self, w: &mut W) -> fmt::Result {
         writeln!(w, "impl {name} {{", name = self.name)?;
+        self.gen_all(w)?;
         self.gen_code(w)?;
         self.gen_apply(w)?;
+        self.gen_is_settable_after_use(w)?;
+        writeln!(w, "}}")?;
+        self.gen_try_from(w)?;
+        self.gen_from_str(w)
+    }
+
+    fn gen_all<W: fmt::Write>(&self, w: &mut W) -> fmt::Result {
+        writeln!(
+            w,
+            "{t}/// All the variants that don't take a parameter.",
+            t = TAB1
+        )?;
+        writeln!(w, "{t}pub const ALL: &[Self] = &[", t = TAB1)?;
+        for option in self.options.iter() {
+            if !self.has_param(option) {
+                writeln!(
+                    w,
+                    "{t}{scope}::{name},",
+                    t = TAB2,
+                    scope = self.name,
+                    name = option.name
+                )?;
+            }
+        }
+        writeln!(w, "{t}];", t = TAB1)
+    }
+
+    fn gen_try_from<W: fmt::Write>(&self, w: &mut W) -> fmt::Result {
+        writeln!(w, "impl TryFrom<i32> for {name} {{", name = self.name)?;
+        writeln!(w, "{t}type Error = OptionParseError;", t = TAB1)?;
+        writeln!(w)?;
+        writeln!(
+            w,
+            "{t}fn try_from(code: i32) -> Result<Self, Self::Error> {{",
+            t = TAB1
+        )?;
+        writeln!(w, "{t}match code {{", t = TAB2)?;
+        for option in self.options_by_code() {
+            if self.has_param(option) {
+                writeln!(
+                    w,
+                    "{t}{code} => Err(OptionParseError::ParameterRequired(\"{c_name}\")),",
+                    t = TAB3,
+                    code = option.code,
+                    c_name = option.c_name,
+                )?;
+            } else {
+                writeln!(
+                    w,
+                    "{t}{code} => Ok({scope}::{name}),",
+                    t = TAB3,
+                    code = option.code,
+                    scope = self.name,
+                    name = option.name,
+                )?;
+            }
+        }
+        writeln!(
+            w,
+            "{t}code => Err(OptionParseError::UnknownCode(code)),",
+            t = TAB3
+        )?;
+        writeln!(w, "{t}}}", t = TAB2)?;
+        writeln!(w, "{t}}}", t = TAB1)?;
+        writeln!(w, "}}")
+    }
+
+    fn gen_from_str<W: fmt::Write>(&self, w: &mut W) -> fmt::Result {
+        writeln!(w, "impl FromStr for {name} {{", name = self.name)?;
+        writeln!(w, "{t}type Err = OptionParseError;", t = TAB1)?;
+        writeln!(w)?;
+        writeln!(
+            w,
+            "{t}fn from_str(name: &str) -> Result<Self, Self::Err> {{",
+            t = TAB1
+        )?;
+        writeln!(w, "{t}match name {{", t = TAB2)?;
+        for option in self.options.iter() {
+            if self.has_param(option) {
+                writeln!(
+                    w,
+                    "{t}\"{c_name}\" => Err(OptionParseError::ParameterRequired(\"{c_name}\")),",
+                    t = TAB3,
+                    c_name = option.c_name,
+                )?;
+            } else {
+                writeln!(
+                    w,
+                    "{t}\"{c_name}\" => Ok({scope}::{name}),",
+                    t = TAB3,
+                    c_name = option.c_name,
+                    scope = self.name,
+                    name = option.name,
+                )?;
+            }
+        }
+        writeln!(
+            w,
+            "{t}_ => Err(OptionParseError::UnknownName(name.to_string())),",
+            t = TAB3
+        )?;
+        writeln!(w, "{t}}}", t = TAB2)?;
+        writeln!(w, "{t}}}", t = TAB1)?;
         writeln!(w, "}}")
     }
 
+    fn gen_test<W: fmt::Write>(&self, w: &mut W) -> fmt::Result {
+        writeln!(w, "{t}#[test]", t = TAB1)?;
+        writeln!(
+            w,
+            "{t}fn test_{name}_round_trip() {{",
+            t = TAB1,
+            name = to_rs_fn_name(&self.name)
+        )?;
+        for option in self.options_by_code() {
+            writeln!(w, "{t}let code = {code};", t = TAB2, code = option.code)?;
+            if self.has_param(option) {
+                writeln!(
+                    w,
+                    "{t}assert!({scope}::try_from(code).is_err());",
+                    t = TAB2,
+                    scope = self.name,
+                )?;
+            } else {
+                writeln!(
+                    w,
+                    "{t}assert_eq!(i64::from({scope}::try_from(code).unwrap().code()), i64::from(code));",
+                    t = TAB2,
+                    scope = self.name,
+                )?;
+            }
+        }
+        for option in self.options.iter() {
+            if self.has_param(option) {
+                writeln!(
+                    w,
+                    "{t}assert!({scope}::from_str(\"{c_name}\").is_err());",
+                    t = TAB2,
+                    scope = self.name,
+                    c_name = option.c_name,
+                )?;
+            } else {
+                writeln!(
+                    w,
+                    "{t}assert_eq!(i64::from({scope}::from_str(\"{c_name}\").unwrap().code()), {code});",
+                    t = TAB2,
+                    scope = self.name,
+                    c_name = option.c_name,
+                    code = option.code,
+                )?;
+            }
+        }
+        writeln!(
+            w,
+            "{t}for option in {scope}::ALL {{",
+            t = TAB2,
+            scope = self.name
+        )?;
+        writeln!(
+            w,
+            "{t}let code = i32::try_from(i64::from(option.code())).unwrap();",
+            t = TAB3
+        )?;
+        writeln!(
+            w,
+            "{t}assert_eq!(i64::from({scope}::try_from(code).unwrap().code()), i64::from(code));",
+            t = TAB3,
+            scope = self.name,
+        )?;
+        writeln!(w, "{t}}}", t = TAB2)?;
+        writeln!(
+            w,
+            "{t}assert!({scope}::try_from(i32::MAX).is_err());",
+            t = TAB2,
+            scope = self.name,
+        )?;
+        writeln!(
+            w,
+            "{t}assert!({scope}::from_str(\"NOT_AN_OPTION\").is_err());",
+            t = TAB2,
+            scope = self.name,
+        )?;
+        writeln!(w, "{t}}}", t = TAB1)
+    }
+
+    /// Returns `true` if the variant of `option` holds its parameter.
+    fn has_param(&self, option: &FdbOption) -> bool {
+        self.with_ty() && option.get_ty().is_some()
+    }
+
+    /// Returns one option per code, deprecated options are skipped if another option shares
+    /// their code.
+    fn options_by_code(&self) -> Vec<&FdbOption> {
+        let mut options: Vec<&FdbOption> = Vec::new();
+        for option in self.options.iter() {
+            match options.iter_mut().find(|o| o.code == option.code) {
+                Some(o) => {
+                    if o.is_deprecated() && !option.is_deprecated() {
+                        *o = option;
+                    }
+                }
+                None => options.push(option),
+            }
+        }
+        options
+    }
+
     fn gen_code<W: fmt::Write>(&self, w: &mut W) -> fmt::Result {
         writeln!(
             w,
@@ -70,6 +279,21 @@ impl FdbScope {
         }
 
         writeln!(w, "{t}}}", t = TAB2)?;
+        writeln!(w, "{t}}}", t = TAB1)?;
+        writeln!(w)?;
+        writeln!(
+            w,
+            "{t}/// The code of the option, as defined in `fdb.options`.",
+            t = TAB1
+        )?;
+        writeln!(
+            w,
+            "{t}// the type bindgen gives to the C enum depends on the platform",
+            t = TAB1
+        )?;
+        writeln!(w, "{t}#[allow(clippy::unnecessary_cast)]", t = TAB1)?;
+        writeln!(w, "{t}pub fn code_i32(&self) -> i32 {{", t = TAB1)?;
+        writeln!(w, "{t}self.code() as i32", t = TAB2)?;
         writeln!(w, "{t}}}", t = TAB1)
     }
 
@@ -84,6 +308,28 @@ impl FdbScope {
             None => String::new(),
         };
 
+        writeln!(w, "{t}/// Sets the option with the C API.", t = TAB1)?;
+        writeln!(w, "{t}///", t = TAB1)?;
+        writeln!(w, "{t}/// # Safety", t = TAB1)?;
+        writeln!(w, "{t}///", t = TAB1)?;
+        if first_arg.is_empty() {
+            writeln!(
+                w,
+                "{t}/// The API version must have been selected with `fdb_select_api_version`.",
+                t = TAB1
+            )?;
+        } else {
+            writeln!(
+                w,
+                "{t}/// `target` must be a valid pointer to a live `{arg}`.",
+                t = TAB1,
+                arg = self.apply_arg_name().unwrap_or_default()
+            )?;
+        }
+        if first_arg.is_empty() {
+            // the network options are set by the API builders of the crate, in the right order
+            writeln!(w, "{t}#[doc(hidden)]", t = TAB1)?;
+        }
         writeln!(
             w,
             "{t}pub unsafe fn apply(&self{args}) -> FdbResult<()> {{",
@@ -111,14 +357,13 @@ impl FdbScope {
                 }
                 FdbOptionTy::Int => {
                     writeln!(w, "(v) => {{")?;
+                    if let Some(range) = option.int_range() {
+                        range.gen_check(w, &option.c_name)?;
+                    }
+                    writeln!(w, "{}let data: [u8; 8] = v.to_ne_bytes();", TAB4)?;
                     writeln!(
                         w,
-                        "{}let data: [u8;8] = std::mem::transmute(v as i64);",
-                        TAB4,
-                    )?;
-                    writeln!(
-                        w,
-                        "{}fdb_sys::{}({}, data.as_ptr() as *const u8, 8)",
+                        "{}fdb_sys::{}({}, data.as_ptr(), 8)",
                         TAB4, fn_name, args
                     )?;
                     writeln!(w, "{t}}}", t = TAB3)?;
@@ -127,8 +372,8 @@ impl FdbScope {
                     writeln!(w, "(ref v) => {{")?;
                     writeln!(
                         w,
-                        "{}fdb_sys::{}({}, v.as_ptr() as *const u8, \
-                         i32::try_from(v.len()).expect(\"len to fit in i32\"))\n",
+                        "{}fdb_sys::{}({}, v.as_ptr(), \
+                         i32::try_from(v.len()).expect(\"len to fit in i32\"))",
                         TAB4, fn_name, args
                     )?;
                     writeln!(w, "{t}}}", t = TAB3)?;
@@ -137,8 +382,8 @@ impl FdbScope {
                     writeln!(w, "(ref v) => {{")?;
                     writeln!(
                         w,
-                        "{}fdb_sys::{}({}, v.as_ptr() as *const u8, \
-                         i32::try_from(v.len()).expect(\"len to fit in i32\"))\n",
+                        "{}fdb_sys::{}({}, v.as_ptr(), \
+                         i32::try_from(v.len()).expect(\"len to fit in i32\"))",
                         TAB4, fn_name, args
                     )?;
                     writeln!(w, "{t}}}", t = TAB3)?;
@@ -183,6 +428,60 @@ impl FdbScope {
         Some(s)
     }
 
+    fn gen_is_settable_after_use<W: fmt::Write>(&self, w: &mut W) -> fmt::Result {
+        if self.apply_fn_name().is_none() {
+            return Ok(());
+        }
+
+        writeln!(w)?;
+        writeln!(
+            w,
+            "{t}/// Returns `false` if the option must be set before the first use of its target, as",
+            t = TAB1
+        )?;
+        writeln!(
+            w,
+            "{t}/// stated by its documentation. Options without such a statement return `true`.",
+            t = TAB1
+        )?;
+        if self
+            .options
+            .iter()
+            .any(|option| !option.is_settable_after_use())
+        {
+            // `matches!` requires Rust 1.42
+            writeln!(w, "{t}#[allow(clippy::match_like_matches_macro)]", t = TAB1)?;
+        }
+        writeln!(
+            w,
+            "{t}pub fn is_settable_after_use(&self) -> bool {{",
+            t = TAB1
+        )?;
+        let before_use: Vec<&FdbOption> = self
+            .options
+            .iter()
+            .filter(|option| !option.is_settable_after_use())
+            .collect();
+        if before_use.is_empty() {
+            writeln!(w, "{t}true", t = TAB2)?;
+        } else {
+            writeln!(w, "{t}match *self {{", t = TAB2)?;
+            for option in before_use {
+                writeln!(
+                    w,
+                    "{t}{scope}::{name}{param} => false,",
+                    t = TAB3,
+                    scope = self.name,
+                    name = option.name,
+                    param = if self.has_param(option) { "(..)" } else { "" },
+                )?;
+            }
+            writeln!(w, "{t}_ => true,", t = TAB3)?;
+            writeln!(w, "{t}}}", t = TAB2)?;
+        }
+        writeln!(w, "{t}}}", t = TAB1)
+    }
+
     fn apply_fn_name(&self) -> Option<&'static str> {
         let s = match self.name.as_str() {
             "NetworkOption" => "fdb_network_set_option",
@@ -229,6 +528,21 @@ impl FdbOption {
         }
         if !self.description.is_empty() {
             writeln!(w, "{t}/// {desc}", t = TAB1, desc = self.description)?;
+            writeln!(w, "{t}///", t = TAB1)?;
+        }
+        writeln!(
+            w,
+            "{t}/// Option code: `{code}`",
+            t = TAB1,
+            code = self.code
+        )?;
+        if self.is_deprecated() {
+            writeln!(
+                w,
+                "{t}#[deprecated(note = {note:?})]",
+                t = TAB1,
+                note = self.description
+            )?;
         }
 
         if let (true, Some(ty)) = (with_ty, self.get_ty()) {
@@ -239,9 +553,27 @@ impl FdbOption {
         Ok(())
     }
 
+    fn is_deprecated(&self) -> bool {
+        self.description.starts_with("Deprecated")
+    }
+
+    /// Returns the range of valid values documented by the parameter description or the
+    /// description of an Int option.
+    fn int_range(&self) -> Option<IntRange> {
+        IntRange::parse(&self.param_description).or_else(|| IntRange::parse(&self.description))
+    }
+
+    /// Returns `false` if the description states that the option must be set before the first
+    /// use of its target, e.g. "Must be set before setting up the network".
+    fn is_settable_after_use(&self) -> bool {
+        let description = self.description.to_lowercase();
+        !(description.contains("must be set before setting up")
+            || description.contains("error to set this option after"))
+    }
+
     fn get_ty(&self) -> Option<&'static str> {
         match self.param_type {
-            FdbOptionTy::Int => Some("i32"),
+            FdbOptionTy::Int => Some("i64"),
             FdbOptionTy::Str => Some("String"),
             FdbOptionTy::Bytes => Some("Vec<u8>"),
             FdbOptionTy::Empty => None,
@@ -249,6 +581,102 @@ impl FdbOption {
     }
 }
 
+/// The documented range of valid values of an Int option, checked before applying the option.
+#[derive(Debug, PartialEq, Eq)]
+struct IntRange {
+    min: i64,
+    max: i64,
+    /// The range as written in the documentation, e.g. `[-1, INT_MAX]`
+    text: String,
+}
+
+impl IntRange {
+    /// Parses the first range in `desc` written as `[min, max]`, `between min and max` or
+    /// `cannot be larger than max`.
+    ///
+    /// Each shape is tried in turn, a bracket that is not a range falling through to the others.
+    fn parse(desc: &str) -> Option<Self> {
+        Self::parse_brackets(desc)
+            .or_else(|| Self::parse_between(desc))
+            .or_else(|| Self::parse_larger_than(desc))
+    }
+
+    fn parse_brackets(desc: &str) -> Option<Self> {
+        let start = desc.find('[')?;
+        let end = start + desc[start..].find(']')?;
+        let mut bounds = desc[start + 1..end].split(',');
+        let min = parse_bound(bounds.next()?)?;
+        let max = parse_bound(bounds.next()?)?;
+        if bounds.next().is_some() {
+            return None;
+        }
+        Some(IntRange {
+            min,
+            max,
+            text: desc[start..=end].to_string(),
+        })
+    }
+
+    fn parse_between(desc: &str) -> Option<Self> {
+        let start = desc.find("between ")?;
+        let mut words = desc[start + "between ".len()..].split_whitespace();
+        let min = parse_bound(words.next()?)?;
+        if words.next()? != "and" {
+            return None;
+        }
+        let max = parse_bound(words.next()?)?;
+        Some(IntRange::new(min, max))
+    }
+
+    fn parse_larger_than(desc: &str) -> Option<Self> {
+        let start = desc.find("cannot be larger than ")?;
+        let max = desc[start + "cannot be larger than ".len()..]
+            .split_whitespace()
+            .next()?;
+        // the values documented this way are counts
+        Some(IntRange::new(0, parse_bound(max)?))
+    }
+
+    fn new(min: i64, max: i64) -> Self {
+        IntRange {
+            min,
+            max,
+            text: format!("[{}, {}]", min, max),
+        }
+    }
+
+    /// Emits the check of the value `v`, failing with `invalid_option_value` (2006).
+    fn gen_check<W: fmt::Write>(&self, w: &mut W, c_name: &str) -> fmt::Result {
+        writeln!(
+            w,
+            "{t}if !({min}..={max}).contains(&v) {{",
+            t = TAB4,
+            min = self.min,
+            max = self.max
+        )?;
+        writeln!(
+            w,
+            "{t}return Err(FdbError::from_code(2006).with_context(format!(\"{c_name} value {{}} is out of range {range}\", v)));",
+            t = TAB4,
+            c_name = c_name,
+            range = self.text,
+        )?;
+        writeln!(w, "{t}}}", t = TAB4)
+    }
+}
+
+/// Parses a bound of a documented range, e.g. `-1`, `INT_MAX` or `100.`
+fn parse_bound(bound: &str) -> Option<i64> {
+    let bound = bound.trim().trim_end_matches(&['.', ','][..]);
+    match bound {
+        "INT_MIN" => Some(i64::from(i32::MIN)),
+        "INT_MAX" => Some(i64::from(i32::MAX)),
+        "INT64_MIN" => Some(i64::MIN),
+        "INT64_MAX" => Some(i64::MAX),
+        _ => bound.parse().ok(),
+    }
+}
+
 fn to_rs_enum_name(v: &str) -> String {
     let mut is_start_of_word = true;
     v.chars()
@@ -266,6 +694,17 @@ fn to_rs_enum_name(v: &str) -> String {
         .collect()
 }
 
+fn to_rs_fn_name(v: &str) -> String {
+    let mut name = String::new();
+    for c in v.chars() {
+        if c.is_ascii_uppercase() && !name.is_empty() {
+            name.push('_');
+        }
+        name.push(c.to_ascii_lowercase());
+    }
+    name
+}
+
 impl From<Vec<OwnedAttribute>> for FdbOption {
     fn from(attrs: Vec<OwnedAttribute>) -> Self {
         let mut opt = Self::default();
@@ -346,29 +785,160 @@ where
     panic!("unexpected end of token");
 }
 
-#[cfg(all(not(feature = "embedded-fdb-include"), target_os = "linux"))]
-const OPTIONS_DATA: &[u8] = include_bytes!("/usr/include/foundationdb/fdb.options");
+#[cfg(target_os = "linux")]
+const INSTALLED_OPTIONS_PATH: &str = "/usr/include/foundationdb/fdb.options";
+#[cfg(target_os = "macos")]
+const INSTALLED_OPTIONS_PATH: &str = "/usr/local/include/foundationdb/fdb.options";
+#[cfg(target_os = "windows")]
+const INSTALLED_OPTIONS_PATH: &str =
+    "C:/Program Files/foundationdb/include/foundationdb/fdb.options";
+#[cfg(not(any(target_os = "linux", target_os = "macos", target_os = "windows")))]
+const INSTALLED_OPTIONS_PATH: &str = "/usr/include/foundationdb/fdb.options";
+
+#[cfg(feature = "fdb-5_1")]
+const VENDORED_OPTIONS: Option<&[u8]> = Some(include_bytes!("../include/510/fdb.options"));
+#[cfg(feature = "fdb-5_2")]
+const VENDORED_OPTIONS: Option<&[u8]> = Some(include_bytes!("../include/520/fdb.options"));
+#[cfg(feature = "fdb-6_0")]
+const VENDORED_OPTIONS: Option<&[u8]> = Some(include_bytes!("../include/600/fdb.options"));
+#[cfg(feature = "fdb-6_1")]
+const VENDORED_OPTIONS: Option<&[u8]> = Some(include_bytes!("../include/610/fdb.options"));
+#[cfg(feature = "fdb-6_2")]
+const VENDORED_OPTIONS: Option<&[u8]> = Some(include_bytes!("../include/620/fdb.options"));
+#[cfg(feature = "fdb-7_1")]
+const VENDORED_OPTIONS: Option<&[u8]> = Some(include_bytes!("../include/710/fdb.options"));
+#[cfg(not(any(
+    feature = "fdb-5_1",
+    feature = "fdb-5_2",
+    feature = "fdb-6_0",
+    feature = "fdb-6_1",
+    feature = "fdb-6_2",
+    feature = "fdb-7_1"
+)))]
+const VENDORED_OPTIONS: Option<&[u8]> = None;
+
+/// The `fdb.options` file read by `emit`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum OptionsSource {
+    /// The file installed with the FoundationDB client at this path
+    Installed(PathBuf),
+    /// The copy vendored for the selected API version, as requested by the
+    /// `embedded-fdb-include` feature
+    Vendored,
+    /// The copy vendored for the selected API version, because the FoundationDB client is not
+    /// installed at this path
+    Fallback(PathBuf),
+}
+
+impl OptionsSource {
+    /// Returns the installed file if there is one, or the vendored copy otherwise.
+    pub fn detect() -> Self {
+        if cfg!(feature = "embedded-fdb-include") {
+            return OptionsSource::Vendored;
+        }
+        let path = PathBuf::from(INSTALLED_OPTIONS_PATH);
+        if path.is_file() {
+            OptionsSource::Installed(path)
+        } else {
+            OptionsSource::Fallback(path)
+        }
+    }
+
+    /// Returns the content of the file.
+    fn read(&self) -> io::Result<Cow<'static, [u8]>> {
+        match self {
+            OptionsSource::Installed(path) => std::fs::read(path).map(Cow::Owned),
+            OptionsSource::Vendored | OptionsSource::Fallback(..) => match VENDORED_OPTIONS {
+                Some(data) => Ok(Cow::Borrowed(data)),
+                None => Err(io::Error::new(
+                    io::ErrorKind::NotFound,
+                    "no fdb.options is vendored without a fdb-* feature",
+                )),
+            },
+        }
+    }
+}
 
-#[cfg(all(not(feature = "embedded-fdb-include"), target_os = "macos"))]
-const OPTIONS_DATA: &[u8] = include_bytes!("/usr/local/include/foundationdb/fdb.options");
+const OPTION_PARSE_ERROR: &str = r#"
+/// The error returned when an option cannot be built from its code or its name.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum OptionParseError {
+    /// No option has this code.
+    UnknownCode(i32),
+    /// No option has this name.
+    UnknownName(String),
+    /// The option with this name takes a parameter.
+    ParameterRequired(&'static str),
+}
 
-#[cfg(all(not(feature = "embedded-fdb-include"), target_os = "windows"))]
-const OPTIONS_DATA: &[u8] =
-    include_bytes!("C:/Program Files/foundationdb/include/foundationdb/fdb.options");
+impl std::fmt::Display for OptionParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            OptionParseError::UnknownCode(code) => write!(f, "unknown option code {}", code),
+            OptionParseError::UnknownName(name) => write!(f, "unknown option name {:?}", name),
+            OptionParseError::ParameterRequired(name) => {
+                write!(f, "option {} requires a parameter", name)
+            }
+        }
+    }
+}
 
-#[cfg(all(feature = "embedded-fdb-include", feature = "fdb-5_1"))]
-const OPTIONS_DATA: &[u8] = include_bytes!("../include/510/fdb.options");
-#[cfg(all(feature = "embedded-fdb-include", feature = "fdb-5_2"))]
-const OPTIONS_DATA: &[u8] = include_bytes!("../include/520/fdb.options");
-#[cfg(all(feature = "embedded-fdb-include", feature = "fdb-6_0"))]
-const OPTIONS_DATA: &[u8] = include_bytes!("../include/600/fdb.options");
-#[cfg(all(feature = "embedded-fdb-include", feature = "fdb-6_1"))]
-const OPTIONS_DATA: &[u8] = include_bytes!("../include/610/fdb.options");
-#[cfg(all(feature = "embedded-fdb-include", feature = "fdb-6_2"))]
-const OPTIONS_DATA: &[u8] = include_bytes!("../include/620/fdb.options");
+impl std::error::Error for OptionParseError {}
+"#;
 
+/// Emits the options of the `fdb.options` file found by `OptionsSource::detect`.
+///
+/// # Panics
+///
+/// Panics if the file cannot be read.
 pub fn emit(w: &mut impl fmt::Write) -> fmt::Result {
-    let mut reader = OPTIONS_DATA;
+    emit_from(&OptionsSource::detect(), w)
+}
+
+/// Emits the options of the `fdb.options` file of `source`.
+///
+/// # Panics
+///
+/// Panics if the file cannot be read.
+pub fn emit_from(source: &OptionsSource, w: &mut impl fmt::Write) -> fmt::Result {
+    let data = source
+        .read()
+        .unwrap_or_else(|err| panic!("couldn't read fdb.options from {:?}: {}", source, err));
+    emit_options(&data, w)
+}
+
+/// Formats `code` with `rustfmt`, or returns it as is if `rustfmt` is not available.
+///
+/// The `RUSTFMT` environment variable overrides the `rustfmt` binary, like for cargo.
+pub fn rustfmt(code: String) -> String {
+    let rustfmt = std::env::var_os("RUSTFMT").unwrap_or_else(|| "rustfmt".into());
+    run_rustfmt(Path::new(&rustfmt), &code).unwrap_or(code)
+}
+
+/// Returns the output of `rustfmt` given `code`, or `None` if it failed.
+fn run_rustfmt(rustfmt: &Path, code: &str) -> Option<String> {
+    let mut child = Command::new(rustfmt)
+        .arg("--edition")
+        .arg("2018")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()
+        .ok()?;
+    let mut stdin = child.stdin.take()?;
+    let input = code.to_string();
+    // rustfmt may fill its output pipe before reading all its input
+    let writer = std::thread::spawn(move || stdin.write_all(input.as_bytes()));
+    let output = child.wait_with_output().ok()?;
+    writer.join().ok()?.ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    String::from_utf8(output.stdout).ok()
+}
+
+/// Emits the options described by the content of a `fdb.options` file.
+fn emit_options(mut reader: &[u8], w: &mut impl fmt::Write) -> fmt::Result {
     let parser = EventReader::new(&mut reader);
     let mut iter = parser.into_iter();
     let mut scopes = Vec::new();
@@ -399,12 +969,160 @@ pub fn emit(w: &mut impl fmt::Write) -> fmt::Result {
     }
 
     writeln!(w, "use std::convert::TryFrom;")?;
+    writeln!(w, "use std::str::FromStr;")?;
     writeln!(w, "use crate::{{FdbError, FdbResult}};")?;
     writeln!(w, "use foundationdb_sys as fdb_sys;")?;
+    w.write_str(OPTION_PARSE_ERROR)?;
     for scope in scopes.iter() {
         scope.gen_ty(w)?;
         scope.gen_impl(w)?;
     }
 
+    writeln!(w, "#[cfg(test)]")?;
+    writeln!(w, "mod tests {{")?;
+    writeln!(w, "{t}use super::*;", t = TAB1)?;
+    for scope in scopes.iter() {
+        writeln!(w)?;
+        scope.gen_test(w)?;
+    }
+    writeln!(w, "}}")?;
+
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const OPTIONS: &str = r#"<?xml version="1.0"?>
+<Options>
+    <Scope name="TransactionOption">
+        <Option name="causal_write_risky" code="10"
+            description="The transaction, if not self-conflicting, may be committed a second time after commit succeeds, in the event of a fault"/>
+        <Option name="durability_dev_null_is_web_scale" code="130"
+            description="Deprecated"/>
+        <Option name="timeout" code="500"
+            paramType="Int" paramDescription="value in milliseconds of timeout"
+            description="Set a timeout in milliseconds which, when elapsed, will cause the transaction automatically to be cancelled."/>
+        <Option name="retry_limit" code="501"
+            paramType="Int" paramDescription="number of times to retry"
+            description="Set a maximum number of retries. Valid parameter values are ``[-1, INT_MAX]``."/>
+        <Option name="priority_system_immediate" code="200"
+            description="Specifies that this transaction should be treated as highest priority and that lower priority transactions should block behind this one. It is an error to set this option after performing any reads or writes on the transaction."/>
+        <Option name="secret" code="900" hidden="true"/>
+    </Scope>
+</Options>
+"#;
+
+    fn emitted() -> String {
+        let mut out = String::new();
+        emit_options(OPTIONS.as_bytes(), &mut out).expect("failed to emit options");
+        out
+    }
+
+    #[test]
+    fn test_emit_doc_comments() {
+        let out = emitted();
+        assert!(out.contains(
+            "    /// The transaction, if not self-conflicting, may be committed a second time after commit succeeds, in the event of a fault\n    ///\n    /// Option code: `10`\n    CausalWriteRisky,\n"
+        ));
+        assert!(out.contains(
+            "    /// value in milliseconds of timeout\n    ///\n    /// Set a timeout in milliseconds which, when elapsed, will cause the transaction automatically to be cancelled.\n    ///\n    /// Option code: `500`\n    Timeout(i64),\n"
+        ));
+        assert!(!out.contains("Secret"));
+    }
+
+    #[test]
+    fn test_emit_is_settable_after_use() {
+        let out = emitted();
+        assert!(out.contains(
+            "    pub fn is_settable_after_use(&self) -> bool {\n        match *self {\n            TransactionOption::PrioritySystemImmediate => false,\n            _ => true,\n        }\n    }\n"
+        ));
+    }
+
+    #[test]
+    fn test_emit_deprecated() {
+        let out = emitted();
+        assert!(out.contains(
+            "    /// Option code: `130`\n    #[deprecated(note = \"Deprecated\")]\n    DurabilityDevNullIsWebScale,\n"
+        ));
+        assert_eq!(out.matches("#[deprecated").count(), 1);
+    }
+
+    #[test]
+    fn test_int_range() {
+        assert_eq!(
+            IntRange::parse("Valid parameter values are ``[-1, INT_MAX]``."),
+            Some(IntRange {
+                min: -1,
+                max: i64::from(i32::MAX),
+                text: "[-1, INT_MAX]".to_string(),
+            })
+        );
+        assert_eq!(
+            IntRange::parse("probability expressed as a percentage between 0 and 100"),
+            Some(IntRange::new(0, 100))
+        );
+        assert_eq!(
+            IntRange::parse("Defaults to 10000 and cannot be larger than 1000000."),
+            Some(IntRange::new(0, 1_000_000))
+        );
+        // a bracket that is not a range doesn't hide the other shapes
+        assert_eq!(
+            IntRange::parse("[in percent] between 0 and 100"),
+            Some(IntRange::new(0, 100))
+        );
+        assert_eq!(
+            IntRange::parse("[count] cannot be larger than 1000."),
+            Some(IntRange::new(0, 1000))
+        );
+        assert_eq!(
+            IntRange::parse("[unterminated between 1 and 10"),
+            Some(IntRange::new(1, 10))
+        );
+        assert_eq!(IntRange::parse("[release version],[source version]"), None);
+        assert_eq!(IntRange::parse("value in milliseconds of timeout"), None);
+    }
+
+    #[test]
+    fn test_emit_int_range_check() {
+        let out = emitted();
+        assert!(out.contains(
+            "            TransactionOption::RetryLimit(v) => {\n                if !(-1..=2147483647).contains(&v) {\n                return Err(FdbError::from_code(2006).with_context(format!(\"RETRY_LIMIT value {} is out of range [-1, INT_MAX]\", v)));\n"
+        ));
+        // options without a documented range are not checked
+        assert!(out.contains(
+            "            TransactionOption::Timeout(v) => {\n                let data: [u8; 8] = v.to_ne_bytes();\n"
+        ));
+    }
+
+    #[test]
+    fn test_emit_vendored() {
+        for data in &[
+            &include_bytes!("../include/510/fdb.options")[..],
+            &include_bytes!("../include/520/fdb.options")[..],
+            &include_bytes!("../include/600/fdb.options")[..],
+            &include_bytes!("../include/610/fdb.options")[..],
+            &include_bytes!("../include/620/fdb.options")[..],
+            &include_bytes!("../include/710/fdb.options")[..],
+        ] {
+            let mut out = String::new();
+            emit_options(data, &mut out).expect("failed to emit options");
+            assert!(out.contains("pub enum TransactionOption {"));
+            assert!(out.contains("    /// # Safety\n"));
+            assert!(out.contains(
+                "    #[doc(hidden)]\n    pub unsafe fn apply(&self) -> FdbResult<()> {\n"
+            ));
+        }
+    }
+
+    #[test]
+    fn test_emit_code_i32() {
+        let out = emitted();
+        assert!(out.contains(
+            "    pub fn code(&self) -> fdb_sys::FDBTransactionOption {\n        match *self {\n            TransactionOption::CausalWriteRisky => fdb_sys::FDBTransactionOption_FDB_TR_OPTION_CAUSAL_WRITE_RISKY,\n"
+        ));
+        assert!(out
+            .contains("    pub fn code_i32(&self) -> i32 {\n        self.code() as i32\n    }\n"));
+    }
+}
@@ -24,6 +24,8 @@ const INCLUDE_PATH: &str = "-I./include/600";
 const INCLUDE_PATH: &str = "-I./include/610";
 #[cfg(all(feature = "embedded-fdb-include", feature = "fdb-6_2"))]
 const INCLUDE_PATH: &str = "-I./include/620";
+#[cfg(all(feature = "embedded-fdb-include", feature = "fdb-7_1"))]
+const INCLUDE_PATH: &str = "-I./include/710";
 
 fn main() {
     // Link against fdb_c.
@@ -63,6 +65,10 @@ fn main() {
     {
         api_version = 620;
     }
+    #[cfg(feature = "fdb-7_1")]
+    {
+        api_version = 710;
+    }
 
     // Sigh, bindgen only takes a String for its header path, but that's UTF-8 while
     // PathBuf is OS-native...
@@ -3,3 +3,27 @@
 #![allow(non_snake_case)]
 #![allow(clippy::unreadable_literal)]
 include!(concat!(env!("OUT_DIR"), "/bindings.rs"));
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // the versions of the features, written independently of the build script
+    #[cfg(feature = "fdb-5_1")]
+    const FEATURE_API_VERSION: u32 = 510;
+    #[cfg(feature = "fdb-5_2")]
+    const FEATURE_API_VERSION: u32 = 520;
+    #[cfg(feature = "fdb-6_0")]
+    const FEATURE_API_VERSION: u32 = 600;
+    #[cfg(feature = "fdb-6_1")]
+    const FEATURE_API_VERSION: u32 = 610;
+    #[cfg(feature = "fdb-6_2")]
+    const FEATURE_API_VERSION: u32 = 620;
+    #[cfg(feature = "fdb-7_1")]
+    const FEATURE_API_VERSION: u32 = 710;
+
+    #[test]
+    fn test_api_version() {
+        assert_eq!(FDB_API_VERSION, FEATURE_API_VERSION);
+    }
+}
@@ -0,0 +1,87 @@
+// Copyright 2018 foundationdb-rs developers, https://github.com/Clikengo/foundationdb-rs/graphs/contributors
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use std::borrow::Cow;
+
+use foundationdb::tuple::{pack, pack_into, unpack, Element, Subspace, TuplePack};
+
+fn bench_pack(c: &mut Criterion) {
+    let subspace = Subspace::from(("bench", "pack"));
+    let tuple = ("user", 42i64, "some\x00name", true);
+
+    let mut group = c.benchmark_group("pack");
+    group.bench_function("pack", |b| b.iter(|| pack(black_box(&tuple))));
+    group.bench_function("pack_into", |b| {
+        let mut buf = Vec::with_capacity(64);
+        b.iter(|| {
+            buf.clear();
+            pack_into(black_box(&tuple), &mut buf);
+        })
+    });
+    group.bench_function("pack_into_slice", |b| {
+        let mut buf = [0u8; 64];
+        b.iter(|| black_box(&tuple).pack_into_slice(&mut buf).unwrap())
+    });
+    group.bench_function("pack_size_hint", |b| {
+        b.iter(|| black_box(&tuple).pack_size_hint())
+    });
+    group.finish();
+
+    let mut group = c.benchmark_group("subspace_pack");
+    group.bench_function("pack", |b| b.iter(|| subspace.pack(black_box(&tuple))));
+    group.bench_function("pack_into_slice", |b| {
+        let mut buf = [0u8; 64];
+        b.iter(|| {
+            subspace
+                .pack_into_slice(black_box(&tuple), &mut buf)
+                .unwrap()
+        })
+    });
+    group.bench_function("unpack", |b| {
+        let key = subspace.pack(&tuple);
+        b.iter(|| {
+            subspace
+                .unpack::<(Cow<str>, i64, Cow<str>, bool)>(black_box(&key))
+                .unwrap()
+        })
+    });
+    group.finish();
+}
+
+fn bench_unpack(c: &mut Criterion) {
+    let flat = pack(&("user", 42i64, "some\x00name", true));
+    let nested = pack(&(
+        "index",
+        ("nested", -1i64, 3.5f64),
+        vec![1i64, 2, 3, 4],
+        Some("optional"),
+    ));
+
+    let mut group = c.benchmark_group("unpack");
+    group.bench_function("owned", |b| {
+        b.iter(|| unpack::<(String, i64, String, bool)>(black_box(&flat)).unwrap())
+    });
+    group.bench_function("borrowed", |b| {
+        b.iter(|| unpack::<(Cow<str>, i64, Cow<str>, bool)>(black_box(&flat)).unwrap())
+    });
+    group.bench_function("nested", |b| {
+        b.iter(|| {
+            unpack::<(Cow<str>, (Cow<str>, i64, f64), Vec<i64>, Option<Cow<str>>)>(black_box(
+                &nested,
+            ))
+            .unwrap()
+        })
+    });
+    group.bench_function("element", |b| {
+        b.iter(|| unpack::<Element>(black_box(&nested)).unwrap())
+    });
+    group.finish();
+}
+
+criterion_group!(benches, bench_pack, bench_unpack);
+criterion_main!(benches);
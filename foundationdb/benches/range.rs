@@ -0,0 +1,127 @@
+// Copyright 2018 foundationdb-rs developers, https://github.com/Clikengo/foundationdb-rs/graphs/contributors
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! Streams 100k keys with `get_ranges`, and copies them with `get_ranges_keyvalues` and
+//! `get_range_into`, requires a running cluster.
+//!
+//! Skipped unless `FDB_BENCH_CLUSTER` is set, to the path of a cluster file or to an empty string
+//! to use the default cluster file.
+
+use criterion::{black_box, Criterion, Throughput};
+use foundationdb::future::KeyValueBuffer;
+use foundationdb::options::StreamingMode;
+use foundationdb::testing::data::DataSet;
+use foundationdb::{Database, RangeOption};
+use futures::executor::block_on;
+use futures::prelude::*;
+
+const COUNT: usize = 100_000;
+
+async fn stream_range(db: &Database, data: &DataSet, mode: StreamingMode) -> usize {
+    let trx = db.create_trx().expect("failed to create transaction");
+    let (begin, end) = data.range();
+    let opt = RangeOption {
+        mode,
+        ..RangeOption::from((begin.as_slice(), end.as_slice()))
+    };
+    trx.get_ranges(opt, true)
+        .try_fold(0, |count, values| future::ok(count + values.len()))
+        .await
+        .expect("failed to read range")
+}
+
+fn range_opt(data: &DataSet) -> RangeOption<'static> {
+    let (begin, end) = data.range();
+    RangeOption {
+        mode: StreamingMode::WantAll,
+        ..RangeOption::from((begin, end))
+    }
+}
+
+/// Owns each keyvalue, as an application keeping the rows it read would
+async fn copy_keyvalues(db: &Database, data: &DataSet) -> Vec<(Vec<u8>, Vec<u8>)> {
+    let trx = db.create_trx().expect("failed to create transaction");
+    trx.get_ranges_keyvalues(range_opt(data), true)
+        .map_ok(|kv| (kv.key().to_vec(), kv.value().to_vec()))
+        .try_collect()
+        .await
+        .expect("failed to read range")
+}
+
+async fn copy_into(db: &Database, data: &DataSet, buffer: &mut KeyValueBuffer) {
+    let trx = db.create_trx().expect("failed to create transaction");
+    buffer.clear();
+    trx.get_range_into(range_opt(data), true, buffer)
+        .await
+        .expect("failed to read range")
+}
+
+fn bench_range(c: &mut Criterion, db: &Database) {
+    let data = DataSet::new(b"\x02bench_range", COUNT);
+    block_on(data.clear(db)).expect("failed to clear data set");
+    block_on(data.write(db)).expect("failed to write data set");
+
+    let mut group = c.benchmark_group("get_ranges");
+    group.throughput(Throughput::Elements(COUNT as u64));
+    group.sample_size(10);
+    for (name, mode) in &[
+        ("iterator", StreamingMode::Iterator),
+        ("want_all", StreamingMode::WantAll),
+        ("serial", StreamingMode::Serial),
+    ] {
+        group.bench_function(*name, |b| {
+            b.iter(|| {
+                let count = block_on(stream_range(db, &data, black_box(mode.clone())));
+                assert_eq!(count, COUNT);
+            })
+        });
+    }
+    group.finish();
+
+    let mut group = c.benchmark_group("copy_range");
+    group.throughput(Throughput::Elements(COUNT as u64));
+    group.sample_size(10);
+    group.bench_function("get_ranges_keyvalues", |b| {
+        b.iter(|| {
+            let rows = block_on(copy_keyvalues(db, &data));
+            assert_eq!(black_box(rows).len(), COUNT);
+        })
+    });
+    let mut buffer = KeyValueBuffer::new();
+    group.bench_function("get_range_into", |b| {
+        b.iter(|| {
+            block_on(copy_into(db, &data, &mut buffer));
+            assert_eq!(black_box(&buffer).len(), COUNT);
+        })
+    });
+    group.finish();
+
+    block_on(data.clear(db)).expect("failed to clear data set");
+}
+
+fn main() {
+    let cluster_file = match std::env::var("FDB_BENCH_CLUSTER") {
+        Ok(cluster_file) => cluster_file,
+        Err(..) => {
+            eprintln!("FDB_BENCH_CLUSTER is not set, skipping the range benchmarks");
+            return;
+        }
+    };
+
+    foundationdb::run(|| {
+        let db = if cluster_file.is_empty() {
+            Database::default()
+        } else {
+            Database::from_path(&cluster_file)
+        }
+        .expect("failed to open database");
+
+        let mut criterion = Criterion::default().configure_from_args();
+        bench_range(&mut criterion, &db);
+        criterion.final_summary();
+    });
+}
@@ -0,0 +1,92 @@
+// Copyright 2018 foundationdb-rs developers, https://github.com/Clikengo/foundationdb-rs/graphs/contributors
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! Measures the overhead of the `transact` retry loop when there is no conflict, requires a
+//! running cluster.
+//!
+//! Skipped unless `FDB_BENCH_CLUSTER` is set, to the path of a cluster file or to an empty string
+//! to use the default cluster file.
+
+use criterion::Criterion;
+use foundationdb::{Database, FdbError, TransactOption};
+use futures::executor::block_on;
+use futures::prelude::*;
+
+const KEY: &[u8] = b"\x02bench_transact";
+
+fn bench_transact(c: &mut Criterion, db: &Database) {
+    let mut group = c.benchmark_group("transact");
+    group.bench_function("manual", |b| {
+        b.iter(|| {
+            block_on(async {
+                let trx = db.create_trx()?;
+                trx.set(KEY, b"manual");
+                trx.commit().await?;
+                Ok::<_, FdbError>(())
+            })
+            .expect("failed to commit")
+        })
+    });
+    group.bench_function("transact_boxed", |b| {
+        b.iter(|| {
+            block_on(db.transact_boxed(
+                (),
+                |trx, ()| {
+                    trx.set(KEY, b"transact_boxed");
+                    future::ok::<_, FdbError>(()).boxed()
+                },
+                TransactOption::default(),
+            ))
+            .expect("failed to commit")
+        })
+    });
+    group.bench_function("transact_boxed_idempotent", |b| {
+        b.iter(|| {
+            block_on(db.transact_boxed(
+                (),
+                |trx, ()| {
+                    trx.set(KEY, b"transact_boxed_idempotent");
+                    future::ok::<_, FdbError>(()).boxed()
+                },
+                TransactOption::idempotent(),
+            ))
+            .expect("failed to commit")
+        })
+    });
+    group.finish();
+
+    block_on(async {
+        let trx = db.create_trx()?;
+        trx.clear(KEY);
+        trx.commit().await?;
+        Ok::<_, FdbError>(())
+    })
+    .expect("failed to clear key");
+}
+
+fn main() {
+    let cluster_file = match std::env::var("FDB_BENCH_CLUSTER") {
+        Ok(cluster_file) => cluster_file,
+        Err(..) => {
+            eprintln!("FDB_BENCH_CLUSTER is not set, skipping the transact benchmarks");
+            return;
+        }
+    };
+
+    foundationdb::run(|| {
+        let db = if cluster_file.is_empty() {
+            Database::default()
+        } else {
+            Database::from_path(&cluster_file)
+        }
+        .expect("failed to open database");
+
+        let mut criterion = Criterion::default().configure_from_args();
+        bench_transact(&mut criterion, &db);
+        criterion.final_summary();
+    });
+}
@@ -5,11 +5,30 @@ use std::fs::File;
 use std::io::prelude::*;
 use std::path::PathBuf;
 
+use foundationdb_gen::OptionsSource;
+
 fn main() {
     let out_path = PathBuf::from(env::var("OUT_DIR").expect("OUT_DIR is undefined!"));
     let options_file = out_path.join("options.rs");
+
+    let source = OptionsSource::detect();
+    match &source {
+        OptionsSource::Installed(path) => {
+            println!("cargo:rerun-if-changed={}", path.display());
+        }
+        OptionsSource::Vendored => {}
+        OptionsSource::Fallback(path) => {
+            println!(
+                "cargo:warning={} not found, using the fdb.options vendored for the selected API version",
+                path.display()
+            );
+        }
+    }
+    println!("cargo:rerun-if-env-changed=RUSTFMT");
+
     let mut options = String::new();
-    foundationdb_gen::emit(&mut options).expect("couldn't emit options.rs code!");
+    foundationdb_gen::emit_from(&source, &mut options).expect("couldn't emit options.rs code!");
+    let options = foundationdb_gen::rustfmt(options);
 
     File::create(options_file)
         .expect("couldn't create options.rs!")
@@ -0,0 +1,553 @@
+// Copyright 2018 foundationdb-rs developers, https://github.com/Clikengo/foundationdb-rs/graphs/contributors
+// Copyright 2013-2018 Apple, Inc and the FoundationDB project authors.
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! Port of the [class scheduling tutorial](https://apple.github.io/foundationdb/class-scheduling.html).
+//!
+//! ```console
+//! $> cargo run --example class_scheduling
+//! ```
+//!
+//! The `attends` and `class` subspaces are directories of the directory layer, the seats left in
+//! each class are little-endian counters updated by atomic additions, and every operation runs
+//! in the retry loop of `Database::transact_boxed_local`. After a simulation of concurrent
+//! students, the scheduling invariants are checked: no class is over capacity, and the seats
+//! left match the schedules of the students.
+//!
+//! The core functions of this example are also used by `tests/class_scheduling.rs`, which runs
+//! the same checks after a larger randomized workload.
+
+use std::collections::HashMap;
+use std::thread;
+
+use futures::prelude::*;
+use rand::{rngs::ThreadRng, seq::SliceRandom};
+
+use foundationdb as fdb;
+use foundationdb::directory::{Directory, DirectoryError, DirectoryLayer};
+use foundationdb::options::MutationType;
+use foundationdb::tuple::{pack, Subspace};
+use foundationdb::{Database, FdbError, TransactError, TransactOption, Transaction};
+
+/// Maximum number of classes a student can attend.
+pub const MAX_CLASSES_PER_STUDENT: usize = 5;
+
+/// Number of seats of each class in the tutorial.
+pub const SEATS_PER_CLASS: i64 = 100;
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+#[derive(Debug)]
+pub enum Error {
+    FdbError(FdbError),
+    DirectoryError(DirectoryError),
+    NoRemainingSeats,
+    TooManyClasses,
+}
+
+impl From<FdbError> for Error {
+    fn from(err: FdbError) -> Self {
+        Error::FdbError(err)
+    }
+}
+
+impl From<DirectoryError> for Error {
+    fn from(err: DirectoryError) -> Self {
+        match err {
+            DirectoryError::FdbError(err) => Error::FdbError(err),
+            err => Error::DirectoryError(err),
+        }
+    }
+}
+
+impl TransactError for Error {
+    fn try_into_fdb_error(self) -> std::result::Result<FdbError, Self> {
+        match self {
+            Error::FdbError(err) => Ok(err),
+            _ => Err(self),
+        }
+    }
+}
+
+// Data model:
+// attends/(student, class) = ""
+// class/(class_name) = seats left, as a little-endian i64
+
+/// The subspaces used by the class scheduling application.
+#[derive(Debug, Clone)]
+pub struct Schema {
+    attends: Subspace,
+    class: Subspace,
+}
+
+impl Schema {
+    /// Creates the schema stored under the given subspace.
+    pub fn new(root: &Subspace) -> Self {
+        Self {
+            attends: root.subspace(&"attends"),
+            class: root.subspace(&"class"),
+        }
+    }
+
+    /// Creates or opens the `attends` and `class` directories under `path`, in the default
+    /// directory layer.
+    pub async fn open(db: &Database, path: &[&str]) -> Result<Self> {
+        let directory = DirectoryLayer::default();
+        Ok(Self {
+            attends: open_directory(db, &directory, path, "attends").await?,
+            class: open_directory(db, &directory, path, "class").await?,
+        })
+    }
+}
+
+/// Creates or opens the directory `name` under `path`.
+async fn open_directory(
+    db: &Database,
+    directory: &DirectoryLayer,
+    path: &[&str],
+    name: &str,
+) -> Result<Subspace> {
+    let mut path: Vec<String> = path.iter().map(|name| name.to_string()).collect();
+    path.push(name.to_string());
+    let output = directory.db_create_or_open(db, &path, None, None).await?;
+    Ok(output
+        .into_subspace()
+        .expect("the schema directories are not partitions"))
+}
+
+// Generate 1,620 classes like '9:00 chem for dummies'
+const LEVELS: &[&str] = &[
+    "intro",
+    "for dummies",
+    "remedial",
+    "101",
+    "201",
+    "301",
+    "mastery",
+    "lab",
+    "seminar",
+];
+
+const TYPES: &[&str] = &[
+    "chem", "bio", "cs", "geometry", "calc", "alg", "film", "music", "art", "dance",
+];
+
+const TIMES: &[&str] = &[
+    "2:00", "3:00", "4:00", "5:00", "6:00", "7:00", "8:00", "9:00", "10:00", "11:00", "12:00",
+    "13:00", "14:00", "15:00", "16:00", "17:00", "18:00", "19:00",
+];
+
+pub fn all_classes() -> Vec<String> {
+    let mut class_names: Vec<String> = Vec::new();
+    for level in LEVELS {
+        for _type in TYPES {
+            for time in TIMES {
+                class_names.push(format!("{} {} {}", time, _type, level));
+            }
+        }
+    }
+
+    class_names
+}
+
+/// Clears the schema and creates the given classes with `seats` available seats each.
+pub async fn init(db: &Database, schema: &Schema, classes: &[String], seats: i64) -> Result<()> {
+    let trx = db.create_trx()?;
+    trx.clear_subspace_range(&schema.attends);
+    trx.clear_subspace_range(&schema.class);
+    for class in classes {
+        trx.set(&schema.class.pack(class), &seats.to_le_bytes());
+    }
+
+    trx.commit().await.map_err(FdbError::from)?;
+    Ok(())
+}
+
+fn decode_seats(value: &[u8]) -> i64 {
+    let mut seats = [0; 8];
+    seats.copy_from_slice(value);
+    i64::from_le_bytes(seats)
+}
+
+/// Returns the number of seats left in `class`, or `None` if the class doesn't exist.
+pub async fn seats_left(trx: &Transaction, schema: &Schema, class: &str) -> Result<Option<i64>> {
+    let seats = trx.get(&schema.class.pack(&class), false).await?;
+    Ok(seats.map(|seats| decode_seats(&seats)))
+}
+
+/// Returns the classes `student` is attending.
+pub async fn student_classes(
+    trx: &Transaction,
+    schema: &Schema,
+    student: &str,
+) -> Result<Vec<String>> {
+    let attends = schema.attends.subspace(&student);
+    let range = attends.range_option();
+    let got_range = trx.get_range(&range, 1_024, false).await?;
+
+    Ok(got_range
+        .iter()
+        .map(|key_value| {
+            attends
+                .unpack(key_value.key())
+                .expect("failed to decode class")
+        })
+        .collect())
+}
+
+async fn available_classes_trx(trx: &Transaction, schema: &Schema) -> Result<Vec<String>> {
+    let range = schema.class.range_option();
+    let got_range = trx.get_range(&range, 1_024, false).await?;
+    let mut available_classes = Vec::<String>::new();
+
+    for key_value in got_range.iter() {
+        if decode_seats(key_value.value()) > 0 {
+            let class: String = schema
+                .class
+                .unpack(key_value.key())
+                .expect("failed to decode class");
+            available_classes.push(class);
+        }
+    }
+
+    Ok(available_classes)
+}
+
+/// Returns the classes with at least one seat left.
+pub async fn available_classes(db: &Database, schema: &Schema) -> Result<Vec<String>> {
+    db.transact_boxed_local(
+        schema,
+        |trx, schema| available_classes_trx(trx, schema).boxed_local(),
+        TransactOption::default(),
+    )
+    .await
+}
+
+async fn drop_trx(trx: &Transaction, schema: &Schema, student: &str, class: &str) -> Result<()> {
+    let attends_key = schema.attends.pack(&(student, class));
+    if trx.get(&attends_key, false).await?.is_none() {
+        // not taking this class
+        return Ok(());
+    }
+
+    // giving the seat back doesn't need to read the seats left, so concurrent drops of the same
+    // class don't conflict
+    let class_key = schema.class.pack(&class);
+    trx.atomic_op(&class_key, &1i64.to_le_bytes(), MutationType::Add);
+    trx.clear(&attends_key);
+
+    Ok(())
+}
+
+/// Drops `class` from the schedule of `student`.
+///
+/// Dropping a class the student is not attending does nothing.
+pub async fn drop_class(db: &Database, schema: &Schema, student: &str, class: &str) -> Result<()> {
+    db.transact_boxed_local(
+        (schema, student, class),
+        |trx, (schema, student, class)| drop_trx(trx, schema, student, class).boxed_local(),
+        TransactOption::default(),
+    )
+    .await
+}
+
+async fn signup_trx(trx: &Transaction, schema: &Schema, student: &str, class: &str) -> Result<()> {
+    let attends_key = schema.attends.pack(&(student, class));
+    if trx.get(&attends_key, false).await?.is_some() {
+        // already taking this class
+        return Ok(());
+    }
+
+    let class_key = schema.class.pack(&class);
+    let available_seats = decode_seats(
+        &trx.get(&class_key, false)
+            .await?
+            .expect("class seats were not initialized"),
+    );
+
+    if available_seats <= 0 {
+        return Err(Error::NoRemainingSeats);
+    }
+
+    let attends_range = schema.attends.subspace(&student).range_option();
+    if trx.get_range(&attends_range, 1_024, false).await?.len() >= MAX_CLASSES_PER_STUDENT {
+        return Err(Error::TooManyClasses);
+    }
+
+    trx.atomic_op(&class_key, &(-1i64).to_le_bytes(), MutationType::Add);
+    trx.set(&attends_key, &pack(&""));
+
+    Ok(())
+}
+
+/// Signs `student` up for `class`.
+///
+/// Fails with `Error::NoRemainingSeats` if the class is full, and with `Error::TooManyClasses`
+/// if the student already attends `MAX_CLASSES_PER_STUDENT` classes.
+pub async fn signup(db: &Database, schema: &Schema, student: &str, class: &str) -> Result<()> {
+    db.transact_boxed_local(
+        (schema, student, class),
+        |trx, (schema, student, class)| signup_trx(trx, schema, student, class).boxed_local(),
+        TransactOption::default(),
+    )
+    .await
+}
+
+/// Replaces `old_class` by `new_class` in the schedule of `student`.
+///
+/// Both operations are done in the same transaction, so if signing up for the new class fails,
+/// the student keeps the old class.
+pub async fn switch_classes(
+    db: &Database,
+    schema: &Schema,
+    student: &str,
+    old_class: &str,
+    new_class: &str,
+) -> Result<()> {
+    async fn switch_classes_body(
+        trx: &Transaction,
+        schema: &Schema,
+        student: &str,
+        old_class: &str,
+        new_class: &str,
+    ) -> Result<()> {
+        drop_trx(trx, schema, student, old_class).await?;
+        signup_trx(trx, schema, student, new_class).await?;
+        Ok(())
+    }
+
+    db.transact_boxed_local(
+        (schema, student, old_class, new_class),
+        |trx, (schema, student, old_class, new_class)| {
+            switch_classes_body(trx, schema, student, old_class, new_class).boxed_local()
+        },
+        TransactOption::default(),
+    )
+    .await
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum Mood {
+    Add,
+    Drop,
+    Switch,
+}
+
+async fn perform_op(
+    db: &Database,
+    schema: &Schema,
+    rng: &mut ThreadRng,
+    mood: Mood,
+    student: &str,
+    all_classes: &[String],
+    my_classes: &mut Vec<String>,
+) -> Result<()> {
+    match mood {
+        Mood::Add => {
+            let class = all_classes.choose(rng).unwrap();
+            signup(db, schema, student, class).await?;
+            if !my_classes.contains(class) {
+                my_classes.push(class.to_string());
+            }
+        }
+        Mood::Drop => {
+            let class = my_classes.choose(rng).unwrap().to_string();
+            drop_class(db, schema, student, &class).await?;
+            my_classes.retain(|s| s != &class);
+        }
+        Mood::Switch => {
+            let old_class = my_classes.choose(rng).unwrap().to_string();
+            let new_class = all_classes.choose(rng).unwrap();
+            switch_classes(db, schema, student, &old_class, new_class).await?;
+            my_classes.retain(|s| s != &old_class);
+            if !my_classes.contains(new_class) {
+                my_classes.push(new_class.to_string());
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Runs `num_ops` random signup, drop and switch operations for `student`.
+pub async fn simulate_student(
+    db: &Database,
+    schema: &Schema,
+    student: &str,
+    all_classes: &[String],
+    num_ops: usize,
+) -> Result<()> {
+    let mut rng = rand::thread_rng();
+
+    let mut available_classes = all_classes.to_vec();
+    let mut my_classes = Vec::<String>::new();
+
+    for _ in 0..num_ops {
+        let mut moods = Vec::<Mood>::new();
+
+        if !my_classes.is_empty() {
+            moods.push(Mood::Drop);
+            moods.push(Mood::Switch);
+        }
+
+        if my_classes.len() < MAX_CLASSES_PER_STUDENT && !available_classes.is_empty() {
+            moods.push(Mood::Add);
+        }
+
+        let mood = match moods.choose(&mut rng) {
+            Some(mood) => *mood,
+            None => break,
+        };
+
+        match perform_op(
+            db,
+            schema,
+            &mut rng,
+            mood,
+            student,
+            &available_classes,
+            &mut my_classes,
+        )
+        .await
+        {
+            Ok(()) => {}
+            Err(Error::FdbError(err)) => return Err(Error::FdbError(err)),
+            // on business errors we recheck for available classes
+            Err(_) => {
+                available_classes = self::available_classes(db, schema).await?;
+                my_classes = student_classes(&db.create_trx()?, schema, student).await?;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Runs `students` concurrent students doing `ops_per_student` operations each.
+pub fn run_sim(
+    db: Database,
+    schema: &Schema,
+    all_classes: &[String],
+    students: usize,
+    ops_per_student: usize,
+) -> Vec<String> {
+    let mut threads = Vec::with_capacity(students);
+    for i in 0..students {
+        let db = db.clone();
+        let schema = schema.clone();
+        let all_classes = all_classes.to_vec();
+        let student = format!("s{}", i);
+        threads.push(thread::spawn(move || {
+            futures::executor::block_on(simulate_student(
+                &db,
+                &schema,
+                &student,
+                &all_classes,
+                ops_per_student,
+            ))
+            .expect("failed to simulate student");
+            student
+        }));
+    }
+
+    threads
+        .into_iter()
+        .map(|thread| thread.join().expect("failed to join thread"))
+        .collect()
+}
+
+/// Checks that no class of `classes` is over capacity, and that the seats left in each class
+/// match the schedules of `students`, every class having `seats` seats.
+///
+/// # Panics
+///
+/// Panics if an invariant doesn't hold.
+pub async fn check_invariants(
+    db: &Database,
+    schema: &Schema,
+    classes: &[String],
+    students: &[String],
+    seats: i64,
+) -> Result<()> {
+    let trx = db.create_trx()?;
+
+    let mut attendees = HashMap::<String, i64>::new();
+    for student in students {
+        let student_classes = student_classes(&trx, schema, student).await?;
+        assert!(
+            student_classes.len() <= MAX_CLASSES_PER_STUDENT,
+            "{} attends {} classes",
+            student,
+            student_classes.len()
+        );
+        for class in student_classes {
+            assert!(classes.contains(&class), "unknown class {}", class);
+            *attendees.entry(class).or_default() += 1;
+        }
+    }
+
+    for class in classes {
+        let seats_left = seats_left(&trx, schema, class)
+            .await?
+            .expect("class seats were not initialized");
+        assert!(seats_left >= 0, "{} is over capacity", class);
+
+        let attendees = attendees.get(class).copied().unwrap_or(0);
+        assert_eq!(
+            seats_left + attendees,
+            seats,
+            "{} seats are inconsistent with schedules",
+            class
+        );
+    }
+
+    let available = available_classes(db, schema).await?;
+    for class in classes {
+        let seats_left = seats_left(&trx, schema, class).await?.unwrap();
+        assert_eq!(available.contains(class), seats_left > 0);
+    }
+
+    Ok(())
+}
+
+const STUDENTS: usize = 10;
+const OPS_PER_STUDENT: usize = 10;
+
+fn main() {
+    let _guard = unsafe { fdb::boot() };
+    let db = futures::executor::block_on(fdb::Database::new_compat(None))
+        .expect("failed to get database");
+    let schema = futures::executor::block_on(Schema::open(&db, &["class-scheduling"]))
+        .expect("failed to open the schema");
+    let all_classes = all_classes();
+
+    futures::executor::block_on(init(&db, &schema, &all_classes, SEATS_PER_CLASS))
+        .expect("failed to initialize data");
+    println!("Initialized");
+
+    let students = run_sim(db.clone(), &schema, &all_classes, STUDENTS, OPS_PER_STUDENT);
+    for student in students.iter() {
+        let trx = db.create_trx().expect("could not create transaction");
+        let classes = futures::executor::block_on(student_classes(&trx, &schema, student))
+            .expect("failed to get student classes");
+        for class in classes {
+            println!("{} is taking: {}", student, class);
+        }
+    }
+
+    futures::executor::block_on(check_invariants(
+        &db,
+        &schema,
+        &all_classes,
+        &students,
+        SEATS_PER_CLASS,
+    ))
+    .expect("failed to check the invariants");
+    println!(
+        "Ran {} operations, the schedules are consistent",
+        STUDENTS * OPS_PER_STUDENT
+    );
+}
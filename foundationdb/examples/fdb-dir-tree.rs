@@ -0,0 +1,85 @@
+// Copyright 2018 foundationdb-rs developers, https://github.com/Clikengo/foundationdb-rs/graphs/contributors
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! Prints the directory hierarchy of the default cluster.
+//!
+//! ```console
+//! $> cargo run --example fdb-dir-tree -- [--json] [PATH_SEGMENT...]
+//! ```
+//!
+//! The `--json` output requires the `serde` feature.
+
+use foundationdb::directory::{DirectoryError, DirectoryLayer, DirectoryTree};
+use foundationdb::{Database, TransactOption};
+use futures::prelude::*;
+
+fn print_tree(tree: &DirectoryTree, depth: usize) {
+    let name = if depth == 0 && tree.name.is_empty() {
+        "/"
+    } else {
+        &tree.name
+    };
+    let mut line = format!("{:indent$}{}", "", name, indent = 2 * depth);
+    if tree.partition {
+        line.push_str(" (partition)");
+    } else if !tree.layer.is_empty() {
+        line.push_str(&format!(" [{}]", String::from_utf8_lossy(&tree.layer)));
+    }
+    println!("{}", line);
+    for child in &tree.children {
+        print_tree(child, depth + 1);
+    }
+}
+
+#[cfg(feature = "serde")]
+fn print_json(tree: &DirectoryTree) {
+    println!(
+        "{}",
+        serde_json::to_string_pretty(tree).expect("failed to serialize the tree")
+    );
+}
+
+#[cfg(not(feature = "serde"))]
+fn print_json(_tree: &DirectoryTree) {
+    eprintln!("--json requires the `serde` feature");
+    std::process::exit(2);
+}
+
+async fn dump(json: bool, path: Vec<String>) -> Result<(), DirectoryError> {
+    let db = Database::default()?;
+    let directory = DirectoryLayer::default();
+    let tree = db
+        .transact_boxed(
+            (&directory, path),
+            |trx, (directory, path)| directory.tree(trx, path).boxed(),
+            TransactOption::read_only(),
+        )
+        .await?;
+
+    if json {
+        print_json(&tree);
+    } else {
+        print_tree(&tree, 0);
+    }
+    Ok(())
+}
+
+fn main() {
+    let mut json = false;
+    let mut path = Vec::new();
+    for arg in std::env::args().skip(1) {
+        match arg.as_str() {
+            "--json" => json = true,
+            _ => path.push(arg),
+        }
+    }
+
+    if let Err(err) = foundationdb::run(|| futures::executor::block_on(dump(json, path))) {
+        eprintln!("error: {}", err);
+        std::process::exit(1);
+    }
+}
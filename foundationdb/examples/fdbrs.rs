@@ -0,0 +1,534 @@
+// Copyright 2018 foundationdb-rs developers, https://github.com/Clikengo/foundationdb-rs/graphs/contributors
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! A small `fdbcli`-like tool to read and write keys of the default cluster.
+//!
+//! ```console
+//! $> cargo run --example fdbrs -- set '("user", 42)' alice
+//! $> cargo run --example fdbrs -- get '("user", 42)'
+//! b"alice"
+//! $> cargo run --example fdbrs -- scan '("user",)' --limit 10 --reverse
+//! $> cargo run --example fdbrs -- clear 0x02757365720015
+//! $> cargo run --example fdbrs -- clearrange a b
+//! $> cargo run --example fdbrs -- dir list app/users
+//! ```
+//!
+//! Keys and values are either:
+//! - hexadecimal bytes, starting with `0x`, e.g. `0x0102ff`,
+//! - a tuple literal, starting with `(`, e.g. `("a", 1, -2.5, b"\x00", nil, true, ("nested",))`,
+//!   packed with the tuple layer,
+//! - any other string, used as is.
+//!
+//! Bytes are printed like `tuple::Bytes`. The exit code is 0 on success, 1 if the key was not
+//! found or the operation failed, and 2 if the arguments are invalid.
+//!
+//! The command and literal parsers of this example are tested by `tests/fdbrs.rs`.
+
+use std::borrow::Cow;
+use std::fmt;
+use std::io::{self, Write};
+
+use futures::prelude::*;
+
+use foundationdb::directory::{Directory, DirectoryError, DirectoryLayer};
+use foundationdb::tuple::{pack, Bytes, Element, FromHexError};
+use foundationdb::{key, Database, FdbError, RangeOption, TransactOption};
+
+const USAGE: &str = "usage: fdbrs <command>
+
+commands:
+    get <key>
+    set <key> <value>
+    scan <prefix> [--limit N] [--reverse]
+    clear <key>
+    clearrange <begin> <end>
+    dir list [path/to/directory]";
+
+/// A parsed command line.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Command {
+    Get(Vec<u8>),
+    Set(Vec<u8>, Vec<u8>),
+    Scan {
+        prefix: Vec<u8>,
+        limit: Option<usize>,
+        reverse: bool,
+    },
+    Clear(Vec<u8>),
+    ClearRange(Vec<u8>, Vec<u8>),
+    DirList(Vec<String>),
+}
+
+#[derive(Debug)]
+pub enum Error {
+    /// Invalid command line, reported with the usage
+    Usage(String),
+    /// Invalid key or value
+    Literal(LiteralError),
+    /// `get` did not find the key
+    NotFound(Vec<u8>),
+    Fdb(FdbError),
+    Directory(DirectoryError),
+    Io(io::Error),
+}
+
+impl Error {
+    /// The exit code of the process for this error.
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            Error::Usage(_) | Error::Literal(_) => 2,
+            _ => 1,
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::Usage(msg) => write!(f, "{}\n\n{}", msg, USAGE),
+            Error::Literal(err) => err.fmt(f),
+            Error::NotFound(key) => write!(f, "{} not found", Bytes::from(key.as_slice())),
+            Error::Fdb(err) => err.fmt(f),
+            Error::Directory(err) => err.fmt(f),
+            Error::Io(err) => err.fmt(f),
+        }
+    }
+}
+
+impl From<LiteralError> for Error {
+    fn from(err: LiteralError) -> Self {
+        Error::Literal(err)
+    }
+}
+
+impl From<FdbError> for Error {
+    fn from(err: FdbError) -> Self {
+        Error::Fdb(err)
+    }
+}
+
+impl From<DirectoryError> for Error {
+    fn from(err: DirectoryError) -> Self {
+        Error::Directory(err)
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(err: io::Error) -> Self {
+        Error::Io(err)
+    }
+}
+
+/// The error returned when a key or a value can't be parsed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LiteralError {
+    /// The byte offset of the error in the literal
+    pub position: usize,
+    pub message: Cow<'static, str>,
+}
+
+impl LiteralError {
+    fn new(position: usize, message: impl Into<Cow<'static, str>>) -> Self {
+        Self {
+            position,
+            message: message.into(),
+        }
+    }
+}
+
+impl fmt::Display for LiteralError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} at position {}", self.message, self.position)
+    }
+}
+
+impl std::error::Error for LiteralError {}
+
+/// Parses a key or a value, as hexadecimal bytes, a packed tuple literal or raw bytes.
+pub fn parse_bytes(literal: &str) -> Result<Vec<u8>, LiteralError> {
+    if literal.starts_with("0x") {
+        Bytes::from_hex(&literal[2..])
+            .map(Bytes::into_owned)
+            .map_err(|err| match err {
+                FromHexError::InvalidCharacter(index) => {
+                    LiteralError::new(index + 2, "invalid hexadecimal digit")
+                }
+                FromHexError::OddLength => LiteralError::new(2, err.to_string()),
+            })
+    } else if literal.starts_with('(') {
+        parse_tuple(literal).map(|tuple| pack(&tuple))
+    } else {
+        Ok(literal.as_bytes().to_vec())
+    }
+}
+
+/// Parses a tuple literal, e.g. `("a", 1, (nil, true))`, into an `Element::Tuple`.
+///
+/// Supported elements are strings (`"a\"b"`), byte strings with `\xNN` escapes (`b"\x00"`),
+/// integers, doubles (`-2.5`, `1e3`), `nil`, `true`, `false` and nested tuples. A single element
+/// tuple can be written with a trailing comma, e.g. `("a",)`.
+pub fn parse_tuple(literal: &str) -> Result<Element<'static>, LiteralError> {
+    let mut parser = Parser {
+        input: literal,
+        pos: 0,
+    };
+    parser.skip_whitespace();
+    let element = parser.tuple()?;
+    parser.skip_whitespace();
+    if parser.pos < literal.len() {
+        return Err(parser.error("unexpected trailing characters"));
+    }
+    Ok(element)
+}
+
+struct Parser<'a> {
+    input: &'a str,
+    pos: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn error(&self, message: impl Into<Cow<'static, str>>) -> LiteralError {
+        LiteralError::new(self.pos, message)
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.input[self.pos..].chars().next()
+    }
+
+    fn bump(&mut self) -> Option<char> {
+        let c = self.peek()?;
+        self.pos += c.len_utf8();
+        Some(c)
+    }
+
+    fn expect(&mut self, expected: char) -> Result<(), LiteralError> {
+        match self.peek() {
+            Some(c) if c == expected => {
+                self.bump();
+                Ok(())
+            }
+            _ => Err(self.error(format!("expected `{}`", expected))),
+        }
+    }
+
+    fn skip_whitespace(&mut self) {
+        while self.peek().map_or(false, char::is_whitespace) {
+            self.bump();
+        }
+    }
+
+    fn tuple(&mut self) -> Result<Element<'static>, LiteralError> {
+        self.expect('(')?;
+        let mut elements = Vec::new();
+        loop {
+            self.skip_whitespace();
+            if self.peek() == Some(')') {
+                break;
+            }
+            elements.push(self.element()?);
+            self.skip_whitespace();
+            match self.peek() {
+                Some(',') => {
+                    self.bump();
+                }
+                Some(')') => break,
+                _ => return Err(self.error("expected `,` or `)`")),
+            }
+        }
+        self.bump();
+        Ok(Element::Tuple(elements))
+    }
+
+    fn element(&mut self) -> Result<Element<'static>, LiteralError> {
+        let rest = &self.input[self.pos..];
+        match self.peek() {
+            Some('(') => self.tuple(),
+            Some('"') => Ok(Element::String(self.string()?.into())),
+            Some('b') if rest.starts_with("b\"") => {
+                self.bump();
+                let bytes = self.byte_string()?;
+                Ok(Element::Bytes(bytes.into()))
+            }
+            Some(c) if c == '-' || c == '+' || c.is_ascii_digit() => self.number(),
+            Some(c) if c.is_ascii_alphabetic() => {
+                let start = self.pos;
+                while self.peek().map_or(false, |c| c.is_ascii_alphanumeric()) {
+                    self.bump();
+                }
+                match &self.input[start..self.pos] {
+                    "nil" | "null" | "None" => Ok(Element::Nil),
+                    "true" => Ok(Element::Bool(true)),
+                    "false" => Ok(Element::Bool(false)),
+                    _ => Err(LiteralError::new(start, "unknown identifier")),
+                }
+            }
+            Some(_) => Err(self.error("unexpected character")),
+            None => Err(self.error("unexpected end of literal")),
+        }
+    }
+
+    fn number(&mut self) -> Result<Element<'static>, LiteralError> {
+        let start = self.pos;
+        self.bump();
+        while self.peek().map_or(false, |c| {
+            c.is_ascii_alphanumeric() || c == '.' || c == '-' || c == '+'
+        }) {
+            self.bump();
+        }
+        let number = &self.input[start..self.pos];
+        if let Ok(int) = number.parse() {
+            Ok(Element::Int(int))
+        } else if let Ok(double) = number.parse() {
+            Ok(Element::Double(double))
+        } else {
+            Err(LiteralError::new(start, "invalid number"))
+        }
+    }
+
+    fn escape(&mut self) -> Result<u8, LiteralError> {
+        let start = self.pos;
+        self.expect('\\')?;
+        match self.bump() {
+            Some('\\') => Ok(b'\\'),
+            Some('"') => Ok(b'"'),
+            Some('n') => Ok(b'\n'),
+            Some('t') => Ok(b'\t'),
+            Some('0') => Ok(0),
+            Some('x') => {
+                let hex = self.input.get(self.pos..self.pos + 2).unwrap_or("");
+                let byte = u8::from_str_radix(hex, 16)
+                    .map_err(|_| LiteralError::new(start, "invalid `\\x` escape"))?;
+                self.pos += 2;
+                Ok(byte)
+            }
+            _ => Err(LiteralError::new(start, "invalid escape")),
+        }
+    }
+
+    fn string(&mut self) -> Result<String, LiteralError> {
+        let start = self.pos;
+        self.expect('"')?;
+        let mut string = String::new();
+        loop {
+            match self.peek() {
+                Some('"') => {
+                    self.bump();
+                    return Ok(string);
+                }
+                Some('\\') => {
+                    let escape = self.pos;
+                    let byte = self.escape()?;
+                    if !byte.is_ascii() {
+                        return Err(LiteralError::new(escape, "non ASCII escape in a string"));
+                    }
+                    string.push(byte as char);
+                }
+                Some(c) => {
+                    self.bump();
+                    string.push(c);
+                }
+                None => return Err(LiteralError::new(start, "unterminated string")),
+            }
+        }
+    }
+
+    fn byte_string(&mut self) -> Result<Vec<u8>, LiteralError> {
+        let start = self.pos;
+        self.expect('"')?;
+        let mut bytes = Vec::new();
+        loop {
+            match self.peek() {
+                Some('"') => {
+                    self.bump();
+                    return Ok(bytes);
+                }
+                Some('\\') => bytes.push(self.escape()?),
+                Some(c) => {
+                    self.bump();
+                    let mut buf = [0; 4];
+                    bytes.extend_from_slice(c.encode_utf8(&mut buf).as_bytes());
+                }
+                None => return Err(LiteralError::new(start, "unterminated byte string")),
+            }
+        }
+    }
+}
+
+/// Parses the command line arguments, without the program name.
+pub fn parse_args(args: &[String]) -> Result<Command, Error> {
+    let (command, args) = args
+        .split_first()
+        .ok_or_else(|| Error::Usage("missing command".to_string()))?;
+    let positional = |count: usize| -> Result<Vec<&String>, Error> {
+        let positional: Vec<&String> = args.iter().filter(|a| !a.starts_with("--")).collect();
+        if positional.len() != count {
+            return Err(Error::Usage(format!(
+                "`{}` expects {} argument(s)",
+                command, count
+            )));
+        }
+        Ok(positional)
+    };
+
+    match command.as_str() {
+        "get" => Ok(Command::Get(parse_bytes(positional(1)?[0])?)),
+        "set" => {
+            let args = positional(2)?;
+            Ok(Command::Set(parse_bytes(args[0])?, parse_bytes(args[1])?))
+        }
+        "scan" => {
+            let mut prefix = None;
+            let mut limit = None;
+            let mut reverse = false;
+            let mut args = args.iter();
+            while let Some(arg) = args.next() {
+                match arg.as_str() {
+                    "--reverse" => reverse = true,
+                    "--limit" => {
+                        let n = args
+                            .next()
+                            .and_then(|n| n.parse().ok())
+                            .ok_or_else(|| Error::Usage("invalid --limit".to_string()))?;
+                        limit = Some(n);
+                    }
+                    _ if prefix.is_none() => prefix = Some(parse_bytes(arg)?),
+                    _ => return Err(Error::Usage(format!("unexpected argument `{}`", arg))),
+                }
+            }
+            Ok(Command::Scan {
+                prefix: prefix.unwrap_or_default(),
+                limit,
+                reverse,
+            })
+        }
+        "clear" => Ok(Command::Clear(parse_bytes(positional(1)?[0])?)),
+        "clearrange" => {
+            let args = positional(2)?;
+            Ok(Command::ClearRange(
+                parse_bytes(args[0])?,
+                parse_bytes(args[1])?,
+            ))
+        }
+        "dir" => match args {
+            [list] if list == "list" => Ok(Command::DirList(Vec::new())),
+            [list, path] if list == "list" => Ok(Command::DirList(
+                path.split('/')
+                    .filter(|s| !s.is_empty())
+                    .map(String::from)
+                    .collect(),
+            )),
+            _ => Err(Error::Usage("expected `dir list [path]`".to_string())),
+        },
+        _ => Err(Error::Usage(format!("unknown command `{}`", command))),
+    }
+}
+
+/// Executes `command` on `db`, writing its output to `out`.
+pub async fn execute<W: Write>(db: &Database, command: Command, out: &mut W) -> Result<(), Error> {
+    match command {
+        Command::Get(key) => match db.get(&key).await? {
+            Some(value) => writeln!(out, "{}", Bytes::from(value))?,
+            None => return Err(Error::NotFound(key)),
+        },
+        Command::Set(key, value) => {
+            db.transact_boxed(
+                (key, value),
+                |trx, (key, value)| {
+                    trx.set(key, value);
+                    future::ok(()).boxed()
+                },
+                TransactOption::idempotent(),
+            )
+            .await?;
+        }
+        Command::Scan {
+            prefix,
+            limit,
+            reverse,
+        } => {
+            let end = key::strinc(&prefix).unwrap_or_else(|| vec![0xff]);
+            let opt = RangeOption {
+                limit,
+                reverse,
+                ..RangeOption::from((prefix, end))
+            };
+            let kvs = db
+                .transact_boxed(
+                    opt,
+                    |trx, opt| {
+                        trx.get_ranges(opt.clone(), false)
+                            .try_fold(Vec::new(), |mut kvs, values| {
+                                kvs.extend(
+                                    values
+                                        .iter()
+                                        .map(|kv| (kv.key().to_vec(), kv.value().to_vec())),
+                                );
+                                future::ok(kvs)
+                            })
+                            .boxed()
+                    },
+                    TransactOption::read_only(),
+                )
+                .await?;
+            for (key, value) in kvs {
+                writeln!(out, "{} = {}", Bytes::from(key), Bytes::from(value))?;
+            }
+        }
+        Command::Clear(key) => {
+            db.transact_boxed(
+                key,
+                |trx, key| {
+                    trx.clear(key);
+                    future::ok(()).boxed()
+                },
+                TransactOption::idempotent(),
+            )
+            .await?;
+        }
+        Command::ClearRange(begin, end) => {
+            db.transact_boxed(
+                (begin, end),
+                |trx, (begin, end)| {
+                    trx.clear_range(begin, end);
+                    future::ok(()).boxed()
+                },
+                TransactOption::idempotent(),
+            )
+            .await?;
+        }
+        Command::DirList(path) => {
+            let directory = DirectoryLayer::default();
+            let names = db
+                .transact_boxed(
+                    (&directory, path),
+                    |trx, (directory, path)| directory.list(trx, path).boxed(),
+                    TransactOption::read_only(),
+                )
+                .await?;
+            for name in names {
+                writeln!(out, "{}", name)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let result = parse_args(&args).and_then(|command| {
+        foundationdb::run(|| {
+            futures::executor::block_on(async {
+                let db = Database::default()?;
+                execute(&db, command, &mut io::stdout()).await
+            })
+        })
+    });
+    if let Err(err) = result {
+        eprintln!("error: {}", err);
+        std::process::exit(err.exit_code());
+    }
+}
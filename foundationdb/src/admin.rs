@@ -0,0 +1,89 @@
+// Copyright 2018 foundationdb-rs developers, https://github.com/Clikengo/foundationdb-rs/graphs/contributors
+// Copyright 2013-2018 Apple, Inc and the FoundationDB project authors.
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! Administrative operations of the FDBDatabase C API
+//!
+//! These operations act on the cluster itself, not on its data. They exist for chaos testing
+//! and disaster recovery, and can make a cluster unavailable or lose committed data.
+//!
+//! https://apple.github.io/foundationdb/api-c.html#database
+
+use foundationdb_sys as fdb_sys;
+use futures::prelude::*;
+
+use crate::future::FdbFuture;
+use crate::transaction::{fdb_bool, fdb_len};
+use crate::{Database, FdbResult};
+
+impl Database {
+    /// Reboots the worker process listening on `address`, e.g. `"127.0.0.1:4500"`.
+    ///
+    /// **DANGER**: this makes the roles of the worker unavailable until they are recruited again,
+    /// only use it to test the resilience of a cluster.
+    ///
+    /// If `check_file` is true, the worker only reboots if its data files are readable. The worker
+    /// stays suspended for `duration` seconds before rebooting.
+    ///
+    /// Returns `0` if no worker listens on `address`, a non-zero value otherwise.
+    pub fn reboot_worker(
+        &self,
+        address: &str,
+        check_file: bool,
+        duration: i32,
+    ) -> impl Future<Output = FdbResult<i64>> + Send + Sync + Unpin {
+        FdbFuture::new(unsafe {
+            fdb_sys::fdb_database_reboot_worker(
+                self.inner.as_ptr(),
+                address.as_ptr(),
+                fdb_len(address.len(), "address"),
+                fdb_bool(check_file),
+                duration,
+            )
+        })
+    }
+
+    /// Forces a recovery of the cluster, making `dc_id` the primary datacenter.
+    ///
+    /// **DANGER**: mutations that were committed but not yet replicated to `dc_id` are lost
+    /// forever. This is only meant to recover a cluster whose primary datacenter went down for
+    /// good.
+    pub fn force_recovery_with_data_loss(
+        &self,
+        dc_id: &str,
+    ) -> impl Future<Output = FdbResult<()>> + Send + Sync + Unpin {
+        FdbFuture::new(unsafe {
+            fdb_sys::fdb_database_force_recovery_with_data_loss(
+                self.inner.as_ptr(),
+                dc_id.as_ptr(),
+                fdb_len(dc_id.len(), "dc_id"),
+            )
+        })
+    }
+
+    /// Creates a snapshot of the whole cluster, running `snap_command` on every process.
+    ///
+    /// **DANGER**: the cluster stops accepting commits while the snapshot is taken.
+    ///
+    /// `uid` is a 32 characters hexadecimal identifier of the snapshot. This fails if the cluster
+    /// is not configured to allow snapshots.
+    pub fn create_snapshot(
+        &self,
+        uid: &str,
+        snap_command: &str,
+    ) -> impl Future<Output = FdbResult<()>> + Send + Sync + Unpin {
+        FdbFuture::new(unsafe {
+            fdb_sys::fdb_database_create_snapshot(
+                self.inner.as_ptr(),
+                uid.as_ptr(),
+                fdb_len(uid.len(), "uid"),
+                snap_command.as_ptr(),
+                fdb_len(snap_command.len(), "snap_command"),
+            )
+        })
+    }
+}
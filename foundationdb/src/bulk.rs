@@ -0,0 +1,228 @@
+// Copyright 2018 foundationdb-rs developers, https://github.com/Clikengo/foundationdb-rs/graphs/contributors
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! Loading many key-value pairs in as many transactions as needed
+
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+use std::time::{Duration, Instant};
+
+use futures::future::{BoxFuture, Either};
+use futures::prelude::*;
+use futures::stream::FuturesUnordered;
+
+use crate::{Database, FdbError, FdbResult, TransactOption};
+
+/// Options of `Database::bulk_insert`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BulkOptions {
+    /// The maximum number of bytes of keys and values written by a transaction, 1,000,000 by
+    /// default.
+    ///
+    /// Transactions are limited to 10,000,000 bytes, and larger transactions take longer to
+    /// commit and to retry.
+    pub max_batch_bytes: usize,
+    /// The maximum number of pairs written by a transaction, 10,000 by default.
+    pub max_batch_count: usize,
+    /// The number of transactions committed concurrently, 1 by default.
+    pub concurrency: usize,
+}
+
+impl Default for BulkOptions {
+    fn default() -> Self {
+        Self {
+            max_batch_bytes: 1_000_000,
+            max_batch_count: 10_000,
+            concurrency: 1,
+        }
+    }
+}
+
+/// The statistics of a `Database::bulk_insert`
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct BulkStats {
+    /// The number of pairs written, a key written twice being counted twice
+    pub keys: usize,
+    /// The number of bytes of the keys and values written
+    pub bytes: usize,
+    /// The number of committed transactions
+    pub transactions: usize,
+    /// The number of retried transaction attempts
+    pub retries: usize,
+    /// The duration of the whole insertion
+    pub elapsed: Duration,
+}
+
+/// The pairs written by a transaction
+#[derive(Default)]
+struct Batch {
+    pairs: Vec<(Vec<u8>, Vec<u8>)>,
+    bytes: usize,
+    /// The hashes of the keys, to commit the batches writing the same key in order
+    hashes: Vec<u64>,
+    /// `true` once the next pair would exceed the size limit
+    closed: bool,
+}
+
+impl Batch {
+    fn push(&mut self, key: Vec<u8>, value: Vec<u8>) {
+        let mut hasher = DefaultHasher::new();
+        key.hash(&mut hasher);
+        self.hashes.push(hasher.finish());
+        self.bytes += key.len() + value.len();
+        self.pairs.push((key, value));
+    }
+
+    fn is_full(&self, opts: &BulkOptions) -> bool {
+        self.closed
+            || self.pairs.len() >= opts.max_batch_count
+            || self.bytes >= opts.max_batch_bytes
+    }
+}
+
+/// The outcome of a batch: the number of attempts, and the batch without its pairs
+type BatchFuture<'a> = BoxFuture<'a, (FdbResult<usize>, Batch)>;
+
+impl Database {
+    /// Writes the pairs of `items`, in transactions of at most `opts.max_batch_count` pairs
+    /// and about `opts.max_batch_bytes` bytes, committing up to `opts.concurrency` of them
+    /// concurrently.
+    ///
+    /// Each transaction is committed in the retry loop of `transact`. If a key is written
+    /// several times, the last value streamed wins: a transaction is only committed once the
+    /// transactions writing the same keys before it are. The insertion stops at the first
+    /// error, the transactions already committed are not rolled back.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `opts.max_batch_count` or `opts.concurrency` is 0.
+    pub async fn bulk_insert<S>(&self, items: S, opts: BulkOptions) -> FdbResult<BulkStats>
+    where
+        S: Stream<Item = (Vec<u8>, Vec<u8>)>,
+    {
+        assert!(opts.max_batch_count > 0, "max_batch_count must not be 0");
+        assert!(opts.concurrency > 0, "concurrency must not be 0");
+
+        let started = Instant::now();
+        let mut stats = BulkStats::default();
+        let mut in_flight: FuturesUnordered<BatchFuture> = FuturesUnordered::new();
+        // the number of batches in flight writing each key hash
+        let mut pending: HashMap<u64, usize> = HashMap::new();
+        let mut batch = Batch::default();
+        let mut next_pair = None;
+        let mut items_done = false;
+        futures::pin_mut!(items);
+
+        loop {
+            let ready = batch.is_full(&opts) || (items_done && !batch.pairs.is_empty());
+            if ready {
+                let blocked = in_flight.len() >= opts.concurrency
+                    || batch.hashes.iter().any(|hash| pending.contains_key(hash));
+                if !blocked {
+                    for hash in &batch.hashes {
+                        *pending.entry(*hash).or_insert(0) += 1;
+                    }
+                    let batch = std::mem::take(&mut batch);
+                    in_flight.push(self.commit_batch(batch));
+                    continue;
+                }
+            } else if let Some((key, value)) = next_pair.take() {
+                batch.push(key, value);
+                continue;
+            } else if !items_done {
+                let item = if in_flight.is_empty() {
+                    Either::Left(items.next().await)
+                } else {
+                    // keeps driving the batches in flight while waiting for the items
+                    match future::select(items.next(), in_flight.next()).await {
+                        Either::Left((item, _)) => Either::Left(item),
+                        Either::Right((done, _)) => Either::Right(done),
+                    }
+                };
+                match item {
+                    Either::Left(Some((key, value))) => {
+                        let size = key.len() + value.len();
+                        if !batch.pairs.is_empty() && batch.bytes + size > opts.max_batch_bytes {
+                            batch.closed = true;
+                            next_pair = Some((key, value));
+                        } else {
+                            batch.push(key, value);
+                        }
+                    }
+                    Either::Left(None) => items_done = true,
+                    Either::Right(Some(done)) => {
+                        record_batch(&mut stats, &mut pending, done)?;
+                    }
+                    Either::Right(None) => {}
+                }
+                continue;
+            }
+
+            // the next batch is blocked, or all the batches are dispatched
+            match in_flight.next().await {
+                Some(done) => record_batch(&mut stats, &mut pending, done)?,
+                None => break,
+            }
+        }
+
+        stats.elapsed = started.elapsed();
+        Ok(stats)
+    }
+
+    fn commit_batch(&self, batch: Batch) -> BatchFuture {
+        let Batch {
+            pairs,
+            bytes,
+            hashes,
+            ..
+        } = batch;
+        self.transact_boxed(
+            (pairs, 0usize),
+            |trx, (pairs, attempts)| {
+                *attempts += 1;
+                for (key, value) in pairs.iter() {
+                    trx.set(key, value);
+                }
+                future::ok::<_, FdbError>(*attempts).boxed()
+            },
+            // writing the same pairs again is harmless
+            TransactOption::idempotent(),
+        )
+        .map(move |attempts| {
+            let batch = Batch {
+                bytes,
+                hashes,
+                ..Batch::default()
+            };
+            (attempts, batch)
+        })
+        .boxed()
+    }
+}
+
+/// Adds a finished batch to `stats`, and releases its keys.
+fn record_batch(
+    stats: &mut BulkStats,
+    pending: &mut HashMap<u64, usize>,
+    (attempts, batch): (FdbResult<usize>, Batch),
+) -> FdbResult<()> {
+    for hash in &batch.hashes {
+        if let Some(count) = pending.get_mut(hash) {
+            *count -= 1;
+            if *count == 0 {
+                pending.remove(hash);
+            }
+        }
+    }
+    let attempts = attempts?;
+    stats.keys += batch.hashes.len();
+    stats.bytes += batch.bytes;
+    stats.transactions += 1;
+    stats.retries += attempts - 1;
+    Ok(())
+}
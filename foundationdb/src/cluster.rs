@@ -92,9 +92,8 @@ impl TryFrom<FdbFutureHandle> for Database {
         let mut v: *mut fdb_sys::FDBDatabase = std::ptr::null_mut();
         error::eval(unsafe { fdb_sys::fdb_future_get_database(f.as_ptr(), &mut v) })?;
 
-        Ok(Database {
-            inner: NonNull::new(v)
-                .expect("fdb_future_get_database to not return null if there is no error"),
-        })
+        Ok(Database::new_from_pointer(NonNull::new(v).expect(
+            "fdb_future_get_database to not return null if there is no error",
+        )))
     }
 }
@@ -0,0 +1,333 @@
+// Copyright 2018 foundationdb-rs developers, https://github.com/Clikengo/foundationdb-rs/graphs/contributors
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! Locating and parsing cluster files
+//!
+//! A cluster file (often called `fdb.cluster`) contains a single connection string of the form
+//! `description:ID@host:port,host:port,...`, the addresses being the coordinators of the
+//! cluster. See the [administration guide] for details.
+//!
+//! [administration guide]: https://apple.github.io/foundationdb/administration.html#cluster-file-format
+
+use std::env;
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+/// The environment variable overriding the cluster file path
+pub const CLUSTER_FILE_ENV: &str = "FDB_CLUSTER_FILE";
+
+/// The name of the cluster file looked up in the working directory
+pub const LOCAL_CLUSTER_FILE: &str = "fdb.cluster";
+
+/// Returns the path of the cluster file the client library uses when none is given.
+///
+/// This is the value of the `FDB_CLUSTER_FILE` environment variable if it is set, then
+/// `fdb.cluster` in the working directory if it exists, then `default_config_path()`.
+pub fn resolve() -> PathBuf {
+    if let Some(path) = env::var_os(CLUSTER_FILE_ENV) {
+        if !path.is_empty() {
+            return PathBuf::from(path);
+        }
+    }
+
+    let local = Path::new(LOCAL_CLUSTER_FILE);
+    if local.is_file() {
+        return local.to_path_buf();
+    }
+
+    PathBuf::from(crate::default_config_path())
+}
+
+/// The content of a cluster file
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ClusterDescription {
+    /// A name for the cluster, made of alphanumeric characters and underscores
+    pub description: String,
+    /// An arbitrary identifier, made of alphanumeric characters, changed when the coordinators
+    /// change
+    pub id: String,
+    /// The coordination servers of the cluster
+    pub coordinators: Vec<CoordinatorAddress>,
+}
+
+impl fmt::Display for ClusterDescription {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}:{}@", self.description, self.id)?;
+        for (i, coordinator) in self.coordinators.iter().enumerate() {
+            if i > 0 {
+                write!(f, ",")?;
+            }
+            write!(f, "{}", coordinator)?;
+        }
+        Ok(())
+    }
+}
+
+/// The address of a coordination server
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct CoordinatorAddress {
+    /// An IPv4 or IPv6 address (without brackets), or a hostname
+    pub host: String,
+    pub port: u16,
+    /// `true` if the address has the `:tls` suffix
+    pub tls: bool,
+}
+
+impl fmt::Display for CoordinatorAddress {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.host.contains(':') {
+            write!(f, "[{}]:{}", self.host, self.port)?;
+        } else {
+            write!(f, "{}:{}", self.host, self.port)?;
+        }
+        if self.tls {
+            write!(f, ":tls")?;
+        }
+        Ok(())
+    }
+}
+
+/// The errors returned by `parse`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ParseError {
+    /// The cluster file doesn't contain a connection string
+    Empty,
+    /// The cluster file contains more than one connection string
+    MultipleConnectionStrings,
+    /// The connection string is not of the form `description:ID@addresses`
+    InvalidFormat,
+    /// The description contains characters other than alphanumeric characters and underscores
+    InvalidDescription(String),
+    /// The ID contains characters other than alphanumeric characters
+    InvalidId(String),
+    /// The connection string has no coordinator
+    NoCoordinators,
+    /// A coordinator address is not of the form `host:port`, optionally followed by `:tls`
+    InvalidCoordinator(String),
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ParseError::Empty => write!(f, "the cluster file is empty"),
+            ParseError::MultipleConnectionStrings => {
+                write!(
+                    f,
+                    "the cluster file contains more than one connection string"
+                )
+            }
+            ParseError::InvalidFormat => write!(
+                f,
+                "the connection string is not of the form `description:ID@addresses`"
+            ),
+            ParseError::InvalidDescription(description) => {
+                write!(f, "invalid cluster description {:?}", description)
+            }
+            ParseError::InvalidId(id) => write!(f, "invalid cluster ID {:?}", id),
+            ParseError::NoCoordinators => write!(f, "the cluster has no coordinators"),
+            ParseError::InvalidCoordinator(address) => {
+                write!(f, "invalid coordinator address {:?}", address)
+            }
+        }
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+/// Parses the content of a cluster file.
+///
+/// Empty lines and lines starting with `#` are ignored.
+pub fn parse(content: &str) -> Result<ClusterDescription, ParseError> {
+    let mut lines = content
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'));
+    let connection_string = lines.next().ok_or(ParseError::Empty)?;
+    if lines.next().is_some() {
+        return Err(ParseError::MultipleConnectionStrings);
+    }
+
+    let at = connection_string
+        .find('@')
+        .ok_or(ParseError::InvalidFormat)?;
+    let (name, addresses) = (&connection_string[..at], &connection_string[at + 1..]);
+    let colon = name.find(':').ok_or(ParseError::InvalidFormat)?;
+    let (description, id) = (&name[..colon], &name[colon + 1..]);
+
+    if description.is_empty()
+        || !description
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_')
+    {
+        return Err(ParseError::InvalidDescription(description.to_owned()));
+    }
+    if id.is_empty() || !id.chars().all(|c| c.is_ascii_alphanumeric()) {
+        return Err(ParseError::InvalidId(id.to_owned()));
+    }
+    if addresses.trim().is_empty() {
+        return Err(ParseError::NoCoordinators);
+    }
+
+    let coordinators = addresses
+        .split(',')
+        .map(|address| parse_coordinator(address.trim()))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    Ok(ClusterDescription {
+        description: description.to_owned(),
+        id: id.to_owned(),
+        coordinators,
+    })
+}
+
+fn parse_coordinator(address: &str) -> Result<CoordinatorAddress, ParseError> {
+    let invalid = || ParseError::InvalidCoordinator(address.to_owned());
+
+    let (rest, tls) = match address.rfind(':') {
+        Some(colon) if &address[colon + 1..] == "tls" => (&address[..colon], true),
+        _ => (address, false),
+    };
+
+    let colon = rest.rfind(':').ok_or_else(invalid)?;
+    let (host, port) = (&rest[..colon], &rest[colon + 1..]);
+    let host = if host.contains(':') {
+        // IPv6 addresses are enclosed in brackets: `[addr]:port`
+        let mut parts = host.split(&['[', ']'][..]);
+        match (parts.next(), parts.next(), parts.next(), parts.next()) {
+            (Some(""), Some(ip), Some(""), None) => ip,
+            _ => return Err(invalid()),
+        }
+    } else {
+        host
+    };
+
+    if host.is_empty() || host.contains(char::is_whitespace) {
+        return Err(invalid());
+    }
+    let port = port.parse::<u16>().map_err(|_| invalid())?;
+
+    Ok(CoordinatorAddress {
+        host: host.to_owned(),
+        port,
+        tls,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn coordinator(host: &str, port: u16, tls: bool) -> CoordinatorAddress {
+        CoordinatorAddress {
+            host: host.to_owned(),
+            port,
+            tls,
+        }
+    }
+
+    #[test]
+    fn test_parse_valid() {
+        let cluster = parse("docker:docker@127.0.0.1:4500\n").unwrap();
+        assert_eq!(cluster.description, "docker");
+        assert_eq!(cluster.id, "docker");
+        assert_eq!(
+            cluster.coordinators,
+            vec![coordinator("127.0.0.1", 4500, false)]
+        );
+        assert_eq!(cluster.to_string(), "docker:docker@127.0.0.1:4500");
+
+        let cluster = parse("# the cluster\n\n  my_db:a1B2c3@fdb-0.local:4500  \n").unwrap();
+        assert_eq!(cluster.description, "my_db");
+        assert_eq!(cluster.id, "a1B2c3");
+        assert_eq!(
+            cluster.coordinators,
+            vec![coordinator("fdb-0.local", 4500, false)]
+        );
+    }
+
+    #[test]
+    fn test_parse_multiple_coordinators() {
+        let cluster = parse("prod:abc123@10.0.0.1:4500,10.0.0.2:4501, [::1]:4502").unwrap();
+        assert_eq!(
+            cluster.coordinators,
+            vec![
+                coordinator("10.0.0.1", 4500, false),
+                coordinator("10.0.0.2", 4501, false),
+                coordinator("::1", 4502, false),
+            ]
+        );
+        assert_eq!(
+            cluster.to_string(),
+            "prod:abc123@10.0.0.1:4500,10.0.0.2:4501,[::1]:4502"
+        );
+    }
+
+    #[test]
+    fn test_parse_tls() {
+        let cluster = parse("prod:abc123@10.0.0.1:4500:tls,[2001:db8::1]:4500:tls").unwrap();
+        assert_eq!(
+            cluster.coordinators,
+            vec![
+                coordinator("10.0.0.1", 4500, true),
+                coordinator("2001:db8::1", 4500, true),
+            ]
+        );
+        assert_eq!(
+            cluster.to_string(),
+            "prod:abc123@10.0.0.1:4500:tls,[2001:db8::1]:4500:tls"
+        );
+    }
+
+    #[test]
+    fn test_parse_malformed() {
+        assert_eq!(parse(""), Err(ParseError::Empty));
+        assert_eq!(parse("# only a comment\n"), Err(ParseError::Empty));
+        assert_eq!(
+            parse("a:b@1.2.3.4:4500\nc:d@1.2.3.4:4500"),
+            Err(ParseError::MultipleConnectionStrings)
+        );
+        assert_eq!(parse("a:b"), Err(ParseError::InvalidFormat));
+        assert_eq!(parse("ab@1.2.3.4:4500"), Err(ParseError::InvalidFormat));
+        assert_eq!(
+            parse("a-b:c@1.2.3.4:4500"),
+            Err(ParseError::InvalidDescription("a-b".to_owned()))
+        );
+        assert_eq!(
+            parse(":c@1.2.3.4:4500"),
+            Err(ParseError::InvalidDescription("".to_owned()))
+        );
+        assert_eq!(
+            parse("a:c_d@1.2.3.4:4500"),
+            Err(ParseError::InvalidId("c_d".to_owned()))
+        );
+        assert_eq!(parse("a:b@"), Err(ParseError::NoCoordinators));
+        assert_eq!(
+            parse("a:b@1.2.3.4"),
+            Err(ParseError::InvalidCoordinator("1.2.3.4".to_owned()))
+        );
+        assert_eq!(
+            parse("a:b@1.2.3.4:port"),
+            Err(ParseError::InvalidCoordinator("1.2.3.4:port".to_owned()))
+        );
+        assert_eq!(
+            parse("a:b@1.2.3.4:99999"),
+            Err(ParseError::InvalidCoordinator("1.2.3.4:99999".to_owned()))
+        );
+        assert_eq!(
+            parse("a:b@1.2.3.4:4500,"),
+            Err(ParseError::InvalidCoordinator("".to_owned()))
+        );
+        assert_eq!(
+            parse("a:b@::1:4500"),
+            Err(ParseError::InvalidCoordinator("::1:4500".to_owned()))
+        );
+        assert_eq!(
+            parse("a:b@[::1:4500"),
+            Err(ParseError::InvalidCoordinator("[::1:4500".to_owned()))
+        );
+    }
+}
@@ -11,33 +11,67 @@
 //! https://apple.github.io/foundationdb/api-c.html#database
 
 use std::convert::TryInto;
+use std::fmt;
 use std::marker::PhantomData;
 use std::pin::Pin;
 use std::ptr::NonNull;
+use std::sync::Arc;
 use std::time::{Duration, Instant};
 
 use foundationdb_sys as fdb_sys;
 
+#[cfg(feature = "fdb-7_1")]
+use crate::api::ProtocolVersion;
+#[cfg(feature = "fdb-7_1")]
+use crate::future::FdbFuture;
+use crate::future::{AddressParseError, FdbSlice, StorageServerAddress};
 use crate::options;
+use crate::trace;
 use crate::transaction::*;
+#[cfg(feature = "metrics")]
+use crate::Instrumentation;
+use crate::KeySelector;
 use crate::{error, FdbError, FdbResult};
 
 use futures::prelude::*;
+use rand::Rng;
 
 /// Represents a FoundationDB database
 ///
 /// A mutable, lexicographically ordered mapping from binary keys to binary values.
 ///
 /// Modifications to a database are performed via transactions.
+///
+/// The database can be used from multiple threads at the same time, e.g. to call `create_trx`
+/// concurrently. Cloning it is cheap: the clones share the same connection, which is closed
+/// once all of them are dropped, so there is no need to wrap it in an `Arc`. The settings of
+/// a clone, like `set_default_transaction_options`, are independent.
+#[derive(Clone)]
 pub struct Database {
-    pub(crate) inner: NonNull<fdb_sys::FDBDatabase>,
+    pub(crate) inner: Arc<DatabaseHandle>,
+    default_trx_options: Vec<options::TransactionOption>,
+    size_validation: bool,
+    read_fault_injector: Option<ReadFaultInjector>,
+    read_version_cache: Option<Arc<ReadVersionCache>>,
+    #[cfg(feature = "metrics")]
+    instrumentation: Option<Arc<dyn Instrumentation>>,
+}
+assert_impl_all!(Database: Clone, Send, Sync);
+
+/// An FDBDatabase of the C API, which is thread-safe.
+pub(crate) struct DatabaseHandle(NonNull<fdb_sys::FDBDatabase>);
+
+impl DatabaseHandle {
+    pub(crate) fn as_ptr(&self) -> *mut fdb_sys::FDBDatabase {
+        self.0.as_ptr()
+    }
 }
-unsafe impl Send for Database {}
-unsafe impl Sync for Database {}
-impl Drop for Database {
+unsafe impl Send for DatabaseHandle {}
+unsafe impl Sync for DatabaseHandle {}
+impl Drop for DatabaseHandle {
     fn drop(&mut self) {
         unsafe {
-            fdb_sys::fdb_database_destroy(self.inner.as_ptr());
+            fdb_sys::fdb_database_destroy(self.as_ptr());
         }
     }
 }
@@ -56,10 +90,9 @@ impl Database {
         let err = unsafe { fdb_sys::fdb_create_database(path_ptr, &mut v) };
         drop(path_str); // path_str own the CString that we are getting the ptr from
         error::eval(err)?;
-        Ok(Database {
-            inner: NonNull::new(v)
-                .expect("fdb_create_database to not return null if there is no error"),
-        })
+        Ok(Database::new_from_pointer(NonNull::new(v).expect(
+            "fdb_create_database to not return null if there is no error",
+        )))
     }
 
     /// Create a database for the given configuration path
@@ -74,11 +107,31 @@ impl Database {
 }
 
 impl Database {
+    pub(crate) fn new_from_pointer(inner: NonNull<fdb_sys::FDBDatabase>) -> Self {
+        Database {
+            inner: Arc::new(DatabaseHandle(inner)),
+            default_trx_options: Vec::new(),
+            size_validation: false,
+            read_fault_injector: None,
+            read_version_cache: None,
+            #[cfg(feature = "metrics")]
+            instrumentation: None,
+        }
+    }
+
     /// Create a database for the given configuration path
     ///
+    /// If `path` is `None`, the path is found with `cluster_file::resolve`.
+    ///
     /// This is a compatibility api. If you only use API version ≥ 610 you should
     /// use `Database::new`, `Database::from_path` or  `Database::default`.
     pub async fn new_compat(path: Option<&str>) -> FdbResult<Database> {
+        let resolved = match path {
+            Some(_) => None,
+            None => crate::cluster_file::resolve().to_str().map(str::to_owned),
+        };
+        let path = path.or(resolved.as_deref());
+
         #[cfg(any(feature = "fdb-5_1", feature = "fdb-5_2", feature = "fdb-6_0"))]
         {
             let cluster = crate::cluster::Cluster::new(path).await?;
@@ -93,83 +146,450 @@ impl Database {
     }
 
     /// Called to set an option an on `Database`.
+    ///
+    /// The returned error names the option that could not be set. Some options must be set
+    /// before the database is used, see `DatabaseOption::is_settable_after_use`.
     pub fn set_option(&self, opt: options::DatabaseOption) -> FdbResult<()> {
         unsafe { opt.apply(self.inner.as_ptr()) }
+            .map_err(|err| err.with_context(format!("failed to set {:?}", opt)))
+    }
+
+    /// Sets several options on this `Database`, in order, see `set_option`.
+    ///
+    /// Stops at the first option that can't be set and returns its error, which names it. The
+    /// options set before it stay set.
+    pub fn set_options(
+        &self,
+        opts: impl IntoIterator<Item = options::DatabaseOption>,
+    ) -> FdbResult<()> {
+        opts.into_iter().try_for_each(|opt| self.set_option(opt))
+    }
+
+    /// Sets the options applied to every transaction created by this database, e.g.
+    /// `TransactionOption::Timeout` or `TransactionOption::RetryLimit`.
+    ///
+    /// The options are applied by `create_trx` before the transaction is returned, and again by
+    /// `transact` after each retry. Only the transactions created after this call are affected.
+    pub fn set_default_transaction_options(&mut self, options: Vec<options::TransactionOption>) {
+        self.default_trx_options = options;
+    }
+
+    /// Enables or disables the validation of the sizes of keys and values in the transactions
+    /// created by this database, see `Transaction::set_size_validation`.
+    ///
+    /// Only the transactions created after this call are affected.
+    pub fn set_size_validation(&mut self, enabled: bool) {
+        self.size_validation = enabled;
+    }
+
+    /// Enables the caching of read versions: the transactions of this database calling
+    /// `Transaction::use_cached_read_version`, as well as the one-shot reads `get`, `get_range`
+    /// and `get_key`, reuse the latest read version obtained or committed by another one, as long
+    /// as it is younger than `max_staleness`.
+    ///
+    /// This saves the round trip to get a read version, which dominates the latency of short
+    /// reads, at the cost of consistency: the reads may not see what other clients committed
+    /// during the last `max_staleness`, see `Transaction::use_cached_read_version`. The version
+    /// cached is forgotten when the cluster reports it as a future version (`future_version`,
+    /// 1009). `max_staleness` should stay well below 5 seconds, the age at which reads fail with
+    /// `transaction_too_old` (1007).
+    ///
+    /// Only the transactions created after this call use the cache, which is shared with the
+    /// clones of this database made after this call.
+    pub fn set_read_version_cache(&mut self, max_staleness: Duration) {
+        self.read_version_cache = Some(Arc::new(ReadVersionCache::new(max_staleness)));
+    }
+
+    /// Applies the options set by `set_default_transaction_options` to `trx`.
+    fn apply_default_transaction_options(&self, trx: &Transaction) -> FdbResult<()> {
+        for opt in &self.default_trx_options {
+            trx.set_option(opt.clone())?;
+        }
+        Ok(())
     }
 
     /// Creates a new transaction on the given database.
+    ///
+    /// Fails if one of the options set by `set_default_transaction_options` can't be applied.
     pub fn create_trx(&self) -> FdbResult<Transaction> {
         let mut trx: *mut fdb_sys::FDBTransaction = std::ptr::null_mut();
         let err =
             unsafe { fdb_sys::fdb_database_create_transaction(self.inner.as_ptr(), &mut trx) };
         error::eval(err)?;
-        Ok(Transaction::new(NonNull::new(trx).expect(
-            "fdb_database_create_transaction to not return null if there is no error",
-        )))
+        let mut trx = Transaction::new(
+            NonNull::new(trx)
+                .expect("fdb_database_create_transaction to not return null if there is no error"),
+        );
+        #[cfg(feature = "metrics")]
+        {
+            if self.instrumentation.is_some() {
+                trx.instrumentation = self.instrumentation.clone();
+            }
+        }
+        if self.size_validation {
+            trx.set_size_validation(true);
+        }
+        trx.read_version_cache = self.read_version_cache.clone();
+        self.apply_default_transaction_options(&trx)?;
+        Ok(trx)
+    }
+
+    /// Returns the protocol version spoken by the cluster.
+    ///
+    /// The future resolves once the client is connected to the cluster.
+    #[cfg(feature = "fdb-7_1")]
+    pub fn get_server_protocol_version(
+        &self,
+    ) -> impl Future<Output = FdbResult<ProtocolVersion>> + Send + Sync + Unpin {
+        // an expected version of 0 resolves with the current protocol version
+        FdbFuture::<u64>::new(unsafe {
+            fdb_sys::fdb_database_get_server_protocol(self.inner.as_ptr(), 0)
+        })
+        .map_ok(ProtocolVersion)
+    }
+
+    /// Sets the instrumentation of the transactions of this database, overriding the one set by
+    /// `set_instrumentation`.
+    ///
+    /// Only the transactions created after this call are instrumented.
+    #[cfg(feature = "metrics")]
+    pub fn set_instrumentation(&mut self, instrumentation: Arc<dyn Instrumentation>) {
+        self.instrumentation = Some(instrumentation);
+    }
+
+    /// Reports a retry of `transact` with the error `code` to the instrumentation, if any.
+    #[cfg_attr(not(feature = "metrics"), allow(unused_variables))]
+    fn report_retry(&self, span: &trace::TransactSpan, code: i32) {
+        span.retry(code);
+        #[cfg(feature = "metrics")]
+        {
+            let instrumentation = self
+                .instrumentation
+                .clone()
+                .or_else(crate::metrics::global_instrumentation);
+            if let Some(instrumentation) = instrumentation {
+                instrumentation.on_retry(code);
+            }
+        }
+    }
+
+    /// Reports the conflicting ranges of the failed commit `e` to the span and the
+    /// instrumentation, if any.
+    ///
+    /// This is only informative: the retry goes on if they can't be read.
+    #[cfg(feature = "fdb-7_1")]
+    async fn report_conflict(&self, span: &trace::TransactSpan, e: &TransactionCommitError) {
+        let ranges = match e.conflicting_keys().await {
+            Ok(ranges) => ranges,
+            Err(_) => return,
+        };
+        span.conflict(ranges.len());
+        #[cfg(feature = "metrics")]
+        {
+            let instrumentation = self
+                .instrumentation
+                .clone()
+                .or_else(crate::metrics::global_instrumentation);
+            if let Some(instrumentation) = instrumentation {
+                instrumentation.on_conflict(&ranges);
+            }
+        }
     }
 
     /// `transact` returns a future which retries on error. It tries to resolve a future created by
     /// caller-provided function `f` inside a retry loop, providing it with a newly created
     /// transaction. After caller-provided future resolves, the transaction will be committed
-    /// automatically.
+    /// automatically, unless `TransactOption::is_read_only` is set.
     ///
     /// # Warning
     ///
     /// It might retry indefinitely if the transaction is highly contentious. It is recommended to
-    /// set `TransactionOption::RetryLimit` or `TransactionOption::SetTimeout` on the transaction
-    /// if the task need to be guaranteed to finish.
+    /// set `TransactOption::retry_limit` or `TransactOption::deadline` if the task need to be
+    /// guaranteed to finish. Transaction options set by `f`, like `TransactionOption::Timeout`,
+    /// must be set again on every attempt, unlike the ones set by
+    /// `Database::set_default_transaction_options`, unless `TransactOption::remember_options` is
+    /// enabled.
     ///
     /// Once [Generic Associated Types](https://github.com/rust-lang/rfcs/blob/master/text/1598-generic_associated_types.md)
     /// lands in stable rust, the returned future of f won't need to be boxed anymore, also the
     /// lifetime limitations around f might be lowered.
-    pub async fn transact<F>(&self, mut f: F, options: TransactOption) -> Result<F::Item, F::Error>
+    ///
+    /// `transact_boxed` and `transact_boxed_local` are easier to use, see the example of
+    /// `transact_boxed`.
+    pub async fn transact<F>(&self, f: F, options: TransactOption) -> Result<F::Item, F::Error>
+    where
+        F: DatabaseTransact,
+    {
+        let span = trace::TransactSpan::new();
+        span.instrument(self.transact_attempts(f, options, &span))
+            .await
+    }
+
+    async fn transact_attempts<F>(
+        &self,
+        mut f: F,
+        options: TransactOption,
+        span: &trace::TransactSpan,
+    ) -> Result<F::Item, F::Error>
     where
         F: DatabaseTransact,
     {
         let is_idempotent = options.is_idempotent;
+        let is_read_only = options.is_read_only;
         let time_out = options.time_out.map(|d| Instant::now() + d);
+        let deadline = options.deadline.map(|d| Instant::now() + d);
         let retry_limit = options.retry_limit;
+        let max_transaction_size = options.max_transaction_size;
+        let remember_options = options.remember_options.unwrap_or(false);
+        let backoff = options.backoff.clone();
+        #[cfg(feature = "fdb-7_1")]
+        let report_conflicting_keys = options.report_conflicting_keys;
+        let classify = move |err: &FdbError, attempt: usize| match &options.retry_classifier {
+            Some(classifier) => classifier(err, attempt),
+            None => RetryDecision::default_for(err, is_idempotent),
+        };
         let mut tries: u32 = 0;
         let mut trx = self.create_trx()?;
+        trx.remember_options(remember_options);
         let mut can_retry = move || {
             tries += 1;
             retry_limit.map(|limit| tries < limit).unwrap_or(true)
                 && time_out.map(|t| Instant::now() < t).unwrap_or(true)
         };
+        let mut attempt: usize = 0;
+        let mut retries: u32 = 0;
         loop {
+            attempt += 1;
+            span.attempt(attempt);
+            if let Some(deadline) = deadline {
+                // `on_error` and `reset` clear the timeout of the transaction
+                if let Err(err) = apply_deadline(&trx, deadline) {
+                    break Err(F::Error::from(span.failed(err)));
+                }
+            }
+            #[cfg(feature = "fdb-7_1")]
+            {
+                // `on_error` and `reset` clear the options of the transaction
+                if report_conflicting_keys {
+                    let option = options::TransactionOption::ReportConflictingKeys;
+                    if let Err(err) = trx.set_option(option) {
+                        break Err(F::Error::from(span.failed(err)));
+                    }
+                }
+            }
             let r = f.transact(trx).await;
             f = r.0;
             trx = r.1;
             trx = match r.2 {
+                // reads don't need a commit, they are all done at the same read version
+                Ok(item) if is_read_only => break Ok(item),
+                Ok(_)
+                    if max_transaction_size
+                        .filter(|&max| trx.estimated_mutation_bytes() > max)
+                        .is_some() =>
+                {
+                    let size = trx.estimated_mutation_bytes();
+                    let limit = max_transaction_size.unwrap_or_default();
+                    break Err(F::Error::from(
+                        span.failed(FdbError::from_size_guard(size, limit)),
+                    ));
+                }
                 Ok(item) => match trx.commit().await {
                     Ok(_) => break Ok(item),
                     Err(e) => {
-                        if (is_idempotent || !e.is_maybe_committed()) && can_retry() {
-                            e.on_error().await?
-                        } else {
-                            break Err(F::Error::from(e.into()));
+                        #[cfg(feature = "fdb-7_1")]
+                        {
+                            if report_conflicting_keys && e.code() == NOT_COMMITTED {
+                                self.report_conflict(span, &e).await;
+                            }
+                        }
+                        match classify(&e, attempt) {
+                            RetryDecision::Retry { via_on_error } if can_retry() => {
+                                retries += 1;
+                                let delay = match backoff.delay(retries, &e) {
+                                    Some(delay) => delay,
+                                    None => break Err(F::Error::from(span.failed(e.into()))),
+                                };
+                                self.report_retry(span, e.code());
+                                let trx = if via_on_error {
+                                    e.on_error().await?
+                                } else {
+                                    e.reset()
+                                };
+                                backoff_delay(delay).await;
+                                trx
+                            }
+                            RetryDecision::RetryFresh if can_retry() => {
+                                retries += 1;
+                                let delay = match backoff.delay(retries, &e) {
+                                    Some(delay) => delay,
+                                    None => break Err(F::Error::from(span.failed(e.into()))),
+                                };
+                                self.report_retry(span, e.code());
+                                let options = e.reset().applied_options();
+                                backoff_delay(delay).await;
+                                self.fresh_trx(options, remember_options)?
+                            }
+                            _ => break Err(F::Error::from(span.failed(e.into()))),
                         }
                     }
                 },
                 Err(user_err) => match user_err.try_into_fdb_error() {
-                    Ok(e) => {
-                        if (is_idempotent || !e.is_maybe_committed()) && can_retry() {
-                            trx.on_error(e).await?
-                        } else {
-                            break Err(F::Error::from(e));
+                    Ok(e) => match classify(&e, attempt) {
+                        RetryDecision::Retry { via_on_error } if can_retry() => {
+                            retries += 1;
+                            let delay = match backoff.delay(retries, &e) {
+                                Some(delay) => delay,
+                                None => break Err(F::Error::from(span.failed(e))),
+                            };
+                            self.report_retry(span, e.code());
+                            let trx = if via_on_error {
+                                trx.on_error(e).await?
+                            } else {
+                                trx.reset();
+                                trx
+                            };
+                            backoff_delay(delay).await;
+                            trx
                         }
-                    }
+                        RetryDecision::RetryFresh if can_retry() => {
+                            retries += 1;
+                            let delay = match backoff.delay(retries, &e) {
+                                Some(delay) => delay,
+                                None => break Err(F::Error::from(span.failed(e))),
+                            };
+                            self.report_retry(span, e.code());
+                            let options = trx.applied_options();
+                            drop(trx);
+                            backoff_delay(delay).await;
+                            self.fresh_trx(options, remember_options)?
+                        }
+                        _ => break Err(F::Error::from(span.failed(e))),
+                    },
                     Err(user_err) => break Err(user_err),
                 },
             };
+            // `on_error` and `reset` may clear the default options
+            if let Err(err) = self.apply_default_transaction_options(&trx) {
+                break Err(F::Error::from(span.failed(err)));
+            }
+        }
+    }
+
+    /// Creates the transaction of a `RetryDecision::RetryFresh` attempt, setting again the
+    /// `options` applied to the failed transaction.
+    fn fresh_trx(
+        &self,
+        options: Vec<options::TransactionOption>,
+        remember_options: bool,
+    ) -> FdbResult<Transaction> {
+        let mut trx = self.create_trx()?;
+        trx.remember_options(remember_options);
+        for option in options {
+            trx.set_option(option)?;
         }
+        Ok(trx)
     }
 
+    /// Runs `f` in the retry loop of `transact`, without boxing its future.
+    ///
+    /// `f` owns the transaction while it runs and gives it back with its result, so that the loop
+    /// can commit it or handle the error with `on_error`. Unlike `transact_boxed`, there is no
+    /// data parameter: state shared between the attempts is captured by the closure, usually as
+    /// an `Arc`.
+    ///
+    /// ```no_run
+    /// # async fn example(db: &foundationdb::Database) -> foundationdb::FdbResult<()> {
+    /// use std::sync::atomic::{AtomicUsize, Ordering};
+    /// use std::sync::Arc;
+    ///
+    /// let attempts = Arc::new(AtomicUsize::new(0));
+    /// let value = db
+    ///     .transact_owned(
+    ///         |trx| {
+    ///             let attempts = attempts.clone();
+    ///             async move {
+    ///                 attempts.fetch_add(1, Ordering::SeqCst);
+    ///                 match trx.get(b"key", false).await {
+    ///                     Ok(value) => Ok((trx, value.map(|value| value.to_vec()))),
+    ///                     Err(err) => Err((trx, err)),
+    ///                 }
+    ///             }
+    ///         },
+    ///         foundationdb::TransactOption::default(),
+    ///     )
+    ///     .await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn transact_owned<F, Fut, T>(&self, f: F, options: TransactOption) -> FdbResult<T>
+    where
+        F: Fn(Transaction) -> Fut + Send + Sync,
+        Fut: Future<Output = Result<(Transaction, T), (Transaction, FdbError)>>,
+    {
+        self.transact(owned::FnOwned { f }, options).await
+    }
+
+    /// Runs `f` with `data` in the retry loop of `transact`.
+    ///
+    /// Unless `TransactOption::remember_options` is set to `false`, the options set by `f` are
+    /// remembered and applied again to the transaction after each retry, including the fresh
+    /// transactions created for `RetryDecision::RetryFresh`, see `Transaction::remember_options`.
+    ///
+    /// # Example
+    ///
+    /// Signing a student up for a class, as in the class scheduling tutorial ported by
+    /// `examples/class_scheduling.rs`:
+    ///
+    /// ```no_run
+    /// use foundationdb::options::MutationType;
+    /// use foundationdb::tuple::Subspace;
+    /// use foundationdb::{Database, FdbError, TransactOption, Transaction};
+    /// use futures::prelude::*;
+    ///
+    /// /// Returns `false` if the class is full.
+    /// async fn signup(
+    ///     trx: &Transaction,
+    ///     classes: &Subspace,
+    ///     attends: &Subspace,
+    ///     student: &str,
+    ///     class: &str,
+    /// ) -> Result<bool, FdbError> {
+    ///     let class_key = classes.pack(&class);
+    ///     let seats = match trx.get(&class_key, false).await? {
+    ///         Some(seats) if seats.len() == 8 => {
+    ///             let mut bytes = [0; 8];
+    ///             bytes.copy_from_slice(&seats);
+    ///             i64::from_le_bytes(bytes)
+    ///         }
+    ///         _ => 0,
+    ///     };
+    ///     if seats <= 0 {
+    ///         return Ok(false);
+    ///     }
+    ///     trx.atomic_op(&class_key, &(-1i64).to_le_bytes(), MutationType::Add);
+    ///     trx.set(&attends.pack(&(student, class)), b"");
+    ///     Ok(true)
+    /// }
+    ///
+    /// # async fn example(db: &Database, classes: &Subspace, attends: &Subspace) -> Result<(), FdbError> {
+    /// let signed_up = db
+    ///     .transact_boxed(
+    ///         (classes, attends, "alice", "9:00 chem 101"),
+    ///         |trx, (classes, attends, student, class)| {
+    ///             signup(trx, classes, attends, student, class).boxed()
+    ///         },
+    ///         TransactOption::default(),
+    ///     )
+    ///     .await?;
+    /// # Ok(())
+    /// # }
+    /// ```
     pub fn transact_boxed<'trx, F, D, T, E>(
         &'trx self,
         data: D,
         f: F,
-        options: TransactOption,
+        mut options: TransactOption,
     ) -> impl Future<Output = Result<T, E>> + Send + 'trx
     where
         for<'a> F: FnMut(
@@ -182,6 +602,7 @@ impl Database {
         E: Send + 'trx,
         D: Send + 'trx,
     {
+        options.remember_options.get_or_insert(true);
         self.transact(
             boxed::FnMutBoxed {
                 f,
@@ -192,11 +613,12 @@ impl Database {
         )
     }
 
+    /// Same as `transact_boxed`, for closures whose future is not `Send`.
     pub fn transact_boxed_local<'trx, F, D, T, E>(
         &'trx self,
         data: D,
         f: F,
-        options: TransactOption,
+        mut options: TransactOption,
     ) -> impl Future<Output = Result<T, E>> + 'trx
     where
         for<'a> F:
@@ -207,6 +629,7 @@ impl Database {
         E: 'trx,
         D: 'trx,
     {
+        options.remember_options.get_or_insert(true);
         self.transact(
             boxed_local::FnMutBoxedLocal {
                 f,
@@ -217,6 +640,27 @@ impl Database {
         )
     }
 }
+/// Sets the timeout of `trx` to the time left before `deadline`, failing with
+/// `transaction_timed_out` if the deadline has passed.
+fn apply_deadline(trx: &Transaction, deadline: Instant) -> FdbResult<()> {
+    let remaining = deadline
+        .saturating_duration_since(Instant::now())
+        .as_millis();
+    // a timeout of 0 disables the timeout
+    if remaining == 0 {
+        return Err(FdbError::from_code(TRANSACTION_TIMED_OUT));
+    }
+    let timeout = remaining.min(i32::MAX as u128) as i64;
+    trx.set_option(options::TransactionOption::Timeout(timeout))
+}
+
+/// Waits for `delay` before retrying, if it is not zero.
+async fn backoff_delay(delay: Duration) {
+    if delay > Duration::from_secs(0) {
+        crate::timer::delay(delay).await;
+    }
+}
+
 pub trait DatabaseTransact: Sized {
     type Item;
     type Error: TransactError;
@@ -224,6 +668,46 @@ pub trait DatabaseTransact: Sized {
     fn transact(self, trx: Transaction) -> Self::Future;
 }
 
+#[allow(clippy::type_complexity)]
+mod owned {
+    use super::*;
+    use futures::future::{Join, Map, Ready};
+
+    pub struct FnOwned<F> {
+        pub f: F,
+    }
+
+    fn flatten<F, T>(
+        (f, r): (
+            FnOwned<F>,
+            Result<(Transaction, T), (Transaction, FdbError)>,
+        ),
+    ) -> (FnOwned<F>, Transaction, FdbResult<T>) {
+        match r {
+            Ok((trx, item)) => (f, trx, Ok(item)),
+            Err((trx, err)) => (f, trx, Err(err)),
+        }
+    }
+
+    impl<F, Fut, T> DatabaseTransact for FnOwned<F>
+    where
+        F: Fn(Transaction) -> Fut,
+        Fut: Future<Output = Result<(Transaction, T), (Transaction, FdbError)>>,
+    {
+        type Item = T;
+        type Error = FdbError;
+        type Future = Map<
+            Join<Ready<Self>, Fut>,
+            fn((Self, Fut::Output)) -> (Self, Transaction, FdbResult<T>),
+        >;
+
+        fn transact(self, trx: Transaction) -> Self::Future {
+            let fut = (self.f)(trx);
+            future::join(future::ready(self), fut).map(flatten::<F, T> as fn(_) -> _)
+        }
+    }
+}
+
 #[allow(clippy::needless_lifetimes)]
 #[allow(clippy::type_complexity)]
 mod boxed {
@@ -344,12 +828,167 @@ impl TransactError for FdbError {
     }
 }
 
+/// What `Database::transact` should do after an attempt failed with a given error.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RetryDecision {
+    /// Retry the closure on the same transaction.
+    ///
+    /// If `via_on_error` is true, the transaction goes through `Transaction::on_error`, which
+    /// applies the FoundationDB backoff strategy and fails for non retryable errors. Otherwise the
+    /// transaction is only reset and retried immediately.
+    Retry { via_on_error: bool },
+    /// Drop the failed transaction and retry the closure with a new one created from the database.
+    ///
+    /// The options remembered by the failed transaction, see `Transaction::remember_options`, are
+    /// set again on the new one, like after `Transaction::reset`.
+    RetryFresh,
+    /// Stop retrying and return the error to the caller.
+    Abort,
+}
+
+impl RetryDecision {
+    /// The decision taken by `Database::transact` when no `retry_classifier` is provided.
+    ///
+    /// Errors are handed to `Transaction::on_error`, unless the transaction may have been
+    /// committed and the transaction is not idempotent.
+    pub fn default_for(err: &FdbError, is_idempotent: bool) -> Self {
+        if is_idempotent || !err.is_maybe_committed() {
+            RetryDecision::Retry { via_on_error: true }
+        } else {
+            RetryDecision::Abort
+        }
+    }
+}
+
+/// A function that decides if and how `Database::transact` retries after an error.
+///
+/// It is called with the error and the number of the attempt that failed, starting at 1.
+pub type RetryClassifier = Arc<dyn Fn(&FdbError, usize) -> RetryDecision + Send + Sync>;
+
+/// A function returning the delay to wait before the retry `retry` (starting at 1) after an
+/// error, or `None` to stop retrying and return the error, see `BackoffPolicy::Custom`.
+pub type BackoffFn = Arc<dyn Fn(u32, &FdbError) -> Option<Duration> + Send + Sync>;
+
+/// How long `Database::transact` waits before retrying, in addition to the backoff of
+/// `Transaction::on_error`.
+///
+/// The delay is waited client-side, once `on_error` resolved or the transaction was reset.
+#[derive(Clone)]
+pub enum BackoffPolicy {
+    /// Only the backoff of `Transaction::on_error`, which grows exponentially up to
+    /// `TransactionOption::MaxRetryDelay` (1 second by default).
+    FdbDefault,
+    /// Waits a random duration between zero and `base * multiplier^(retry - 1)`, capped at
+    /// `max`.
+    ExponentialJitter {
+        base: Duration,
+        max: Duration,
+        multiplier: f64,
+    },
+    /// Calls the function before each retry, which can also be used to log the retries.
+    Custom(BackoffFn),
+}
+
+impl BackoffPolicy {
+    /// Returns the delay to wait before the retry `retry` (starting at 1) after `err`, or `None`
+    /// to stop retrying.
+    pub fn delay(&self, retry: u32, err: &FdbError) -> Option<Duration> {
+        match self {
+            BackoffPolicy::FdbDefault => Some(Duration::from_secs(0)),
+            BackoffPolicy::ExponentialJitter {
+                base,
+                max,
+                multiplier,
+            } => {
+                let exponent = retry.saturating_sub(1).min(i32::MAX as u32) as i32;
+                let ceiling = base.as_secs_f64() * multiplier.powi(exponent);
+                let ceiling = if ceiling.is_finite() {
+                    ceiling.min(max.as_secs_f64()).max(0.)
+                } else {
+                    max.as_secs_f64()
+                };
+                let jitter: f64 = rand::thread_rng().gen();
+                let secs = ceiling * jitter;
+                // `max` can be rounded up by the conversion to f64, out of the range of Duration
+                if secs >= max.as_secs_f64() {
+                    Some(*max)
+                } else {
+                    Some(Duration::from_secs_f64(secs))
+                }
+            }
+            BackoffPolicy::Custom(f) => f(retry, err),
+        }
+    }
+}
+
+impl Default for BackoffPolicy {
+    fn default() -> Self {
+        BackoffPolicy::FdbDefault
+    }
+}
+
+impl fmt::Debug for BackoffPolicy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BackoffPolicy::FdbDefault => f.write_str("FdbDefault"),
+            BackoffPolicy::ExponentialJitter {
+                base,
+                max,
+                multiplier,
+            } => f
+                .debug_struct("ExponentialJitter")
+                .field("base", base)
+                .field("max", max)
+                .field("multiplier", multiplier)
+                .finish(),
+            BackoffPolicy::Custom(_) => f.write_str("Custom(..)"),
+        }
+    }
+}
+
 /// A set of options that controls the behavior of `Database::transact`.
 #[derive(Default, Clone)]
 pub struct TransactOption {
     pub retry_limit: Option<u32>,
+    /// Stops retrying once this duration has elapsed, returning the last error.
     pub time_out: Option<Duration>,
+    /// Fails with `transaction_timed_out` (1031) once this duration has elapsed.
+    ///
+    /// Unlike `time_out`, the deadline also interrupts an attempt in progress: each attempt sets
+    /// `TransactionOption::Timeout` to the time left, so that pending reads and the commit fail
+    /// when the deadline passes.
+    pub deadline: Option<Duration>,
     pub is_idempotent: bool,
+    /// The transaction is not committed once `f` succeeds, saving the commit round trip.
+    ///
+    /// Only set this if `f` doesn't modify the database, as its mutations are discarded. Errors
+    /// returned by `f`, such as `transaction_too_old`, are still retried.
+    pub is_read_only: bool,
+    /// Overrides `RetryDecision::default_for`. `retry_limit` and `time_out` still apply.
+    pub retry_classifier: Option<RetryClassifier>,
+    /// Fails with `transaction_too_large` (2101) instead of committing once the transaction
+    /// wrote more than this number of bytes, see `Transaction::estimated_mutation_bytes`.
+    ///
+    /// The commit would fail anyway above the transaction size limit (10,000,000 bytes by
+    /// default), this saves sending the mutations to the cluster. The error is not retried, and
+    /// `FdbError::transaction_too_large` tells it apart from the error of a failed commit.
+    pub max_transaction_size: Option<usize>,
+    /// Enables `Transaction::remember_options` on the transactions of the retry loop, so that the
+    /// options set by `f` are applied again after each retry.
+    ///
+    /// Defaults to enabled for `transact_boxed` and `transact_boxed_local`, and to disabled for
+    /// `transact` and `transact_owned`.
+    pub remember_options: Option<bool>,
+    /// Waits before each retry, and stops retrying when the policy returns `None`.
+    /// `retry_limit` and `time_out` still apply.
+    pub backoff: BackoffPolicy,
+    /// Sets `TransactionOption::ReportConflictingKeys` on each attempt, and reports the ranges
+    /// that made a commit fail with `not_committed` (1020) to the `metrics` instrumentation and
+    /// to the `tracing` span, see `TransactionCommitError::conflicting_keys`.
+    ///
+    /// Reading the conflicting ranges delays each retry by a round trip.
+    #[cfg(feature = "fdb-7_1")]
+    pub report_conflicting_keys: bool,
 }
 
 impl TransactOption {
@@ -360,4 +999,452 @@ impl TransactOption {
             ..TransactOption::default()
         }
     }
+
+    /// A read-only TransactOption, the transaction is never committed
+    pub fn read_only() -> Self {
+        Self {
+            is_read_only: true,
+            ..TransactOption::default()
+        }
+    }
+
+    /// A TransactOption failing with `transaction_timed_out` once `deadline` has elapsed
+    pub fn deadline(deadline: Duration) -> Self {
+        Self {
+            deadline: Some(deadline),
+            ..TransactOption::default()
+        }
+    }
+
+    /// A TransactOption failing with `transaction_too_large` instead of committing more than
+    /// `bytes` bytes of mutations
+    pub fn max_transaction_size(bytes: usize) -> Self {
+        Self {
+            max_transaction_size: Some(bytes),
+            ..TransactOption::default()
+        }
+    }
+
+    /// A TransactOption that uses `classifier` to decide how errors are retried
+    pub fn retry_classifier(classifier: RetryClassifier) -> Self {
+        Self {
+            retry_classifier: Some(classifier),
+            ..TransactOption::default()
+        }
+    }
+
+    /// A TransactOption that waits as requested by `backoff` before each retry
+    pub fn backoff(backoff: BackoffPolicy) -> Self {
+        Self {
+            backoff,
+            ..TransactOption::default()
+        }
+    }
+
+    /// A TransactOption reporting the conflicting ranges of the failed commits if `enabled`
+    #[cfg(feature = "fdb-7_1")]
+    pub fn report_conflicting_keys(enabled: bool) -> Self {
+        Self {
+            report_conflicting_keys: enabled,
+            ..TransactOption::default()
+        }
+    }
+}
+
+/// `transaction_timed_out` error code
+const TRANSACTION_TIMED_OUT: i32 = 1031;
+/// `not_committed` error code
+#[cfg(feature = "fdb-7_1")]
+const NOT_COMMITTED: i32 = 1020;
+
+/// Deadline of the one-shot reads of `Database`, like `Database::get`. This is also the maximum
+/// lifetime of a transaction.
+const ONE_SHOT_READ_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Number of versions before the current read version that `oldest_readable_version_hint`
+/// starts probing from. FoundationDB keeps about 5 seconds (5,000,000 versions) of history.
+const AS_OF_PROBE_WINDOW: i64 = 10_000_000;
+/// Maximum number of probes done by `oldest_readable_version_hint`.
+const AS_OF_MAX_PROBES: usize = 16;
+
+/// The values read by `Database::read_as_of`.
+///
+/// It can't be cloned, as the values are owned by the futures they were read from.
+#[derive(Debug)]
+pub struct AsOfResult {
+    /// The value of each requested key, in the order of the requested keys.
+    pub values: Vec<Option<FdbSlice>>,
+    /// The read version the values were read at.
+    pub version: i64,
+}
+
+/// An error returned by `Database::read_as_of`.
+#[derive(Debug, Clone)]
+pub enum AsOfError {
+    /// The requested version is older than the versions kept by the cluster
+    /// (`transaction_too_old`, 1007).
+    VersionTooOld,
+    /// The requested version is newer than the current read version of the cluster
+    /// (`future_version`, 1009).
+    VersionInFuture,
+    /// Any other error of the reads, e.g. a timeout
+    FdbError(FdbError),
+}
+
+impl fmt::Display for AsOfError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            AsOfError::VersionTooOld => write!(f, "version is too old to be read"),
+            AsOfError::VersionInFuture => write!(f, "version is in the future"),
+            AsOfError::FdbError(err) => err.fmt(f),
+        }
+    }
+}
+
+impl std::error::Error for AsOfError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            AsOfError::FdbError(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<FdbError> for AsOfError {
+    fn from(err: FdbError) -> Self {
+        match err.code() {
+            1007 => AsOfError::VersionTooOld,
+            1009 => AsOfError::VersionInFuture,
+            _ => AsOfError::FdbError(err),
+        }
+    }
+}
+
+impl Database {
+    /// Reads `keys` as they were at the given committed `version`.
+    ///
+    /// This is meant for debugging and tooling: the cluster only keeps a few seconds of history,
+    /// use `oldest_readable_version_hint` to find out how far back `read_as_of` can go.
+    ///
+    /// Keys are read with snapshot reads from a dedicated transaction, which is never committed.
+    pub async fn read_as_of(&self, version: i64, keys: &[&[u8]]) -> Result<AsOfResult, AsOfError> {
+        // Reading in the future would wait for the version before failing
+        let current_version = self.create_trx()?.get_read_version().await?;
+        if version > current_version {
+            return Err(AsOfError::VersionInFuture);
+        }
+
+        let trx = self.create_trx()?;
+        trx.set_read_version(version);
+        let values = future::try_join_all(keys.iter().map(|key| trx.get(key, true))).await?;
+
+        Ok(AsOfResult { values, version })
+    }
+
+    /// Returns an estimation of the oldest version that can be read with `read_as_of`.
+    ///
+    /// The estimation is computed by probing reads at older versions with a bounded binary
+    /// search, so it is only accurate to a few hundred versions. As the cluster keeps moving
+    /// forward, the returned version might not be readable anymore by the time it is used.
+    pub async fn oldest_readable_version_hint(&self) -> FdbResult<i64> {
+        let current_version = self.create_trx()?.get_read_version().await?;
+
+        let mut too_old = current_version.saturating_sub(AS_OF_PROBE_WINDOW).max(0);
+        if self.is_version_readable(too_old).await? {
+            return Ok(too_old);
+        }
+
+        let mut readable = current_version;
+        for _ in 0..AS_OF_MAX_PROBES {
+            if readable - too_old <= 1 {
+                break;
+            }
+            let version = too_old + (readable - too_old) / 2;
+            if self.is_version_readable(version).await? {
+                readable = version;
+            } else {
+                too_old = version;
+            }
+        }
+
+        Ok(readable)
+    }
+
+    async fn is_version_readable(&self, version: i64) -> FdbResult<bool> {
+        let trx = self.create_trx()?;
+        trx.set_read_version(version);
+        match trx.get(b"", true).await {
+            Ok(_) => Ok(true),
+            Err(err) if err.code() == 1007 => Ok(false),
+            Err(err) => Err(err),
+        }
+    }
+}
+
+/// A function called before each attempt of the one-shot reads of `Database`, see
+/// `Database::set_read_fault_injector`.
+///
+/// If it returns an error, the attempt fails with this error instead of reading the database.
+pub type ReadFaultInjector = Arc<dyn Fn() -> Option<FdbError> + Send + Sync>;
+
+impl Database {
+    /// Reads the value of `key` with a snapshot read.
+    ///
+    /// This is a shortcut for a transaction doing a single read: the read is retried on
+    /// retryable errors, like `future_version` (1009), and fails with `transaction_timed_out`
+    /// (1031) after 5 seconds. It uses a cached read version if `set_read_version_cache` was
+    /// called.
+    pub async fn get(&self, key: &[u8]) -> FdbResult<Option<Vec<u8>>> {
+        self.read_snapshot(move |trx| async move {
+            match trx.get(key, true).await {
+                Ok(value) => Ok((trx, value.map(|value| value.to_vec()))),
+                Err(err) => Err((trx, err)),
+            }
+        })
+        .await
+    }
+
+    /// Reads all the key-value pairs of `opt` with snapshot reads, see `Database::get`.
+    ///
+    /// All the pairs are read by the same transaction, so they are consistent with each other,
+    /// but the range must be small enough to be read in 5 seconds.
+    pub async fn get_range(&self, opt: &RangeOption<'_>) -> FdbResult<Vec<(Vec<u8>, Vec<u8>)>> {
+        self.read_snapshot(move |trx| async move {
+            let kvs = crate::FdbRead::read_range(&trx, opt, true).await;
+            match kvs {
+                Ok(kvs) => Ok((trx, kvs)),
+                Err(err) => Err((trx, err)),
+            }
+        })
+        .await
+    }
+
+    /// Resolves `selector` with a snapshot read, see `Database::get`.
+    pub async fn get_key(&self, selector: &KeySelector<'_>) -> FdbResult<Vec<u8>> {
+        self.read_snapshot(move |trx| async move {
+            match trx.get_key(selector, true).await {
+                Ok(key) => Ok((trx, key.to_vec())),
+                Err(err) => Err((trx, err)),
+            }
+        })
+        .await
+    }
+
+    /// Returns the addresses of the storage servers responsible for `key`, see
+    /// `Transaction::get_addresses_for_key`, retried like `Database::get`.
+    ///
+    /// The addresses that can't be parsed are returned as errors, along with the other ones.
+    pub async fn storage_servers_for_key(
+        &self,
+        key: &[u8],
+    ) -> FdbResult<Vec<Result<StorageServerAddress, AddressParseError>>> {
+        self.read_snapshot(move |trx| async move {
+            match trx.get_addresses_for_key(key).await {
+                Ok(addresses) => {
+                    let parsed = addresses.parsed().collect();
+                    Ok((trx, parsed))
+                }
+                Err(err) => Err((trx, err)),
+            }
+        })
+        .await
+    }
+
+    /// Sets a function called before each attempt of `get`, `get_range`, `get_key` and
+    /// `storage_servers_for_key`, to test how the errors of the reads are handled.
+    pub fn set_read_fault_injector(&mut self, injector: ReadFaultInjector) {
+        self.read_fault_injector = Some(injector);
+    }
+
+    async fn read_snapshot<F, Fut, T>(&self, read: F) -> FdbResult<T>
+    where
+        F: Fn(Transaction) -> Fut + Send + Sync,
+        Fut: Future<Output = Result<(Transaction, T), (Transaction, FdbError)>>,
+    {
+        let options = TransactOption {
+            is_idempotent: true,
+            is_read_only: true,
+            deadline: Some(ONE_SHOT_READ_TIMEOUT),
+            ..TransactOption::default()
+        };
+        let read = &read;
+        self.transact_owned(
+            move |trx| match self
+                .read_fault_injector
+                .as_ref()
+                .and_then(|inject| inject())
+            {
+                Some(err) => future::Either::Left(future::err((trx, err))),
+                None => future::Either::Right(async move {
+                    if self.read_version_cache.is_some() {
+                        if let Err(err) = trx.use_cached_read_version().await {
+                            return Err((trx, err));
+                        }
+                    }
+                    read(trx).await
+                }),
+            },
+            options,
+        )
+        .await
+    }
+}
+
+/// The progress of `Database::clear_range_chunked`, reported after each cleared chunk.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ClearProgress {
+    /// The number of keys cleared by the chunk
+    pub cleared: usize,
+    /// The number of keys cleared since the beginning of the stream
+    pub total: usize,
+    /// The last key cleared by the chunk
+    pub last_key: Vec<u8>,
+    /// The beginning of the range left to clear, the key after `last_key`.
+    ///
+    /// Passing it as `begin` to `clear_range_chunked` resumes the deletion.
+    pub continuation: Vec<u8>,
+}
+
+impl Database {
+    /// Clears the keys of `[begin, end)` by chunks of at most `chunk` keys, one transaction per
+    /// chunk, waiting `throttle` between chunks.
+    ///
+    /// `Transaction::clear_range` clears a range of any size at once; this is for deletions that
+    /// must be spread over time, e.g. to limit the data distribution work they cause, or whose
+    /// progress must be reported. Each transaction reads the first keys of the remaining range
+    /// and clears exactly these keys, so keys inserted concurrently after them are left for the
+    /// next chunks. The stream ends once the remaining range is empty, or after the first
+    /// error, which stops the deletion at the `continuation` of the last reported progress.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `chunk` is 0.
+    pub fn clear_range_chunked<'a>(
+        &'a self,
+        begin: &[u8],
+        end: &[u8],
+        chunk: usize,
+        throttle: Option<Duration>,
+    ) -> impl Stream<Item = FdbResult<ClearProgress>> + Send + 'a {
+        assert!(chunk > 0, "chunk must not be 0");
+        let end = end.to_vec();
+        stream::unfold(Some((begin.to_vec(), 0, true)), move |state| {
+            let end = end.clone();
+            async move {
+                let (begin, total, first) = state?;
+                if let (false, Some(throttle)) = (first, throttle) {
+                    crate::timer::delay(throttle).await;
+                }
+                let cleared = self
+                    .transact_boxed(
+                        (begin, end),
+                        move |trx, (begin, end)| {
+                            async move {
+                                let opt = RangeOption {
+                                    limit: Some(chunk),
+                                    mode: options::StreamingMode::Exact,
+                                    ..RangeOption::from((begin.as_slice(), end.as_slice()))
+                                };
+                                let kvs = trx.get_range(&opt, 1, false).await?;
+                                let last_key = match kvs.last() {
+                                    Some(kv) => kv.key().to_vec(),
+                                    None => return Ok(None),
+                                };
+                                let continuation = crate::key::key_after(&last_key);
+                                trx.clear_range(begin, &continuation);
+                                Ok::<_, FdbError>(Some((kvs.len(), last_key, continuation)))
+                            }
+                            .boxed()
+                        },
+                        TransactOption::default(),
+                    )
+                    .await;
+                match cleared {
+                    Ok(Some((cleared, last_key, continuation))) => {
+                        let progress = ClearProgress {
+                            cleared,
+                            total: total + cleared,
+                            last_key,
+                            continuation: continuation.clone(),
+                        };
+                        Some((Ok(progress), Some((continuation, total + cleared, false))))
+                    }
+                    Ok(None) => None,
+                    Err(err) => Some((Err(err), None)),
+                }
+            }
+        })
+    }
+}
+
+/// The system key holding the connection string of the cluster, before the special key
+/// `\xff\xff/connection_string` was added in FoundationDB 6.3
+#[cfg(not(feature = "fdb-7_1"))]
+const COORDINATORS_KEY: &[u8] = b"\xff/coordinators";
+
+/// `internal_error` error code
+const INTERNAL_ERROR: i32 = 4100;
+
+/// Reads the connection string of the cluster in `trx`.
+async fn read_connection_string(trx: &Transaction) -> FdbResult<String> {
+    #[cfg(feature = "fdb-7_1")]
+    let value = trx
+        .get_special(crate::special_keys::SpecialKey::ConnectionString)
+        .await?;
+    #[cfg(not(feature = "fdb-7_1"))]
+    let value = {
+        trx.set_option(options::TransactionOption::ReadSystemKeys)?;
+        trx.get(COORDINATORS_KEY, true).await?
+    };
+    let value = value.ok_or_else(|| FdbError::from_code(INTERNAL_ERROR))?;
+    String::from_utf8(value.to_vec()).map_err(|_| FdbError::from_code(INTERNAL_ERROR))
+}
+
+impl Database {
+    /// Returns the connection string of the cluster, `description:ID@addresses`, as currently
+    /// known by the client.
+    ///
+    /// It is read from the special key `\xff\xff/connection_string` with the `fdb-7_1` feature,
+    /// and from the system key `\xff/coordinators` otherwise. Fails with an `internal_error`
+    /// (4100) if the key is missing or not UTF-8.
+    pub async fn connection_string(&self) -> FdbResult<String> {
+        self.transact_boxed(
+            (),
+            |trx, ()| read_connection_string(trx).boxed(),
+            TransactOption::read_only(),
+        )
+        .await
+    }
+
+    /// Returns a stream that yields the connection string of the cluster, then each new
+    /// connection string after the coordinators changed.
+    ///
+    /// The client updates the cluster file by itself when the coordinators change, this lets
+    /// long-running processes log or alert about it. The C API doesn't notify these changes,
+    /// so the connection string is read again every `interval`. The first error is yielded
+    /// and ends the stream.
+    pub fn watch_connection_string(
+        &self,
+        interval: Duration,
+    ) -> impl Stream<Item = FdbResult<String>> + Send + '_ {
+        stream::unfold(
+            Some(None),
+            move |state: Option<Option<String>>| async move {
+                let mut last = state?;
+                loop {
+                    if last.is_some() {
+                        crate::timer::delay(interval).await;
+                    }
+                    match self.connection_string().await {
+                        Ok(current) if last.as_ref() == Some(&current) => {}
+                        Ok(current) => {
+                            last = Some(current.clone());
+                            return Some((Ok(current), Some(last)));
+                        }
+                        Err(err) => return Some((Err(err), None)),
+                    }
+                }
+            },
+        )
+    }
 }
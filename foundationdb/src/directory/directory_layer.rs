@@ -0,0 +1,830 @@
+// Copyright 2018 foundationdb-rs developers, https://github.com/Clikengo/foundationdb-rs/graphs/contributors
+// Copyright 2013-2018 Apple, Inc and the FoundationDB project authors.
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+use std::pin::Pin;
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use futures::prelude::*;
+
+use super::node::Node;
+use super::{Directory, DirectoryError, DirectoryOutput, DirectoryPartition, DirectorySubspace};
+use crate::key::strinc;
+use crate::tuple::hca::HighContentionAllocator;
+use crate::tuple::{pack_into, prefix_range, Element, Subspace};
+use crate::{KeySelector, RangeOption, Transaction};
+
+/// The default prefix of the node subspace, where the directory layer stores its metadata.
+pub const DEFAULT_NODE_PREFIX: &[u8] = b"\xFE";
+/// The layer of directory partitions.
+pub const PARTITION_LAYER: &[u8] = b"partition";
+
+pub(crate) const LAYER_SUFFIX: &[u8] = b"layer";
+const VERSION_SUFFIX: &[u8] = b"version";
+const HCA_SUFFIX: &[u8] = b"hca";
+const SUBDIRS: i64 = 0;
+
+const MAJOR_VERSION: u32 = 1;
+const MINOR_VERSION: u32 = 0;
+const PATCH_VERSION: u32 = 0;
+
+type DirectoryFuture<'a, T> = Pin<Box<dyn Future<Output = Result<T, DirectoryError>> + Send + 'a>>;
+
+/// The metadata of a directory, as returned by `DirectoryLayer::stat`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DirectoryStat {
+    /// The prefix of the content of the directory
+    pub prefix: Vec<u8>,
+    /// The layer the directory was created with
+    pub layer: Vec<u8>,
+    /// `true` if the directory has at least one sub-directory
+    pub has_children: bool,
+    /// `true` if the directory is a partition
+    pub is_partition: bool,
+}
+
+/// A DirectoryLayer defines a new root directory.
+///
+/// The node subspace and content subspace control where the directory metadata and contents,
+/// respectively, are stored. The default root directory has a node subspace with raw prefix
+/// `\xFE` and a content subspace with no prefix.
+#[derive(Debug, Clone)]
+pub struct DirectoryLayer {
+    pub(crate) root_node: Subspace,
+    pub(crate) node_subspace: Subspace,
+    pub(crate) content_subspace: Subspace,
+    pub(crate) allocator: Arc<HighContentionAllocator>,
+    pub(crate) allow_manual_prefixes: bool,
+    pub(crate) path: Vec<String>,
+}
+
+impl Default for DirectoryLayer {
+    /// The default root directory stores directory layer metadata in keys beginning with `\xFE`,
+    /// and allocates newly created directories in (unused) prefixes starting with 0x00 through
+    /// 0xFD.
+    fn default() -> Self {
+        Self::new(
+            Subspace::from_bytes(DEFAULT_NODE_PREFIX),
+            Subspace::all(),
+            false,
+        )
+    }
+}
+
+impl DirectoryLayer {
+    /// Creates a new root directory, storing its metadata in `node_subspace` and allocating the
+    /// prefixes of its directories in `content_subspace`.
+    ///
+    /// If `allow_manual_prefixes` is false, directories can only be created with an
+    /// automatically allocated prefix.
+    pub fn new(
+        node_subspace: Subspace,
+        content_subspace: Subspace,
+        allow_manual_prefixes: bool,
+    ) -> Self {
+        Self::new_with_path(
+            node_subspace,
+            content_subspace,
+            allow_manual_prefixes,
+            Vec::new(),
+        )
+    }
+
+    pub(crate) fn new_with_path(
+        node_subspace: Subspace,
+        content_subspace: Subspace,
+        allow_manual_prefixes: bool,
+        path: Vec<String>,
+    ) -> Self {
+        let root_node = node_subspace.subspace(&node_subspace.bytes());
+        let allocator = HighContentionAllocator::new(root_node.subspace(&HCA_SUFFIX));
+
+        DirectoryLayer {
+            root_node,
+            node_subspace,
+            content_subspace,
+            allocator: Arc::new(allocator),
+            allow_manual_prefixes,
+            path,
+        }
+    }
+
+    /// Returns the path of this directory layer, empty unless it is the layer of a partition.
+    pub fn get_path(&self) -> &[String] {
+        &self.path
+    }
+
+    /// Returns the layer of the root directory, which is always empty.
+    pub fn get_layer(&self) -> &[u8] {
+        &[]
+    }
+
+    fn node_with_prefix(&self, prefix: &[u8]) -> Subspace {
+        self.node_subspace.subspace(&prefix)
+    }
+
+    /// Returns the content prefix of the node stored in `node`.
+    fn node_prefix(&self, node: &Subspace) -> Result<Vec<u8>, DirectoryError> {
+        Ok(self.node_subspace.unpack(node.bytes())?)
+    }
+
+    pub(crate) fn contents_of_node(
+        &self,
+        node: &Subspace,
+        path: &[String],
+        layer: &[u8],
+    ) -> Result<DirectoryOutput, DirectoryError> {
+        let prefix = self.node_prefix(node)?;
+
+        let mut new_path = self.path.clone();
+        new_path.extend_from_slice(path);
+
+        if layer == PARTITION_LAYER {
+            Ok(DirectoryOutput::DirectoryPartition(
+                DirectoryPartition::new(new_path, prefix, self.clone()),
+            ))
+        } else {
+            Ok(DirectoryOutput::DirectorySubspace(DirectorySubspace::new(
+                new_path,
+                prefix,
+                self,
+                layer.to_vec(),
+            )))
+        }
+    }
+
+    /// Returns the directory layer of the partition `node` is the root of.
+    fn partition_layer(&self, node: &Node) -> Result<DirectoryLayer, DirectoryError> {
+        match node.get_contents(self)? {
+            DirectoryOutput::DirectoryPartition(partition) => Ok(partition.directory_layer()),
+            DirectoryOutput::DirectorySubspace(_) => {
+                Err(DirectoryError::InvalidDirectoryLayerMetadata)
+            }
+        }
+    }
+
+    /// Checks the version of the stored metadata, writing it if needed and `allow_creation`
+    /// is true.
+    async fn check_version(
+        &self,
+        trx: &Transaction,
+        allow_creation: bool,
+    ) -> Result<(), DirectoryError> {
+        let version_key = self.root_node.pack(&VERSION_SUFFIX);
+        let version = match trx.get(&version_key, false).await? {
+            Some(version) => version,
+            None => {
+                if allow_creation {
+                    let mut value = Vec::with_capacity(12);
+                    value.extend_from_slice(&MAJOR_VERSION.to_le_bytes());
+                    value.extend_from_slice(&MINOR_VERSION.to_le_bytes());
+                    value.extend_from_slice(&PATCH_VERSION.to_le_bytes());
+                    trx.set(&version_key, &value);
+                }
+                return Ok(());
+            }
+        };
+
+        if version.len() != 12 {
+            return Err(DirectoryError::InvalidDirectoryLayerMetadata);
+        }
+        let mut parts = [0u32; 3];
+        for (part, bytes) in parts.iter_mut().zip(version.chunks(4)) {
+            let mut buf = [0u8; 4];
+            buf.copy_from_slice(bytes);
+            *part = u32::from_le_bytes(buf);
+        }
+        let [major, minor, patch] = parts;
+
+        if major > MAJOR_VERSION {
+            return Err(DirectoryError::IncompatibleVersion(major, minor, patch));
+        }
+        if minor > MINOR_VERSION && allow_creation {
+            return Err(DirectoryError::ReadOnlyVersion(major, minor, patch));
+        }
+
+        Ok(())
+    }
+
+    /// Walks `path` from the root node, stopping early at missing nodes and partitions.
+    async fn find(&self, trx: &Transaction, path: &[String]) -> Result<Node, DirectoryError> {
+        let mut node =
+            Node::load(trx, Some(self.root_node.clone()), Vec::new(), path.to_vec()).await?;
+
+        for (i, name) in path.iter().enumerate() {
+            let node_subspace = match node.subspace {
+                Some(ref subspace) => subspace,
+                None => break,
+            };
+            let key = node_subspace.subspace(&SUBDIRS).pack(name);
+            let subspace = trx
+                .get(&key, false)
+                .await?
+                .map(|prefix| self.node_with_prefix(&prefix));
+
+            node = Node::load(trx, subspace, path[..=i].to_vec(), path.to_vec()).await?;
+
+            if !node.exists() || node.layer == PARTITION_LAYER {
+                break;
+            }
+        }
+
+        Ok(node)
+    }
+
+    /// Returns the node whose prefix contains `key`, if any.
+    async fn node_containing_key(
+        &self,
+        trx: &Transaction,
+        key: &[u8],
+        snapshot: bool,
+    ) -> Result<Option<Subspace>, DirectoryError> {
+        if key.starts_with(self.node_subspace.bytes()) {
+            return Ok(Some(self.root_node.clone()));
+        }
+
+        let (begin, _) = self.node_subspace.range();
+        let mut end = self.node_subspace.pack(&key);
+        end.push(0x00);
+        let range = RangeOption {
+            limit: Some(1),
+            reverse: true,
+            ..RangeOption::from((begin, end))
+        };
+
+        let kvs = trx.get_range(&range, 1, snapshot).await?;
+        if let Some(kv) = kvs.first() {
+            let tuple: Vec<Element> = self.node_subspace.unpack(kv.key())?;
+            let prev_prefix = tuple
+                .first()
+                .and_then(Element::as_bytes)
+                .ok_or(DirectoryError::InvalidDirectoryLayerMetadata)?;
+            if key.starts_with(prev_prefix) {
+                return Ok(Some(self.node_with_prefix(prev_prefix)));
+            }
+        }
+
+        Ok(None)
+    }
+
+    async fn is_prefix_free(
+        &self,
+        trx: &Transaction,
+        prefix: &[u8],
+        snapshot: bool,
+    ) -> Result<bool, DirectoryError> {
+        if prefix.is_empty() {
+            return Ok(false);
+        }
+
+        if self
+            .node_containing_key(trx, prefix, snapshot)
+            .await?
+            .is_some()
+        {
+            return Ok(false);
+        }
+
+        let begin = self.node_subspace.pack(&prefix);
+        let end = match strinc(prefix) {
+            Some(end) => self.node_subspace.pack(&end.as_slice()),
+            // `prefix` is only made of 0xff bytes, check up to the end of the node subspace
+            None => self.node_subspace.range().1,
+        };
+        is_range_empty(trx, begin, end, snapshot).await
+    }
+
+    /// Returns the names and node subspaces of the sub-directories of `node`.
+    async fn subdirs(
+        &self,
+        trx: &Transaction,
+        node: &Subspace,
+    ) -> Result<Vec<(String, Subspace)>, DirectoryError> {
+        let subdirs = node.subspace(&SUBDIRS);
+        let kvs = trx
+            .get_subspace_ranges(&subdirs, false)
+            .try_fold(Vec::new(), |mut kvs, values| {
+                for kv in values.iter() {
+                    kvs.push((kv.key().to_vec(), kv.value().to_vec()));
+                }
+                future::ok(kvs)
+            })
+            .await?;
+
+        let mut out = Vec::with_capacity(kvs.len());
+        for (key, prefix) in kvs {
+            let name: String = subdirs.unpack(&key)?;
+            out.push((name, self.node_with_prefix(&prefix)));
+        }
+        Ok(out)
+    }
+
+    fn create_or_open_internal<'a>(
+        &'a self,
+        trx: &'a Transaction,
+        path: &'a [String],
+        prefix: Option<&'a [u8]>,
+        layer: Option<&'a [u8]>,
+        allow_create: bool,
+        allow_open: bool,
+    ) -> DirectoryFuture<'a, DirectoryOutput> {
+        async move {
+            self.check_version(trx, false).await?;
+
+            if prefix.is_some() && !self.allow_manual_prefixes {
+                if self.path.is_empty() {
+                    return Err(DirectoryError::PrefixNotAllowed);
+                } else {
+                    return Err(DirectoryError::CannotPrefixInPartition);
+                }
+            }
+
+            if path.is_empty() {
+                return Err(DirectoryError::CannotOpenRoot);
+            }
+
+            let existing_node = self.find(trx, path).await?;
+            if existing_node.exists() {
+                if existing_node.is_in_partition(false) {
+                    let subpath = existing_node.get_partition_subpath();
+                    let partition_layer = self.partition_layer(&existing_node)?;
+                    return partition_layer
+                        .create_or_open_internal(
+                            trx,
+                            &subpath,
+                            prefix,
+                            layer,
+                            allow_create,
+                            allow_open,
+                        )
+                        .await;
+                }
+
+                if !allow_open {
+                    return Err(DirectoryError::DirAlreadyExists);
+                }
+
+                if let Some(layer) = layer {
+                    if !layer.is_empty() && existing_node.layer != layer {
+                        return Err(DirectoryError::IncompatibleLayer);
+                    }
+                }
+
+                return existing_node.get_contents(self);
+            }
+
+            if !allow_create {
+                return Err(DirectoryError::DirectoryDoesNotExists);
+            }
+
+            self.check_version(trx, true).await?;
+
+            let prefix = match prefix {
+                None => {
+                    let id = self.allocator.allocate(trx).await?;
+                    let mut prefix = self.content_subspace.bytes().to_vec();
+                    pack_into(&id, &mut prefix);
+
+                    let end = strinc(&prefix).expect("allocated prefix ends with an integer");
+                    if !is_range_empty(trx, prefix.clone(), end, false).await? {
+                        return Err(DirectoryError::PrefixNotEmpty);
+                    }
+
+                    if !self.is_prefix_free(trx, &prefix, true).await? {
+                        return Err(DirectoryError::ManualPrefixConflict);
+                    }
+
+                    prefix
+                }
+                Some(prefix) => {
+                    if !self.is_prefix_free(trx, prefix, false).await? {
+                        return Err(DirectoryError::DirectoryPrefixInUse);
+                    }
+                    prefix.to_vec()
+                }
+            };
+
+            let (name, parent_path) = path.split_last().expect("path to not be empty");
+            let parent_node = if parent_path.is_empty() {
+                self.root_node.clone()
+            } else {
+                match self
+                    .create_or_open_internal(trx, parent_path, None, None, true, true)
+                    .await?
+                {
+                    DirectoryOutput::DirectorySubspace(parent) => {
+                        self.node_with_prefix(parent.bytes())
+                    }
+                    DirectoryOutput::DirectoryPartition(_) => {
+                        return Err(DirectoryError::InvalidDirectoryLayerMetadata)
+                    }
+                }
+            };
+
+            let node = self.node_with_prefix(&prefix);
+            let layer = layer.unwrap_or(&[]);
+            trx.set(&parent_node.subspace(&SUBDIRS).pack(name), &prefix);
+            trx.set(&node.pack(&LAYER_SUFFIX), layer);
+
+            self.contents_of_node(&node, path, layer)
+        }
+        .boxed()
+    }
+
+    fn remove_internal<'a>(
+        &'a self,
+        trx: &'a Transaction,
+        path: &'a [String],
+        fail_on_nonexistent: bool,
+    ) -> DirectoryFuture<'a, bool> {
+        async move {
+            self.check_version(trx, true).await?;
+
+            if path.is_empty() {
+                return Err(DirectoryError::CannotModifyRootDirectory);
+            }
+
+            let node = self.find(trx, path).await?;
+            if !node.exists() {
+                return if fail_on_nonexistent {
+                    Err(DirectoryError::DirectoryDoesNotExists)
+                } else {
+                    Ok(false)
+                };
+            }
+
+            if node.is_in_partition(false) {
+                let subpath = node.get_partition_subpath();
+                let partition_layer = self.partition_layer(&node)?;
+                return partition_layer
+                    .remove_internal(trx, &subpath, fail_on_nonexistent)
+                    .await;
+            }
+
+            let subspace = node.subspace.expect("node to exist");
+            self.remove_recursive(trx, &subspace).await?;
+            self.remove_from_parent(trx, path).await?;
+
+            Ok(true)
+        }
+        .boxed()
+    }
+
+    /// Clears the content and the metadata of `node` and of all its sub-directories.
+    fn remove_recursive<'a>(
+        &'a self,
+        trx: &'a Transaction,
+        node: &'a Subspace,
+    ) -> DirectoryFuture<'a, ()> {
+        async move {
+            for (_, subdir) in self.subdirs(trx, node).await? {
+                self.remove_recursive(trx, &subdir).await?;
+            }
+
+            let prefix = self.node_prefix(node)?;
+            // a manual prefix in the special keyspace has no content a transaction could clear
+            if let Some((begin, end)) = prefix_range(&prefix) {
+                trx.clear_range(&begin, &end);
+            }
+            trx.clear_subspace_range(node);
+
+            Ok(())
+        }
+        .boxed()
+    }
+
+    async fn remove_from_parent(
+        &self,
+        trx: &Transaction,
+        path: &[String],
+    ) -> Result<(), DirectoryError> {
+        let (name, parent_path) = path.split_last().expect("path to not be empty");
+        let parent = self.find(trx, parent_path).await?;
+        if let Some(subspace) = parent.subspace {
+            trx.clear(&subspace.subspace(&SUBDIRS).pack(name));
+        }
+        Ok(())
+    }
+
+    /// Returns the metadata of the directory at `path`, or `None` if it does not exist.
+    ///
+    /// Unlike `open`, this doesn't check the layer of the directory, and only reads the metadata
+    /// of the path and whether the directory has a sub-directory.
+    pub async fn stat(
+        &self,
+        trx: &Transaction,
+        path: &[String],
+    ) -> Result<Option<DirectoryStat>, DirectoryError> {
+        self.stat_internal(trx, path).await
+    }
+
+    /// Returns `true` if the directory at `path` has neither sub-directories nor content keys.
+    ///
+    /// The content keys of a partition are the keys under its prefix, except the metadata of its
+    /// directory layer. Fails with `DirectoryError::DirectoryDoesNotExists` if the directory does
+    /// not exist.
+    pub async fn is_empty(
+        &self,
+        trx: &Transaction,
+        path: &[String],
+    ) -> Result<bool, DirectoryError> {
+        let stat = self
+            .stat(trx, path)
+            .await?
+            .ok_or(DirectoryError::DirectoryDoesNotExists)?;
+        if stat.has_children {
+            return Ok(false);
+        }
+        let end = if stat.is_partition {
+            // the node subspace of the partition directory layer
+            [&stat.prefix[..], DEFAULT_NODE_PREFIX].concat()
+        } else {
+            match prefix_range(&stat.prefix) {
+                Some((_, end)) => end,
+                // a manual prefix in the special keyspace, where no content can be stored
+                None => return Ok(true),
+            }
+        };
+        is_range_empty(trx, stat.prefix, end, false).await
+    }
+
+    /// Removes the directory at `path`, like `remove_if_exists`, only if it is empty.
+    ///
+    /// Fails with `DirectoryError::NotEmpty` if the directory has sub-directories or content
+    /// keys, see `is_empty`. Returns `false` if the directory does not exist. Unlike `remove`,
+    /// this never deletes data stored in the directory.
+    pub async fn remove_if_empty(
+        &self,
+        trx: &Transaction,
+        path: &[String],
+    ) -> Result<bool, DirectoryError> {
+        if path.is_empty() {
+            return Err(DirectoryError::CannotModifyRootDirectory);
+        }
+        match self.is_empty(trx, path).await {
+            Ok(true) => self.remove_internal(trx, path, false).await,
+            Ok(false) => Err(DirectoryError::NotEmpty),
+            Err(DirectoryError::DirectoryDoesNotExists) => Ok(false),
+            Err(err) => Err(err),
+        }
+    }
+
+    /// Opens the directory at `path`, checking that its prefix is still `expected_prefix`.
+    ///
+    /// This is for prefixes persisted outside of the database, e.g. in a configuration, to
+    /// detect that the directory was moved or removed and another one created at its path since.
+    /// Fails with `DirectoryError::PrefixMismatch` if the prefix differs, and with
+    /// `DirectoryError::DirectoryDoesNotExists` if there is no directory at `path`. The layer of
+    /// the directory is not checked.
+    pub async fn open_cached(
+        &self,
+        trx: &Transaction,
+        path: &[String],
+        expected_prefix: &[u8],
+    ) -> Result<DirectoryOutput, DirectoryError> {
+        let stat = self
+            .stat(trx, path)
+            .await?
+            .ok_or(DirectoryError::DirectoryDoesNotExists)?;
+        if stat.prefix != expected_prefix {
+            return Err(DirectoryError::PrefixMismatch);
+        }
+        self.open(trx, path, None).await
+    }
+
+    /// Returns `true` if the directory at `path` exists and its prefix is `prefix`.
+    ///
+    /// Like `open_cached`, but only reads the metadata of the path, see `stat`.
+    pub async fn verify_prefix(
+        &self,
+        trx: &Transaction,
+        path: &[String],
+        prefix: &[u8],
+    ) -> Result<bool, DirectoryError> {
+        Ok(match self.stat(trx, path).await? {
+            Some(stat) => stat.prefix == prefix,
+            None => false,
+        })
+    }
+
+    fn stat_internal<'a>(
+        &'a self,
+        trx: &'a Transaction,
+        path: &'a [String],
+    ) -> DirectoryFuture<'a, Option<DirectoryStat>> {
+        async move {
+            self.check_version(trx, false).await?;
+
+            if path.is_empty() {
+                return Err(DirectoryError::CannotOpenRoot);
+            }
+
+            let node = self.find(trx, path).await?;
+            let subspace = match node.subspace {
+                Some(ref subspace) => subspace,
+                None => return Ok(None),
+            };
+
+            if node.is_in_partition(false) {
+                let subpath = node.get_partition_subpath();
+                return self
+                    .partition_layer(&node)?
+                    .stat_internal(trx, &subpath)
+                    .await;
+            }
+
+            // the sub-directories of a partition are stored by its own directory layer
+            let is_partition = node.layer == PARTITION_LAYER;
+            let subdirs = if is_partition {
+                self.partition_layer(&node)?.root_node.subspace(&SUBDIRS)
+            } else {
+                subspace.subspace(&SUBDIRS)
+            };
+            let (begin, end) = subdirs.range();
+            let has_children = !is_range_empty(trx, begin, end, false).await?;
+
+            Ok(Some(DirectoryStat {
+                prefix: self.node_prefix(subspace)?,
+                layer: node.layer.clone(),
+                has_children,
+                is_partition,
+            }))
+        }
+        .boxed()
+    }
+}
+
+#[async_trait]
+impl Directory for DirectoryLayer {
+    async fn create_or_open(
+        &self,
+        trx: &Transaction,
+        path: &[String],
+        prefix: Option<&[u8]>,
+        layer: Option<&[u8]>,
+    ) -> Result<DirectoryOutput, DirectoryError> {
+        self.create_or_open_internal(trx, path, prefix, layer, true, true)
+            .await
+    }
+
+    async fn create(
+        &self,
+        trx: &Transaction,
+        path: &[String],
+        prefix: Option<&[u8]>,
+        layer: Option<&[u8]>,
+    ) -> Result<DirectoryOutput, DirectoryError> {
+        self.create_or_open_internal(trx, path, prefix, layer, true, false)
+            .await
+    }
+
+    async fn open(
+        &self,
+        trx: &Transaction,
+        path: &[String],
+        layer: Option<&[u8]>,
+    ) -> Result<DirectoryOutput, DirectoryError> {
+        self.create_or_open_internal(trx, path, None, layer, false, true)
+            .await
+    }
+
+    async fn exists(&self, trx: &Transaction, path: &[String]) -> Result<bool, DirectoryError> {
+        self.check_version(trx, false).await?;
+
+        let node = self.find(trx, path).await?;
+        if !node.exists() {
+            return Ok(false);
+        }
+
+        if node.is_in_partition(false) {
+            let subpath = node.get_partition_subpath();
+            return self.partition_layer(&node)?.exists(trx, &subpath).await;
+        }
+
+        Ok(true)
+    }
+
+    async fn move_directory(
+        &self,
+        _trx: &Transaction,
+        _new_path: &[String],
+    ) -> Result<DirectoryOutput, DirectoryError> {
+        Err(DirectoryError::CannotMoveRootDirectory)
+    }
+
+    async fn move_to(
+        &self,
+        trx: &Transaction,
+        old_path: &[String],
+        new_path: &[String],
+    ) -> Result<DirectoryOutput, DirectoryError> {
+        self.check_version(trx, true).await?;
+
+        // paths are compared segment by segment, `["a", "bc"]` is not inside `["a", "b"]`
+        if new_path.starts_with(old_path) {
+            return Err(DirectoryError::BadDestinationDirectory);
+        }
+
+        let old_node = self.find(trx, old_path).await?;
+        let new_node = self.find(trx, new_path).await?;
+
+        if !old_node.exists() {
+            return Err(DirectoryError::DirectoryDoesNotExists);
+        }
+
+        if old_node.is_in_partition(false) || new_node.is_in_partition(false) {
+            if !old_node.is_in_partition(false)
+                || !new_node.is_in_partition(false)
+                || old_node.current_path != new_node.current_path
+            {
+                return Err(DirectoryError::CannotMoveBetweenPartition);
+            }
+
+            let old_subpath = old_node.get_partition_subpath();
+            let new_subpath = new_node.get_partition_subpath();
+            return self
+                .partition_layer(&new_node)?
+                .move_to(trx, &old_subpath, &new_subpath)
+                .await;
+        }
+
+        if new_node.exists() {
+            return Err(DirectoryError::DirAlreadyExists);
+        }
+
+        let (new_name, new_parent_path) = new_path.split_last().expect("path to not be empty");
+        let parent_node = self.find(trx, new_parent_path).await?;
+        let parent_subspace = match parent_node.subspace {
+            Some(subspace) => subspace,
+            None => return Err(DirectoryError::ParentDirDoesNotExists),
+        };
+
+        let old_subspace = old_node.subspace.expect("node to exist");
+        let old_prefix = self.node_prefix(&old_subspace)?;
+        trx.set(
+            &parent_subspace.subspace(&SUBDIRS).pack(new_name),
+            &old_prefix,
+        );
+        self.remove_from_parent(trx, old_path).await?;
+
+        self.contents_of_node(&old_subspace, new_path, &old_node.layer)
+    }
+
+    async fn remove(&self, trx: &Transaction, path: &[String]) -> Result<bool, DirectoryError> {
+        self.remove_internal(trx, path, true).await
+    }
+
+    async fn remove_if_exists(
+        &self,
+        trx: &Transaction,
+        path: &[String],
+    ) -> Result<bool, DirectoryError> {
+        self.remove_internal(trx, path, false).await
+    }
+
+    async fn list(
+        &self,
+        trx: &Transaction,
+        path: &[String],
+    ) -> Result<Vec<String>, DirectoryError> {
+        self.check_version(trx, false).await?;
+
+        let node = self.find(trx, path).await?;
+        let subspace = match node.subspace {
+            Some(ref subspace) => subspace,
+            None => return Err(DirectoryError::DirectoryDoesNotExists),
+        };
+
+        if node.is_in_partition(true) {
+            let subpath = node.get_partition_subpath();
+            return self.partition_layer(&node)?.list(trx, &subpath).await;
+        }
+
+        Ok(self
+            .subdirs(trx, subspace)
+            .await?
+            .into_iter()
+            .map(|(name, _)| name)
+            .collect())
+    }
+}
+
+async fn is_range_empty(
+    trx: &Transaction,
+    begin: Vec<u8>,
+    end: Vec<u8>,
+    snapshot: bool,
+) -> Result<bool, DirectoryError> {
+    let range = RangeOption {
+        begin: KeySelector::first_greater_or_equal(begin),
+        end: KeySelector::first_greater_or_equal(end),
+        limit: Some(1),
+        ..RangeOption::default()
+    };
+    Ok(trx.get_range(&range, 1, snapshot).await?.is_empty())
+}
@@ -0,0 +1,219 @@
+// Copyright 2018 foundationdb-rs developers, https://github.com/Clikengo/foundationdb-rs/graphs/contributors
+// Copyright 2013-2018 Apple, Inc and the FoundationDB project authors.
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+use async_trait::async_trait;
+
+use super::directory_layer::{DEFAULT_NODE_PREFIX, PARTITION_LAYER};
+use super::{Directory, DirectoryError, DirectoryLayer, DirectoryOutput, DirectorySubspace};
+use crate::tuple::Subspace;
+use crate::Transaction;
+
+/// A directory partition is a directory whose sub-directories are all stored under its own
+/// prefix, with their own directory layer metadata.
+///
+/// The prefix of a partition cannot be used directly to store keys, because it is shared with
+/// all the directories of the partition.
+#[derive(Debug, Clone)]
+pub struct DirectoryPartition {
+    directory_subspace: DirectorySubspace,
+    parent_directory_layer: DirectoryLayer,
+}
+
+impl DirectoryPartition {
+    pub(crate) fn new(
+        path: Vec<String>,
+        prefix: Vec<u8>,
+        parent_directory_layer: DirectoryLayer,
+    ) -> Self {
+        let mut node_prefix = prefix.clone();
+        node_prefix.extend_from_slice(DEFAULT_NODE_PREFIX);
+
+        let directory_layer = DirectoryLayer::new_with_path(
+            Subspace::from_bytes(&node_prefix),
+            Subspace::from_bytes(&prefix),
+            false,
+            path.clone(),
+        );
+
+        DirectoryPartition {
+            directory_subspace: DirectorySubspace::new(
+                path,
+                prefix,
+                &directory_layer,
+                PARTITION_LAYER.to_vec(),
+            ),
+            parent_directory_layer,
+        }
+    }
+
+    /// Returns this partition with the directories inside it managed by a directory layer storing
+    /// its metadata in `node_subspace` and allocating their prefixes in `content_subspace`.
+    ///
+    /// By default, the metadata of a partition is stored under its prefix followed by `\xFE`,
+    /// and the prefixes are allocated under its prefix. The layout is not recorded in the
+    /// database, so the partition must always be used through `with_layout` with the same
+    /// subspaces, and its directories can't be reached from the parent directory layer.
+    /// Removing the partition also clears `node_subspace`.
+    pub fn with_layout(self, node_subspace: Subspace, content_subspace: Subspace) -> Self {
+        let path = self.get_path().to_vec();
+        let directory_layer =
+            DirectoryLayer::new_with_path(node_subspace, content_subspace, false, path.clone());
+
+        DirectoryPartition {
+            directory_subspace: DirectorySubspace::new(
+                path,
+                self.directory_subspace.bytes().to_vec(),
+                &directory_layer,
+                PARTITION_LAYER.to_vec(),
+            ),
+            parent_directory_layer: self.parent_directory_layer,
+        }
+    }
+
+    /// Returns the absolute path of this partition.
+    pub fn get_path(&self) -> &[String] {
+        self.directory_subspace.get_path()
+    }
+
+    /// Returns the prefix shared by all the directories of this partition.
+    pub fn prefix(&self) -> &[u8] {
+        self.directory_subspace.bytes()
+    }
+
+    /// Returns the layer of this partition, which is always `PARTITION_LAYER`.
+    pub fn get_layer(&self) -> &[u8] {
+        PARTITION_LAYER
+    }
+
+    /// Returns the directory layer managing the directories inside this partition.
+    pub(crate) fn directory_layer(&self) -> DirectoryLayer {
+        self.directory_subspace.directory_layer().clone()
+    }
+
+    /// The partition itself is managed by its parent layer, its content by its own layer.
+    fn get_layer_for_path(&self, path: &[String]) -> &DirectoryLayer {
+        if path.is_empty() {
+            &self.parent_directory_layer
+        } else {
+            self.directory_subspace.directory_layer()
+        }
+    }
+
+    /// Clears the metadata of the directories inside this partition if it is stored outside of
+    /// its prefix, see `with_layout`.
+    fn clear_node_subspace(&self, trx: &Transaction) {
+        let node_subspace = &self.directory_subspace.directory_layer().node_subspace;
+        if !node_subspace
+            .bytes()
+            .starts_with(self.directory_subspace.bytes())
+        {
+            trx.clear_subspace_range(node_subspace);
+        }
+    }
+}
+
+#[async_trait]
+impl Directory for DirectoryPartition {
+    async fn create_or_open(
+        &self,
+        trx: &Transaction,
+        path: &[String],
+        prefix: Option<&[u8]>,
+        layer: Option<&[u8]>,
+    ) -> Result<DirectoryOutput, DirectoryError> {
+        self.directory_subspace
+            .create_or_open(trx, path, prefix, layer)
+            .await
+    }
+
+    async fn create(
+        &self,
+        trx: &Transaction,
+        path: &[String],
+        prefix: Option<&[u8]>,
+        layer: Option<&[u8]>,
+    ) -> Result<DirectoryOutput, DirectoryError> {
+        self.directory_subspace
+            .create(trx, path, prefix, layer)
+            .await
+    }
+
+    async fn open(
+        &self,
+        trx: &Transaction,
+        path: &[String],
+        layer: Option<&[u8]>,
+    ) -> Result<DirectoryOutput, DirectoryError> {
+        self.directory_subspace.open(trx, path, layer).await
+    }
+
+    async fn exists(&self, trx: &Transaction, path: &[String]) -> Result<bool, DirectoryError> {
+        let directory_layer = self.get_layer_for_path(path);
+        let path = self
+            .directory_subspace
+            .partition_subpath(path, directory_layer);
+        directory_layer.exists(trx, &path).await
+    }
+
+    async fn move_directory(
+        &self,
+        trx: &Transaction,
+        new_path: &[String],
+    ) -> Result<DirectoryOutput, DirectoryError> {
+        self.directory_subspace
+            .move_directory_with(trx, &self.parent_directory_layer, new_path)
+            .await
+    }
+
+    async fn move_to(
+        &self,
+        trx: &Transaction,
+        old_path: &[String],
+        new_path: &[String],
+    ) -> Result<DirectoryOutput, DirectoryError> {
+        self.directory_subspace
+            .move_to(trx, old_path, new_path)
+            .await
+    }
+
+    async fn remove(&self, trx: &Transaction, path: &[String]) -> Result<bool, DirectoryError> {
+        let directory_layer = self.get_layer_for_path(path);
+        let subpath = self
+            .directory_subspace
+            .partition_subpath(path, directory_layer);
+        let removed = directory_layer.remove(trx, &subpath).await?;
+        if path.is_empty() {
+            self.clear_node_subspace(trx);
+        }
+        Ok(removed)
+    }
+
+    async fn remove_if_exists(
+        &self,
+        trx: &Transaction,
+        path: &[String],
+    ) -> Result<bool, DirectoryError> {
+        let directory_layer = self.get_layer_for_path(path);
+        let subpath = self
+            .directory_subspace
+            .partition_subpath(path, directory_layer);
+        let removed = directory_layer.remove_if_exists(trx, &subpath).await?;
+        if removed && path.is_empty() {
+            self.clear_node_subspace(trx);
+        }
+        Ok(removed)
+    }
+
+    async fn list(
+        &self,
+        trx: &Transaction,
+        path: &[String],
+    ) -> Result<Vec<String>, DirectoryError> {
+        self.directory_subspace.list(trx, path).await
+    }
+}
@@ -0,0 +1,262 @@
+// Copyright 2018 foundationdb-rs developers, https://github.com/Clikengo/foundationdb-rs/graphs/contributors
+// Copyright 2013-2018 Apple, Inc and the FoundationDB project authors.
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+use async_trait::async_trait;
+
+use super::{Directory, DirectoryError, DirectoryLayer, DirectoryOutput};
+use crate::tuple::{KeyspaceOps, PackResult, Subspace, TuplePack, TupleUnpack};
+use crate::Transaction;
+
+/// A directory that can be used as a `Subspace`.
+///
+/// Its prefix is the one allocated by the directory layer when the directory was created.
+///
+/// The paths given to its `Directory` methods are relative to this directory: opening `["b"]`
+/// from the directory `["a"]` opens `["a", "b"]`. Use `directory_layer` to work with absolute
+/// paths.
+#[derive(Debug, Clone)]
+pub struct DirectorySubspace {
+    subspace: Subspace,
+    path: Vec<String>,
+    layer: Vec<u8>,
+    directory_layer: DirectoryLayer,
+}
+
+impl DirectorySubspace {
+    pub(crate) fn new(
+        path: Vec<String>,
+        prefix: Vec<u8>,
+        directory_layer: &DirectoryLayer,
+        layer: Vec<u8>,
+    ) -> Self {
+        DirectorySubspace {
+            subspace: Subspace::from_bytes(&prefix),
+            path,
+            layer,
+            directory_layer: directory_layer.clone(),
+        }
+    }
+
+    /// Returns a new Subspace whose prefix extends this directory with a given tuple encodable.
+    pub fn subspace<T: TuplePack>(&self, t: &T) -> Subspace {
+        self.subspace.subspace(t)
+    }
+
+    /// Returns the prefix of this directory.
+    pub fn bytes(&self) -> &[u8] {
+        self.subspace.bytes()
+    }
+
+    /// Returns the key encoding the specified Tuple with the prefix of this directory prepended.
+    pub fn pack<T: TuplePack>(&self, t: &T) -> Vec<u8> {
+        self.subspace.pack(t)
+    }
+
+    /// Returns the Tuple encoded by the given key with the prefix of this directory removed.
+    pub fn unpack<'de, T: TupleUnpack<'de>>(&self, key: &'de [u8]) -> PackResult<T> {
+        self.subspace.unpack(key)
+    }
+
+    /// Returns true if the provided key starts with the prefix of this directory.
+    pub fn is_start_of(&self, key: &[u8]) -> bool {
+        self.subspace.is_start_of(key)
+    }
+
+    /// Returns the first and last key of this directory.
+    pub fn range(&self) -> (Vec<u8>, Vec<u8>) {
+        self.subspace.range()
+    }
+
+    /// Returns the absolute path of this directory.
+    pub fn get_path(&self) -> &[String] {
+        &self.path
+    }
+
+    /// Returns the absolute path of the parent of this directory.
+    pub fn parent_path(&self) -> &[String] {
+        match self.path.split_last() {
+            Some((_, parent)) => parent,
+            None => &[],
+        }
+    }
+
+    /// Returns the layer this directory was created with.
+    pub fn get_layer(&self) -> &[u8] {
+        &self.layer
+    }
+
+    /// Returns the directory layer managing this directory.
+    ///
+    /// Its paths are relative to the partition containing this directory, or absolute if the
+    /// directory is not in a partition.
+    pub fn directory_layer(&self) -> &DirectoryLayer {
+        &self.directory_layer
+    }
+
+    /// Returns the `Subspace` of this directory, dropping its directory metadata.
+    pub fn into_subspace(self) -> Subspace {
+        self.subspace
+    }
+
+    /// Opens the directory at `sub_path`, relative to this directory.
+    ///
+    /// This is `Directory::open`, named to make explicit that the path of this directory is
+    /// prepended to `sub_path`.
+    pub async fn relative_open(
+        &self,
+        trx: &Transaction,
+        sub_path: &[String],
+        layer: Option<&[u8]>,
+    ) -> Result<DirectoryOutput, DirectoryError> {
+        self.open(trx, sub_path, layer).await
+    }
+
+    /// Converts a path relative to this directory into a path relative to `directory_layer`.
+    pub(crate) fn partition_subpath(
+        &self,
+        path: &[String],
+        directory_layer: &DirectoryLayer,
+    ) -> Vec<String> {
+        let mut subpath = self.path[directory_layer.path.len()..].to_vec();
+        subpath.extend_from_slice(path);
+        subpath
+    }
+
+    /// Moves this directory to `new_path` using `directory_layer`, the layer that owns it.
+    pub(crate) async fn move_directory_with(
+        &self,
+        trx: &Transaction,
+        directory_layer: &DirectoryLayer,
+        new_path: &[String],
+    ) -> Result<DirectoryOutput, DirectoryError> {
+        let partition_path = &directory_layer.path;
+        if !new_path.starts_with(partition_path) {
+            return Err(DirectoryError::CannotMoveBetweenPartition);
+        }
+
+        let partition_len = partition_path.len();
+        directory_layer
+            .move_to(trx, &self.path[partition_len..], &new_path[partition_len..])
+            .await
+    }
+}
+
+impl KeyspaceOps for DirectorySubspace {
+    fn bytes(&self) -> PackResult<&[u8]> {
+        Ok(DirectorySubspace::bytes(self))
+    }
+
+    fn pack<T: TuplePack>(&self, t: &T) -> PackResult<Vec<u8>> {
+        Ok(DirectorySubspace::pack(self, t))
+    }
+
+    fn unpack<'de, T: TupleUnpack<'de>>(&self, key: &'de [u8]) -> PackResult<T> {
+        DirectorySubspace::unpack(self, key)
+    }
+
+    fn range(&self) -> PackResult<(Vec<u8>, Vec<u8>)> {
+        Ok(DirectorySubspace::range(self))
+    }
+
+    fn subspace<T: TuplePack>(&self, t: &T) -> PackResult<Subspace> {
+        Ok(DirectorySubspace::subspace(self, t))
+    }
+}
+
+impl From<DirectorySubspace> for Subspace {
+    fn from(directory: DirectorySubspace) -> Self {
+        directory.into_subspace()
+    }
+}
+
+#[async_trait]
+impl Directory for DirectorySubspace {
+    async fn create_or_open(
+        &self,
+        trx: &Transaction,
+        path: &[String],
+        prefix: Option<&[u8]>,
+        layer: Option<&[u8]>,
+    ) -> Result<DirectoryOutput, DirectoryError> {
+        let path = self.partition_subpath(path, &self.directory_layer);
+        self.directory_layer
+            .create_or_open(trx, &path, prefix, layer)
+            .await
+    }
+
+    async fn create(
+        &self,
+        trx: &Transaction,
+        path: &[String],
+        prefix: Option<&[u8]>,
+        layer: Option<&[u8]>,
+    ) -> Result<DirectoryOutput, DirectoryError> {
+        let path = self.partition_subpath(path, &self.directory_layer);
+        self.directory_layer.create(trx, &path, prefix, layer).await
+    }
+
+    async fn open(
+        &self,
+        trx: &Transaction,
+        path: &[String],
+        layer: Option<&[u8]>,
+    ) -> Result<DirectoryOutput, DirectoryError> {
+        let path = self.partition_subpath(path, &self.directory_layer);
+        self.directory_layer.open(trx, &path, layer).await
+    }
+
+    async fn exists(&self, trx: &Transaction, path: &[String]) -> Result<bool, DirectoryError> {
+        let path = self.partition_subpath(path, &self.directory_layer);
+        self.directory_layer.exists(trx, &path).await
+    }
+
+    async fn move_directory(
+        &self,
+        trx: &Transaction,
+        new_path: &[String],
+    ) -> Result<DirectoryOutput, DirectoryError> {
+        self.move_directory_with(trx, &self.directory_layer, new_path)
+            .await
+    }
+
+    async fn move_to(
+        &self,
+        trx: &Transaction,
+        old_path: &[String],
+        new_path: &[String],
+    ) -> Result<DirectoryOutput, DirectoryError> {
+        let old_path = self.partition_subpath(old_path, &self.directory_layer);
+        let new_path = self.partition_subpath(new_path, &self.directory_layer);
+        self.directory_layer
+            .move_to(trx, &old_path, &new_path)
+            .await
+    }
+
+    async fn remove(&self, trx: &Transaction, path: &[String]) -> Result<bool, DirectoryError> {
+        let path = self.partition_subpath(path, &self.directory_layer);
+        self.directory_layer.remove(trx, &path).await
+    }
+
+    async fn remove_if_exists(
+        &self,
+        trx: &Transaction,
+        path: &[String],
+    ) -> Result<bool, DirectoryError> {
+        let path = self.partition_subpath(path, &self.directory_layer);
+        self.directory_layer.remove_if_exists(trx, &path).await
+    }
+
+    async fn list(
+        &self,
+        trx: &Transaction,
+        path: &[String],
+    ) -> Result<Vec<String>, DirectoryError> {
+        let path = self.partition_subpath(path, &self.directory_layer);
+        self.directory_layer.list(trx, &path).await
+    }
+}
@@ -0,0 +1,243 @@
+// Copyright 2018 foundationdb-rs developers, https://github.com/Clikengo/foundationdb-rs/graphs/contributors
+// Copyright 2013-2018 Apple, Inc and the FoundationDB project authors.
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! Errors that can be thrown by the directory layer
+
+use std::fmt;
+
+use crate::tuple::hca::HcaError;
+use crate::tuple::PackError;
+use crate::{FdbError, TransactError};
+
+/// The enumeration holding all possible errors from the directory layer.
+pub enum DirectoryError {
+    /// The root directory cannot be opened
+    CannotOpenRoot,
+    /// The root directory cannot be removed
+    CannotModifyRootDirectory,
+    /// The root directory cannot be moved
+    CannotMoveRootDirectory,
+    /// Directories cannot be moved between partitions
+    CannotMoveBetweenPartition,
+    /// The destination directory cannot be a subdirectory of the source directory
+    BadDestinationDirectory,
+    /// The directory already exists
+    DirAlreadyExists,
+    /// The directory does not exist
+    DirectoryDoesNotExists,
+    /// The parent of the destination directory does not exist
+    ParentDirDoesNotExists,
+    /// The directory was created with an incompatible layer
+    IncompatibleLayer,
+    /// A prefix can only be specified if manual prefixes are enabled
+    PrefixNotAllowed,
+    /// A prefix cannot be specified inside a partition
+    CannotPrefixInPartition,
+    /// The given prefix is already in use
+    DirectoryPrefixInUse,
+    /// The database has keys stored at the prefix chosen by the automatic prefix allocator
+    PrefixNotEmpty,
+    /// Manually allocated prefixes conflict with the automatic prefix allocator
+    ManualPrefixConflict,
+    /// The stored directory layer metadata version cannot be read by this implementation
+    IncompatibleVersion(u32, u32, u32),
+    /// The stored directory layer metadata version is read-only for this implementation
+    ReadOnlyVersion(u32, u32, u32),
+    /// The stored directory layer metadata is invalid
+    InvalidDirectoryLayerMetadata,
+    /// The directory tree is deeper or larger than the given `TreeLimits`
+    TreeLimitExceeded,
+    /// The directory has sub-directories or content keys
+    NotEmpty,
+    /// The prefix of the directory differs from the expected one
+    PrefixMismatch,
+    FdbError(FdbError),
+    PackError(PackError),
+    HcaError(HcaError),
+}
+
+impl fmt::Display for DirectoryError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            DirectoryError::CannotOpenRoot => write!(f, "the root directory cannot be opened"),
+            DirectoryError::CannotModifyRootDirectory => {
+                write!(f, "the root directory cannot be removed")
+            }
+            DirectoryError::CannotMoveRootDirectory => {
+                write!(f, "the root directory cannot be moved")
+            }
+            DirectoryError::CannotMoveBetweenPartition => {
+                write!(f, "cannot move between partitions")
+            }
+            DirectoryError::BadDestinationDirectory => write!(
+                f,
+                "the destination directory cannot be a subdirectory of the source directory"
+            ),
+            DirectoryError::DirAlreadyExists => write!(f, "the directory already exists"),
+            DirectoryError::DirectoryDoesNotExists => write!(f, "the directory does not exist"),
+            DirectoryError::ParentDirDoesNotExists => {
+                write!(f, "the parent of the destination directory does not exist")
+            }
+            DirectoryError::IncompatibleLayer => {
+                write!(f, "the directory was created with an incompatible layer")
+            }
+            DirectoryError::PrefixNotAllowed => write!(
+                f,
+                "cannot specify a prefix unless manual prefixes are enabled"
+            ),
+            DirectoryError::CannotPrefixInPartition => {
+                write!(f, "cannot specify a prefix in a partition")
+            }
+            DirectoryError::DirectoryPrefixInUse => write!(f, "the given prefix is already in use"),
+            DirectoryError::PrefixNotEmpty => write!(
+                f,
+                "the database has keys stored at the prefix chosen by the automatic prefix allocator"
+            ),
+            DirectoryError::ManualPrefixConflict => write!(
+                f,
+                "the directory layer has manually allocated prefixes that conflict with the automatic prefix allocator"
+            ),
+            DirectoryError::IncompatibleVersion(major, minor, patch) => write!(
+                f,
+                "cannot load directory with version {}.{}.{}",
+                major, minor, patch
+            ),
+            DirectoryError::ReadOnlyVersion(major, minor, patch) => write!(
+                f,
+                "directory with version {}.{}.{} is read-only",
+                major, minor, patch
+            ),
+            DirectoryError::InvalidDirectoryLayerMetadata => {
+                write!(f, "invalid directory layer metadata")
+            }
+            DirectoryError::TreeLimitExceeded => {
+                write!(f, "the directory tree exceeds the given limits")
+            }
+            DirectoryError::NotEmpty => write!(f, "the directory is not empty"),
+            DirectoryError::PrefixMismatch => {
+                write!(f, "the directory prefix differs from the expected one")
+            }
+            DirectoryError::FdbError(err) => err.fmt(f),
+            DirectoryError::PackError(err) => err.fmt(f),
+            DirectoryError::HcaError(err) => err.fmt(f),
+        }
+    }
+}
+
+impl fmt::Debug for DirectoryError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            DirectoryError::FdbError(err) => err.fmt(f),
+            DirectoryError::PackError(err) => err.fmt(f),
+            DirectoryError::HcaError(err) => err.fmt(f),
+            _ => fmt::Display::fmt(self, f),
+        }
+    }
+}
+
+impl std::error::Error for DirectoryError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            DirectoryError::FdbError(err) => Some(err),
+            DirectoryError::PackError(err) => Some(err),
+            DirectoryError::HcaError(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<FdbError> for DirectoryError {
+    fn from(err: FdbError) -> Self {
+        DirectoryError::FdbError(err)
+    }
+}
+
+impl From<PackError> for DirectoryError {
+    fn from(err: PackError) -> Self {
+        DirectoryError::PackError(err)
+    }
+}
+
+impl From<HcaError> for DirectoryError {
+    fn from(err: HcaError) -> Self {
+        match err {
+            HcaError::FdbError(err) => DirectoryError::FdbError(err),
+            HcaError::PackError(err) => DirectoryError::PackError(err),
+            err => DirectoryError::HcaError(err),
+        }
+    }
+}
+
+impl TransactError for DirectoryError {
+    fn try_into_fdb_error(self) -> Result<FdbError, Self> {
+        match self {
+            DirectoryError::FdbError(err) => Ok(err),
+            _ => Err(self),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::error::Error;
+
+    #[test]
+    fn test_display() {
+        assert_eq!(
+            DirectoryError::CannotOpenRoot.to_string(),
+            "the root directory cannot be opened"
+        );
+        assert_eq!(
+            DirectoryError::DirectoryDoesNotExists.to_string(),
+            "the directory does not exist"
+        );
+        assert_eq!(
+            DirectoryError::IncompatibleVersion(2, 0, 1).to_string(),
+            "cannot load directory with version 2.0.1"
+        );
+        assert_eq!(
+            DirectoryError::ReadOnlyVersion(1, 1, 0).to_string(),
+            "directory with version 1.1.0 is read-only"
+        );
+        assert_eq!(
+            DirectoryError::from(PackError::BadPrefix).to_string(),
+            "bad prefix"
+        );
+        assert_eq!(
+            DirectoryError::from(HcaError::PoisonError).to_string(),
+            "mutex poisoned"
+        );
+    }
+
+    #[test]
+    fn test_source() {
+        assert!(DirectoryError::DirAlreadyExists.source().is_none());
+
+        let err = DirectoryError::from(FdbError::from_code(1020));
+        let source = err.source().expect("missing source");
+        let source = source.downcast_ref::<FdbError>().expect("not an FdbError");
+        assert_eq!(source.code(), 1020);
+
+        let err = DirectoryError::from(PackError::TrailingBytes);
+        let source = err.source().expect("missing source");
+        match source.downcast_ref::<PackError>() {
+            Some(PackError::TrailingBytes) => {}
+            _ => panic!("not the inner PackError"),
+        }
+
+        // errors from the allocator are flattened
+        let err = DirectoryError::from(HcaError::FdbError(FdbError::from_code(1020)));
+        let source = err.source().expect("missing source");
+        assert!(source.downcast_ref::<FdbError>().is_some());
+
+        let err = DirectoryError::from(HcaError::InvalidDirectoryLayerMetadata);
+        let source = err.source().expect("missing source");
+        assert!(source.downcast_ref::<HcaError>().is_some());
+    }
+}
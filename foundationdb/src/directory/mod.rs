@@ -0,0 +1,465 @@
+// Copyright 2018 foundationdb-rs developers, https://github.com/Clikengo/foundationdb-rs/graphs/contributors
+// Copyright 2013-2018 Apple, Inc and the FoundationDB project authors.
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! Directory layer
+//!
+//! The directory layer provides a way to define the structure of your keyspace in a hierarchical
+//! way, similar to a file system. Each directory is identified by a path (a list of unicode
+//! strings) and is mapped to a short, automatically allocated, key prefix.
+//!
+//! See the [Developer Guide](https://apple.github.io/foundationdb/developer-guide.html#directories)
+//! for more details.
+//!
+//! # Example
+//!
+//! ```no_run
+//! use foundationdb::directory::{Directory, DirectoryLayer};
+//!
+//! async fn example(db: &foundationdb::Database) -> Result<(), foundationdb::directory::DirectoryError> {
+//!     let directory = DirectoryLayer::default();
+//!     let trx = db.create_trx()?;
+//!
+//!     let path = vec![String::from("app"), String::from("users")];
+//!     let users = directory.create_or_open(&trx, &path, None, None).await?;
+//!     trx.set(&users.pack(&("alice",)), b"");
+//!
+//!     // Directory operations are transactional, nothing is written until the commit.
+//!     trx.commit().await?;
+//!     Ok(())
+//! }
+//! ```
+//!
+//! The `db_*` methods of `Directory` run a single operation in its own transaction, retried and
+//! committed by `Database::transact_boxed`:
+//!
+//! ```no_run
+//! use foundationdb::directory::{Directory, DirectoryLayer};
+//!
+//! async fn example(db: &foundationdb::Database) -> Result<(), foundationdb::directory::DirectoryError> {
+//!     let directory = DirectoryLayer::default();
+//!
+//!     let path = vec![String::from("app"), String::from("users")];
+//!     let users = directory.db_create_or_open(db, &path, None, None).await?;
+//!     assert_eq!(users.get_path(), path.as_slice());
+//!     assert!(directory.db_exists(db, &path).await?);
+//!     Ok(())
+//! }
+//! ```
+
+mod directory_layer;
+mod directory_partition;
+mod directory_subspace;
+mod error;
+mod node;
+mod tree;
+
+pub use directory_layer::{DirectoryLayer, DirectoryStat, DEFAULT_NODE_PREFIX, PARTITION_LAYER};
+pub use directory_partition::DirectoryPartition;
+pub use directory_subspace::DirectorySubspace;
+pub use error::DirectoryError;
+pub use tree::{DirectoryTree, TreeLimits};
+
+use std::convert::TryFrom;
+
+use async_trait::async_trait;
+
+use crate::tuple::{KeyspaceOps, PackError, PackResult, Subspace, TuplePack, TupleUnpack};
+use crate::{Database, TransactOption, Transaction};
+
+/// Operations available on every node of the directory hierarchy.
+///
+/// Paths are always relative to the directory the method is called on.
+#[async_trait]
+pub trait Directory {
+    /// Opens the directory at `path`, creating it (and its parents) if it does not exist.
+    ///
+    /// If `layer` is given and not empty, it is recorded when the directory is created and
+    /// checked against the recorded one when it is opened.
+    /// A `prefix` can only be given if the directory layer allows manual prefixes.
+    async fn create_or_open(
+        &self,
+        trx: &Transaction,
+        path: &[String],
+        prefix: Option<&[u8]>,
+        layer: Option<&[u8]>,
+    ) -> Result<DirectoryOutput, DirectoryError>;
+
+    /// Creates the directory at `path` (and its parents if needed), failing if it already
+    /// exists.
+    async fn create(
+        &self,
+        trx: &Transaction,
+        path: &[String],
+        prefix: Option<&[u8]>,
+        layer: Option<&[u8]>,
+    ) -> Result<DirectoryOutput, DirectoryError>;
+
+    /// Opens the directory at `path`, failing if it does not exist.
+    async fn open(
+        &self,
+        trx: &Transaction,
+        path: &[String],
+        layer: Option<&[u8]>,
+    ) -> Result<DirectoryOutput, DirectoryError>;
+
+    /// Checks if the directory at `path` exists.
+    async fn exists(&self, trx: &Transaction, path: &[String]) -> Result<bool, DirectoryError>;
+
+    /// Moves this directory to `new_path`, an absolute path.
+    ///
+    /// There is no effect on the prefix of the directory or on its content, its sub-directories
+    /// are moved along. The parent of `new_path` must already exist, and `new_path` must be in
+    /// the same partition as this directory and outside of this directory. The root directory
+    /// cannot be moved.
+    async fn move_directory(
+        &self,
+        trx: &Transaction,
+        new_path: &[String],
+    ) -> Result<DirectoryOutput, DirectoryError>;
+
+    /// Moves the directory at `old_path` to `new_path`.
+    ///
+    /// There is no effect on the prefix of the directory or on its content. The parent of
+    /// `new_path` must already exist, and `new_path` must be outside of `old_path`.
+    async fn move_to(
+        &self,
+        trx: &Transaction,
+        old_path: &[String],
+        new_path: &[String],
+    ) -> Result<DirectoryOutput, DirectoryError>;
+
+    /// Removes the directory at `path`, its sub-directories and all of their content.
+    ///
+    /// Fails if the directory does not exist.
+    async fn remove(&self, trx: &Transaction, path: &[String]) -> Result<bool, DirectoryError>;
+
+    /// Removes the directory at `path`, its sub-directories and all of their content.
+    ///
+    /// Returns `false` if the directory did not exist.
+    async fn remove_if_exists(
+        &self,
+        trx: &Transaction,
+        path: &[String],
+    ) -> Result<bool, DirectoryError>;
+
+    /// Lists the names of the immediate sub-directories of the directory at `path`.
+    async fn list(&self, trx: &Transaction, path: &[String])
+        -> Result<Vec<String>, DirectoryError>;
+
+    /// Same as `create_or_open`, in a transaction of `db` which is committed before returning.
+    ///
+    /// The transaction is retried on retryable errors, see `Database::transact_boxed`.
+    async fn db_create_or_open(
+        &self,
+        db: &Database,
+        path: &[String],
+        prefix: Option<&[u8]>,
+        layer: Option<&[u8]>,
+    ) -> Result<DirectoryOutput, DirectoryError> {
+        db.transact_boxed(
+            (self, path, prefix, layer),
+            |trx, (dir, path, prefix, layer)| dir.create_or_open(trx, *path, *prefix, *layer),
+            TransactOption::default(),
+        )
+        .await
+    }
+
+    /// Same as `open`, in a transaction of `db`, see `db_create_or_open`.
+    async fn db_open(
+        &self,
+        db: &Database,
+        path: &[String],
+        layer: Option<&[u8]>,
+    ) -> Result<DirectoryOutput, DirectoryError> {
+        db.transact_boxed(
+            (self, path, layer),
+            |trx, (dir, path, layer)| dir.open(trx, *path, *layer),
+            TransactOption::read_only(),
+        )
+        .await
+    }
+
+    /// Same as `exists`, in a transaction of `db`, see `db_create_or_open`.
+    async fn db_exists(&self, db: &Database, path: &[String]) -> Result<bool, DirectoryError> {
+        db.transact_boxed(
+            (self, path),
+            |trx, (dir, path)| dir.exists(trx, *path),
+            TransactOption::read_only(),
+        )
+        .await
+    }
+
+    /// Same as `remove`, in a transaction of `db` which is committed before returning, see
+    /// `db_create_or_open`.
+    async fn db_remove(&self, db: &Database, path: &[String]) -> Result<bool, DirectoryError> {
+        db.transact_boxed(
+            (self, path),
+            |trx, (dir, path)| dir.remove(trx, *path),
+            TransactOption::default(),
+        )
+        .await
+    }
+
+    /// Same as `list`, in a transaction of `db`, see `db_create_or_open`.
+    async fn db_list(&self, db: &Database, path: &[String]) -> Result<Vec<String>, DirectoryError> {
+        db.transact_boxed(
+            (self, path),
+            |trx, (dir, path)| dir.list(trx, *path),
+            TransactOption::read_only(),
+        )
+        .await
+    }
+}
+
+/// The result of opening a directory: either a regular directory or a partition.
+#[derive(Debug, Clone)]
+pub enum DirectoryOutput {
+    DirectorySubspace(DirectorySubspace),
+    DirectoryPartition(DirectoryPartition),
+}
+
+impl DirectoryOutput {
+    /// Returns a new Subspace whose prefix extends this directory with a given tuple encodable.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the directory is a partition, see `KeyspaceOps::subspace`.
+    pub fn subspace<T: TuplePack>(&self, t: &T) -> Subspace {
+        KeyspaceOps::subspace(self, t).unwrap_or_else(|err| panic!("{}", err))
+    }
+
+    /// Returns the prefix of this directory.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the directory is a partition, see `KeyspaceOps::bytes`.
+    pub fn bytes(&self) -> &[u8] {
+        KeyspaceOps::bytes(self).unwrap_or_else(|err| panic!("{}", err))
+    }
+
+    /// Returns the key encoding the specified Tuple with the prefix of this directory prepended.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the directory is a partition, see `KeyspaceOps::pack`.
+    pub fn pack<T: TuplePack>(&self, t: &T) -> Vec<u8> {
+        KeyspaceOps::pack(self, t).unwrap_or_else(|err| panic!("{}", err))
+    }
+
+    /// Returns the Tuple encoded by the given key with the prefix of this directory removed.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the directory is a partition, see `KeyspaceOps::unpack`.
+    pub fn unpack<'de, T: TupleUnpack<'de>>(&self, key: &'de [u8]) -> PackResult<T> {
+        self.directory_subspace("unpack keys using")
+            .unwrap_or_else(|err| panic!("{}", err))
+            .unpack(key)
+    }
+
+    /// Returns the first and last key of this directory.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the directory is a partition, see `KeyspaceOps::range`.
+    pub fn range(&self) -> (Vec<u8>, Vec<u8>) {
+        KeyspaceOps::range(self).unwrap_or_else(|err| panic!("{}", err))
+    }
+
+    /// Returns the directory as a `DirectorySubspace`, or an error stating that `operation` can't
+    /// be done with the prefix of a partition.
+    fn directory_subspace(&self, operation: &str) -> PackResult<&DirectorySubspace> {
+        match self {
+            DirectoryOutput::DirectorySubspace(d) => Ok(d),
+            DirectoryOutput::DirectoryPartition(_) => Err(PackError::Message(
+                format!("cannot {} the root of a directory partition", operation).into(),
+            )),
+        }
+    }
+
+    /// Returns the absolute path of this directory.
+    pub fn get_path(&self) -> &[String] {
+        match self {
+            DirectoryOutput::DirectorySubspace(d) => d.get_path(),
+            DirectoryOutput::DirectoryPartition(d) => d.get_path(),
+        }
+    }
+
+    /// Returns the layer this directory was created with.
+    pub fn get_layer(&self) -> &[u8] {
+        match self {
+            DirectoryOutput::DirectorySubspace(d) => d.get_layer(),
+            DirectoryOutput::DirectoryPartition(d) => d.get_layer(),
+        }
+    }
+
+    /// Returns the `Subspace` of this directory, or `None` if the directory is a partition,
+    /// whose prefix can't be used directly.
+    pub fn into_subspace(self) -> Option<Subspace> {
+        match self {
+            DirectoryOutput::DirectorySubspace(d) => Some(d.into_subspace()),
+            DirectoryOutput::DirectoryPartition(_) => None,
+        }
+    }
+}
+
+/// Fails with `PackError::Message` for a partition, whose prefix can't be used directly.
+impl KeyspaceOps for DirectoryOutput {
+    fn bytes(&self) -> PackResult<&[u8]> {
+        Ok(self.directory_subspace("get key for")?.bytes())
+    }
+
+    fn pack<T: TuplePack>(&self, t: &T) -> PackResult<Vec<u8>> {
+        Ok(self.directory_subspace("pack keys using")?.pack(t))
+    }
+
+    fn unpack<'de, T: TupleUnpack<'de>>(&self, key: &'de [u8]) -> PackResult<T> {
+        self.directory_subspace("unpack keys using")?.unpack(key)
+    }
+
+    fn range(&self) -> PackResult<(Vec<u8>, Vec<u8>)> {
+        Ok(self.directory_subspace("get range for")?.range())
+    }
+
+    fn subspace<T: TuplePack>(&self, t: &T) -> PackResult<Subspace> {
+        Ok(self.directory_subspace("open subspace in")?.subspace(t))
+    }
+}
+
+impl From<DirectorySubspace> for DirectoryOutput {
+    fn from(directory: DirectorySubspace) -> Self {
+        DirectoryOutput::DirectorySubspace(directory)
+    }
+}
+
+impl From<DirectoryPartition> for DirectoryOutput {
+    fn from(partition: DirectoryPartition) -> Self {
+        DirectoryOutput::DirectoryPartition(partition)
+    }
+}
+
+/// Fails with the partition, whose prefix can't be used as a `Subspace`.
+impl TryFrom<DirectoryOutput> for Subspace {
+    type Error = DirectoryPartition;
+
+    fn try_from(directory: DirectoryOutput) -> Result<Self, Self::Error> {
+        match directory {
+            DirectoryOutput::DirectorySubspace(d) => Ok(d.into_subspace()),
+            DirectoryOutput::DirectoryPartition(p) => Err(p),
+        }
+    }
+}
+
+#[async_trait]
+impl Directory for DirectoryOutput {
+    async fn create_or_open(
+        &self,
+        trx: &Transaction,
+        path: &[String],
+        prefix: Option<&[u8]>,
+        layer: Option<&[u8]>,
+    ) -> Result<DirectoryOutput, DirectoryError> {
+        match self {
+            DirectoryOutput::DirectorySubspace(d) => d.create_or_open(trx, path, prefix, layer),
+            DirectoryOutput::DirectoryPartition(d) => d.create_or_open(trx, path, prefix, layer),
+        }
+        .await
+    }
+
+    async fn create(
+        &self,
+        trx: &Transaction,
+        path: &[String],
+        prefix: Option<&[u8]>,
+        layer: Option<&[u8]>,
+    ) -> Result<DirectoryOutput, DirectoryError> {
+        match self {
+            DirectoryOutput::DirectorySubspace(d) => d.create(trx, path, prefix, layer),
+            DirectoryOutput::DirectoryPartition(d) => d.create(trx, path, prefix, layer),
+        }
+        .await
+    }
+
+    async fn open(
+        &self,
+        trx: &Transaction,
+        path: &[String],
+        layer: Option<&[u8]>,
+    ) -> Result<DirectoryOutput, DirectoryError> {
+        match self {
+            DirectoryOutput::DirectorySubspace(d) => d.open(trx, path, layer),
+            DirectoryOutput::DirectoryPartition(d) => d.open(trx, path, layer),
+        }
+        .await
+    }
+
+    async fn exists(&self, trx: &Transaction, path: &[String]) -> Result<bool, DirectoryError> {
+        match self {
+            DirectoryOutput::DirectorySubspace(d) => d.exists(trx, path),
+            DirectoryOutput::DirectoryPartition(d) => d.exists(trx, path),
+        }
+        .await
+    }
+
+    async fn move_directory(
+        &self,
+        trx: &Transaction,
+        new_path: &[String],
+    ) -> Result<DirectoryOutput, DirectoryError> {
+        match self {
+            DirectoryOutput::DirectorySubspace(d) => d.move_directory(trx, new_path),
+            DirectoryOutput::DirectoryPartition(d) => d.move_directory(trx, new_path),
+        }
+        .await
+    }
+
+    async fn move_to(
+        &self,
+        trx: &Transaction,
+        old_path: &[String],
+        new_path: &[String],
+    ) -> Result<DirectoryOutput, DirectoryError> {
+        match self {
+            DirectoryOutput::DirectorySubspace(d) => d.move_to(trx, old_path, new_path),
+            DirectoryOutput::DirectoryPartition(d) => d.move_to(trx, old_path, new_path),
+        }
+        .await
+    }
+
+    async fn remove(&self, trx: &Transaction, path: &[String]) -> Result<bool, DirectoryError> {
+        match self {
+            DirectoryOutput::DirectorySubspace(d) => d.remove(trx, path),
+            DirectoryOutput::DirectoryPartition(d) => d.remove(trx, path),
+        }
+        .await
+    }
+
+    async fn remove_if_exists(
+        &self,
+        trx: &Transaction,
+        path: &[String],
+    ) -> Result<bool, DirectoryError> {
+        match self {
+            DirectoryOutput::DirectorySubspace(d) => d.remove_if_exists(trx, path),
+            DirectoryOutput::DirectoryPartition(d) => d.remove_if_exists(trx, path),
+        }
+        .await
+    }
+
+    async fn list(
+        &self,
+        trx: &Transaction,
+        path: &[String],
+    ) -> Result<Vec<String>, DirectoryError> {
+        match self {
+            DirectoryOutput::DirectorySubspace(d) => d.list(trx, path),
+            DirectoryOutput::DirectoryPartition(d) => d.list(trx, path),
+        }
+        .await
+    }
+}
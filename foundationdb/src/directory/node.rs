@@ -0,0 +1,78 @@
+// Copyright 2018 foundationdb-rs developers, https://github.com/Clikengo/foundationdb-rs/graphs/contributors
+// Copyright 2013-2018 Apple, Inc and the FoundationDB project authors.
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+use super::directory_layer::{LAYER_SUFFIX, PARTITION_LAYER};
+use super::{DirectoryError, DirectoryLayer, DirectoryOutput};
+use crate::tuple::Subspace;
+use crate::Transaction;
+
+/// A node of the directory layer metadata, as found while walking a path.
+#[derive(Debug, Clone)]
+pub(crate) struct Node {
+    /// The metadata subspace of the node, `None` if the node does not exist
+    pub(crate) subspace: Option<Subspace>,
+    /// The path of this node
+    pub(crate) current_path: Vec<String>,
+    /// The path that was looked up
+    pub(crate) target_path: Vec<String>,
+    /// The layer of the node, empty if the node does not exist
+    pub(crate) layer: Vec<u8>,
+}
+
+impl Node {
+    /// Loads the metadata of the node stored at `subspace`.
+    pub(crate) async fn load(
+        trx: &Transaction,
+        subspace: Option<Subspace>,
+        current_path: Vec<String>,
+        target_path: Vec<String>,
+    ) -> Result<Node, DirectoryError> {
+        let layer = match &subspace {
+            Some(subspace) => match trx.get(&subspace.pack(&LAYER_SUFFIX), false).await? {
+                Some(layer) => layer.to_vec(),
+                None => Vec::new(),
+            },
+            None => Vec::new(),
+        };
+
+        Ok(Node {
+            subspace,
+            current_path,
+            target_path,
+            layer,
+        })
+    }
+
+    pub(crate) fn exists(&self) -> bool {
+        self.subspace.is_some()
+    }
+
+    /// Returns true if the target path is inside the partition this node is the root of.
+    pub(crate) fn is_in_partition(&self, include_empty_subpath: bool) -> bool {
+        self.exists()
+            && self.layer == PARTITION_LAYER
+            && (include_empty_subpath || self.target_path.len() > self.current_path.len())
+    }
+
+    /// Returns the remainder of the target path, relative to this node.
+    pub(crate) fn get_partition_subpath(&self) -> Vec<String> {
+        self.target_path[self.current_path.len()..].to_vec()
+    }
+
+    pub(crate) fn get_contents(
+        &self,
+        directory_layer: &DirectoryLayer,
+    ) -> Result<DirectoryOutput, DirectoryError> {
+        match &self.subspace {
+            Some(subspace) => {
+                directory_layer.contents_of_node(subspace, &self.current_path, &self.layer)
+            }
+            None => Err(DirectoryError::DirectoryDoesNotExists),
+        }
+    }
+}
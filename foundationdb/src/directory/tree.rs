@@ -0,0 +1,214 @@
+// Copyright 2018 foundationdb-rs developers, https://github.com/Clikengo/foundationdb-rs/graphs/contributors
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! Snapshot of the directory hierarchy, for operational tooling
+
+use futures::future::BoxFuture;
+use futures::prelude::*;
+
+use super::{Directory, DirectoryError, DirectoryLayer, DirectoryOutput};
+use crate::Transaction;
+
+/// A directory and all its sub-directories, as returned by `DirectoryLayer::tree`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DirectoryTree {
+    /// The last segment of the path of the directory, empty for the root directory
+    pub name: String,
+    /// The layer the directory was created with
+    pub layer: Vec<u8>,
+    /// `true` if the directory is a partition, whose sub-directories are listed like the ones
+    /// of regular directories
+    pub partition: bool,
+    /// The sub-directories, sorted by name
+    pub children: Vec<DirectoryTree>,
+}
+
+impl DirectoryTree {
+    /// Returns the number of directories in this tree, including this one.
+    pub fn len(&self) -> usize {
+        1 + self.children.iter().map(DirectoryTree::len).sum::<usize>()
+    }
+
+    /// Always `false`, a tree contains at least its root.
+    pub fn is_empty(&self) -> bool {
+        false
+    }
+}
+
+/// The limits on the size of the tree built by `DirectoryLayer::tree_with_limits`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TreeLimits {
+    /// The maximum depth of the tree, the root being at depth 0
+    pub max_depth: usize,
+    /// The maximum number of directories in the tree, including the root
+    pub max_nodes: usize,
+}
+
+impl Default for TreeLimits {
+    fn default() -> Self {
+        Self {
+            max_depth: 64,
+            max_nodes: 10_000,
+        }
+    }
+}
+
+impl DirectoryLayer {
+    /// Returns the tree of the directories under `root_path`, with the default `TreeLimits`.
+    ///
+    /// Partitions are descended into transparently, their node is marked with `partition`.
+    pub async fn tree(
+        &self,
+        trx: &Transaction,
+        root_path: &[String],
+    ) -> Result<DirectoryTree, DirectoryError> {
+        self.tree_with_limits(trx, root_path, TreeLimits::default())
+            .await
+    }
+
+    /// Returns the tree of the directories under `root_path`.
+    ///
+    /// Fails with `DirectoryError::TreeLimitExceeded` if the tree is deeper or larger than
+    /// allowed by `limits`. The whole tree is read in `trx`, which must stay below the
+    /// transaction time limit.
+    pub async fn tree_with_limits(
+        &self,
+        trx: &Transaction,
+        root_path: &[String],
+        limits: TreeLimits,
+    ) -> Result<DirectoryTree, DirectoryError> {
+        let (layer, partition) = if root_path.is_empty() {
+            (Vec::new(), false)
+        } else {
+            let dir = self.open(trx, root_path, None).await?;
+            let partition = is_partition(&dir);
+            (dir.get_layer().to_vec(), partition)
+        };
+        let name = root_path.last().cloned().unwrap_or_default();
+        let mut nodes = 1;
+        if nodes > limits.max_nodes {
+            return Err(DirectoryError::TreeLimitExceeded);
+        }
+        self.tree_node(
+            trx,
+            root_path.to_vec(),
+            name,
+            layer,
+            partition,
+            0,
+            &limits,
+            &mut nodes,
+        )
+        .await
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn tree_node<'a>(
+        &'a self,
+        trx: &'a Transaction,
+        path: Vec<String>,
+        name: String,
+        layer: Vec<u8>,
+        partition: bool,
+        depth: usize,
+        limits: &'a TreeLimits,
+        nodes: &'a mut usize,
+    ) -> BoxFuture<'a, Result<DirectoryTree, DirectoryError>> {
+        async move {
+            let names = self.list(trx, &path).await?;
+            if !names.is_empty() && depth >= limits.max_depth {
+                return Err(DirectoryError::TreeLimitExceeded);
+            }
+            *nodes += names.len();
+            if *nodes > limits.max_nodes {
+                return Err(DirectoryError::TreeLimitExceeded);
+            }
+
+            let mut children = Vec::with_capacity(names.len());
+            for name in names {
+                let mut child_path = path.clone();
+                child_path.push(name.clone());
+                let child = self.open(trx, &child_path, None).await?;
+                let child_partition = is_partition(&child);
+                let child_layer = child.get_layer().to_vec();
+                children.push(
+                    self.tree_node(
+                        trx,
+                        child_path,
+                        name,
+                        child_layer,
+                        child_partition,
+                        depth + 1,
+                        limits,
+                        nodes,
+                    )
+                    .await?,
+                );
+            }
+
+            Ok(DirectoryTree {
+                name,
+                layer,
+                partition,
+                children,
+            })
+        }
+        .boxed()
+    }
+}
+
+fn is_partition(dir: &DirectoryOutput) -> bool {
+    match dir {
+        DirectoryOutput::DirectoryPartition(_) => true,
+        DirectoryOutput::DirectorySubspace(_) => false,
+    }
+}
+
+#[cfg(feature = "serde")]
+mod serde_impl {
+    use serde::ser::{Serialize, SerializeStruct, Serializer};
+
+    use super::DirectoryTree;
+    use crate::tuple::Bytes;
+
+    impl Serialize for DirectoryTree {
+        fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            let mut state = serializer.serialize_struct("DirectoryTree", 4)?;
+            state.serialize_field("name", &self.name)?;
+            state.serialize_field("layer", &Bytes::from(self.layer.as_slice()))?;
+            state.serialize_field("partition", &self.partition)?;
+            state.serialize_field("children", &self.children)?;
+            state.end()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn leaf(name: &str) -> DirectoryTree {
+        DirectoryTree {
+            name: name.to_string(),
+            layer: Vec::new(),
+            partition: false,
+            children: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_len() {
+        let mut root = leaf("");
+        assert_eq!(root.len(), 1);
+        let mut a = leaf("a");
+        a.children.push(leaf("b"));
+        a.children.push(leaf("c"));
+        root.children.push(a);
+        root.children.push(leaf("d"));
+        assert_eq!(root.len(), 5);
+    }
+}
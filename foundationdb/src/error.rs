@@ -8,10 +8,16 @@
 
 //! Error types for the Fdb crate
 
+use std::borrow::Cow;
 use std::ffi::CStr;
 use std::fmt;
+use std::io;
 
+use crate::directory::DirectoryError;
 use crate::options;
+use crate::tuple::hca::HcaError;
+use crate::tuple::PackError;
+use crate::TransactError;
 use foundationdb_sys as fdb_sys;
 
 pub(crate) fn eval(error_code: fdb_sys::fdb_error_t) -> FdbResult<()> {
@@ -23,20 +29,66 @@ pub(crate) fn eval(error_code: fdb_sys::fdb_error_t) -> FdbResult<()> {
     }
 }
 
+/// `tag_throttled` error code
+const TAG_THROTTLED: i32 = 1213;
+/// `timed_out` error code
+const TIMED_OUT: i32 = 1004;
+/// `transaction_timed_out` error code
+const TRANSACTION_TIMED_OUT: i32 = 1031;
+/// `transaction_too_large` error code
+const TRANSACTION_TOO_LARGE: i32 = 2101;
+
 /// The Standard Error type of FoundationDB
-#[derive(Debug, Copy, Clone)]
+#[derive(Debug, Clone)]
 pub struct FdbError {
     /// The FoundationDB error code
     error_code: i32,
+    /// What the failed operation was doing, boxed to keep the error small
+    context: Option<Box<Cow<'static, str>>>,
+    /// Set when the size guard of `TransactOption::max_transaction_size` tripped
+    too_large: Option<Box<TransactionTooLarge>>,
 }
 
 impl FdbError {
     /// Converts from a raw foundationDB error code
     pub fn from_code(error_code: fdb_sys::fdb_error_t) -> Self {
-        Self { error_code }
+        Self {
+            error_code,
+            context: None,
+            too_large: None,
+        }
+    }
+
+    /// The `transaction_too_large` (2101) error of the size guard of
+    /// `TransactOption::max_transaction_size`.
+    pub(crate) fn from_size_guard(size: usize, limit: usize) -> Self {
+        let mut err = Self::from_code(TRANSACTION_TOO_LARGE).with_context(format!(
+            "{} bytes of mutations are above max_transaction_size {}",
+            size, limit
+        ));
+        err.too_large = Some(Box::new(TransactionTooLarge { size, limit }));
+        err
+    }
+
+    /// Adds a description of the failed operation, displayed before the message of the error as
+    /// `context: message (code)`.
+    ///
+    /// If the error already has a context, the new one is prepended to it.
+    pub fn with_context(mut self, ctx: impl Into<Cow<'static, str>>) -> Self {
+        let ctx = match self.context.take() {
+            Some(inner) => Cow::Owned(format!("{}: {}", ctx.into(), inner)),
+            None => ctx.into(),
+        };
+        self.context = Some(Box::new(ctx));
+        self
+    }
+
+    /// The context added by `with_context`, if any
+    pub fn context(&self) -> Option<&str> {
+        self.context.as_deref().map(|ctx| ctx.as_ref())
     }
 
-    pub fn message(self) -> &'static str {
+    pub fn message(&self) -> &'static str {
         let error_str =
             unsafe { CStr::from_ptr::<'static>(fdb_sys::fdb_get_error(self.error_code)) };
         error_str
@@ -44,7 +96,7 @@ impl FdbError {
             .expect("bad error string from FoundationDB")
     }
 
-    fn is_error_predicate(self, predicate: options::ErrorPredicate) -> bool {
+    fn is_error_predicate(&self, predicate: options::ErrorPredicate) -> bool {
         let check =
             unsafe { fdb_sys::fdb_error_predicate(predicate.code() as i32, self.error_code) };
 
@@ -52,33 +104,234 @@ impl FdbError {
     }
 
     /// Indicates the transaction may have succeeded, though not in a way the system can verify.
-    pub fn is_maybe_committed(self) -> bool {
+    pub fn is_maybe_committed(&self) -> bool {
         self.is_error_predicate(options::ErrorPredicate::MaybeCommitted)
     }
 
     /// Indicates the operations in the transactions should be retried because of transient error.
-    pub fn is_retryable(self) -> bool {
+    pub fn is_retryable(&self) -> bool {
         self.is_error_predicate(options::ErrorPredicate::Retryable)
     }
 
     /// Indicates the transaction has not committed, though in a way that can be retried.
-    pub fn is_retryable_not_committed(self) -> bool {
+    pub fn is_retryable_not_committed(&self) -> bool {
         self.is_error_predicate(options::ErrorPredicate::RetryableNotCommitted)
     }
 
+    /// Indicates the transaction was throttled because one of its tags is throttled, see
+    /// `Transaction::set_tag`.
+    ///
+    /// This error is retryable, `Transaction::on_error` waits for the throttling to end.
+    pub fn is_tag_throttled(&self) -> bool {
+        self.error_code == TAG_THROTTLED
+    }
+
+    /// Returns the sizes that tripped the size guard of `TransactOption::max_transaction_size`,
+    /// if this `transaction_too_large` (2101) error was raised by the bindings before committing.
+    ///
+    /// This is `None` for the `transaction_too_large` errors returned by the cluster on commit.
+    pub fn transaction_too_large(&self) -> Option<&TransactionTooLarge> {
+        self.too_large.as_deref()
+    }
+
     /// Raw foundationdb error code
-    pub fn code(self) -> i32 {
+    pub fn code(&self) -> i32 {
         self.error_code
     }
 }
 
+/// A transaction found above `TransactOption::max_transaction_size` before being committed, see
+/// `FdbError::transaction_too_large`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TransactionTooLarge {
+    /// The bytes of mutations of the transaction, see `Transaction::estimated_mutation_bytes`
+    pub size: usize,
+    /// The value of `TransactOption::max_transaction_size`
+    pub limit: usize,
+}
+
 impl fmt::Display for FdbError {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        self.message().fmt(f)
+        match self.context() {
+            Some(ctx) => write!(f, "{}: {} ({})", ctx, self.message(), self.error_code),
+            None => self.message().fmt(f),
+        }
     }
 }
 
 impl std::error::Error for FdbError {}
 
+/// Timeouts are converted to `ErrorKind::TimedOut`, the other retryable errors to
+/// `ErrorKind::Interrupted`, and the remaining ones to `ErrorKind::Other`.
+impl From<FdbError> for io::Error {
+    fn from(err: FdbError) -> Self {
+        let kind = match err.code() {
+            TIMED_OUT | TRANSACTION_TIMED_OUT => io::ErrorKind::TimedOut,
+            _ if err.is_retryable() => io::ErrorKind::Interrupted,
+            _ => io::ErrorKind::Other,
+        };
+        io::Error::new(kind, err)
+    }
+}
+
 /// Alias for `Result<..., FdbError>`
 pub type FdbResult<T> = Result<T, FdbError>;
+
+/// An error from any part of this crate
+///
+/// Applications using several layers can use this type as their single error type. Errors
+/// wrapping an `FdbError` are flattened into `FdbBindingError::FdbError`, so that `transact`
+/// can retry them.
+pub enum FdbBindingError {
+    FdbError(FdbError),
+    DirectoryError(DirectoryError),
+    PackError(PackError),
+    HcaError(HcaError),
+}
+
+impl fmt::Display for FdbBindingError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            FdbBindingError::FdbError(err) => err.fmt(f),
+            FdbBindingError::DirectoryError(err) => err.fmt(f),
+            FdbBindingError::PackError(err) => err.fmt(f),
+            FdbBindingError::HcaError(err) => err.fmt(f),
+        }
+    }
+}
+
+impl fmt::Debug for FdbBindingError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            FdbBindingError::FdbError(err) => err.fmt(f),
+            FdbBindingError::DirectoryError(err) => err.fmt(f),
+            FdbBindingError::PackError(err) => err.fmt(f),
+            FdbBindingError::HcaError(err) => err.fmt(f),
+        }
+    }
+}
+
+impl std::error::Error for FdbBindingError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            FdbBindingError::FdbError(err) => Some(err),
+            FdbBindingError::DirectoryError(err) => Some(err),
+            FdbBindingError::PackError(err) => Some(err),
+            FdbBindingError::HcaError(err) => Some(err),
+        }
+    }
+}
+
+impl From<FdbError> for FdbBindingError {
+    fn from(err: FdbError) -> Self {
+        FdbBindingError::FdbError(err)
+    }
+}
+
+impl From<DirectoryError> for FdbBindingError {
+    fn from(err: DirectoryError) -> Self {
+        match err {
+            DirectoryError::FdbError(err) => FdbBindingError::FdbError(err),
+            err => FdbBindingError::DirectoryError(err),
+        }
+    }
+}
+
+impl From<PackError> for FdbBindingError {
+    fn from(err: PackError) -> Self {
+        FdbBindingError::PackError(err)
+    }
+}
+
+impl From<HcaError> for FdbBindingError {
+    fn from(err: HcaError) -> Self {
+        match err {
+            HcaError::FdbError(err) => FdbBindingError::FdbError(err),
+            HcaError::PackError(err) => FdbBindingError::PackError(err),
+            err => FdbBindingError::HcaError(err),
+        }
+    }
+}
+
+impl TransactError for FdbBindingError {
+    fn try_into_fdb_error(self) -> Result<FdbError, Self> {
+        match self {
+            FdbBindingError::FdbError(err) => Ok(err),
+            _ => Err(self),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::error::Error;
+
+    #[test]
+    fn test_binding_error() {
+        let err = FdbBindingError::from(DirectoryError::FdbError(FdbError::from_code(1020)));
+        match err.try_into_fdb_error() {
+            Ok(err) => assert_eq!(err.code(), 1020),
+            Err(err) => panic!("expected an FdbError, got {:?}", err),
+        }
+
+        let err = FdbBindingError::from(DirectoryError::DirAlreadyExists);
+        assert_eq!(err.to_string(), "the directory already exists");
+        let source = err.source().expect("missing source");
+        assert!(source.downcast_ref::<DirectoryError>().is_some());
+
+        let err = FdbBindingError::from(HcaError::PackError(PackError::BadPrefix));
+        assert_eq!(err.to_string(), "bad prefix");
+        assert!(err.source().unwrap().downcast_ref::<PackError>().is_some());
+    }
+
+    #[test]
+    fn test_context() {
+        let err = FdbError::from_code(1020);
+        assert_eq!(err.context(), None);
+        assert_eq!(
+            err.to_string(),
+            "Transaction not committed due to conflict with another transaction"
+        );
+
+        let err = err.with_context("committing the user");
+        assert_eq!(err.code(), 1020);
+        assert_eq!(err.context(), Some("committing the user"));
+        assert_eq!(
+            err.to_string(),
+            "committing the user: Transaction not committed due to conflict with another \
+             transaction (1020)"
+        );
+
+        let err = err.with_context(format!("request {}", 42));
+        assert_eq!(err.context(), Some("request 42: committing the user"));
+
+        assert!(std::mem::size_of::<FdbError>() <= 2 * std::mem::size_of::<usize>());
+    }
+
+    #[test]
+    fn test_io_error() {
+        let err = io::Error::from(FdbError::from_code(1031).with_context("reading"));
+        assert_eq!(err.kind(), io::ErrorKind::TimedOut);
+        let inner = err.get_ref().expect("missing inner error");
+        let inner = inner.downcast_ref::<FdbError>().expect("not an FdbError");
+        assert_eq!(inner.code(), 1031);
+        assert_eq!(inner.context(), Some("reading"));
+
+        let err = io::Error::from(FdbError::from_code(1004));
+        assert_eq!(err.kind(), io::ErrorKind::TimedOut);
+
+        let err = io::Error::from(FdbError::from_code(1020));
+        assert_eq!(err.kind(), io::ErrorKind::Interrupted);
+
+        // key_outside_legal_range is not retryable
+        let err = io::Error::from(FdbError::from_code(2004));
+        assert_eq!(err.kind(), io::ErrorKind::Other);
+    }
+
+    #[test]
+    fn test_is_tag_throttled() {
+        assert!(FdbError::from_code(1213).is_tag_throttled());
+        assert!(!FdbError::from_code(1020).is_tag_throttled());
+    }
+}
@@ -22,14 +22,17 @@
 //! important for reducing the latency of transactions.
 //!
 
+use std::any::Any;
 use std::convert::TryFrom;
 use std::ffi::CStr;
 use std::fmt;
+use std::net::{IpAddr, SocketAddr};
 use std::ops::Deref;
 use std::os::raw::c_char;
 use std::pin::Pin;
 use std::ptr::NonNull;
 use std::rc::Rc;
+use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::Arc;
 
 use foundationdb_sys as fdb_sys;
@@ -38,10 +41,27 @@ use futures::task::{AtomicWaker, Context, Poll};
 
 use crate::{error, FdbError, FdbResult};
 
+/// The number of `FdbFutureHandle` alive, see `live_futures`
+static LIVE_FUTURES: AtomicUsize = AtomicUsize::new(0);
+
+/// Returns the number of FoundationDB C futures currently alive in this process.
+///
+/// This includes the pending futures and the ones owning a result, like `FdbSlice` or
+/// `FdbValues`. It is meant to check that futures are released, e.g. in tests: a C future and
+/// its memory are freed as soon as the Rust value owning it is dropped.
+pub fn live_futures() -> usize {
+    LIVE_FUTURES.load(Ordering::SeqCst)
+}
+
 /// An opaque type that represents a Future in the FoundationDB C API.
 pub(crate) struct FdbFutureHandle(NonNull<fdb_sys::FDBFuture>);
 
 impl FdbFutureHandle {
+    fn new(f: NonNull<fdb_sys::FDBFuture>) -> Self {
+        LIVE_FUTURES.fetch_add(1, Ordering::SeqCst);
+        FdbFutureHandle(f)
+    }
+
     pub const fn as_ptr(&self) -> *mut fdb_sys::FDBFuture {
         self.0.as_ptr()
     }
@@ -52,7 +72,12 @@ impl Drop for FdbFutureHandle {
     fn drop(&mut self) {
         // `fdb_future_destroy` cancels the future, so we don't need to call
         // `fdb_future_cancel` explicitly.
+        //
+        // The callback set by `FdbFuture::poll` may still run after this, e.g. on the network
+        // thread while it cancels the future, or never if the network is stopped. It only uses
+        // the reference to the waker it owns, so it doesn't need to be waited for.
         unsafe { fdb_sys::fdb_future_destroy(self.as_ptr()) }
+        LIVE_FUTURES.fetch_sub(1, Ordering::SeqCst);
     }
 }
 
@@ -72,7 +97,7 @@ where
 {
     pub(crate) fn new(f: *mut fdb_sys::FDBFuture) -> Self {
         Self {
-            f: Some(FdbFutureHandle(
+            f: Some(FdbFutureHandle::new(
                 NonNull::new(f).expect("FDBFuture to not be null"),
             )),
             waker: None,
@@ -99,14 +124,22 @@ where
             });
             waker.register(cx.waker());
             if register {
+                // the callback owns a reference to the waker, released when it runs, so that it
+                // can't outlive it even if it runs after this future is dropped
                 let network_waker: Arc<AtomicWaker> = waker.clone();
                 let network_waker_ptr = Arc::into_raw(network_waker);
-                unsafe {
+                let err = unsafe {
                     fdb_sys::fdb_future_set_callback(
                         f_ptr,
                         Some(fdb_future_callback),
                         network_waker_ptr as *mut _,
-                    );
+                    )
+                };
+                if let Err(err) = error::eval(err) {
+                    // the callback will never run, its reference is released here instead
+                    drop(unsafe { Arc::from_raw(network_waker_ptr) });
+                    self.waker = None;
+                    return Poll::Ready(Err(err));
                 }
             }
             Poll::Pending
@@ -121,6 +154,10 @@ where
 
 // The callback from fdb C API can be called from multiple threads. so this callback should be
 // thread-safe.
+//
+// It is called exactly once per successful `fdb_future_set_callback`, unless the network is
+// stopped before the future is ready, in which case its reference to the waker is leaked, which
+// is harmless.
 extern "C" fn fdb_future_callback(
     _f: *mut fdb_sys::FDBFuture,
     callback_parameter: *mut ::std::os::raw::c_void,
@@ -149,6 +186,23 @@ impl AsRef<[u8]> for FdbSlice {
         self.deref()
     }
 }
+impl fmt::Debug for FdbSlice {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        crate::tuple::Bytes::from(self.deref()).fmt(f)
+    }
+}
+
+impl FdbSlice {
+    /// Converts the slice into `FdbBytes` without copying it
+    pub fn into_bytes(self) -> FdbBytes {
+        let owner: Arc<dyn Any + Send + Sync> = Arc::new(self._f);
+        FdbBytes {
+            _owner: owner,
+            value: self.value,
+            len: self.len as usize,
+        }
+    }
+}
 
 impl TryFrom<FdbFutureHandle> for FdbSlice {
     type Error = FdbError;
@@ -183,6 +237,59 @@ impl TryFrom<FdbFutureHandle> for Option<FdbSlice> {
     }
 }
 
+/// Bytes owned by a foundationDB future that you can own
+///
+/// Unlike `FdbSlice`, multiple `FdbBytes` can share the same future, which is freed once all of
+/// them are dropped. This allows taking the keys and values out of `FdbValues` without copying
+/// them and without borrowing it.
+#[derive(Clone)]
+pub struct FdbBytes {
+    _owner: Arc<dyn Any + Send + Sync>,
+    value: *const u8,
+    len: usize,
+}
+unsafe impl Sync for FdbBytes {}
+unsafe impl Send for FdbBytes {}
+
+impl FdbBytes {
+    /// Safety: `slice` must stay valid until `owner` is dropped
+    unsafe fn new(owner: &Arc<dyn Any + Send + Sync>, slice: &[u8]) -> Self {
+        FdbBytes {
+            _owner: owner.clone(),
+            value: slice.as_ptr(),
+            len: slice.len(),
+        }
+    }
+}
+
+impl Deref for FdbBytes {
+    type Target = [u8];
+    fn deref(&self) -> &Self::Target {
+        unsafe { std::slice::from_raw_parts(self.value, self.len) }
+    }
+}
+impl AsRef<[u8]> for FdbBytes {
+    fn as_ref(&self) -> &[u8] {
+        self.deref()
+    }
+}
+impl<'a> From<&'a FdbBytes> for crate::tuple::Bytes<'a> {
+    fn from(bytes: &'a FdbBytes) -> Self {
+        crate::tuple::Bytes::from(bytes.deref())
+    }
+}
+impl PartialEq for FdbBytes {
+    fn eq(&self, other: &Self) -> bool {
+        self.deref() == other.deref()
+    }
+}
+impl Eq for FdbBytes {}
+impl fmt::Debug for FdbBytes {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        crate::tuple::Bytes::from(self.deref()).fmt(f)
+    }
+}
+
 /// A slice of addresses owned by a foundationDB future
 pub struct FdbAddresses {
     _f: FdbFutureHandle,
@@ -229,6 +336,15 @@ impl AsRef<[FdbAddress]> for FdbAddresses {
     }
 }
 
+impl FdbAddresses {
+    /// Parses the addresses, see `FdbAddress::parse`.
+    pub fn parsed(
+        &self,
+    ) -> impl Iterator<Item = Result<StorageServerAddress, AddressParseError>> + '_ {
+        self.iter().map(FdbAddress::parse)
+    }
+}
+
 /// An address owned by a foundationDB future
 ///
 /// Because the data it represent is owned by the future in FdbAddresses, you
@@ -252,6 +368,206 @@ impl AsRef<CStr> for FdbAddress {
     }
 }
 
+impl FdbAddress {
+    /// Parses the address, of the form `ip:port`, optionally followed by `:tls`.
+    pub fn parse(&self) -> Result<StorageServerAddress, AddressParseError> {
+        match self.to_str() {
+            Ok(address) => address.parse(),
+            Err(_) => Err(AddressParseError(self.to_string_lossy().into_owned())),
+        }
+    }
+}
+
+/// A slice of keys owned by a foundationDB future
+#[cfg(feature = "fdb-7_1")]
+pub struct FdbKeys {
+    _f: FdbFutureHandle,
+    keys: *const fdb_sys::FDBKey,
+    len: i32,
+}
+#[cfg(feature = "fdb-7_1")]
+unsafe impl Sync for FdbKeys {}
+#[cfg(feature = "fdb-7_1")]
+unsafe impl Send for FdbKeys {}
+
+#[cfg(feature = "fdb-7_1")]
+impl TryFrom<FdbFutureHandle> for FdbKeys {
+    type Error = FdbError;
+
+    fn try_from(f: FdbFutureHandle) -> FdbResult<Self> {
+        let mut keys = std::ptr::null();
+        let mut len = 0;
+
+        error::eval(unsafe { fdb_sys::fdb_future_get_key_array(f.as_ptr(), &mut keys, &mut len) })?;
+
+        Ok(FdbKeys { _f: f, keys, len })
+    }
+}
+
+/// Views the `len` keys at `keys` as a slice of `FdbKey`.
+///
+/// # Safety
+///
+/// `keys` must point to `len` keys, valid for `'a`.
+#[cfg(feature = "fdb-7_1")]
+unsafe fn key_slice<'a>(keys: *const fdb_sys::FDBKey, len: i32) -> &'a [FdbKey] {
+    assert_eq_size!(FdbKey, fdb_sys::FDBKey);
+    assert_eq_align!(FdbKey, fdb_sys::FDBKey);
+    if len == 0 {
+        // the pointer may be null for an empty array
+        return &[];
+    }
+    &*(std::slice::from_raw_parts(keys, len as usize) as *const [fdb_sys::FDBKey]
+        as *const [FdbKey])
+}
+
+#[cfg(feature = "fdb-7_1")]
+impl Deref for FdbKeys {
+    type Target = [FdbKey];
+
+    fn deref(&self) -> &Self::Target {
+        // safe because the keys are owned by the future
+        unsafe { key_slice(self.keys, self.len) }
+    }
+}
+#[cfg(feature = "fdb-7_1")]
+impl AsRef<[FdbKey]> for FdbKeys {
+    fn as_ref(&self) -> &[FdbKey] {
+        self.deref()
+    }
+}
+
+#[cfg(feature = "fdb-7_1")]
+impl<'a> IntoIterator for &'a FdbKeys {
+    type Item = &'a FdbKey;
+    type IntoIter = std::slice::Iter<'a, FdbKey>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.deref().iter()
+    }
+}
+
+#[cfg(feature = "fdb-7_1")]
+impl fmt::Debug for FdbKeys {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_list().entries(self.iter()).finish()
+    }
+}
+
+/// A key owned by a foundationDB future
+///
+/// Because the data it represent is owned by the future in FdbKeys, you
+/// can never own a FdbKey directly, you can only have references to it.
+/// This way, you can never obtain a lifetime greater than the lifetime of the
+/// slice that gave you access to it.
+#[cfg(feature = "fdb-7_1")]
+#[repr(transparent)]
+pub struct FdbKey(fdb_sys::FDBKey);
+
+#[cfg(feature = "fdb-7_1")]
+impl FdbKey {
+    /// key
+    pub fn key(&self) -> &[u8] {
+        unsafe { std::slice::from_raw_parts(self.0.key as *const u8, self.0.key_length as usize) }
+    }
+}
+
+#[cfg(feature = "fdb-7_1")]
+impl Deref for FdbKey {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        self.key()
+    }
+}
+#[cfg(feature = "fdb-7_1")]
+impl AsRef<[u8]> for FdbKey {
+    fn as_ref(&self) -> &[u8] {
+        self.key()
+    }
+}
+
+#[cfg(feature = "fdb-7_1")]
+impl PartialEq for FdbKey {
+    fn eq(&self, other: &Self) -> bool {
+        self.key() == other.key()
+    }
+}
+#[cfg(feature = "fdb-7_1")]
+impl Eq for FdbKey {}
+#[cfg(feature = "fdb-7_1")]
+impl fmt::Debug for FdbKey {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        crate::tuple::Bytes::from(self.key()).fmt(f)
+    }
+}
+
+/// The address of a storage server, as returned by `Transaction::get_addresses_for_key`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct StorageServerAddress {
+    pub ip: IpAddr,
+    pub port: u16,
+    /// `true` if the address has the `:tls` suffix
+    pub tls: bool,
+}
+
+impl StorageServerAddress {
+    /// Returns the IP address and the port of the server.
+    pub fn socket_addr(&self) -> SocketAddr {
+        SocketAddr::new(self.ip, self.port)
+    }
+}
+
+impl std::str::FromStr for StorageServerAddress {
+    type Err = AddressParseError;
+
+    fn from_str(address: &str) -> Result<Self, AddressParseError> {
+        let (rest, tls) = match address.rfind(':') {
+            Some(colon) if &address[colon + 1..] == "tls" => (&address[..colon], true),
+            _ => (address, false),
+        };
+        // IPv6 addresses are enclosed in brackets: `[addr]:port`
+        let addr = rest
+            .parse::<SocketAddr>()
+            .map_err(|_| AddressParseError(address.to_owned()))?;
+        Ok(StorageServerAddress {
+            ip: addr.ip(),
+            port: addr.port(),
+            tls,
+        })
+    }
+}
+
+impl fmt::Display for StorageServerAddress {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.socket_addr())?;
+        if self.tls {
+            write!(f, ":tls")?;
+        }
+        Ok(())
+    }
+}
+
+/// The error returned when the address of a storage server is not of the form `ip:port`,
+/// optionally followed by `:tls`, e.g. if a future version of FoundationDB changes it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AddressParseError(String);
+
+impl AddressParseError {
+    /// Returns the address that could not be parsed.
+    pub fn address(&self) -> &str {
+        &self.0
+    }
+}
+
+impl fmt::Display for AddressParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "invalid storage server address {:?}", self.0)
+    }
+}
+
+impl std::error::Error for AddressParseError {}
+
 /// An slice of keyvalues owned by a foundationDB future
 pub struct FdbValues {
     _f: FdbFutureHandle,
@@ -267,6 +583,26 @@ impl FdbValues {
     pub fn more(&self) -> bool {
         self.more
     }
+
+    /// Converts the keyvalues into pairs of `FdbBytes` without copying them
+    ///
+    /// All the pairs share the future, it is freed once all of them are dropped.
+    pub fn into_bytes_vec(self) -> Vec<(FdbBytes, FdbBytes)> {
+        let owner: Arc<dyn Any + Send + Sync> = Arc::new(self._f);
+        // safe because the keyvalues are owned by the future in `owner`
+        let keyvalues = unsafe {
+            std::slice::from_raw_parts(self.keyvalues as *const FdbKeyValue, self.len as usize)
+        };
+        keyvalues
+            .iter()
+            .map(|kv| unsafe {
+                (
+                    FdbBytes::new(&owner, kv.key()),
+                    FdbBytes::new(&owner, kv.value()),
+                )
+            })
+            .collect()
+    }
 }
 
 impl TryFrom<FdbFutureHandle> for FdbValues {
@@ -472,17 +808,167 @@ impl fmt::Debug for FdbKeyValue {
     }
 }
 
+/// Keyvalues copied out of `FdbValues`, that outlive the futures they were read from
+///
+/// The keys and values are appended to two contiguous buffers, so reading many small
+/// keyvalues needs a few allocations, and none once the buffer is reused after `clear`.
+#[derive(Clone, Default, PartialEq, Eq)]
+pub struct KeyValueBuffer {
+    keys: Vec<u8>,
+    values: Vec<u8>,
+    /// The end of each key in `keys` and of each value in `values`
+    ends: Vec<(usize, usize)>,
+}
+
+impl KeyValueBuffer {
+    /// Creates an empty buffer
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates an empty buffer with room for `len` keyvalues, and `bytes` bytes of keys and
+    /// of values
+    pub fn with_capacity(len: usize, bytes: usize) -> Self {
+        Self {
+            keys: Vec::with_capacity(bytes),
+            values: Vec::with_capacity(bytes),
+            ends: Vec::with_capacity(len),
+        }
+    }
+
+    /// The number of keyvalues in the buffer
+    pub fn len(&self) -> usize {
+        self.ends.len()
+    }
+
+    /// `true` if the buffer holds no keyvalue
+    pub fn is_empty(&self) -> bool {
+        self.ends.is_empty()
+    }
+
+    /// Removes all the keyvalues, keeping the allocated memory
+    pub fn clear(&mut self) {
+        self.keys.clear();
+        self.values.clear();
+        self.ends.clear();
+    }
+
+    /// Appends a copy of `key` and `value`
+    pub fn push(&mut self, key: &[u8], value: &[u8]) {
+        self.keys.extend_from_slice(key);
+        self.values.extend_from_slice(value);
+        self.ends.push((self.keys.len(), self.values.len()));
+    }
+
+    /// Appends a copy of every keyvalue of `values`
+    pub fn extend_from(&mut self, values: &[FdbKeyValue]) {
+        let (keys, bytes) = values.iter().fold((0, 0), |(k, v), kv| {
+            (k + kv.key().len(), v + kv.value().len())
+        });
+        self.keys.reserve(keys);
+        self.values.reserve(bytes);
+        self.ends.reserve(values.len());
+        for kv in values {
+            self.push(kv.key(), kv.value());
+        }
+    }
+
+    /// Returns the key and the value at `index`
+    pub fn get(&self, index: usize) -> Option<(&[u8], &[u8])> {
+        let (key_end, value_end) = *self.ends.get(index)?;
+        let (key_start, value_start) = match index {
+            0 => (0, 0),
+            _ => self.ends[index - 1],
+        };
+        Some((
+            &self.keys[key_start..key_end],
+            &self.values[value_start..value_end],
+        ))
+    }
+
+    /// Returns the last keyvalue, e.g. to continue a scan after it
+    pub fn last(&self) -> Option<(&[u8], &[u8])> {
+        self.get(self.len().checked_sub(1)?)
+    }
+
+    /// Iterates over the keys and values, in the order they were appended
+    pub fn iter(&self) -> KeyValueBufferIter {
+        KeyValueBufferIter {
+            buffer: self,
+            pos: 0,
+            end: self.len(),
+        }
+    }
+}
+
+impl fmt::Debug for KeyValueBuffer {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_list()
+            .entries(self.iter().map(|(key, value)| {
+                (
+                    crate::tuple::Bytes::from(key),
+                    crate::tuple::Bytes::from(value),
+                )
+            }))
+            .finish()
+    }
+}
+
+impl<'a> IntoIterator for &'a KeyValueBuffer {
+    type Item = (&'a [u8], &'a [u8]);
+    type IntoIter = KeyValueBufferIter<'a>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+/// An iterator over the keys and values of a `KeyValueBuffer`
+#[derive(Clone)]
+pub struct KeyValueBufferIter<'a> {
+    buffer: &'a KeyValueBuffer,
+    pos: usize,
+    end: usize,
+}
+impl<'a> Iterator for KeyValueBufferIter<'a> {
+    type Item = (&'a [u8], &'a [u8]);
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.pos < self.end {
+            self.pos += 1;
+            self.buffer.get(self.pos - 1)
+        } else {
+            None
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let rem = self.end - self.pos;
+        (rem, Some(rem))
+    }
+}
+impl<'a> ExactSizeIterator for KeyValueBufferIter<'a> {}
+impl<'a> DoubleEndedIterator for KeyValueBufferIter<'a> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        if self.pos < self.end {
+            self.end -= 1;
+            self.buffer.get(self.end)
+        } else {
+            None
+        }
+    }
+}
+
 impl TryFrom<FdbFutureHandle> for i64 {
     type Error = FdbError;
 
     fn try_from(f: FdbFutureHandle) -> FdbResult<Self> {
         let mut version: i64 = 0;
         error::eval(unsafe {
-            #[cfg(feature = "fdb-6_2")]
+            #[cfg(any(feature = "fdb-6_2", feature = "fdb-7_1"))]
             {
                 fdb_sys::fdb_future_get_int64(f.as_ptr(), &mut version)
             }
-            #[cfg(not(feature = "fdb-6_2"))]
+            #[cfg(not(any(feature = "fdb-6_2", feature = "fdb-7_1")))]
             {
                 fdb_sys::fdb_future_get_version(f.as_ptr(), &mut version)
             }
@@ -491,9 +977,169 @@ impl TryFrom<FdbFutureHandle> for i64 {
     }
 }
 
+#[cfg(feature = "fdb-7_1")]
+impl TryFrom<FdbFutureHandle> for u64 {
+    type Error = FdbError;
+
+    fn try_from(f: FdbFutureHandle) -> FdbResult<Self> {
+        let mut value: u64 = 0;
+        error::eval(unsafe { fdb_sys::fdb_future_get_uint64(f.as_ptr(), &mut value) })?;
+        Ok(value)
+    }
+}
+
 impl TryFrom<FdbFutureHandle> for () {
     type Error = FdbError;
     fn try_from(_f: FdbFutureHandle) -> FdbResult<Self> {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fdb_bytes_keep_alive() {
+        let data = Arc::new(b"hello world".to_vec());
+        let weak = Arc::downgrade(&data);
+        let owner: Arc<dyn Any + Send + Sync> = data.clone();
+        let (hello, world) = unsafe {
+            (
+                FdbBytes::new(&owner, &data[..5]),
+                FdbBytes::new(&owner, &data[6..]),
+            )
+        };
+        drop(owner);
+        drop(data);
+
+        assert_eq!(&*hello, b"hello");
+        assert_eq!(&*world, b"world");
+        assert_eq!(crate::tuple::Bytes::from(&world).as_ref(), b"world");
+
+        let hello_clone = hello.clone();
+        drop(hello);
+        drop(world);
+        assert!(weak.upgrade().is_some());
+        assert_eq!(&*hello_clone, b"hello");
+
+        drop(hello_clone);
+        assert!(weak.upgrade().is_none());
+    }
+
+    #[test]
+    fn test_key_value_buffer() {
+        let mut buffer = KeyValueBuffer::new();
+        assert!(buffer.is_empty());
+        assert_eq!(buffer.iter().next(), None);
+        assert_eq!(buffer.last(), None);
+
+        buffer.push(b"a", b"1");
+        buffer.push(b"", b"");
+        buffer.push(b"bc", b"234");
+        assert_eq!(buffer.len(), 3);
+        assert_eq!(buffer.get(1), Some((&b""[..], &b""[..])));
+        assert_eq!(buffer.get(3), None);
+        assert_eq!(buffer.last(), Some((&b"bc"[..], &b"234"[..])));
+
+        let pairs: Vec<(&[u8], &[u8])> = buffer.iter().collect();
+        assert_eq!(
+            pairs,
+            vec![
+                (&b"a"[..], &b"1"[..]),
+                (&b""[..], &b""[..]),
+                (&b"bc"[..], &b"234"[..])
+            ]
+        );
+        let mut iter = buffer.iter();
+        assert_eq!(iter.len(), 3);
+        assert_eq!(iter.next_back(), Some((&b"bc"[..], &b"234"[..])));
+        assert_eq!(iter.next(), Some((&b"a"[..], &b"1"[..])));
+        assert_eq!(iter.len(), 1);
+        assert_eq!(iter.next_back(), Some((&b""[..], &b""[..])));
+        assert_eq!(iter.next(), None);
+        assert_eq!(iter.next_back(), None);
+        assert_eq!(
+            format!("{:?}", buffer),
+            r#"[(b"a", b"1"), (b"", b""), (b"bc", b"234")]"#
+        );
+
+        let capacity = buffer.keys.capacity();
+        buffer.clear();
+        assert!(buffer.is_empty());
+        assert_eq!(buffer.keys.capacity(), capacity);
+        buffer.push(b"d", b"5");
+        assert_eq!(
+            buffer.iter().collect::<Vec<_>>(),
+            vec![(&b"d"[..], &b"5"[..])]
+        );
+    }
+
+    #[cfg(feature = "fdb-7_1")]
+    #[test]
+    fn test_key_slice() {
+        let data: [&[u8]; 3] = [b"a", b"", b"\x00\xffkey"];
+        let raw: Vec<fdb_sys::FDBKey> = data
+            .iter()
+            .map(|key| fdb_sys::FDBKey {
+                key: key.as_ptr(),
+                key_length: key.len() as i32,
+            })
+            .collect();
+
+        let keys = unsafe { key_slice(raw.as_ptr(), raw.len() as i32) };
+        assert_eq!(keys.len(), 3);
+        for (key, expected) in keys.iter().zip(&data) {
+            assert_eq!(key.key(), *expected);
+            assert_eq!(&**key, *expected);
+        }
+        assert_eq!(format!("{:?}", keys[2]), r#"b"\x00\xffkey""#);
+        assert_eq!(keys[0], keys[0]);
+        assert_ne!(keys[0], keys[1]);
+
+        let empty = unsafe { key_slice(std::ptr::null(), 0) };
+        assert!(empty.is_empty());
+    }
+
+    #[test]
+    fn test_parse_storage_server_address() {
+        fn addr(s: &str) -> Result<StorageServerAddress, AddressParseError> {
+            s.parse()
+        }
+
+        let v4 = addr("10.0.0.3:4500").unwrap();
+        assert_eq!(v4.ip, "10.0.0.3".parse::<IpAddr>().unwrap());
+        assert_eq!(v4.port, 4500);
+        assert!(!v4.tls);
+        assert_eq!(v4.to_string(), "10.0.0.3:4500");
+
+        let tls = addr("10.0.0.3:4500:tls").unwrap();
+        assert_eq!(tls.socket_addr(), v4.socket_addr());
+        assert!(tls.tls);
+        assert_eq!(tls.to_string(), "10.0.0.3:4500:tls");
+
+        let v6 = addr("[2001:db8::1]:4501:tls").unwrap();
+        assert_eq!(v6.ip, "2001:db8::1".parse::<IpAddr>().unwrap());
+        assert_eq!(v6.port, 4501);
+        assert!(v6.tls);
+        assert_eq!(v6.to_string(), "[2001:db8::1]:4501:tls");
+        assert_eq!(
+            addr("[::1]:4500").unwrap().ip,
+            "::1".parse::<IpAddr>().unwrap()
+        );
+
+        for invalid in &[
+            "",
+            "10.0.0.3",
+            "10.0.0.3:tls",
+            "10.0.0.3:65536",
+            "fdb-0.local:4500",
+            "2001:db8::1:4500",
+            "[2001:db8::1]:4500:ssl",
+            "10.0.0.3:4500:tls:tls",
+        ] {
+            let err = addr(invalid).unwrap_err();
+            assert_eq!(err.address(), *invalid);
+        }
+    }
+}
@@ -0,0 +1,200 @@
+// Copyright 2018 foundationdb-rs developers, https://github.com/Clikengo/foundationdb-rs/graphs/contributors
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! Helpers to compute keys from other keys, following the byte-wise ordering of the database
+//!
+//! ```
+//! use foundationdb::key;
+//!
+//! assert_eq!(key::key_after(b"a"), b"a\x00");
+//! assert_eq!(key::strinc(b"a\xff"), Some(b"b".to_vec()));
+//! assert_eq!(key::common_prefix(b"abc", b"abd"), b"ab");
+//! assert_eq!(key::key_between(b"abc", b"abz"), Some(b"abd".to_vec()));
+//! ```
+
+/// Returns the first key after `key`, `key` followed by a `\x00` byte.
+///
+/// No key is strictly between `key` and the returned key, so `[key, key_after(key))` selects
+/// `key` alone.
+pub fn key_after(key: &[u8]) -> Vec<u8> {
+    let mut after = Vec::with_capacity(key.len() + 1);
+    after.extend_from_slice(key);
+    after.push(0x00);
+    after
+}
+
+/// Returns the first key that does not start with `key`, i.e. `key` without its trailing
+/// `\xff` bytes, and with its last byte incremented.
+///
+/// Returns `None` if there is no such key, when `key` is empty or only made of `\xff` bytes.
+pub fn strinc(key: &[u8]) -> Option<Vec<u8>> {
+    let last = key.iter().rposition(|&b| b != 0xff)?;
+    let mut end = key[..=last].to_vec();
+    end[last] += 1;
+    Some(end)
+}
+
+/// Returns the longest common prefix of `a` and `b`.
+pub fn common_prefix<'a>(a: &'a [u8], b: &[u8]) -> &'a [u8] {
+    let len = a.iter().zip(b).take_while(|(a, b)| a == b).count();
+    &a[..len]
+}
+
+/// Returns a short key strictly between `a` and `b`, e.g. to split the range `[a, b)`.
+///
+/// The returned key is one of the shortest keys between `a` and `b`. Returns `None` if there is
+/// no such key, when `a >= b` or when `b` is `key_after(a)`.
+pub fn key_between(a: &[u8], b: &[u8]) -> Option<Vec<u8>> {
+    if a >= b {
+        return None;
+    }
+    let n = common_prefix(a, b).len();
+    if n == a.len() {
+        // `a` is a proper prefix of `b`, `a\x00` is the first key after it
+        return if b.len() == n + 1 && b[n] == 0x00 {
+            None
+        } else {
+            Some(key_after(a))
+        };
+    }
+
+    // a[n] < b[n]
+    if b[n] - a[n] >= 2 {
+        let mut key = b[..n].to_vec();
+        key.push(a[n] + 1);
+        Some(key)
+    } else if b.len() > n + 1 {
+        // a proper prefix of `b`, after `a`
+        Some(b[..=n].to_vec())
+    } else {
+        // the keys after `a` starting with `a[..=n]`
+        match a[n + 1..].iter().position(|&b| b != 0xff) {
+            Some(i) => {
+                let mut key = a[..=n + 1 + i].to_vec();
+                key[n + 1 + i] += 1;
+                Some(key)
+            }
+            None => Some(key_after(a)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::rngs::SmallRng;
+    use rand::{Rng, SeedableRng};
+
+    const ITERATIONS: usize = 10_000;
+
+    /// Short keys from a small alphabet, to get common prefixes and edge bytes often
+    fn random_key(rng: &mut SmallRng) -> Vec<u8> {
+        const BYTES: [u8; 6] = [0x00, 0x01, 0x7f, 0x80, 0xfe, 0xff];
+        let len = rng.gen_range(0, 6);
+        (0..len)
+            .map(|_| BYTES[rng.gen_range(0, BYTES.len())])
+            .collect()
+    }
+
+    #[test]
+    fn test_key_after() {
+        let mut rng = SmallRng::seed_from_u64(1);
+        for _ in 0..ITERATIONS {
+            let key = random_key(&mut rng);
+            let after = key_after(&key);
+            assert!(key < after);
+            assert_eq!(key_between(&key, &after), None);
+
+            let other = random_key(&mut rng);
+            assert!(other <= key || other >= after, "{:?} {:?}", key, other);
+        }
+    }
+
+    #[test]
+    fn test_strinc() {
+        assert_eq!(strinc(b""), None);
+        assert_eq!(strinc(b"\xff\xff"), None);
+        assert_eq!(strinc(b"a"), Some(b"b".to_vec()));
+        assert_eq!(strinc(b"a\xff\xff"), Some(b"b".to_vec()));
+        assert_eq!(strinc(b"\x00\xfe\xff"), Some(b"\x00\xff".to_vec()));
+
+        let mut rng = SmallRng::seed_from_u64(2);
+        for _ in 0..ITERATIONS {
+            let key = random_key(&mut rng);
+            let suffix = random_key(&mut rng);
+            let mut child = key.clone();
+            child.extend_from_slice(&suffix);
+            match strinc(&key) {
+                Some(end) => {
+                    assert!(key < end);
+                    assert!(!end.starts_with(&key));
+                    assert!(child < end, "{:?} {:?}", key, child);
+                    // `end` is the first key that does not start with `key`
+                    let other = random_key(&mut rng);
+                    assert!(
+                        other.starts_with(&key) || other < key || other >= end,
+                        "{:?} {:?}",
+                        key,
+                        other
+                    );
+                }
+                None => assert!(key.iter().all(|&b| b == 0xff)),
+            }
+        }
+    }
+
+    #[test]
+    fn test_common_prefix() {
+        assert_eq!(common_prefix(b"", b"abc"), b"");
+        assert_eq!(common_prefix(b"abc", b"abc"), b"abc");
+        assert_eq!(common_prefix(b"ab", b"abc"), b"ab");
+
+        let mut rng = SmallRng::seed_from_u64(3);
+        for _ in 0..ITERATIONS {
+            let a = random_key(&mut rng);
+            let b = random_key(&mut rng);
+            let prefix = common_prefix(&a, &b);
+            assert!(a.starts_with(prefix) && b.starts_with(prefix));
+            let n = prefix.len();
+            assert!(n == a.len() || n == b.len() || a[n] != b[n]);
+            assert_eq!(common_prefix(&b, &a), prefix);
+        }
+    }
+
+    #[test]
+    fn test_key_between() {
+        assert_eq!(key_between(b"b", b"a"), None);
+        assert_eq!(key_between(b"a", b"a"), None);
+        assert_eq!(key_between(b"a", b"a\x00"), None);
+        assert_eq!(key_between(b"a", b"a\x00\x00"), Some(b"a\x00".to_vec()));
+        assert_eq!(key_between(b"a", b"b"), Some(b"a\x00".to_vec()));
+        assert_eq!(key_between(b"a", b"c"), Some(b"b".to_vec()));
+        assert_eq!(
+            key_between(b"a\xff\xff", b"b"),
+            Some(b"a\xff\xff\x00".to_vec())
+        );
+        assert_eq!(key_between(b"a\xffz", b"b"), Some(b"a\xff{".to_vec()));
+        assert_eq!(key_between(b"abc", b"b"), Some(b"ac".to_vec()));
+
+        let mut rng = SmallRng::seed_from_u64(4);
+        for _ in 0..ITERATIONS {
+            let a = random_key(&mut rng);
+            let b = random_key(&mut rng);
+            match key_between(&a, &b) {
+                Some(key) => {
+                    assert!(a < key && key < b, "{:?} {:?} {:?}", a, key, b);
+                    // no shorter key is between `a` and `b`
+                    let other = random_key(&mut rng);
+                    if a < other && other < b {
+                        assert!(key.len() <= other.len(), "{:?} {:?} {:?}", a, key, b);
+                    }
+                }
+                None => assert!(a >= b || b == key_after(&a), "{:?} {:?}", a, b),
+            }
+        }
+    }
+}
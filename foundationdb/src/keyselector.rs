@@ -10,6 +10,7 @@
 
 use crate::tuple::Bytes;
 use std::borrow::Cow;
+use std::fmt;
 
 /// A `KeySelector` identifies a particular key in the database.
 ///
@@ -81,6 +82,20 @@ impl<'a> KeySelector<'a> {
         Self::new(key.into(), false, 1)
     }
 
+    /// Converts the selector into one owning its key, that can outlive the borrowed key
+    pub fn into_owned(self) -> KeySelector<'static> {
+        KeySelector::new(
+            Cow::Owned(self.key.into_owned()),
+            self.or_equal,
+            self.offset,
+        )
+    }
+
+    /// Returns a copy of the selector owning its key
+    pub fn to_owned(&self) -> KeySelector<'static> {
+        KeySelector::new(Cow::Owned(self.key().to_vec()), self.or_equal, self.offset)
+    }
+
     fn make_key(&mut self, key: &[u8]) {
         match &mut self.key {
             Bytes(Cow::Borrowed(..)) => self.key = Bytes::from(key.to_owned()),
@@ -102,4 +117,94 @@ impl<'a> KeySelector<'a> {
         self.or_equal = true;
         self.offset = 1;
     }
+
+    /// Returns the smallest key this selector can resolve to, if it doesn't depend on the keys
+    /// of the database, i.e. for `first_greater_or_equal` and `first_greater_than` selectors.
+    pub(crate) fn lower_bound(&self) -> Option<impl Iterator<Item = &u8> + '_> {
+        if self.offset != 1 {
+            return None;
+        }
+        // the first key greater than `key` is at least `key\x00`
+        let suffix = if self.or_equal { &b"\x00"[..] } else { &[] };
+        Some(self.key().iter().chain(suffix))
+    }
+}
+
+/// Formats the selector as the constructor it is built from, followed by the difference of its
+/// offset, e.g. `first_greater_or_equal(b"foo")+2`.
+impl<'a> fmt::Display for KeySelector<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let (name, delta) = match (self.or_equal, self.offset > 0) {
+            (false, true) => ("first_greater_or_equal", self.offset - 1),
+            (true, true) => ("first_greater_than", self.offset - 1),
+            (false, false) => ("last_less_than", self.offset),
+            (true, false) => ("last_less_or_equal", self.offset),
+        };
+        write!(f, "{}({})", name, self.key)?;
+        if delta != 0 {
+            write!(f, "{:+}", delta)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_display() {
+        let display = |selector: KeySelector| selector.to_string();
+        assert_eq!(
+            display(KeySelector::first_greater_or_equal(&b"foo"[..])),
+            r#"first_greater_or_equal(b"foo")"#
+        );
+        assert_eq!(
+            display(KeySelector::first_greater_than(&b"foo"[..])),
+            r#"first_greater_than(b"foo")"#
+        );
+        assert_eq!(
+            display(KeySelector::last_less_than(&b"\x00\xff"[..])),
+            r#"last_less_than(b"\x00\xff")"#
+        );
+        assert_eq!(
+            display(KeySelector::last_less_or_equal(&b""[..])),
+            r#"last_less_or_equal(b"")"#
+        );
+        assert_eq!(
+            display(KeySelector::new(Cow::Borrowed(b"foo"), false, 3)),
+            r#"first_greater_or_equal(b"foo")+2"#
+        );
+        assert_eq!(
+            display(KeySelector::new(Cow::Borrowed(b"foo"), true, -2)),
+            r#"last_less_or_equal(b"foo")-2"#
+        );
+        assert_eq!(
+            display(KeySelector::new(Cow::Borrowed(b"foo"), false, 0)),
+            r#"last_less_than(b"foo")"#
+        );
+    }
+
+    // returning the selector checks that it doesn't borrow the dropped key
+    fn owned_selector() -> KeySelector<'static> {
+        let key = b"foo".to_vec();
+        KeySelector::first_greater_than(key.as_slice()).into_owned()
+    }
+
+    #[test]
+    fn test_into_owned() {
+        let selector = owned_selector();
+        assert_eq!(selector.key(), b"foo");
+        assert!(selector.or_equal());
+        assert_eq!(selector.offset(), 1);
+
+        let key = b"bar".to_vec();
+        let borrowed = KeySelector::new(Cow::Borrowed(&key), false, -1);
+        let owned: KeySelector<'static> = borrowed.to_owned();
+        drop(borrowed);
+        drop(key);
+        assert_eq!(owned.key(), b"bar");
+        assert!(!owned.or_equal());
+        assert_eq!(owned.offset(), -1);
+    }
 }
@@ -0,0 +1,291 @@
+// Copyright 2018 foundationdb-rs developers, https://github.com/Clikengo/foundationdb-rs/graphs/contributors
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! Binary large objects, stored as a sequence of chunks.
+//!
+//! Values are limited to 100KB and transactions to 10MB. `BlobSubspace` splits each blob in
+//! chunks of `CHUNK_SIZE` bytes, and writes blobs that don't fit in a single transaction with
+//! multiple transactions.
+//!
+//! The blob subspace contains, for each blob `key`:
+//!
+//! - `(key, 0)`: the metadata of the blob, a packed `(generation, len, chunk_count)`,
+//! - `(key, 1, generation, chunk_index)`: a chunk of the blob.
+//!
+//! Each write stores its chunks under a new generation. The metadata is replaced, and the chunks
+//! of the previous generation are cleared, by the last transaction of the write. A reader gets
+//! the metadata and the chunks with the same transaction, so it sees either the previous or the
+//! new blob, never a mix of both.
+
+use futures::prelude::*;
+use futures::stream::BoxStream;
+
+use super::LayerError;
+use crate::future::FdbValues;
+use crate::tuple::{pack, unpack, Subspace};
+use crate::{Database, FdbResult, TransactOption, Transaction};
+
+/// The size of the chunks blobs are split into.
+pub const CHUNK_SIZE: usize = 90_000;
+
+/// The number of chunks written by each transaction of a write spanning multiple transactions.
+const CHUNKS_PER_TRANSACTION: usize = 64;
+
+const METADATA: i64 = 0;
+const CHUNKS: i64 = 1;
+
+#[derive(Debug, Clone, Copy)]
+struct Metadata {
+    generation: u64,
+    len: u64,
+    chunk_count: u64,
+}
+
+/// Blobs stored in a subspace, identified by a key.
+#[derive(Debug, Clone)]
+pub struct BlobSubspace {
+    subspace: Subspace,
+}
+
+impl BlobSubspace {
+    /// Creates the blob store of the given subspace.
+    ///
+    /// The subspace should not be used by anything else than this blob store.
+    pub fn new(subspace: Subspace) -> Self {
+        Self { subspace }
+    }
+
+    /// Returns the subspace of this blob store.
+    pub fn subspace(&self) -> &Subspace {
+        &self.subspace
+    }
+
+    /// Writes `bytes` as the blob of `key`, replacing the previous blob if any.
+    ///
+    /// Blobs that don't fit in a single transaction are written by multiple transactions, the
+    /// new blob only becomes visible when the last one commits. If the write fails, the previous
+    /// blob is left untouched.
+    pub async fn write(&self, db: &Database, key: &[u8], bytes: &[u8]) -> Result<(), LayerError> {
+        let chunks: Vec<&[u8]> = bytes.chunks(CHUNK_SIZE).collect();
+        let metadata = Metadata {
+            generation: rand::random(),
+            len: bytes.len() as u64,
+            chunk_count: chunks.len() as u64,
+        };
+
+        if chunks.len() <= CHUNKS_PER_TRANSACTION {
+            return db
+                .transact_boxed(
+                    (self, key, chunks.as_slice()),
+                    move |trx, (blob, key, chunks)| {
+                        blob.write_chunks(trx, key, metadata.generation, 0, chunks);
+                        blob.commit_generation(trx, key, metadata).boxed()
+                    },
+                    TransactOption::default(),
+                )
+                .await;
+        }
+
+        for (batch, batch_chunks) in chunks.chunks(CHUNKS_PER_TRANSACTION).enumerate() {
+            let first_index = (batch * CHUNKS_PER_TRANSACTION) as u64;
+            db.transact_boxed(
+                (self, key, batch_chunks),
+                move |trx, (blob, key, chunks)| {
+                    blob.write_chunks(trx, key, metadata.generation, first_index, chunks);
+                    future::ok::<(), LayerError>(()).boxed()
+                },
+                // chunks of an unpublished generation can be written again
+                TransactOption::idempotent(),
+            )
+            .await?;
+        }
+
+        db.transact_boxed(
+            (self, key),
+            move |trx, (blob, key)| blob.commit_generation(trx, key, metadata).boxed(),
+            TransactOption::default(),
+        )
+        .await
+    }
+
+    /// Writes `bytes` as the blob of `key` with the given transaction.
+    ///
+    /// The whole blob must fit in the transaction, use `write` for larger blobs.
+    pub async fn write_trx(
+        &self,
+        trx: &Transaction,
+        key: &[u8],
+        bytes: &[u8],
+    ) -> Result<(), LayerError> {
+        let chunks: Vec<&[u8]> = bytes.chunks(CHUNK_SIZE).collect();
+        let metadata = Metadata {
+            generation: rand::random(),
+            len: bytes.len() as u64,
+            chunk_count: chunks.len() as u64,
+        };
+        self.write_chunks(trx, key, metadata.generation, 0, &chunks);
+        self.commit_generation(trx, key, metadata).await
+    }
+
+    /// Reads the blob of `key`, chunk by chunk.
+    ///
+    /// The stream is empty if there is no blob for `key`. All the chunks are read by `trx`, so
+    /// the blob must be read within the lifetime of a transaction.
+    pub fn read<'a>(
+        &'a self,
+        trx: &'a Transaction,
+        key: &'a [u8],
+        snapshot: bool,
+    ) -> impl Stream<Item = Result<Vec<u8>, LayerError>> + Send + 'a {
+        stream::once(self.metadata(trx, key, snapshot))
+            .map_ok(move |metadata| match metadata {
+                Some(metadata) => self.read_chunks(trx, key, metadata, snapshot),
+                None => stream::empty().boxed(),
+            })
+            .try_flatten()
+    }
+
+    /// Returns the length in bytes of the blob of `key`, or `None` if there is no blob for `key`.
+    pub async fn len(
+        &self,
+        trx: &Transaction,
+        key: &[u8],
+        snapshot: bool,
+    ) -> Result<Option<u64>, LayerError> {
+        Ok(self
+            .metadata(trx, key, snapshot)
+            .await?
+            .map(|metadata| metadata.len))
+    }
+
+    /// Deletes the blob of `key`, including the chunks left by failed writes.
+    pub fn delete(&self, trx: &Transaction, key: &[u8]) {
+        trx.clear_subspace_range(&self.subspace.subspace(&(key,)));
+    }
+
+    fn metadata_key(&self, key: &[u8]) -> Vec<u8> {
+        self.subspace.pack(&(key, METADATA))
+    }
+
+    fn chunks_subspace(&self, key: &[u8], generation: u64) -> Subspace {
+        self.subspace.subspace(&(key, CHUNKS, generation))
+    }
+
+    async fn metadata(
+        &self,
+        trx: &Transaction,
+        key: &[u8],
+        snapshot: bool,
+    ) -> Result<Option<Metadata>, LayerError> {
+        match trx.get(&self.metadata_key(key), snapshot).await? {
+            Some(value) => {
+                let (generation, len, chunk_count) = unpack(&value)?;
+                Ok(Some(Metadata {
+                    generation,
+                    len,
+                    chunk_count,
+                }))
+            }
+            None => Ok(None),
+        }
+    }
+
+    fn write_chunks(
+        &self,
+        trx: &Transaction,
+        key: &[u8],
+        generation: u64,
+        first_index: u64,
+        chunks: &[&[u8]],
+    ) {
+        let subspace = self.chunks_subspace(key, generation);
+        for (index, chunk) in (first_index..).zip(chunks) {
+            trx.set(&subspace.pack(&index), chunk);
+        }
+    }
+
+    /// Makes `metadata` the current version of the blob, and clears the chunks of the
+    /// previous version.
+    async fn commit_generation(
+        &self,
+        trx: &Transaction,
+        key: &[u8],
+        metadata: Metadata,
+    ) -> Result<(), LayerError> {
+        if let Some(previous) = self.metadata(trx, key, false).await? {
+            if previous.generation != metadata.generation {
+                trx.clear_subspace_range(&self.chunks_subspace(key, previous.generation));
+            }
+        }
+
+        let value = pack(&(metadata.generation, metadata.len, metadata.chunk_count));
+        trx.set(&self.metadata_key(key), &value);
+        Ok(())
+    }
+
+    fn read_chunks<'a>(
+        &'a self,
+        trx: &'a Transaction,
+        key: &[u8],
+        metadata: Metadata,
+        snapshot: bool,
+    ) -> BoxStream<'a, Result<Vec<u8>, LayerError>> {
+        let chunks = ChunkReader {
+            batches: trx
+                .get_ranges(
+                    self.chunks_subspace(key, metadata.generation)
+                        .range_option(),
+                    snapshot,
+                )
+                .boxed(),
+            subspace: self.chunks_subspace(key, metadata.generation),
+            next_index: 0,
+            metadata,
+        };
+
+        stream::unfold(Some(chunks), |chunks| async move {
+            let mut chunks = chunks?;
+            match chunks.next_batch().await {
+                Ok(Some(batch)) => Some((Ok(batch), Some(chunks))),
+                Ok(None) => None,
+                Err(err) => Some((Err(err), None)),
+            }
+        })
+        .map_ok(|batch| stream::iter(batch.into_iter().map(Ok)))
+        .try_flatten()
+        .boxed()
+    }
+}
+
+struct ChunkReader<'a> {
+    batches: BoxStream<'a, FdbResult<FdbValues>>,
+    subspace: Subspace,
+    next_index: u64,
+    metadata: Metadata,
+}
+
+impl<'a> ChunkReader<'a> {
+    /// Returns the chunks of the next batch, checking that no chunk is missing.
+    async fn next_batch(&mut self) -> Result<Option<Vec<Vec<u8>>>, LayerError> {
+        let values = match self.batches.try_next().await? {
+            Some(values) => values,
+            None if self.next_index == self.metadata.chunk_count => return Ok(None),
+            None => return Err(LayerError::CorruptedData),
+        };
+
+        let mut batch = Vec::with_capacity(values.len());
+        for kv in values.iter() {
+            let index: u64 = self.subspace.unpack(kv.key())?;
+            if index != self.next_index || index >= self.metadata.chunk_count {
+                return Err(LayerError::CorruptedData);
+            }
+            self.next_index += 1;
+            batch.push(kv.value().to_vec());
+        }
+        Ok(Some(batch))
+    }
+}
@@ -0,0 +1,160 @@
+// Copyright 2018 foundationdb-rs developers, https://github.com/Clikengo/foundationdb-rs/graphs/contributors
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! A record stored as one key per field.
+//!
+//! Storing a large record in a single value means that any two transactions modifying this
+//! record conflict, even if they modify unrelated fields. `FieldedValue` stores each field of
+//! the record under its own key, so that conflicts only happen between transactions touching
+//! the same fields.
+//!
+//! The record subspace contains:
+//!
+//! - `(0, field_name)`: the value of a field,
+//! - `(1,)`: the compacted fields, a packed list of `(field_name, value)`.
+//!
+//! A field stored under its own key takes precedence over the compacted value of this field.
+
+use std::collections::BTreeMap;
+
+use futures::prelude::*;
+
+use super::LayerError;
+use crate::tuple::{pack, unpack, Subspace};
+use crate::Transaction;
+
+const FIELDS: i64 = 0;
+const COMPACTED: i64 = 1;
+
+/// A logical record whose fields are stored under distinct keys of a record subspace.
+#[derive(Debug, Clone)]
+pub struct FieldedValue {
+    fields: Subspace,
+    compacted_key: Vec<u8>,
+    subspace: Subspace,
+}
+
+impl FieldedValue {
+    /// Creates the record stored in the given subspace.
+    ///
+    /// The subspace should not be used by anything else than this record.
+    pub fn new(subspace: Subspace) -> Self {
+        Self {
+            fields: subspace.subspace(&FIELDS),
+            compacted_key: subspace.pack(&(COMPACTED,)),
+            subspace,
+        }
+    }
+
+    /// Returns the subspace of this record.
+    pub fn subspace(&self) -> &Subspace {
+        &self.subspace
+    }
+
+    /// Reads the whole record.
+    ///
+    /// This adds a read conflict range on the whole record, use `read_field` to only conflict
+    /// with the writers of a given field.
+    pub async fn read_all(
+        &self,
+        trx: &Transaction,
+        snapshot: bool,
+    ) -> Result<BTreeMap<String, Vec<u8>>, LayerError> {
+        let record = BTreeMap::new();
+        trx.get_subspace_ranges(&self.subspace, snapshot)
+            .map_err(LayerError::from)
+            .try_fold(record, |mut record, values| {
+                let r = values.iter().try_for_each(|kv| {
+                    if kv.key() == self.compacted_key.as_slice() {
+                        // fields stored under their own key take precedence
+                        for (name, value) in self.unpack_compacted(kv.value())? {
+                            record.entry(name).or_insert(value);
+                        }
+                    } else {
+                        let name: String = self.fields.unpack(kv.key())?;
+                        record.insert(name, kv.value().to_vec());
+                    }
+                    Ok(())
+                });
+                future::ready(r.map(|()| record))
+            })
+            .await
+    }
+
+    /// Reads a single field of the record.
+    ///
+    /// This only conflicts with the writers of this field, and with compactions.
+    pub async fn read_field(
+        &self,
+        trx: &Transaction,
+        name: &str,
+        snapshot: bool,
+    ) -> Result<Option<Vec<u8>>, LayerError> {
+        if let Some(value) = trx.get(&self.fields.pack(&name), snapshot).await? {
+            return Ok(Some(value.to_vec()));
+        }
+
+        match trx.get(&self.compacted_key, snapshot).await? {
+            Some(compacted) => Ok(self
+                .unpack_compacted(&compacted)?
+                .into_iter()
+                .find(|(field, _)| field == name)
+                .map(|(_, value)| value)),
+            None => Ok(None),
+        }
+    }
+
+    /// Writes the given fields of the record.
+    ///
+    /// Only the keys of the given fields are modified, so the other fields of the record, and
+    /// the transactions reading them, are not affected.
+    pub fn write_fields<I, N, V>(&self, trx: &Transaction, changed_fields: I)
+    where
+        I: IntoIterator<Item = (N, V)>,
+        N: AsRef<str>,
+        V: AsRef<[u8]>,
+    {
+        for (name, value) in changed_fields {
+            trx.set(&self.fields.pack(&name.as_ref()), value.as_ref());
+        }
+    }
+
+    /// Merges the given fields into the compacted value of the record.
+    ///
+    /// Fields that rarely change can be compacted into a single key to reduce the number of keys
+    /// read by `read_all`. Compacting conflicts with any reader and writer of the compacted
+    /// fields.
+    pub async fn compact(&self, trx: &Transaction, names: &[&str]) -> Result<(), LayerError> {
+        let mut compacted = match trx.get(&self.compacted_key, false).await? {
+            Some(compacted) => self.unpack_compacted(&compacted)?,
+            None => Vec::new(),
+        };
+
+        for name in names {
+            let key = self.fields.pack(name);
+            if let Some(value) = trx.get(&key, false).await? {
+                match compacted.iter_mut().find(|(field, _)| field == name) {
+                    Some((_, compacted_value)) => *compacted_value = value.to_vec(),
+                    None => compacted.push((name.to_string(), value.to_vec())),
+                }
+                trx.clear(&key);
+            }
+        }
+
+        trx.set(&self.compacted_key, &pack(&compacted));
+        Ok(())
+    }
+
+    /// Clears the whole record.
+    pub fn clear(&self, trx: &Transaction) {
+        trx.clear_subspace_range(&self.subspace);
+    }
+
+    fn unpack_compacted(&self, value: &[u8]) -> Result<Vec<(String, Vec<u8>)>, LayerError> {
+        Ok(unpack(value)?)
+    }
+}
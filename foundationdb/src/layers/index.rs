@@ -0,0 +1,107 @@
+// Copyright 2018 foundationdb-rs developers, https://github.com/Clikengo/foundationdb-rs/graphs/contributors
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! A secondary index, mapping values to the primary keys of the records holding them.
+//!
+//! The index doesn't know how records are stored: the caller updates it in the transaction
+//! that writes a record, giving the indexed value before and after the write, so that the index
+//! stays consistent with the records.
+//!
+//! The index subspace contains one key per record, `value + primary_key`, the concatenation of
+//! the packed value and of the packed primary key. Its value is the packed primary key, so that
+//! the entries of a value prefix can be scanned without knowing the length of the values.
+
+use futures::prelude::*;
+
+use crate::tuple::{Subspace, TuplePack};
+use crate::{FdbResult, RangeOption, Transaction};
+
+/// A secondary index stored in a subspace.
+#[derive(Debug, Clone)]
+pub struct Index {
+    subspace: Subspace,
+}
+
+impl Index {
+    /// Creates the index stored in the given subspace.
+    ///
+    /// The subspace should not be used by anything else than this index.
+    pub fn new(subspace: Subspace) -> Self {
+        Self { subspace }
+    }
+
+    /// Returns the subspace of this index.
+    pub fn subspace(&self) -> &Subspace {
+        &self.subspace
+    }
+
+    /// Updates the entry of the record `pk` after its indexed value changed from `old_value` to
+    /// `new_value`, `None` meaning that the record didn't exist or was deleted.
+    ///
+    /// The entry of the old value is cleared and the one of the new value is set, in `trx`, the
+    /// transaction writing the record. Nothing is written if the values are equal.
+    pub fn update<P, V>(
+        &self,
+        trx: &Transaction,
+        pk: &P,
+        old_value: Option<&V>,
+        new_value: Option<&V>,
+    ) where
+        P: TuplePack,
+        V: TuplePack,
+    {
+        let old_key = old_value.map(|value| self.entry_key(pk, value));
+        let new_key = new_value.map(|value| self.entry_key(pk, value));
+        if old_key == new_key {
+            return;
+        }
+        if let Some(old_key) = old_key {
+            trx.clear(&old_key);
+        }
+        if let Some(new_key) = new_key {
+            trx.set(&new_key, &pk.pack_to_vec());
+        }
+    }
+
+    /// Returns the packed primary keys of the records whose indexed value starts with the
+    /// elements of `value_prefix`, ordered by value then by primary key.
+    ///
+    /// Passing the whole value returns the records holding exactly this value.
+    pub fn scan<'a, V>(
+        &self,
+        trx: &'a Transaction,
+        value_prefix: &V,
+        snapshot: bool,
+    ) -> impl Stream<Item = FdbResult<Vec<u8>>> + Send + 'a
+    where
+        V: TuplePack,
+    {
+        let opt = RangeOption::from(self.subspace.subspace(value_prefix).range());
+        trx.get_ranges(opt, snapshot)
+            .map_ok(|values| {
+                let pks: Vec<FdbResult<Vec<u8>>> =
+                    values.iter().map(|kv| Ok(kv.value().to_vec())).collect();
+                stream::iter(pks)
+            })
+            .try_flatten()
+    }
+
+    /// Clears the whole index.
+    pub fn clear(&self, trx: &Transaction) {
+        trx.clear_subspace_range(&self.subspace);
+    }
+
+    fn entry_key<P, V>(&self, pk: &P, value: &V) -> Vec<u8>
+    where
+        P: TuplePack,
+        V: TuplePack,
+    {
+        let mut key = self.subspace.pack(value);
+        pk.pack_into_vec(&mut key);
+        key
+    }
+}
@@ -0,0 +1,66 @@
+// Copyright 2018 foundationdb-rs developers, https://github.com/Clikengo/foundationdb-rs/graphs/contributors
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! Layers are data models built on top of the key-value store, using only the primitives
+//! offered by transactions and the tuple layer.
+
+#[cfg(feature = "blob")]
+mod blob;
+mod fielded_value;
+mod index;
+#[cfg(feature = "queue")]
+mod queue;
+
+use std::fmt;
+
+use crate::tuple::PackError;
+use crate::{FdbError, TransactError};
+
+#[cfg(feature = "blob")]
+pub use blob::{BlobSubspace, CHUNK_SIZE};
+pub use fielded_value::FieldedValue;
+pub use index::Index;
+#[cfg(feature = "queue")]
+pub use queue::Queue;
+
+/// The errors that can be returned by the layers of this module
+pub enum LayerError {
+    FdbError(FdbError),
+    PackError(PackError),
+    /// The data read by a layer doesn't match what it wrote.
+    CorruptedData,
+}
+
+impl fmt::Debug for LayerError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            LayerError::FdbError(err) => err.fmt(f),
+            LayerError::PackError(err) => err.fmt(f),
+            LayerError::CorruptedData => write!(f, "CorruptedData"),
+        }
+    }
+}
+
+impl From<FdbError> for LayerError {
+    fn from(err: FdbError) -> Self {
+        Self::FdbError(err)
+    }
+}
+impl From<PackError> for LayerError {
+    fn from(err: PackError) -> Self {
+        Self::PackError(err)
+    }
+}
+
+impl TransactError for LayerError {
+    fn try_into_fdb_error(self) -> Result<FdbError, Self> {
+        match self {
+            LayerError::FdbError(err) => Ok(err),
+            _ => Err(self),
+        }
+    }
+}
@@ -0,0 +1,136 @@
+// Copyright 2018 foundationdb-rs developers, https://github.com/Clikengo/foundationdb-rs/graphs/contributors
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! A FIFO queue whose producers never conflict.
+//!
+//! Items are stored under `(versionstamp, random)` keys written with `SetVersionstampedKey`: the
+//! key of an item is only known once its transaction commits, so concurrent pushes write
+//! distinct keys without reading anything, and items are ordered by commit version.
+//!
+//! Consumers read the first item at snapshot isolation and only add a conflict range on this
+//! item, so that a pop conflicts with the concurrent pops of the same item, but not with pushes
+//! or with pops of other items.
+
+use std::sync::atomic::{AtomicU16, Ordering};
+use std::sync::Arc;
+
+use futures::prelude::*;
+
+use crate::options::{ConflictRangeType, StreamingMode};
+use crate::tuple::{Subspace, Versionstamp};
+use crate::{Database, FdbResult, RangeOption, TransactOption, Transaction};
+
+/// A FIFO queue stored in a subspace.
+#[derive(Debug, Clone)]
+pub struct Queue {
+    subspace: Subspace,
+    // orders the items pushed by this queue in the same transaction
+    user_version: Arc<AtomicU16>,
+}
+
+impl Queue {
+    /// Creates the queue stored in the given subspace.
+    ///
+    /// The subspace should not be used by anything else than this queue.
+    pub fn new(subspace: Subspace) -> Self {
+        Self {
+            subspace,
+            user_version: Arc::new(AtomicU16::new(0)),
+        }
+    }
+
+    /// Returns the subspace of this queue.
+    pub fn subspace(&self) -> &Subspace {
+        &self.subspace
+    }
+
+    /// Pushes `value` at the end of the queue.
+    ///
+    /// This doesn't read anything, so concurrent pushes never conflict. The position of the item
+    /// is the commit version of `trx`.
+    pub fn push(&self, trx: &Transaction, value: &[u8]) {
+        let user_version = self.user_version.fetch_add(1, Ordering::Relaxed);
+        // different `Queue` instances may push to the same transaction
+        let id: u64 = rand::random();
+        trx.set_versionstamped_key(
+            &self.subspace,
+            &(Versionstamp::incomplete(user_version), id),
+            value,
+        );
+    }
+
+    /// Removes the first item of the queue in its own transaction, retrying on conflicts.
+    ///
+    /// Returns `None` if the queue is empty.
+    pub async fn pop(&self, db: &Database) -> FdbResult<Option<Vec<u8>>> {
+        db.transact_boxed(
+            self,
+            |trx, queue| queue.pop_in(trx).boxed(),
+            TransactOption::default(),
+        )
+        .await
+    }
+
+    /// Removes the first item of the queue in `trx`.
+    ///
+    /// The item is read at snapshot isolation, only a conflict range on the item itself is added,
+    /// so `trx` only conflicts with the transactions popping the same item.
+    pub async fn pop_in(&self, trx: &Transaction) -> FdbResult<Option<Vec<u8>>> {
+        let (key, value) = match self.first(trx, true).await? {
+            Some(item) => item,
+            None => return Ok(None),
+        };
+        let mut end = key.clone();
+        end.push(0);
+        trx.add_conflict_range(&key, &end, ConflictRangeType::Read)?;
+        trx.clear(&key);
+        Ok(Some(value))
+    }
+
+    /// Returns the first item of the queue without removing it.
+    pub async fn peek(&self, trx: &Transaction, snapshot: bool) -> FdbResult<Option<Vec<u8>>> {
+        Ok(self.first(trx, snapshot).await?.map(|(_, value)| value))
+    }
+
+    /// Returns the number of items in the queue.
+    ///
+    /// This reads the whole queue.
+    pub async fn len(&self, trx: &Transaction, snapshot: bool) -> FdbResult<usize> {
+        let opt = RangeOption {
+            mode: StreamingMode::WantAll,
+            ..RangeOption::from(&self.subspace)
+        };
+        trx.get_ranges(opt, snapshot)
+            .try_fold(0, |len, values| future::ok(len + values.len()))
+            .await
+    }
+
+    /// Returns `true` if the queue has no items.
+    pub async fn is_empty(&self, trx: &Transaction, snapshot: bool) -> FdbResult<bool> {
+        Ok(self.first(trx, snapshot).await?.is_none())
+    }
+
+    /// Removes all the items of the queue.
+    pub fn clear(&self, trx: &Transaction) {
+        trx.clear_subspace_range(&self.subspace);
+    }
+
+    async fn first(
+        &self,
+        trx: &Transaction,
+        snapshot: bool,
+    ) -> FdbResult<Option<(Vec<u8>, Vec<u8>)>> {
+        let opt = RangeOption {
+            limit: Some(1),
+            ..RangeOption::from(&self.subspace)
+        };
+        let values = trx.get_range(&opt, 1, snapshot).await?;
+        Ok(values
+            .first()
+            .map(|kv| (kv.key().to_vec(), kv.value().to_vec())))
+    }
+}
@@ -45,7 +45,7 @@
 //!
 //! ## Initialization
 //!
-//! Due to limitations in the C API, the Client and it's associated Network can only be initialized and run once per the life of a process. Generally the `foundationdb::init` function will be enough to initialize the Client. See `foundationdb::default_api` and `foundationdb::builder` for more configuration options of the Fdb Client.
+//! Due to limitations in the C API, the Client and it's associated Network can only be initialized and run once per the life of a process. Generally the `foundationdb::run` and `foundationdb::run_async` functions will be enough to initialize the Client, they stop the Network once they return. `foundationdb::boot` and `foundationdb::api` are available for more configuration options of the Fdb Client.
 //!
 //! ## Example
 //!
@@ -70,22 +70,16 @@
 //!     Ok(())
 //! }
 //!
-//! // Safe because drop is called before the program exits
-//! let network = unsafe { foundationdb::boot() };
-//! futures::executor::block_on(async_main()).expect("failed to run");
-//! drop(network);
+//! // the network is stopped once the closure returns
+//! foundationdb::run(|| futures::executor::block_on(async_main())).expect("failed to run");
 //! ```
 //!
 //! ```rust
-//! #[tokio::main]
-//! async fn main() {
-//!     // Safe because drop is called before the program exits
-//!     let network = unsafe { foundationdb::boot() };
-//!
-//!     // Have fun with the FDB API
-//!
-//!     // shutdown the client
-//!     drop(network);
+//! fn main() {
+//!     // the network is stopped once the future completes
+//!     foundationdb::run_async(async {
+//!         // Have fun with the FDB API
+//!     });
 //! }
 //! ```
 //!
@@ -95,31 +89,67 @@
 
 #[macro_use]
 extern crate static_assertions;
+#[cfg(feature = "metrics")]
+#[macro_use]
+extern crate lazy_static;
 
+#[cfg(feature = "fdb-7_1")]
+mod admin;
 pub mod api;
+mod bulk;
 #[cfg(any(feature = "fdb-5_1", feature = "fdb-5_2", feature = "fdb-6_0"))]
 pub mod cluster;
+pub mod cluster_file;
 mod database;
+pub mod directory;
 mod error;
 pub mod future;
+pub mod key;
 mod keyselector;
+pub mod layers;
+#[cfg(feature = "metrics")]
+mod metrics;
+mod ops;
 /// Generated configuration types for use with the various `set_option` functions
-#[allow(clippy::all)]
 pub mod options;
+mod redact;
+mod scan;
+pub mod shadow;
+pub mod special_keys;
+#[cfg(feature = "fdb-7_1")]
+mod tenant;
+pub mod testing;
+mod timer;
+mod trace;
 mod transaction;
 pub mod tuple;
+mod watch;
 
 #[cfg(any(feature = "fdb-5_1", feature = "fdb-5_2", feature = "fdb-6_0"))]
 pub use crate::cluster::Cluster;
 
+pub use crate::bulk::{BulkOptions, BulkStats};
 pub use crate::database::*;
+pub use crate::error::FdbBindingError;
 pub use crate::error::FdbError;
 pub use crate::error::FdbResult;
+pub use crate::error::TransactionTooLarge;
 pub use crate::keyselector::*;
+#[cfg(feature = "metrics")]
+pub use crate::metrics::{clear_instrumentation, set_instrumentation, Instrumentation};
+pub use crate::ops::{FdbRead, FdbWrite};
+pub use crate::redact::{is_key_redaction_enabled, set_key_redaction, RedactedBytes};
+pub use crate::scan::{PartialScan, ScanBatch};
+#[cfg(feature = "fdb-7_1")]
+pub use crate::tenant::*;
 pub use crate::transaction::*;
+pub use crate::watch::RangeChange;
 
 /// Initialize the FoundationDB Client API, this can only be called once per process.
 ///
+/// `run` and `run_async` are the safe alternatives, this function is only needed when the
+/// lifetime of the network can't be tied to a scope.
+///
 /// # Returns
 ///
 /// A `NetworkAutoStop` handle which must be dropped before the program exits.
@@ -132,6 +162,10 @@ pub use crate::transaction::*;
 /// This method used to be safe in version `0.4`. But because `drop` on the returned object
 /// might not be called before the program exits, it was found unsafe.
 ///
+/// # Panics
+///
+/// Panics if the network was already booted in this process, even if it was stopped since.
+///
 /// # Examples
 ///
 /// ```rust
@@ -149,13 +183,84 @@ pub use crate::transaction::*;
 /// }
 /// ```
 pub unsafe fn boot() -> api::NetworkAutoStop {
+    if api::is_network_running() {
+        panic!("foundationdb::boot() called twice, the FoundationDB network is already running");
+    }
+    if api::is_network_setup() {
+        panic!("foundationdb::boot() called after the FoundationDB network was stopped, it cannot be restarted");
+    }
     let network_builder = api::FdbApiBuilder::default()
         .build()
         .expect("foundationdb API to be initialized");
     network_builder.boot().expect("fdb network running")
 }
 
+/// Boots the FoundationDB network, runs `f` and stops the network, even if `f` panics.
+///
+/// As the network can only be started once per process, `run` can only be called once, and
+/// `boot`, `run` and `run_async` panic if they are called after it.
+///
+/// The network is not stopped if `f` exits the process without unwinding, e.g. with
+/// `std::process::exit`.
+///
+/// # Panics
+///
+/// Panics if the network was already booted in this process, or if it can't be stopped.
+///
+/// # Examples
+///
+/// ```rust
+/// let value = foundationdb::run(|| {
+///     futures::executor::block_on(async {
+///         let db = foundationdb::Database::default()?;
+///         let trx = db.create_trx()?;
+///         let value = trx.get(b"hello", false).await?;
+///         Ok::<_, foundationdb::FdbError>(value.map(|value| value.to_vec()))
+///     })
+/// });
+/// ```
+pub fn run<F, T>(f: F) -> T
+where
+    F: FnOnce() -> T,
+{
+    // Safe because the network is stopped when it is dropped, on return or on unwind
+    let network = unsafe { boot() };
+    let result = f();
+    network.stop().expect("failed to stop network");
+    result
+}
+
+/// Boots the FoundationDB network, drives `future` to completion on the current thread and stops
+/// the network, even if `future` panics, see `run`.
+///
+/// The future is run by a blocking executor, it can't rely on the reactor of another runtime
+/// and this function must not be called from an asynchronous context.
+///
+/// # Panics
+///
+/// Panics if the network was already booted in this process, or if it can't be stopped.
+///
+/// # Examples
+///
+/// ```rust
+/// let result = foundationdb::run_async(async {
+///     let db = foundationdb::Database::default()?;
+///     let trx = db.create_trx()?;
+///     trx.set(b"hello", b"world");
+///     trx.commit().await?;
+///     Ok::<_, foundationdb::FdbError>(())
+/// });
+/// ```
+pub fn run_async<F>(future: F) -> F::Output
+where
+    F: std::future::Future,
+{
+    run(|| futures::executor::block_on(future))
+}
+
 /// Returns the default Fdb cluster configuration file path
+///
+/// See `cluster_file::resolve` for the path actually used when none is given.
 #[cfg(target_os = "linux")]
 pub fn default_config_path() -> &'static str {
     "/etc/foundationdb/fdb.cluster"
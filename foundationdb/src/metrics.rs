@@ -0,0 +1,101 @@
+// Copyright 2018 foundationdb-rs developers, https://github.com/Clikengo/foundationdb-rs/graphs/contributors
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! Hooks reporting the usage of the database, to feed metrics
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, Instant};
+
+use futures::future::Either;
+use futures::prelude::*;
+
+use crate::FdbError;
+
+/// Receives the operations done by transactions, e.g. to export them as metrics.
+///
+/// All methods do nothing by default. They are called synchronously by the network thread or
+/// by the thread using the transaction, so they should return quickly.
+pub trait Instrumentation: Send + Sync {
+    /// A `Transaction::get` completed after `duration`, reading a value of `bytes` bytes.
+    fn on_get(&self, _duration: Duration, _bytes: usize) {}
+
+    /// A `Transaction::get_range` completed after `duration`, reading `rows` key-value pairs of
+    /// `bytes` bytes.
+    fn on_get_range(&self, _duration: Duration, _rows: usize, _bytes: usize) {}
+
+    /// A `Transaction::commit` completed after `duration`.
+    fn on_commit(&self, _duration: Duration, _result: Result<(), &FdbError>) {}
+
+    /// `Database::transact` is retrying after an error with the given code.
+    fn on_retry(&self, _code: i32) {}
+
+    /// A commit of `Database::transact` failed with `not_committed` (1020) because of the
+    /// `(begin, end)` ranges `ranges`, see `TransactOption::report_conflicting_keys`.
+    ///
+    /// Only called with the `fdb-7_1` feature, before `on_retry`.
+    fn on_conflict(&self, _ranges: &[(Vec<u8>, Vec<u8>)]) {}
+
+    /// A `Transaction::set` or `Transaction::atomic_op` wrote `bytes` bytes of keys and values.
+    fn on_write(&self, _bytes: usize) {}
+}
+
+static GLOBAL_IS_SET: AtomicBool = AtomicBool::new(false);
+
+lazy_static! {
+    static ref GLOBAL: RwLock<Option<Arc<dyn Instrumentation>>> = RwLock::new(None);
+}
+
+/// Sets the instrumentation of the transactions of every database, unless the database has its
+/// own instrumentation (see `Database::set_instrumentation`).
+///
+/// Only the transactions created after this call are instrumented.
+pub fn set_instrumentation(instrumentation: Arc<dyn Instrumentation>) {
+    *GLOBAL.write().expect("instrumentation lock poisoned") = Some(instrumentation);
+    GLOBAL_IS_SET.store(true, Ordering::Release);
+}
+
+/// Removes the instrumentation set by `set_instrumentation`.
+pub fn clear_instrumentation() {
+    GLOBAL_IS_SET.store(false, Ordering::Release);
+    *GLOBAL.write().expect("instrumentation lock poisoned") = None;
+}
+
+/// Returns the instrumentation set by `set_instrumentation`, without locking if there is none.
+pub(crate) fn global_instrumentation() -> Option<Arc<dyn Instrumentation>> {
+    if !GLOBAL_IS_SET.load(Ordering::Acquire) {
+        return None;
+    }
+    GLOBAL
+        .read()
+        .expect("instrumentation lock poisoned")
+        .clone()
+}
+
+/// Calls `report` with the duration and the output of `fut` once it completes.
+///
+/// `fut` is returned as is if there is no instrumentation.
+pub(crate) fn timed<F>(
+    instrumentation: &Option<Arc<dyn Instrumentation>>,
+    fut: F,
+    report: fn(&dyn Instrumentation, Duration, &F::Output),
+) -> impl Future<Output = F::Output> + Send + Sync + Unpin
+where
+    F: Future + Send + Sync + Unpin,
+{
+    match instrumentation {
+        None => Either::Left(fut),
+        Some(instrumentation) => {
+            let instrumentation = instrumentation.clone();
+            let start = Instant::now();
+            Either::Right(fut.map(move |output| {
+                report(&*instrumentation, start.elapsed(), &output);
+                output
+            }))
+        }
+    }
+}
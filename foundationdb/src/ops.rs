@@ -0,0 +1,95 @@
+// Copyright 2018 foundationdb-rs developers, https://github.com/Clikengo/foundationdb-rs/graphs/contributors
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! Read and write operations shared by `Transaction` and the in-memory mock
+//!
+//! Layers written against `FdbRead` and `FdbWrite` instead of `Transaction` can be unit tested
+//! with `testing::MockDatabase` (feature `mock`), without a running cluster.
+
+use async_trait::async_trait;
+use futures::prelude::*;
+
+use crate::options::MutationType;
+use crate::{FdbResult, RangeOption, Transaction};
+
+/// The reads of a transaction.
+///
+/// Values are returned as owned bytes so that implementations don't need to hold buffers
+/// allocated by the client library.
+#[async_trait]
+pub trait FdbRead {
+    /// Reads the value associated with `key`, see `Transaction::get`.
+    async fn get(&self, key: &[u8], snapshot: bool) -> FdbResult<Option<Vec<u8>>>;
+
+    /// Reads every key-value pair of the range, in the order given by `opt.reverse` and up to
+    /// `opt.limit` pairs, see `Transaction::get_ranges`.
+    async fn read_range(
+        &self,
+        opt: &RangeOption<'_>,
+        snapshot: bool,
+    ) -> FdbResult<Vec<(Vec<u8>, Vec<u8>)>>;
+}
+
+/// The writes of a transaction.
+///
+/// Writes are only visible to other transactions once the transaction is committed.
+pub trait FdbWrite {
+    /// Sets the value of `key`, see `Transaction::set`.
+    fn set(&self, key: &[u8], value: &[u8]);
+
+    /// Clears `key`, see `Transaction::clear`.
+    fn clear(&self, key: &[u8]);
+
+    /// Clears the keys of the range `[begin, end)`, see `Transaction::clear_range`.
+    fn clear_range(&self, begin: &[u8], end: &[u8]);
+
+    /// Applies an atomic operation to `key`, see `Transaction::atomic_op`.
+    fn atomic_op(&self, key: &[u8], param: &[u8], op_type: MutationType);
+}
+
+#[async_trait]
+impl FdbRead for Transaction {
+    async fn get(&self, key: &[u8], snapshot: bool) -> FdbResult<Option<Vec<u8>>> {
+        let value = Transaction::get(self, key, snapshot).await?;
+        Ok(value.map(|value| value.to_vec()))
+    }
+
+    async fn read_range(
+        &self,
+        opt: &RangeOption<'_>,
+        snapshot: bool,
+    ) -> FdbResult<Vec<(Vec<u8>, Vec<u8>)>> {
+        self.get_ranges(opt.clone(), snapshot)
+            .try_fold(Vec::new(), |mut kvs, values| {
+                kvs.extend(
+                    values
+                        .iter()
+                        .map(|kv| (kv.key().to_vec(), kv.value().to_vec())),
+                );
+                future::ok(kvs)
+            })
+            .await
+    }
+}
+
+impl FdbWrite for Transaction {
+    fn set(&self, key: &[u8], value: &[u8]) {
+        Transaction::set(self, key, value)
+    }
+
+    fn clear(&self, key: &[u8]) {
+        Transaction::clear(self, key)
+    }
+
+    fn clear_range(&self, begin: &[u8], end: &[u8]) {
+        Transaction::clear_range(self, begin, end)
+    }
+
+    fn atomic_op(&self, key: &[u8], param: &[u8], op_type: MutationType) {
+        Transaction::atomic_op(self, key, param, op_type)
+    }
+}
@@ -1 +1,5 @@
+// The generated options mark the options deprecated by FoundationDB, which are still used by the
+// generated implementations
+#![allow(deprecated)]
+
 include!(concat!(env!("OUT_DIR"), "/options.rs"));
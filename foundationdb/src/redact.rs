@@ -0,0 +1,105 @@
+// Copyright 2018 foundationdb-rs developers, https://github.com/Clikengo/foundationdb-rs/graphs/contributors
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! Redaction of keys and values in formatted output
+
+use std::fmt;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+static KEY_REDACTION: AtomicBool = AtomicBool::new(false);
+
+/// Enables or disables the redaction of keys and values formatted by this crate.
+///
+/// When enabled, `RedactedBytes` and `tuple::Bytes` print the length and a short hash of the
+/// bytes instead of their content, so that logs never contain the data of the database.
+/// Redaction is disabled by default.
+pub fn set_key_redaction(enabled: bool) {
+    KEY_REDACTION.store(enabled, Ordering::Relaxed);
+}
+
+/// Returns `true` if the redaction of keys and values is enabled.
+pub fn is_key_redaction_enabled() -> bool {
+    KEY_REDACTION.load(Ordering::Relaxed)
+}
+
+/// Formats a key or a value, honoring `set_key_redaction`.
+///
+/// Without redaction, bytes are printed as a byte string literal, e.g. `b"key\x00"`. With
+/// redaction, only the length and a 32 bits FNV-1a hash are printed, e.g. `b<1 bytes #e40c292c>`
+/// for `b"a"`. The hash doesn't depend on the process, so the same bytes can be
+/// matched across logs.
+#[derive(Clone, Copy)]
+pub struct RedactedBytes<'a>(pub &'a [u8]);
+
+impl<'a> fmt::Display for RedactedBytes<'a> {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        write_bytes(fmt, self.0, is_key_redaction_enabled())
+    }
+}
+
+impl<'a> fmt::Debug for RedactedBytes<'a> {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Display::fmt(self, fmt)
+    }
+}
+
+fn write_bytes<W: fmt::Write>(w: &mut W, bytes: &[u8], redacted: bool) -> fmt::Result {
+    if redacted {
+        return write!(w, "b<{} bytes #{:08x}>", bytes.len(), fnv1a(bytes));
+    }
+
+    write!(w, "b\"")?;
+    for &byte in bytes.iter() {
+        if byte == b'\\' {
+            write!(w, r"\\")?;
+        } else if byte.is_ascii_alphanumeric() {
+            write!(w, "{}", byte as char)?;
+        } else {
+            write!(w, "\\x{:02x}", byte)?;
+        }
+    }
+    write!(w, "\"")
+}
+
+fn fnv1a(bytes: &[u8]) -> u32 {
+    bytes.iter().fold(0x811c_9dc5, |hash, &byte| {
+        (hash ^ u32::from(byte)).wrapping_mul(0x0100_0193)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn format(bytes: &[u8], redacted: bool) -> String {
+        let mut s = String::new();
+        write_bytes(&mut s, bytes, redacted).unwrap();
+        s
+    }
+
+    #[test]
+    fn test_verbose() {
+        assert_eq!(format(b"key\x00\\\xff", false), r#"b"key\x00\\\xff""#);
+        assert_eq!(format(b"", false), r#"b"""#);
+    }
+
+    #[test]
+    fn test_redacted() {
+        assert_eq!(format(b"", true), "b<0 bytes #811c9dc5>");
+        assert_eq!(format(b"a", true), "b<1 bytes #e40c292c>");
+        assert_eq!(format(b"foobar", true), "b<6 bytes #bf9cf968>");
+        assert!(!format(b"secret", true).contains("secret"));
+    }
+
+    #[test]
+    fn test_default() {
+        // other tests rely on the verbose output, so they never enable redaction
+        assert!(!is_key_redaction_enabled());
+        assert_eq!(RedactedBytes(b"key").to_string(), r#"b"key""#);
+        assert_eq!(format!("{:?}", RedactedBytes(b"key")), r#"b"key""#);
+    }
+}
@@ -0,0 +1,148 @@
+// Copyright 2018 foundationdb-rs developers, https://github.com/Clikengo/foundationdb-rs/graphs/contributors
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! Range reads that stop before the 5 seconds limit of transactions, and scans of ranges too
+//! large to be read by a single transaction
+
+use std::time::{Duration, Instant};
+
+use futures::future::Either;
+use futures::prelude::*;
+
+use crate::future::{FdbKeyValue, FdbValues};
+use crate::{Database, FdbResult, RangeOption, Transaction};
+
+/// The time a transaction of `Database::scan_all` reads for, which leaves one second to the last
+/// batch before the 5 seconds limit of transactions.
+const SCAN_SOFT_DEADLINE: Duration = Duration::from_secs(4);
+
+/// An item of `Transaction::get_ranges_with_deadline`
+pub enum ScanBatch<'a> {
+    /// The next batch of the range, like the items of `Transaction::get_ranges`
+    Values(FdbValues),
+    /// The deadline was reached before the end of the range, this is the last item
+    Partial(PartialScan<'a>),
+}
+
+/// The part of a range left unread by `Transaction::get_ranges_with_deadline`
+#[derive(Debug, Clone)]
+pub struct PartialScan<'a> {
+    /// The rest of the range: the range read, starting right after the last key yielded, or
+    /// ending right before it for a reverse range, with the limit decreased by the number of
+    /// keys yielded
+    pub continued_from: RangeOption<'a>,
+}
+
+impl Transaction {
+    /// Reads the range like `get_ranges`, but stops before requesting a batch once
+    /// `soft_deadline` has passed, yielding the rest of the range as `ScanBatch::Partial`.
+    ///
+    /// Reading a range that takes longer than the 5 seconds limit of transactions fails with
+    /// `transaction_too_old` (1007), losing track of the keys read. With a deadline under this
+    /// limit, the rest of the range can be read by a new transaction instead, see
+    /// `Database::scan_all`. The first batch is always requested, whatever the deadline, and a
+    /// batch requested before the deadline can still complete after it.
+    pub fn get_ranges_with_deadline<'a>(
+        &'a self,
+        opt: RangeOption<'a>,
+        snapshot: bool,
+        soft_deadline: Instant,
+    ) -> impl Stream<Item = FdbResult<ScanBatch<'a>>> + Send + Sync + Unpin + 'a {
+        let opt = if opt.is_trivially_empty() {
+            None
+        } else {
+            Some(opt)
+        };
+        stream::unfold((1, opt), move |(iteration, maybe_opt)| match maybe_opt {
+            Some(opt) if iteration > 1 && Instant::now() >= soft_deadline => {
+                let partial = ScanBatch::Partial(PartialScan {
+                    continued_from: opt,
+                });
+                Either::Right(future::ready(Some((Ok(partial), (iteration, None)))))
+            }
+            Some(opt) => Either::Left(self.get_range(&opt, iteration, snapshot).map(
+                move |maybe_values| {
+                    let next_opt = match &maybe_values {
+                        Ok(values) => opt.next_range(values),
+                        Err(..) => None,
+                    };
+                    let item = maybe_values.map(ScanBatch::Values);
+                    Some((item, (iteration + 1, next_opt)))
+                },
+            )),
+            None => Either::Right(future::ready(None)),
+        })
+    }
+}
+
+impl Database {
+    /// Calls `f` with each key-value pair of the range, reading it with as many transactions as
+    /// needed.
+    ///
+    /// Each transaction reads the range for about 4 seconds with `get_ranges_with_deadline`,
+    /// then the next transaction reads the rest of the range. The reads are snapshot reads, and
+    /// the range is not read at a single version: each transaction sees the writes committed
+    /// before it started. The retryable errors are retried from the last key read, and no key
+    /// is given twice to `f`.
+    ///
+    /// Returns the number of transactions used.
+    pub async fn scan_all<F>(&self, opt: RangeOption<'_>, f: F) -> FdbResult<usize>
+    where
+        F: FnMut(&FdbKeyValue),
+    {
+        self.scan_all_within(opt, SCAN_SOFT_DEADLINE, f).await
+    }
+
+    /// Like `scan_all`, each transaction reading the range for about `per_transaction`.
+    pub async fn scan_all_within<F>(
+        &self,
+        opt: RangeOption<'_>,
+        per_transaction: Duration,
+        mut f: F,
+    ) -> FdbResult<usize>
+    where
+        F: FnMut(&FdbKeyValue),
+    {
+        let mut remaining = Some(opt.into_owned());
+        let mut transactions = 0;
+        let mut trx = self.create_trx()?;
+        while let Some(opt) = remaining.take() {
+            transactions += 1;
+            let soft_deadline = Instant::now() + per_transaction;
+            let mut batches = trx.get_ranges_with_deadline(opt.clone(), true, soft_deadline);
+            // the rest of the range is tracked here, to resume it after an error
+            let mut rest = Some(opt);
+            let mut failed = None;
+            while let Some(item) = batches.next().await {
+                match item {
+                    Ok(ScanBatch::Values(values)) => {
+                        for kv in values.iter() {
+                            f(kv);
+                        }
+                        rest = rest.and_then(|opt| opt.next_range(&values));
+                    }
+                    Ok(ScanBatch::Partial(..)) => break,
+                    Err(err) => {
+                        failed = Some(err);
+                        break;
+                    }
+                }
+            }
+            drop(batches);
+            remaining = rest;
+            if remaining.is_none() {
+                break;
+            }
+            trx = match failed {
+                Some(err) if err.is_retryable() => trx.on_error(err).await?,
+                Some(err) => return Err(err),
+                None => self.create_trx()?,
+            };
+        }
+        Ok(transactions)
+    }
+}
@@ -0,0 +1,296 @@
+// Copyright 2018 foundationdb-rs developers, https://github.com/Clikengo/foundationdb-rs/graphs/contributors
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! Shadow reads, to compare this crate with another client during a migration
+//!
+//! A `ShadowReader` reads keys from a primary `Database` and, for a sample of the reads,
+//! compares the result with the value returned by a secondary source provided by the caller,
+//! usually the legacy client being migrated away from.
+//!
+//! Comparisons are done in a dedicated thread, so reads never wait for the secondary source.
+//! Mismatches are recorded in a bounded `ShadowReport` that never contains the keys or the
+//! values themselves.
+
+use std::collections::hash_map::DefaultHasher;
+use std::collections::VecDeque;
+use std::future::Future;
+use std::hash::{Hash, Hasher};
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+use futures::channel::mpsc;
+use futures::prelude::*;
+use rand::Rng;
+
+use crate::future::FdbSlice;
+use crate::{Database, FdbResult};
+
+/// A set of options that controls the behavior of a `ShadowReader`.
+#[derive(Debug, Clone)]
+pub struct ShadowOptions {
+    /// The fraction of reads that are compared with the secondary source, between `0.0` and `1.0`.
+    pub sample_rate: f64,
+    /// The maximum number of secondary reads running at the same time.
+    pub max_concurrency: usize,
+    /// The maximum number of sampled reads waiting to be compared. Sampled reads are skipped
+    /// when the queue is full.
+    pub queue_size: usize,
+    /// The maximum number of mismatches kept in the report, older mismatches are dropped first.
+    pub max_mismatches: usize,
+}
+
+impl Default for ShadowOptions {
+    fn default() -> Self {
+        Self {
+            sample_rate: 0.01,
+            max_concurrency: 16,
+            queue_size: 1024,
+            max_mismatches: 100,
+        }
+    }
+}
+
+/// How the results of the primary and secondary reads differ.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MismatchKind {
+    /// The key was only found by the secondary source.
+    MissingInPrimary,
+    /// The key was only found by the primary database.
+    MissingInSecondary,
+    /// Both sides found the key, with different values.
+    ValueDiffers,
+}
+
+/// A mismatch between the primary and the secondary reads of a key.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ShadowMismatch {
+    /// A hash of the key, so that the key itself is not kept in memory.
+    pub key_hash: u64,
+    pub key_len: usize,
+    /// The length of the primary value, `None` if the primary didn't find the key.
+    pub primary_len: Option<usize>,
+    /// The length of the secondary value, `None` if the secondary didn't find the key.
+    pub secondary_len: Option<usize>,
+    pub kind: MismatchKind,
+}
+
+/// The aggregated results of the comparisons done by a `ShadowReader`.
+#[derive(Debug, Clone, Default)]
+pub struct ShadowReport {
+    /// Number of reads done on the primary database.
+    pub reads: u64,
+    /// Number of reads selected for comparison.
+    pub sampled: u64,
+    /// Number of sampled reads dropped because the comparison queue was full.
+    pub skipped: u64,
+    /// Number of comparisons done.
+    pub compared: u64,
+    /// Number of comparisons that found a mismatch.
+    pub mismatch_count: u64,
+    /// The most recent mismatches, at most `ShadowOptions::max_mismatches`.
+    pub mismatches: VecDeque<ShadowMismatch>,
+}
+
+struct Comparison {
+    key: Vec<u8>,
+    primary: Option<Vec<u8>>,
+}
+
+/// Reads keys from a primary database and compares a sample of them with a secondary source.
+pub struct ShadowReader {
+    db: Arc<Database>,
+    sample_rate: f64,
+    sender: Option<Mutex<mpsc::Sender<Comparison>>>,
+    report: Arc<Mutex<ShadowReport>>,
+    comparator: Option<thread::JoinHandle<()>>,
+}
+
+impl ShadowReader {
+    /// Creates a shadow reader for `db`, comparing its reads with the values returned by
+    /// `secondary`.
+    ///
+    /// `secondary` is called from the comparison thread with the key to read.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the comparison thread cannot be spawned.
+    pub fn new<F, Fut>(db: Arc<Database>, secondary: F, options: ShadowOptions) -> Self
+    where
+        F: Fn(Vec<u8>) -> Fut + Send + 'static,
+        Fut: Future<Output = Option<Vec<u8>>>,
+    {
+        let (sender, receiver) = mpsc::channel(options.queue_size);
+        let report = Arc::new(Mutex::new(ShadowReport::default()));
+
+        let comparator_report = report.clone();
+        let max_concurrency = options.max_concurrency;
+        let max_mismatches = options.max_mismatches;
+        let comparator = thread::Builder::new()
+            .name("fdb-shadow-reader".to_string())
+            .spawn(move || {
+                let secondary = &secondary;
+                let report = &comparator_report;
+                futures::executor::block_on(receiver.for_each_concurrent(
+                    max_concurrency,
+                    |comparison: Comparison| async move {
+                        let secondary = secondary(comparison.key.clone()).await;
+                        let mismatch = compare(&comparison, secondary.as_deref());
+                        record(report, mismatch, max_mismatches);
+                    },
+                ))
+            })
+            .expect("failed to spawn shadow reader thread");
+
+        Self {
+            db,
+            sample_rate: options.sample_rate,
+            sender: Some(Mutex::new(sender)),
+            report,
+            comparator: Some(comparator),
+        }
+    }
+
+    /// Returns the primary database.
+    pub fn database(&self) -> &Database {
+        &self.db
+    }
+
+    /// Reads `key` from the primary database.
+    ///
+    /// The primary result is returned as soon as it is available, the comparison with the
+    /// secondary source, if this read is sampled, is done later by the comparison thread.
+    pub async fn get(&self, key: &[u8], snapshot: bool) -> FdbResult<Option<FdbSlice>> {
+        let trx = self.db.create_trx()?;
+        let value = trx.get(key, snapshot).await?;
+        self.sample(key, value.as_deref());
+        Ok(value)
+    }
+
+    /// Returns the report of the comparisons done so far.
+    pub fn report(&self) -> ShadowReport {
+        self.report.lock().unwrap().clone()
+    }
+
+    /// Waits for the pending comparisons to be done and returns the final report.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the comparison thread panicked.
+    pub fn finish(mut self) -> ShadowReport {
+        // closing the channel stops the comparison thread once the queue is empty
+        self.sender.take();
+        if let Some(comparator) = self.comparator.take() {
+            comparator.join().expect("shadow reader thread panicked");
+        }
+        self.report()
+    }
+
+    fn sample(&self, key: &[u8], primary: Option<&[u8]>) {
+        let sampled = rand::thread_rng().gen::<f64>() < self.sample_rate;
+
+        let queued = if sampled {
+            let comparison = Comparison {
+                key: key.to_vec(),
+                primary: primary.map(|v| v.to_vec()),
+            };
+            let sender = self.sender.as_ref().expect("shadow reader is finished");
+            sender.lock().unwrap().try_send(comparison).is_ok()
+        } else {
+            false
+        };
+
+        let mut report = self.report.lock().unwrap();
+        report.reads += 1;
+        if sampled {
+            report.sampled += 1;
+            if !queued {
+                report.skipped += 1;
+            }
+        }
+    }
+}
+
+fn compare(comparison: &Comparison, secondary: Option<&[u8]>) -> Option<ShadowMismatch> {
+    let primary = comparison.primary.as_deref();
+    let kind = match (primary, secondary) {
+        (Some(p), Some(s)) if p == s => return None,
+        (None, None) => return None,
+        (Some(_), Some(_)) => MismatchKind::ValueDiffers,
+        (None, Some(_)) => MismatchKind::MissingInPrimary,
+        (Some(_), None) => MismatchKind::MissingInSecondary,
+    };
+
+    let mut hasher = DefaultHasher::new();
+    comparison.key.hash(&mut hasher);
+    Some(ShadowMismatch {
+        key_hash: hasher.finish(),
+        key_len: comparison.key.len(),
+        primary_len: primary.map(<[u8]>::len),
+        secondary_len: secondary.map(<[u8]>::len),
+        kind,
+    })
+}
+
+fn record(report: &Mutex<ShadowReport>, mismatch: Option<ShadowMismatch>, max_mismatches: usize) {
+    let mut report = report.lock().unwrap();
+    report.compared += 1;
+    if let Some(mismatch) = mismatch {
+        report.mismatch_count += 1;
+        if max_mismatches > 0 {
+            if report.mismatches.len() >= max_mismatches {
+                report.mismatches.pop_front();
+            }
+            report.mismatches.push_back(mismatch);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn comparison(primary: Option<&[u8]>) -> Comparison {
+        Comparison {
+            key: b"key".to_vec(),
+            primary: primary.map(|v| v.to_vec()),
+        }
+    }
+
+    #[test]
+    fn test_compare() {
+        assert_eq!(compare(&comparison(None), None), None);
+        assert_eq!(compare(&comparison(Some(b"a")), Some(b"a")), None);
+
+        let mismatch = compare(&comparison(Some(b"a")), Some(b"bc")).unwrap();
+        assert_eq!(mismatch.kind, MismatchKind::ValueDiffers);
+        assert_eq!(mismatch.key_len, 3);
+        assert_eq!(mismatch.primary_len, Some(1));
+        assert_eq!(mismatch.secondary_len, Some(2));
+
+        let mismatch = compare(&comparison(None), Some(b"a")).unwrap();
+        assert_eq!(mismatch.kind, MismatchKind::MissingInPrimary);
+        assert_eq!(mismatch.primary_len, None);
+
+        let mismatch = compare(&comparison(Some(b"a")), None).unwrap();
+        assert_eq!(mismatch.kind, MismatchKind::MissingInSecondary);
+        assert_eq!(mismatch.secondary_len, None);
+    }
+
+    #[test]
+    fn test_record_bounded() {
+        let report = Mutex::new(ShadowReport::default());
+        for _ in 0..10 {
+            record(&report, compare(&comparison(None), Some(b"a")), 3);
+            record(&report, None, 3);
+        }
+
+        let report = report.into_inner().unwrap();
+        assert_eq!(report.compared, 20);
+        assert_eq!(report.mismatch_count, 10);
+        assert_eq!(report.mismatches.len(), 3);
+    }
+}
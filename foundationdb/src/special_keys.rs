@@ -0,0 +1,207 @@
+// Copyright 2018 foundationdb-rs developers, https://github.com/Clikengo/foundationdb-rs/graphs/contributors
+// Copyright 2013-2018 Apple, Inc and the FoundationDB project authors.
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! Typed access to the special key space
+//!
+//! Keys starting with `\xff\xff` are not stored in the database, they are computed by the client
+//! and expose information about the cluster and the transaction itself.
+//!
+//! https://apple.github.io/foundationdb/special-keys.html
+//!
+//! ```no_run
+//! use foundationdb::special_keys::SpecialKey;
+//!
+//! async fn example(db: &foundationdb::Database) -> foundationdb::FdbResult<()> {
+//!     let trx = db.create_trx()?;
+//!     let status = trx.get_special(SpecialKey::StatusJson).await?;
+//!     println!("{}", String::from_utf8_lossy(status.as_deref().unwrap_or_default()));
+//!     Ok(())
+//! }
+//! ```
+
+use futures::prelude::*;
+
+use crate::future::FdbSlice;
+#[cfg(feature = "fdb-7_1")]
+use crate::options::StreamingMode;
+#[cfg(not(feature = "fdb-7_1"))]
+use crate::options::TransactionOption;
+#[cfg(feature = "fdb-7_1")]
+use crate::{FdbRead, RangeOption};
+use crate::{FdbResult, Transaction};
+
+/// A documented key of the special key space.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum SpecialKey {
+    /// `\xff\xff/status/json`, the status of the cluster as a JSON document, like the output of
+    /// `status json` in `fdbcli`
+    StatusJson,
+    /// `\xff\xff/connection_string`, the connection string used by the client
+    #[cfg(feature = "fdb-7_1")]
+    ConnectionString,
+    /// `\xff\xff/cluster_file_path`, the path of the cluster file used by the client
+    #[cfg(feature = "fdb-7_1")]
+    ClusterFilePath,
+    /// `\xff\xff/management/data_distribution/mode`, `1` if data distribution is enabled
+    #[cfg(feature = "fdb-7_1")]
+    DataDistributionMode,
+    /// `\xff\xff/management/consistency_check_suspended`, set if the consistency check is
+    /// suspended
+    #[cfg(feature = "fdb-7_1")]
+    ConsistencyCheckSuspended,
+}
+
+impl SpecialKey {
+    /// The key read by `Transaction::get_special`
+    pub fn key(self) -> &'static [u8] {
+        match self {
+            SpecialKey::StatusJson => b"\xff\xff/status/json",
+            #[cfg(feature = "fdb-7_1")]
+            SpecialKey::ConnectionString => b"\xff\xff/connection_string",
+            #[cfg(feature = "fdb-7_1")]
+            SpecialKey::ClusterFilePath => b"\xff\xff/cluster_file_path",
+            #[cfg(feature = "fdb-7_1")]
+            SpecialKey::DataDistributionMode => b"\xff\xff/management/data_distribution/mode",
+            #[cfg(feature = "fdb-7_1")]
+            SpecialKey::ConsistencyCheckSuspended => {
+                b"\xff\xff/management/consistency_check_suspended"
+            }
+        }
+    }
+}
+
+/// A documented range of the special key space.
+#[cfg(feature = "fdb-7_1")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum SpecialKeyRange {
+    /// `\xff\xff/transaction/read_conflict_range/`, the read conflict ranges of the transaction
+    ///
+    /// Each range is described by two keys: its beginning, with the value `1`, and its end,
+    /// with the value `0`.
+    ReadConflictRanges,
+    /// `\xff\xff/transaction/write_conflict_range/`, the write conflict ranges of the
+    /// transaction, described like `ReadConflictRanges`
+    WriteConflictRanges,
+    /// `\xff\xff/transaction/conflicting_keys/`, the ranges read by the transaction that were
+    /// modified by another transaction, described like `ReadConflictRanges`
+    ///
+    /// Only set after a commit failed with `not_committed` (1020), if the transaction has the
+    /// option `TransactionOption::ReportConflictingKeys`.
+    ConflictingKeys,
+    /// `\xff\xff/worker_interfaces/`, the addresses of the workers of the cluster and their
+    /// interfaces
+    WorkerInterfaces,
+}
+
+#[cfg(feature = "fdb-7_1")]
+impl SpecialKeyRange {
+    /// The prefix of the keys read by `Transaction::get_special_range`
+    pub fn prefix(self) -> &'static [u8] {
+        match self {
+            SpecialKeyRange::ReadConflictRanges => b"\xff\xff/transaction/read_conflict_range/",
+            SpecialKeyRange::WriteConflictRanges => b"\xff\xff/transaction/write_conflict_range/",
+            SpecialKeyRange::ConflictingKeys => b"\xff\xff/transaction/conflicting_keys/",
+            SpecialKeyRange::WorkerInterfaces => b"\xff\xff/worker_interfaces/",
+        }
+    }
+}
+
+/// Sets the options needed to read the special keys with the selected API version.
+#[cfg_attr(feature = "fdb-7_1", allow(unused_variables))]
+fn set_special_read_options(trx: &Transaction) -> FdbResult<()> {
+    // before 6.3, special keys are treated as system keys
+    #[cfg(not(feature = "fdb-7_1"))]
+    trx.set_option(TransactionOption::ReadSystemKeys)?;
+    Ok(())
+}
+
+impl Transaction {
+    /// Reads a key of the special key space, setting the transaction options it requires.
+    ///
+    /// Special keys are always read at snapshot isolation, they don't add conflict ranges.
+    pub fn get_special(
+        &self,
+        key: SpecialKey,
+    ) -> impl Future<Output = FdbResult<Option<FdbSlice>>> + Send + Sync + Unpin {
+        let options = set_special_read_options(self);
+        let value = self.get(key.key(), true);
+        future::ready(options).and_then(move |()| value)
+    }
+
+    /// Reads all the key-value pairs of a range of the special key space, setting the
+    /// transaction options it requires.
+    ///
+    /// The keys are returned without the prefix of the range.
+    #[cfg(feature = "fdb-7_1")]
+    pub async fn get_special_range(
+        &self,
+        range: SpecialKeyRange,
+    ) -> FdbResult<Vec<(Vec<u8>, Vec<u8>)>> {
+        set_special_read_options(self)?;
+        let prefix = range.prefix();
+        let mut end = prefix.to_vec();
+        // all the prefixes end with `/`
+        *end.last_mut().expect("empty special key prefix") += 1;
+        let opt = RangeOption {
+            mode: StreamingMode::WantAll,
+            ..RangeOption::from((prefix, end.as_slice()))
+        };
+        let kvs = FdbRead::read_range(self, &opt, true).await?;
+        Ok(kvs
+            .into_iter()
+            .map(|(key, value)| (key[prefix.len()..].to_vec(), value))
+            .collect())
+    }
+}
+
+/// Pairs the keys of a conflict range listing, like `SpecialKeyRange::ReadConflictRanges`, into
+/// `(begin, end)` ranges.
+///
+/// A range begins at a key with the value `1`, and ends at the next key with the value `0`.
+#[cfg(feature = "fdb-7_1")]
+pub(crate) fn pair_conflict_ranges(kvs: Vec<(Vec<u8>, Vec<u8>)>) -> Vec<(Vec<u8>, Vec<u8>)> {
+    let mut ranges = Vec::with_capacity(kvs.len() / 2);
+    let mut begin = None;
+    for (key, value) in kvs {
+        match value.as_slice() {
+            b"1" => begin = Some(key),
+            b"0" => {
+                if let Some(begin) = begin.take() {
+                    ranges.push((begin, key));
+                }
+            }
+            _ => {}
+        }
+    }
+    ranges
+}
+
+#[cfg(all(test, feature = "fdb-7_1"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pair_conflict_ranges() {
+        let kvs = vec![
+            (b"a".to_vec(), b"1".to_vec()),
+            (b"a\x00".to_vec(), b"0".to_vec()),
+            (b"c".to_vec(), b"1".to_vec()),
+            (b"f".to_vec(), b"0".to_vec()),
+        ];
+        assert_eq!(
+            pair_conflict_ranges(kvs),
+            vec![
+                (b"a".to_vec(), b"a\x00".to_vec()),
+                (b"c".to_vec(), b"f".to_vec()),
+            ]
+        );
+        assert!(pair_conflict_ranges(Vec::new()).is_empty());
+    }
+}
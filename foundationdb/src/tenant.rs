@@ -0,0 +1,162 @@
+// Copyright 2018 foundationdb-rs developers, https://github.com/Clikengo/foundationdb-rs/graphs/contributors
+// Copyright 2013-2018 Apple, Inc and the FoundationDB project authors.
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! Implementations of the FDBTenant C API
+//!
+//! https://apple.github.io/foundationdb/api-c.html#tenant
+
+use std::ptr::NonNull;
+
+use foundationdb_sys as fdb_sys;
+use futures::prelude::*;
+
+use crate::options::{StreamingMode, TransactionOption};
+use crate::transaction::fdb_len;
+use crate::{error, Database, FdbError, FdbResult, RangeOption, TransactOption, Transaction};
+
+/// The prefix of the special keys holding the tenant map
+const TENANT_MAP_PREFIX: &[u8] = b"\xff\xff/management/tenant/map/";
+
+/// Error code returned when the tenant does not exist
+const TENANT_NOT_FOUND: i32 = 2131;
+/// Error code returned when the tenant already exists
+const TENANT_ALREADY_EXISTS: i32 = 2132;
+
+/// Represents a FoundationDB tenant
+///
+/// A tenant is a named, isolated key-space of a database. Transactions created from a tenant
+/// can only see and modify the keys of this tenant.
+pub struct Tenant {
+    inner: NonNull<fdb_sys::FDBTenant>,
+}
+unsafe impl Send for Tenant {}
+unsafe impl Sync for Tenant {}
+impl Drop for Tenant {
+    fn drop(&mut self) {
+        unsafe {
+            fdb_sys::fdb_tenant_destroy(self.inner.as_ptr());
+        }
+    }
+}
+
+impl Tenant {
+    /// Creates a new transaction on the given tenant.
+    ///
+    /// The transaction can only access the keys of this tenant.
+    pub fn create_trx(&self) -> FdbResult<Transaction> {
+        let mut trx: *mut fdb_sys::FDBTransaction = std::ptr::null_mut();
+        let err = unsafe { fdb_sys::fdb_tenant_create_transaction(self.inner.as_ptr(), &mut trx) };
+        error::eval(err)?;
+        Ok(Transaction::new(NonNull::new(trx).expect(
+            "fdb_tenant_create_transaction to not return null if there is no error",
+        )))
+    }
+}
+
+impl Database {
+    /// Opens the tenant with the given name.
+    ///
+    /// The tenant is not required to exist, operations on transactions created from a tenant
+    /// that does not exist will fail.
+    pub fn open_tenant(&self, tenant_name: &[u8]) -> FdbResult<Tenant> {
+        let mut tenant: *mut fdb_sys::FDBTenant = std::ptr::null_mut();
+        let err = unsafe {
+            fdb_sys::fdb_database_open_tenant(
+                self.inner.as_ptr(),
+                tenant_name.as_ptr(),
+                fdb_len(tenant_name.len(), "tenant_name"),
+                &mut tenant,
+            )
+        };
+        error::eval(err)?;
+        Ok(Tenant {
+            inner: NonNull::new(tenant)
+                .expect("fdb_database_open_tenant to not return null if there is no error"),
+        })
+    }
+}
+
+/// Helpers to manage the tenants of a database through the special key space.
+pub struct TenantManagement;
+
+impl TenantManagement {
+    /// Creates a new tenant in the cluster.
+    ///
+    /// Fails with `tenant_already_exists` if a tenant with the same name exists.
+    pub async fn create_tenant(db: &Database, tenant_name: &[u8]) -> FdbResult<()> {
+        let key = tenant_map_key(tenant_name);
+        db.transact_boxed(
+            key.as_slice(),
+            |trx, key| {
+                async move {
+                    trx.set_option(TransactionOption::SpecialKeySpaceEnableWrites)?;
+                    if trx.get(key, false).await?.is_some() {
+                        return Err(FdbError::from_code(TENANT_ALREADY_EXISTS));
+                    }
+                    trx.set(key, &[]);
+                    Ok(())
+                }
+                .boxed()
+            },
+            TransactOption::default(),
+        )
+        .await
+    }
+
+    /// Deletes a tenant from the cluster.
+    ///
+    /// Fails with `tenant_not_found` if the tenant does not exist. A tenant can only be deleted
+    /// if it is empty.
+    pub async fn delete_tenant(db: &Database, tenant_name: &[u8]) -> FdbResult<()> {
+        let key = tenant_map_key(tenant_name);
+        db.transact_boxed(
+            key.as_slice(),
+            |trx, key| {
+                async move {
+                    trx.set_option(TransactionOption::SpecialKeySpaceEnableWrites)?;
+                    if trx.get(key, false).await?.is_none() {
+                        return Err(FdbError::from_code(TENANT_NOT_FOUND));
+                    }
+                    trx.clear(key);
+                    Ok(())
+                }
+                .boxed()
+            },
+            TransactOption::default(),
+        )
+        .await
+    }
+
+    /// Lists the names of the tenants in the range `[begin, end)`, returning at most `limit`
+    /// tenants.
+    pub async fn list_tenants(
+        db: &Database,
+        begin: &[u8],
+        end: &[u8],
+        limit: usize,
+    ) -> FdbResult<Vec<Vec<u8>>> {
+        let range = (tenant_map_key(begin), tenant_map_key(end));
+        let trx = db.create_trx()?;
+        let opt = RangeOption {
+            limit: Some(limit),
+            mode: StreamingMode::WantAll,
+            ..RangeOption::from(range)
+        };
+        let values = trx.get_range(&opt, 1, false).await?;
+        Ok(values
+            .iter()
+            .map(|kv| kv.key()[TENANT_MAP_PREFIX.len()..].to_vec())
+            .collect())
+    }
+}
+
+fn tenant_map_key(tenant_name: &[u8]) -> Vec<u8> {
+    let mut key = TENANT_MAP_PREFIX.to_vec();
+    key.extend_from_slice(tenant_name);
+    key
+}
@@ -0,0 +1,175 @@
+// Copyright 2018 foundationdb-rs developers, https://github.com/Clikengo/foundationdb-rs/graphs/contributors
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! Deterministic data sets, to fill a database for tests and benchmarks
+
+use futures::prelude::*;
+
+use crate::tuple::prefix_range;
+use crate::{Database, FdbResult, TransactOption};
+
+/// The number of bytes of keys and values written by each transaction of `DataSet::write`
+const WRITE_BATCH_BYTES: usize = 1_000_000;
+
+/// `count` key-value pairs under `prefix`, generated deterministically from their index.
+///
+/// The key of the pair `i` is `prefix` followed by `i` as a big-endian `u64`, padded with zeros
+/// up to `key_size` bytes, so the keys are sorted by index. Its value is `value_size`
+/// pseudo-random bytes, always the same for a given index.
+///
+/// ```no_run
+/// use foundationdb::testing::data::DataSet;
+///
+/// async fn example(db: &foundationdb::Database) -> foundationdb::FdbResult<()> {
+///     let data = DataSet {
+///         value_size: 1000,
+///         ..DataSet::new(b"bench", 100_000)
+///     };
+///     data.write(db).await?;
+///     // ...
+///     data.clear(db).await
+/// }
+/// ```
+#[derive(Debug, Clone)]
+pub struct DataSet {
+    /// The prefix of all the keys
+    pub prefix: Vec<u8>,
+    /// The number of key-value pairs
+    pub count: usize,
+    /// The size of the keys, without the prefix. Keys are never shorter than 8 bytes.
+    pub key_size: usize,
+    /// The size of the values
+    pub value_size: usize,
+}
+
+impl DataSet {
+    /// `count` pairs under `prefix`, with 16 bytes keys and 100 bytes values
+    pub fn new(prefix: &[u8], count: usize) -> Self {
+        Self {
+            prefix: prefix.to_vec(),
+            count,
+            key_size: 16,
+            value_size: 100,
+        }
+    }
+
+    /// Returns the key of the pair `index`.
+    pub fn key(&self, index: usize) -> Vec<u8> {
+        let mut key = Vec::with_capacity(self.prefix.len() + self.key_size.max(8));
+        key.extend_from_slice(&self.prefix);
+        key.extend_from_slice(&(index as u64).to_be_bytes());
+        key.resize(self.prefix.len() + self.key_size.max(8), 0);
+        key
+    }
+
+    /// Returns the value of the pair `index`.
+    pub fn value(&self, index: usize) -> Vec<u8> {
+        // xorshift64*, the seed must not be 0
+        let mut state = (index as u64).wrapping_add(0x9E37_79B9_7F4A_7C15) | 1;
+        let mut value = Vec::with_capacity(self.value_size + 8);
+        while value.len() < self.value_size {
+            state ^= state >> 12;
+            state ^= state << 25;
+            state ^= state >> 27;
+            value.extend_from_slice(&state.wrapping_mul(0x2545_F491_4F6C_DD1D).to_le_bytes());
+        }
+        value.truncate(self.value_size);
+        value
+    }
+
+    /// Returns the pairs, in the order of their keys.
+    pub fn iter(&self) -> impl Iterator<Item = (Vec<u8>, Vec<u8>)> + '_ {
+        (0..self.count).map(move |index| (self.key(index), self.value(index)))
+    }
+
+    /// Returns the range `[begin, end)` containing all the keys of the data set.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the prefix is in the special keyspace, see `tuple::prefix_range`.
+    pub fn range(&self) -> (Vec<u8>, Vec<u8>) {
+        prefix_range(&self.prefix).expect("data set prefix in the special keyspace")
+    }
+
+    /// Writes the pairs to `db`, in as many transactions as needed to stay well below the
+    /// transaction size limit.
+    pub async fn write(&self, db: &Database) -> FdbResult<()> {
+        let pair_size = self.prefix.len() + self.key_size.max(8) + self.value_size;
+        let batch = (WRITE_BATCH_BYTES / pair_size).max(1);
+        for start in (0..self.count).step_by(batch) {
+            let end = (start + batch).min(self.count);
+            db.transact_boxed(
+                (self, start..end),
+                |trx, (data, indexes)| {
+                    for index in indexes.clone() {
+                        trx.set(&data.key(index), &data.value(index));
+                    }
+                    future::ok(()).boxed()
+                },
+                TransactOption::idempotent(),
+            )
+            .await?;
+        }
+        Ok(())
+    }
+
+    /// Clears all the keys starting with the prefix of the data set.
+    pub async fn clear(&self, db: &Database) -> FdbResult<()> {
+        let (begin, end) = self.range();
+        db.transact_boxed(
+            (begin, end),
+            |trx, (begin, end)| {
+                trx.clear_range(begin, end);
+                future::ok(()).boxed()
+            },
+            TransactOption::idempotent(),
+        )
+        .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_keys() {
+        let data = DataSet::new(b"prefix", 300);
+        let keys: Vec<_> = data.iter().map(|(key, _)| key).collect();
+        assert_eq!(keys.len(), 300);
+        assert!(keys.iter().all(|key| key.len() == 6 + 16));
+        assert!(keys.iter().all(|key| key.starts_with(b"prefix")));
+        assert!(keys.windows(2).all(|keys| keys[0] < keys[1]));
+
+        let (begin, end) = data.range();
+        assert!(keys.iter().all(|key| &begin <= key && key < &end));
+
+        // keys are never shorter than the index
+        let data = DataSet {
+            key_size: 2,
+            ..DataSet::new(b"", 1)
+        };
+        assert_eq!(data.key(1), vec![0, 0, 0, 0, 0, 0, 0, 1]);
+    }
+
+    #[test]
+    fn test_values() {
+        let data = DataSet {
+            value_size: 13,
+            ..DataSet::new(b"prefix", 10)
+        };
+        assert_eq!(data.value(3).len(), 13);
+        assert_eq!(data.value(3), data.value(3));
+        assert_ne!(data.value(3), data.value(4));
+
+        let empty = DataSet {
+            value_size: 0,
+            ..DataSet::new(b"prefix", 10)
+        };
+        assert!(empty.value(3).is_empty());
+    }
+}
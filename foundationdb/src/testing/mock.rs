@@ -0,0 +1,541 @@
+// Copyright 2018 foundationdb-rs developers, https://github.com/Clikengo/foundationdb-rs/graphs/contributors
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! An in-memory database with the isolation of FoundationDB transactions
+
+use std::cmp::Ordering;
+use std::collections::BTreeMap;
+use std::ops::Bound;
+use std::sync::{Arc, Mutex};
+
+use async_trait::async_trait;
+
+use crate::options::MutationType;
+use crate::{FdbError, FdbRead, FdbResult, FdbWrite, KeySelector, RangeOption};
+
+/// Error code returned when a transaction conflicts with a committed transaction
+const NOT_COMMITTED: i32 = 1020;
+/// Error code returned when a transaction uses a mutation the mock doesn't support
+const CLIENT_INVALID_OPERATION: i32 = 2000;
+/// The maximum size of a value, enforced by `MutationType::AppendIfFits`
+const VALUE_SIZE_LIMIT: usize = 100_000;
+
+type KeyRange = (Vec<u8>, Vec<u8>);
+type KeyValues = BTreeMap<Vec<u8>, Vec<u8>>;
+
+#[derive(Default)]
+struct MockState {
+    data: KeyValues,
+    version: u64,
+    /// The write conflict ranges of the committed transactions, with their commit version
+    commits: Vec<(u64, Vec<KeyRange>)>,
+}
+
+/// An in-memory database, to unit test code written against `FdbRead` and `FdbWrite`.
+///
+/// Transactions read a snapshot of the database taken when they are created, and see their own
+/// writes. Committing a transaction fails with `not_committed` (1020) if a transaction committed
+/// after its creation wrote in one of its read conflict ranges.
+///
+/// This mock is meant for small data sets: every transaction copies the whole database and every
+/// commit is kept to detect conflicts. The read conflict range of a range read spans the keys of
+/// its selectors, ignoring the limit. Versionstamp mutations are not supported, committing a
+/// transaction using them fails with `client_invalid_operation` (2000).
+///
+/// Clones of a `MockDatabase` share the same data.
+#[derive(Clone, Default)]
+pub struct MockDatabase {
+    state: Arc<Mutex<MockState>>,
+}
+
+impl MockDatabase {
+    /// Creates an empty database.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates a new transaction on the database.
+    ///
+    /// This never fails, the result mirrors `Database::create_trx`.
+    pub fn create_trx(&self) -> FdbResult<MockTransaction> {
+        let state = self.state.lock().expect("mock database lock poisoned");
+        Ok(MockTransaction {
+            state: self.state.clone(),
+            read_version: state.version,
+            inner: Mutex::new(MockTransactionState {
+                view: state.data.clone(),
+                mutations: Vec::new(),
+                read_conflicts: Vec::new(),
+                write_conflicts: Vec::new(),
+            }),
+        })
+    }
+
+    /// Returns the committed key-value pairs of the database, in key order.
+    pub fn dump(&self) -> Vec<(Vec<u8>, Vec<u8>)> {
+        let state = self.state.lock().expect("mock database lock poisoned");
+        state
+            .data
+            .iter()
+            .map(|(key, value)| (key.clone(), value.clone()))
+            .collect()
+    }
+}
+
+enum Mutation {
+    Set(Vec<u8>, Vec<u8>),
+    ClearRange(Vec<u8>, Vec<u8>),
+    Atomic(Vec<u8>, Vec<u8>, MutationType),
+}
+
+impl Mutation {
+    /// Returns `false` without applying the mutation if it is not supported.
+    fn apply(&self, data: &mut KeyValues) -> bool {
+        match self {
+            Mutation::Set(key, value) => {
+                data.insert(key.clone(), value.clone());
+            }
+            Mutation::ClearRange(begin, end) => {
+                let keys: Vec<Vec<u8>> = data
+                    .range::<[u8], _>((Bound::Included(&begin[..]), Bound::Excluded(&end[..])))
+                    .map(|(key, _)| key.clone())
+                    .collect();
+                for key in keys {
+                    data.remove(&key);
+                }
+            }
+            Mutation::Atomic(key, param, op_type) => {
+                let existing = data.get(key).map(Vec::as_slice);
+                match atomic_value(existing, param, *op_type) {
+                    Some(Some(value)) => {
+                        data.insert(key.clone(), value);
+                    }
+                    Some(None) => {
+                        data.remove(key);
+                    }
+                    None => return false,
+                }
+            }
+        }
+        true
+    }
+}
+
+/// Returns the value of a key after an atomic operation, `None` if the operation is not
+/// supported.
+fn atomic_value(
+    existing: Option<&[u8]>,
+    param: &[u8],
+    op_type: MutationType,
+) -> Option<Option<Vec<u8>>> {
+    // integer operations work on little-endian values of the size of `param`
+    let resized = || {
+        let mut value = existing.unwrap_or(&[]).to_vec();
+        value.resize(param.len(), 0);
+        value
+    };
+    let compare_le = |a: &[u8], b: &[u8]| a.iter().rev().cmp(b.iter().rev());
+    let value = match op_type {
+        MutationType::Add => {
+            let mut value = resized();
+            let mut carry = 0u16;
+            for (v, p) in value.iter_mut().zip(param) {
+                let sum = u16::from(*v) + u16::from(*p) + carry;
+                *v = sum as u8;
+                carry = sum >> 8;
+            }
+            value
+        }
+        _ if existing.is_none() => match op_type {
+            MutationType::SetVersionstampedKey | MutationType::SetVersionstampedValue => {
+                return None
+            }
+            #[cfg(not(any(feature = "fdb-5_1", feature = "fdb-5_2", feature = "fdb-6_0")))]
+            MutationType::CompareAndClear => return Some(None),
+            _ => param.to_vec(),
+        },
+        MutationType::And | MutationType::BitAnd => {
+            let mut value = resized();
+            value.iter_mut().zip(param).for_each(|(v, p)| *v &= p);
+            value
+        }
+        MutationType::Or | MutationType::BitOr => {
+            let mut value = resized();
+            value.iter_mut().zip(param).for_each(|(v, p)| *v |= p);
+            value
+        }
+        MutationType::Xor | MutationType::BitXor => {
+            let mut value = resized();
+            value.iter_mut().zip(param).for_each(|(v, p)| *v ^= p);
+            value
+        }
+        MutationType::Max => {
+            let value = resized();
+            if compare_le(&value, param) == Ordering::Less {
+                param.to_vec()
+            } else {
+                value
+            }
+        }
+        MutationType::Min => {
+            let value = resized();
+            if compare_le(&value, param) == Ordering::Greater {
+                param.to_vec()
+            } else {
+                value
+            }
+        }
+        MutationType::ByteMax => existing.unwrap_or(&[]).max(param).to_vec(),
+        MutationType::ByteMin => existing.unwrap_or(&[]).min(param).to_vec(),
+        #[cfg(not(feature = "fdb-5_1"))]
+        MutationType::AppendIfFits => {
+            let mut value = existing.unwrap_or(&[]).to_vec();
+            if value.len() + param.len() <= VALUE_SIZE_LIMIT {
+                value.extend_from_slice(param);
+            }
+            value
+        }
+        #[cfg(not(any(feature = "fdb-5_1", feature = "fdb-5_2", feature = "fdb-6_0")))]
+        MutationType::CompareAndClear => {
+            if existing == Some(param) {
+                return Some(None);
+            }
+            existing.unwrap_or(&[]).to_vec()
+        }
+        _ => return None,
+    };
+    Some(Some(value))
+}
+
+struct MockTransactionState {
+    /// The snapshot read by the transaction, with its own writes
+    view: KeyValues,
+    mutations: Vec<Mutation>,
+    read_conflicts: Vec<KeyRange>,
+    write_conflicts: Vec<KeyRange>,
+}
+
+impl MockTransactionState {
+    fn mutate(&mut self, mutation: Mutation, conflict: KeyRange) {
+        mutation.apply(&mut self.view);
+        self.mutations.push(mutation);
+        self.write_conflicts.push(conflict);
+    }
+}
+
+/// A transaction of a `MockDatabase`.
+pub struct MockTransaction {
+    state: Arc<Mutex<MockState>>,
+    read_version: u64,
+    inner: Mutex<MockTransactionState>,
+}
+
+impl MockTransaction {
+    fn lock(&self) -> std::sync::MutexGuard<'_, MockTransactionState> {
+        self.inner.lock().expect("mock transaction lock poisoned")
+    }
+
+    /// Commits the writes of the transaction.
+    ///
+    /// Fails with `not_committed` (1020) if a transaction committed since the creation of this
+    /// one wrote in its read conflict ranges. Nothing is written if the commit fails.
+    pub async fn commit(self) -> FdbResult<()> {
+        let trx = self
+            .inner
+            .into_inner()
+            .expect("mock transaction lock poisoned");
+        let mut state = self.state.lock().expect("mock database lock poisoned");
+
+        let read_version = self.read_version;
+        let conflicts = state
+            .commits
+            .iter()
+            .filter(|(version, _)| *version > read_version)
+            .flat_map(|(_, writes)| writes)
+            .any(|write| {
+                trx.read_conflicts
+                    .iter()
+                    .any(|read| intersects(read, write))
+            });
+        if conflicts {
+            return Err(FdbError::from_code(NOT_COMMITTED));
+        }
+
+        let mut data = state.data.clone();
+        for mutation in &trx.mutations {
+            if !mutation.apply(&mut data) {
+                return Err(FdbError::from_code(CLIENT_INVALID_OPERATION));
+            }
+        }
+        state.data = data;
+        if !trx.write_conflicts.is_empty() {
+            state.version += 1;
+            let version = state.version;
+            state.commits.push((version, trx.write_conflicts));
+        }
+        Ok(())
+    }
+}
+
+fn intersects(a: &KeyRange, b: &KeyRange) -> bool {
+    a.0 < b.1 && b.0 < a.1
+}
+
+/// Returns the range containing only `key`.
+fn single_key_range(key: &[u8]) -> KeyRange {
+    let mut end = key.to_vec();
+    end.push(0);
+    (key.to_vec(), end)
+}
+
+/// Returns the index of the key `selector` resolves to in `view`, between `0` and `view.len()`.
+fn resolve(view: &KeyValues, selector: &KeySelector) -> usize {
+    let key = selector.key();
+    let upper = if selector.or_equal() {
+        Bound::Included(key)
+    } else {
+        Bound::Excluded(key)
+    };
+    // the selector is relative to the last key less than (or equal to) its key
+    let below = view.range::<[u8], _>((Bound::Unbounded, upper)).count() as i64;
+    let index = below - 1 + i64::from(selector.offset());
+    index.max(0).min(view.len() as i64) as usize
+}
+
+#[async_trait]
+impl FdbRead for MockTransaction {
+    async fn get(&self, key: &[u8], snapshot: bool) -> FdbResult<Option<Vec<u8>>> {
+        let mut trx = self.lock();
+        if !snapshot {
+            trx.read_conflicts.push(single_key_range(key));
+        }
+        Ok(trx.view.get(key).cloned())
+    }
+
+    async fn read_range(
+        &self,
+        opt: &RangeOption<'_>,
+        snapshot: bool,
+    ) -> FdbResult<Vec<(Vec<u8>, Vec<u8>)>> {
+        let mut trx = self.lock();
+        if !snapshot && opt.begin.key() < opt.end.key() {
+            trx.read_conflicts
+                .push((opt.begin.key().to_vec(), opt.end.key().to_vec()));
+        }
+
+        let begin = resolve(&trx.view, &opt.begin);
+        let end = resolve(&trx.view, &opt.end);
+        let mut kvs: Vec<(Vec<u8>, Vec<u8>)> = trx
+            .view
+            .iter()
+            .skip(begin)
+            .take(end.saturating_sub(begin))
+            .map(|(key, value)| (key.clone(), value.clone()))
+            .collect();
+        if opt.reverse {
+            kvs.reverse();
+        }
+        if let Some(limit) = opt.limit {
+            kvs.truncate(limit);
+        }
+        Ok(kvs)
+    }
+}
+
+impl FdbWrite for MockTransaction {
+    fn set(&self, key: &[u8], value: &[u8]) {
+        self.lock().mutate(
+            Mutation::Set(key.to_vec(), value.to_vec()),
+            single_key_range(key),
+        );
+    }
+
+    fn clear(&self, key: &[u8]) {
+        let range = single_key_range(key);
+        self.lock().mutate(
+            Mutation::ClearRange(range.0.clone(), range.1.clone()),
+            range,
+        );
+    }
+
+    fn clear_range(&self, begin: &[u8], end: &[u8]) {
+        if begin < end {
+            self.lock().mutate(
+                Mutation::ClearRange(begin.to_vec(), end.to_vec()),
+                (begin.to_vec(), end.to_vec()),
+            );
+        }
+    }
+
+    fn atomic_op(&self, key: &[u8], param: &[u8], op_type: MutationType) {
+        self.lock().mutate(
+            Mutation::Atomic(key.to_vec(), param.to_vec(), op_type),
+            single_key_range(key),
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::executor::block_on;
+
+    fn set(db: &MockDatabase, kvs: &[(&[u8], &[u8])]) {
+        let trx = db.create_trx().unwrap();
+        for (key, value) in kvs {
+            trx.set(key, value);
+        }
+        block_on(trx.commit()).unwrap();
+    }
+
+    fn keys(kvs: Vec<(Vec<u8>, Vec<u8>)>) -> Vec<Vec<u8>> {
+        kvs.into_iter().map(|(key, _)| key).collect()
+    }
+
+    #[test]
+    fn read_your_writes() {
+        let db = MockDatabase::new();
+        set(&db, &[(b"a", b"1"), (b"b", b"2")]);
+
+        let trx = db.create_trx().unwrap();
+        trx.set(b"c", b"3");
+        trx.clear(b"a");
+        assert_eq!(block_on(trx.get(b"a", false)).unwrap(), None);
+        assert_eq!(block_on(trx.get(b"c", false)).unwrap(), Some(b"3".to_vec()));
+
+        // not visible before the commit
+        assert_eq!(db.dump().len(), 2);
+        block_on(trx.commit()).unwrap();
+        assert_eq!(
+            db.dump(),
+            vec![
+                (b"b".to_vec(), b"2".to_vec()),
+                (b"c".to_vec(), b"3".to_vec())
+            ]
+        );
+    }
+
+    #[test]
+    fn conflicts() {
+        let db = MockDatabase::new();
+        set(&db, &[(b"a", b"1")]);
+
+        let t1 = db.create_trx().unwrap();
+        let t2 = db.create_trx().unwrap();
+        let t3 = db.create_trx().unwrap();
+        let t4 = db.create_trx().unwrap();
+        assert_eq!(block_on(t1.get(b"a", false)).unwrap(), Some(b"1".to_vec()));
+        assert_eq!(block_on(t2.get(b"a", false)).unwrap(), Some(b"1".to_vec()));
+        block_on(t3.get(b"a", true)).unwrap();
+        block_on(t4.get(b"b", false)).unwrap();
+        for trx in &[&t1, &t2, &t3, &t4] {
+            trx.set(b"a", b"2");
+        }
+
+        block_on(t1.commit()).unwrap();
+        // reads the key written by t1
+        assert_eq!(block_on(t2.commit()).unwrap_err().code(), NOT_COMMITTED);
+        // snapshot reads and reads of other keys don't conflict
+        block_on(t3.commit()).unwrap();
+        block_on(t4.commit()).unwrap();
+
+        // transactions read the snapshot taken at their creation
+        let t5 = db.create_trx().unwrap();
+        set(&db, &[(b"a", b"3")]);
+        assert_eq!(block_on(t5.get(b"a", true)).unwrap(), Some(b"2".to_vec()));
+    }
+
+    #[test]
+    fn read_range() {
+        let db = MockDatabase::new();
+        set(&db, &[(b"a", b""), (b"b", b""), (b"c", b""), (b"d", b"")]);
+        let trx = db.create_trx().unwrap();
+        let read = |opt: RangeOption| keys(block_on(trx.read_range(&opt, false)).unwrap());
+
+        let all = vec![b"a".to_vec(), b"b".to_vec(), b"c".to_vec(), b"d".to_vec()];
+        assert_eq!(read(RangeOption::from((&b"a"[..], &b"e"[..]))), all);
+        assert_eq!(
+            read(RangeOption::from((&b"b"[..], &b"d"[..]))),
+            vec![b"b".to_vec(), b"c".to_vec()]
+        );
+        assert_eq!(
+            read(RangeOption::from((&b"b"[..], &b"d"[..])).rev()),
+            vec![b"c".to_vec(), b"b".to_vec()]
+        );
+        assert_eq!(
+            read(RangeOption {
+                limit: Some(3),
+                ..RangeOption::from((&b"a"[..], &b"e"[..])).rev()
+            }),
+            vec![b"d".to_vec(), b"c".to_vec(), b"b".to_vec()]
+        );
+        assert_eq!(
+            read(RangeOption::from((
+                KeySelector::first_greater_than(&b"a"[..]),
+                KeySelector::last_less_or_equal(&b"c"[..]),
+            ))),
+            vec![b"b".to_vec()]
+        );
+        assert_eq!(
+            read(RangeOption::from((
+                KeySelector::first_greater_than(&b"a"[..]),
+                KeySelector::first_greater_than(&b"c"[..]),
+            ))),
+            vec![b"b".to_vec(), b"c".to_vec()]
+        );
+        assert_eq!(
+            read(RangeOption::from((
+                KeySelector::new((&b"a"[..]).into(), false, 3),
+                KeySelector::new((&b"z"[..]).into(), false, 10),
+            ))),
+            vec![b"c".to_vec(), b"d".to_vec()]
+        );
+
+        trx.clear_range(b"b", b"d");
+        assert_eq!(
+            read(RangeOption::from((&b"a"[..], &b"e"[..]))),
+            vec![b"a".to_vec(), b"d".to_vec()]
+        );
+        block_on(trx.commit()).unwrap();
+        assert_eq!(keys(db.dump()), vec![b"a".to_vec(), b"d".to_vec()]);
+    }
+
+    #[test]
+    fn atomic_ops() {
+        let db = MockDatabase::new();
+        set(&db, &[(b"n", &[0xff, 0]), (b"s", b"abc")]);
+
+        let trx = db.create_trx().unwrap();
+        trx.atomic_op(b"n", &[1, 0], MutationType::Add);
+        trx.atomic_op(b"m", &[5], MutationType::Add);
+        trx.atomic_op(b"max", &[3, 1], MutationType::Max);
+        trx.atomic_op(b"max", &[4, 0], MutationType::Max);
+        trx.atomic_op(b"s", b"abd", MutationType::ByteMin);
+        trx.atomic_op(b"x", &[0b1100], MutationType::BitXor);
+        trx.atomic_op(b"x", &[0b1010], MutationType::BitXor);
+        assert_eq!(block_on(trx.get(b"n", false)).unwrap(), Some(vec![0, 1]));
+        block_on(trx.commit()).unwrap();
+
+        assert_eq!(
+            db.dump(),
+            vec![
+                (b"m".to_vec(), vec![5]),
+                (b"max".to_vec(), vec![3, 1]),
+                (b"n".to_vec(), vec![0, 1]),
+                (b"s".to_vec(), b"abc".to_vec()),
+                (b"x".to_vec(), vec![0b0110]),
+            ]
+        );
+
+        let trx = db.create_trx().unwrap();
+        trx.atomic_op(b"n", &[0; 14], MutationType::SetVersionstampedValue);
+        assert_eq!(
+            block_on(trx.commit()).unwrap_err().code(),
+            CLIENT_INVALID_OPERATION
+        );
+    }
+}
@@ -0,0 +1,22 @@
+// Copyright 2018 foundationdb-rs developers, https://github.com/Clikengo/foundationdb-rs/graphs/contributors
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! Helpers to test the code using this crate
+//!
+//! `MockDatabase` is an in-memory database implementing the `FdbRead` and `FdbWrite` traits,
+//! so layers written against these traits can be unit tested without a running cluster. It
+//! requires the `mock` feature.
+//!
+//! `data::DataSet` deterministically generates key-value pairs to fill a database for tests and
+//! benchmarks.
+
+pub mod data;
+#[cfg(feature = "mock")]
+mod mock;
+
+#[cfg(feature = "mock")]
+pub use mock::{MockDatabase, MockTransaction};
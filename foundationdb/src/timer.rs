@@ -0,0 +1,186 @@
+// Copyright 2018 foundationdb-rs developers, https://github.com/Clikengo/foundationdb-rs/graphs/contributors
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! Delays waited by a single timer thread, without depending on a runtime
+
+use std::collections::BTreeMap;
+use std::future::Future;
+use std::pin::Pin;
+use std::ptr;
+use std::sync::atomic::{AtomicPtr, AtomicU64, Ordering};
+use std::sync::{Condvar, Mutex, Once};
+use std::task::{Context, Poll, Waker};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// The pending delays, by deadline and id, with the waker of the task waiting for them.
+type Wakers = BTreeMap<(Instant, u64), Waker>;
+
+struct Timer {
+    wakers: Mutex<Wakers>,
+    changed: Condvar,
+}
+
+static TIMER: AtomicPtr<Timer> = AtomicPtr::new(ptr::null_mut());
+static TIMER_START: Once = Once::new();
+static NEXT_ID: AtomicU64 = AtomicU64::new(0);
+
+/// Returns the timer, starting its thread on the first call.
+fn timer() -> &'static Timer {
+    TIMER_START.call_once(|| {
+        let timer: &'static Timer = Box::leak(Box::new(Timer {
+            wakers: Mutex::new(BTreeMap::new()),
+            changed: Condvar::new(),
+        }));
+        thread::Builder::new()
+            .name("foundationdb-timer".to_owned())
+            .spawn(move || timer.run())
+            .expect("failed to start the timer thread");
+        TIMER.store(timer as *const Timer as *mut Timer, Ordering::Release);
+    });
+    // Safe because the timer is leaked, and stored before `call_once` returns
+    unsafe { &*TIMER.load(Ordering::Acquire) }
+}
+
+impl Timer {
+    fn lock(&self) -> std::sync::MutexGuard<'_, Wakers> {
+        self.wakers.lock().unwrap_or_else(|err| err.into_inner())
+    }
+
+    fn register(&self, key: (Instant, u64), waker: Waker) {
+        let mut wakers = self.lock();
+        let earliest = match wakers.keys().next() {
+            Some(next) => key < *next,
+            None => true,
+        };
+        wakers.insert(key, waker);
+        if earliest {
+            self.changed.notify_one();
+        }
+    }
+
+    fn unregister(&self, key: &(Instant, u64)) {
+        self.lock().remove(key);
+    }
+
+    fn run(&self) {
+        let mut elapsed = Vec::new();
+        let mut wakers = self.lock();
+        loop {
+            let now = Instant::now();
+            while let Some(&key) = wakers.keys().next() {
+                if key.0 > now {
+                    break;
+                }
+                elapsed.extend(wakers.remove(&key));
+            }
+            if !elapsed.is_empty() {
+                // woken without the lock, as a waker may poll its delay right away
+                drop(wakers);
+                elapsed.drain(..).for_each(Waker::wake);
+                wakers = self.lock();
+                continue;
+            }
+            wakers = match wakers.keys().next() {
+                Some(&(deadline, _)) => match self.changed.wait_timeout(wakers, deadline - now) {
+                    Ok((wakers, _)) => wakers,
+                    Err(err) => err.into_inner().0,
+                },
+                None => self
+                    .changed
+                    .wait(wakers)
+                    .unwrap_or_else(|err| err.into_inner()),
+            };
+        }
+    }
+}
+
+/// A future completing once its deadline is reached, see `delay`.
+///
+/// Dropping it before its deadline removes it from the timer.
+pub(crate) struct Delay {
+    // `None` if the deadline can't be represented, it is then never reached
+    key: Option<(Instant, u64)>,
+    registered: bool,
+}
+
+impl Future for Delay {
+    type Output = ();
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        let key = match self.key {
+            Some(key) => key,
+            None => return Poll::Pending,
+        };
+        if Instant::now() >= key.0 {
+            if self.registered {
+                timer().unregister(&key);
+                self.registered = false;
+            }
+            return Poll::Ready(());
+        }
+        timer().register(key, cx.waker().clone());
+        self.registered = true;
+        Poll::Pending
+    }
+}
+
+impl Drop for Delay {
+    fn drop(&mut self) {
+        if let (true, Some(key)) = (self.registered, &self.key) {
+            timer().unregister(key);
+        }
+    }
+}
+
+/// Waits for `duration` without depending on a runtime.
+///
+/// All the delays are waited by a single timer thread.
+pub(crate) fn delay(duration: Duration) -> Delay {
+    let id = NEXT_ID.fetch_add(1, Ordering::Relaxed);
+    Delay {
+        key: Instant::now()
+            .checked_add(duration)
+            .map(|deadline| (deadline, id)),
+        registered: false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::future::{self, Either};
+
+    #[test]
+    fn test_delay() {
+        let start = Instant::now();
+        let delays = (0..10).rev().map(|i| delay(Duration::from_millis(i * 5)));
+        futures::executor::block_on(future::join_all(delays));
+        assert!(start.elapsed() >= Duration::from_millis(45));
+
+        // an unrepresentable deadline is never reached
+        let never = delay(Duration::new(u64::MAX, 0));
+        let soon = delay(Duration::from_millis(10));
+        match futures::executor::block_on(future::select(never, soon)) {
+            Either::Right(..) => {}
+            Either::Left(..) => panic!("the delay should never elapse"),
+        }
+    }
+
+    #[test]
+    fn test_delay_drop() {
+        let mut pending = delay(Duration::from_secs(3600));
+        let key = pending.key.expect("the deadline should be representable");
+        let waker = futures::task::noop_waker();
+        let mut cx = Context::from_waker(&waker);
+        assert!(Pin::new(&mut pending).poll(&mut cx).is_pending());
+        assert!(timer().lock().contains_key(&key));
+
+        drop(pending);
+        assert!(!timer().lock().contains_key(&key));
+    }
+}
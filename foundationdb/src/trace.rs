@@ -0,0 +1,139 @@
+// Copyright 2018 foundationdb-rs developers, https://github.com/Clikengo/foundationdb-rs/graphs/contributors
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! Spans reported with `tracing` when the `tracing` feature is enabled
+//!
+//! Without the feature, the functions of this module do nothing and return the futures they
+//! are given as is.
+//!
+//! The spans, all at the `DEBUG` level, are:
+//! - `fdb_transact`, around `Database::transact`, with the number of `attempts` and the
+//!   `error_code` the transaction finally failed with, and a `retrying` event for each retry,
+//!   preceded by a `conflict` event with the number of conflicting `ranges` when
+//!   `TransactOption::report_conflicting_keys` is set,
+//! - `fdb_commit`, around `Transaction::commit`, with its `latency_us` and `error_code`,
+//! - `fdb_get_range`, around each batch of `Transaction::get_range`, with its `iteration` and
+//!   the number of `rows` read.
+
+use futures::prelude::*;
+#[cfg(feature = "tracing")]
+use std::time::Instant;
+#[cfg(feature = "tracing")]
+use tracing::{field, Instrument};
+
+use crate::future::FdbValues;
+use crate::{FdbError, FdbResult};
+
+/// The span of a `Database::transact` call.
+pub(crate) struct TransactSpan {
+    #[cfg(feature = "tracing")]
+    span: tracing::Span,
+}
+
+impl TransactSpan {
+    pub(crate) fn new() -> Self {
+        Self {
+            #[cfg(feature = "tracing")]
+            span: tracing::debug_span!(
+                "fdb_transact",
+                attempts = field::Empty,
+                error_code = field::Empty
+            ),
+        }
+    }
+
+    /// Runs `fut` in the span.
+    pub(crate) fn instrument<F: Future>(&self, fut: F) -> impl Future<Output = F::Output> {
+        #[cfg(feature = "tracing")]
+        let fut = fut.instrument(self.span.clone());
+        fut
+    }
+
+    /// Records the start of the attempt `attempt`, starting at 1.
+    pub(crate) fn attempt(&self, attempt: usize) {
+        #[cfg(feature = "tracing")]
+        self.span.record("attempts", &(attempt as u64));
+        #[cfg(not(feature = "tracing"))]
+        let _ = attempt;
+    }
+
+    /// Reports that the transaction is retried after an error with the given code.
+    pub(crate) fn retry(&self, code: i32) {
+        #[cfg(feature = "tracing")]
+        tracing::debug!(parent: &self.span, code, "retrying");
+        #[cfg(not(feature = "tracing"))]
+        let _ = code;
+    }
+
+    /// Reports that the commit conflicted on `ranges` ranges.
+    #[cfg(feature = "fdb-7_1")]
+    pub(crate) fn conflict(&self, ranges: usize) {
+        #[cfg(feature = "tracing")]
+        tracing::debug!(parent: &self.span, ranges, "conflict");
+        #[cfg(not(feature = "tracing"))]
+        let _ = ranges;
+    }
+
+    /// Records the error the transaction failed with, and returns it.
+    pub(crate) fn failed(&self, err: FdbError) -> FdbError {
+        #[cfg(feature = "tracing")]
+        self.span.record("error_code", &i64::from(err.code()));
+        err
+    }
+}
+
+/// Runs the commit future `fut` in a `fdb_commit` span.
+pub(crate) fn commit<F>(fut: F) -> impl Future<Output = F::Output>
+where
+    F: Future<Output = FdbResult<()>>,
+{
+    #[cfg(feature = "tracing")]
+    let fut = {
+        let span = tracing::debug_span!(
+            "fdb_commit",
+            latency_us = field::Empty,
+            error_code = field::Empty
+        );
+        let record = span.clone();
+        let start = Instant::now();
+        fut.map(move |r| {
+            record.record("latency_us", &(start.elapsed().as_micros() as u64));
+            if let Err(err) = &r {
+                record.record("error_code", &i64::from(err.code()));
+            }
+            r
+        })
+        .instrument(span)
+    };
+    fut
+}
+
+/// Runs the `get_range` future `fut`, reading the batch `iteration`, in a `fdb_get_range` span.
+pub(crate) fn get_range<F>(fut: F, iteration: usize) -> impl Future<Output = F::Output>
+where
+    F: Future<Output = FdbResult<FdbValues>>,
+{
+    #[cfg(feature = "tracing")]
+    let fut = {
+        let span = tracing::debug_span!(
+            "fdb_get_range",
+            iteration = iteration as u64,
+            rows = field::Empty
+        );
+        let record = span.clone();
+        fut.map(move |r| {
+            if let Ok(values) = &r {
+                record.record("rows", &(values.len() as u64));
+            }
+            r
+        })
+        .instrument(span)
+    };
+    #[cfg(not(feature = "tracing"))]
+    let _ = iteration;
+    fut
+}
@@ -14,14 +14,23 @@ use foundationdb_sys as fdb_sys;
 use std::fmt;
 use std::ops::Deref;
 use std::ptr::NonNull;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::{Duration, Instant};
 
 use crate::future::*;
 use crate::keyselector::*;
 use crate::options;
+use crate::redact::RedactedBytes;
+use crate::trace;
+use crate::tuple::Versionstamp;
 use crate::{error, FdbError, FdbResult};
+#[cfg(feature = "metrics")]
+use crate::{metrics, Instrumentation};
 
 use futures::{
-    future, future::Either, stream, Future, FutureExt, Stream, TryFutureExt, TryStreamExt,
+    future, future::Either, stream, Future, FutureExt, Stream, StreamExt, TryFutureExt,
+    TryStreamExt,
 };
 
 /// A committed transaction.
@@ -47,7 +56,7 @@ impl TransactionCommitted {
     pub fn committed_version(&self) -> FdbResult<i64> {
         let mut version: i64 = 0;
         error::eval(unsafe {
-            fdb_sys::fdb_transaction_get_committed_version(self.tr.inner.as_ptr(), &mut version)
+            fdb_sys::fdb_transaction_get_committed_version(self.tr.lock().as_ptr(), &mut version)
         })?;
         Ok(version)
     }
@@ -72,9 +81,37 @@ impl From<TransactionCommitted> for Transaction {
 pub struct TransactionCommitError {
     tr: Transaction,
     err: FdbError,
+    size_violation: Option<SizeViolation>,
 }
 
 impl TransactionCommitError {
+    /// Returns the first key or value too large given to this transaction, if the commit was
+    /// refused because of it, see `Transaction::set_size_validation`.
+    pub fn size_violation(&self) -> Option<&SizeViolation> {
+        self.size_violation.as_ref()
+    }
+
+    /// Returns `true` if the transaction was committed, but has no versionstamp because it was
+    /// read-only, see `Transaction::commit_with_versionstamp`.
+    pub fn is_read_only(&self) -> bool {
+        self.err.code() == NO_COMMIT_VERSION
+    }
+
+    /// Reads the `(begin, end)` ranges read by the transaction that were modified by another
+    /// transaction, and caused the commit to fail with `not_committed` (1020).
+    ///
+    /// The ranges are only reported if the transaction has the option
+    /// `TransactionOption::ReportConflictingKeys`, this returns no range otherwise. They must be
+    /// read before `on_error` or `reset`, which clear them.
+    #[cfg(feature = "fdb-7_1")]
+    pub async fn conflicting_keys(&self) -> FdbResult<Vec<(Vec<u8>, Vec<u8>)>> {
+        let kvs = self
+            .tr
+            .get_special_range(crate::special_keys::SpecialKeyRange::ConflictingKeys)
+            .await?;
+        Ok(crate::special_keys::pair_conflict_ranges(kvs))
+    }
+
     /// Implements the recommended retry and backoff behavior for a transaction. This function knows
     /// which of the error codes generated by other `Transaction` functions represent temporary
     /// error conditions and which represent application errors that should be handled by the
@@ -85,10 +122,17 @@ impl TransactionCommitError {
     /// You should not call this method most of the times and use `Database::transact` which
     /// implements a retry loop strategy for you.
     pub fn on_error(self) -> impl Future<Output = FdbResult<Transaction>> {
+        self.tr.invalidate_read_version_cache(&self.err);
         FdbFuture::<()>::new(unsafe {
-            fdb_sys::fdb_transaction_on_error(self.tr.inner.as_ptr(), self.err.code())
+            fdb_sys::fdb_transaction_on_error(self.tr.lock().as_ptr(), self.err.code())
+        })
+        .and_then(|()| {
+            // `on_error` resets the transaction
+            self.tr.clear_mutations();
+            #[cfg(feature = "fdb-7_1")]
+            self.tr.clear_tags();
+            future::ready(self.tr.reapply_options().map(|()| self.tr))
         })
-        .map_ok(|()| self.tr)
     }
 
     /// Reset the transaction to its initial state.
@@ -115,13 +159,128 @@ impl From<TransactionCommitError> for FdbError {
 
 impl fmt::Debug for TransactionCommitError {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "TransactionCommitError({})", self.err)
+        write!(f, "TransactionCommitError({})", self)
     }
 }
 
 impl fmt::Display for TransactionCommitError {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        self.err.fmt(f)
+        match &self.size_violation {
+            Some(violation) => write!(f, "{}: {}", self.err, violation),
+            None => self.err.fmt(f),
+        }
+    }
+}
+
+/// `key_too_large` error code
+const KEY_TOO_LARGE: i32 = 2102;
+/// `value_too_large` error code
+const VALUE_TOO_LARGE: i32 = 2103;
+/// `no_commit_version` error code, for the versionstamp of a read-only transaction
+const NO_COMMIT_VERSION: i32 = 2021;
+/// `future_version` error code
+const FUTURE_VERSION: i32 = 1009;
+/// `client_invalid_operation` error code
+const CLIENT_INVALID_OPERATION: i32 = 2000;
+/// The maximum size of a key, outside of the system keys
+const KEY_SIZE_LIMIT: usize = 10_000;
+/// The maximum size of a system key, i.e. a key starting with `\xFF`
+const SYSTEM_KEY_SIZE_LIMIT: usize = 30_000;
+/// The maximum size of a value
+const VALUE_SIZE_LIMIT: usize = 100_000;
+/// The number of bytes of the key kept in a `SizeViolation`
+const SIZE_VIOLATION_KEY_PREFIX: usize = 64;
+
+/// The number of reads in flight in `Transaction::get_many`
+pub const GET_MANY_DEFAULT_CONCURRENCY: usize = 128;
+
+/// `invalid_option_value` error code
+#[cfg(feature = "fdb-7_1")]
+const INVALID_OPTION_VALUE: i32 = 2006;
+/// `too_many_tags` error code
+#[cfg(feature = "fdb-7_1")]
+const TOO_MANY_TAGS: i32 = 2109;
+/// `tag_too_long` error code
+#[cfg(feature = "fdb-7_1")]
+const TAG_TOO_LONG: i32 = 2110;
+/// The maximum length of a transaction tag
+#[cfg(feature = "fdb-7_1")]
+const TAG_LENGTH_LIMIT: usize = 16;
+/// The maximum number of tags of a transaction
+#[cfg(feature = "fdb-7_1")]
+const TAGS_PER_TRANSACTION_LIMIT: usize = 5;
+
+/// A key or a value larger than the limits of FoundationDB, found by
+/// `Transaction::set_size_validation`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SizeViolation {
+    key: Vec<u8>,
+    key_len: usize,
+    value_len: Option<usize>,
+    limit: usize,
+}
+
+impl SizeViolation {
+    fn check(key: &[u8], value: Option<&[u8]>) -> Option<Self> {
+        let key_limit = if key.starts_with(b"\xFF") {
+            SYSTEM_KEY_SIZE_LIMIT
+        } else {
+            KEY_SIZE_LIMIT
+        };
+        let (value_len, limit) = match value {
+            _ if key.len() > key_limit => (None, key_limit),
+            Some(value) if value.len() > VALUE_SIZE_LIMIT => (Some(value.len()), VALUE_SIZE_LIMIT),
+            _ => return None,
+        };
+        Some(SizeViolation {
+            key: key[..key.len().min(SIZE_VIOLATION_KEY_PREFIX)].to_vec(),
+            key_len: key.len(),
+            value_len,
+            limit,
+        })
+    }
+
+    /// The error code the commit fails with: `key_too_large` (2102) or `value_too_large` (2103)
+    pub fn code(&self) -> i32 {
+        match self.value_len {
+            None => KEY_TOO_LARGE,
+            Some(_) => VALUE_TOO_LARGE,
+        }
+    }
+
+    /// The first bytes of the key, truncated to 64 bytes
+    pub fn key(&self) -> &[u8] {
+        &self.key
+    }
+
+    /// The size of the key, or of the value if the key is not too large
+    pub fn size(&self) -> usize {
+        self.value_len.unwrap_or(self.key_len)
+    }
+
+    /// The limit exceeded by `size()`
+    pub fn limit(&self) -> usize {
+        self.limit
+    }
+}
+
+impl fmt::Display for SizeViolation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let ellipsis = if self.key_len > self.key.len() {
+            "..."
+        } else {
+            ""
+        };
+        match self.value_len {
+            None => write!(f, "key {}{}", RedactedBytes(&self.key), ellipsis)?,
+            Some(_) => write!(f, "value of key {}{}", RedactedBytes(&self.key), ellipsis)?,
+        }
+        write!(
+            f,
+            " is {} bytes, larger than the limit of {} bytes",
+            self.size(),
+            self.limit
+        )
     }
 }
 
@@ -149,6 +308,49 @@ impl From<TransactionCancelled> for Transaction {
     }
 }
 
+/// The latest read version known by the transactions of a database, see
+/// `Database::set_read_version_cache`.
+pub(crate) struct ReadVersionCache {
+    max_staleness: Duration,
+    // the version, with the instant it was known to be the latest one
+    latest: Mutex<Option<(i64, Instant)>>,
+}
+
+impl ReadVersionCache {
+    pub(crate) fn new(max_staleness: Duration) -> Self {
+        Self {
+            max_staleness,
+            latest: Mutex::new(None),
+        }
+    }
+
+    fn latest(&self) -> std::sync::MutexGuard<Option<(i64, Instant)>> {
+        self.latest.lock().unwrap_or_else(|err| err.into_inner())
+    }
+
+    /// Returns the cached version if it is younger than `max_staleness`.
+    fn get(&self) -> Option<i64> {
+        match *self.latest() {
+            Some((version, at)) if at.elapsed() <= self.max_staleness => Some(version),
+            _ => None,
+        }
+    }
+
+    /// Caches `version`, known to be the latest one at `at`, unless a newer version is cached.
+    fn observe(&self, version: i64, at: Instant) {
+        let mut latest = self.latest();
+        match *latest {
+            Some((cached, _)) if cached >= version => {}
+            _ => *latest = Some((version, at)),
+        }
+    }
+
+    /// Forgets the cached version, e.g. when the cluster reports it as a future version.
+    fn invalidate(&self) {
+        *self.latest() = None;
+    }
+}
+
 /// In FoundationDB, a transaction is a mutable snapshot of a database.
 ///
 /// All read and write operations on a transaction see and modify an otherwise-unchanging version of the database and only change the underlying database if and when the transaction is committed. Read operations do see the effects of previous write operations on the same transaction. Committing a transaction usually succeeds in the absence of conflicts.
@@ -158,18 +360,57 @@ impl From<TransactionCancelled> for Transaction {
 /// Transactions group operations into a unit with the properties of atomicity, isolation, and durability. Transactions also provide the ability to maintain an application’s invariants or integrity constraints, supporting the property of consistency. Together these properties are known as ACID.
 ///
 /// Transactions are also causally consistent: once a transaction has been successfully committed, all subsequently created transactions will see the modifications made by it.
-#[derive(Debug)]
+///
+/// # Thread safety
+///
+/// A transaction is `Send` and `Sync`: it can be shared between threads or tasks, e.g. to read
+/// keys concurrently. The C API doesn't allow concurrent calls on a transaction, so the bindings
+/// make them one at a time with an internal lock, held only while a call is made and not while
+/// its future is pending. Operations made from different threads are applied in the order the
+/// lock was taken, which is not deterministic: synchronize the threads if the order of their
+/// writes matters, e.g. for conflicting `set` and `clear` calls on the same key.
 pub struct Transaction {
     // Order of fields should not be changed, because Rust drops field top-to-bottom, and
     // transaction should be dropped before cluster.
     inner: NonNull<fdb_sys::FDBTransaction>,
+    /// Held during each call to the C API, see `lock`
+    calls: Mutex<()>,
+    mutation_bytes: AtomicUsize,
+    size_violation: Option<Box<Mutex<Option<SizeViolation>>>>,
+    remembered_options: Option<Box<Mutex<Vec<options::TransactionOption>>>>,
+    #[cfg(feature = "fdb-7_1")]
+    tags: Mutex<Vec<Vec<u8>>>,
+    #[cfg(feature = "metrics")]
+    pub(crate) instrumentation: Option<Arc<dyn Instrumentation>>,
+    pub(crate) read_version_cache: Option<Arc<ReadVersionCache>>,
 }
 unsafe impl Send for Transaction {}
+// safe because the calls to the C API are serialized by `calls`
 unsafe impl Sync for Transaction {}
 
+/// A transaction locked for a call to the C API, see `Transaction::lock`
+struct RawTransaction<'a> {
+    _guard: MutexGuard<'a, ()>,
+    inner: NonNull<fdb_sys::FDBTransaction>,
+}
+
+impl<'a> RawTransaction<'a> {
+    fn as_ptr(&self) -> *mut fdb_sys::FDBTransaction {
+        self.inner.as_ptr()
+    }
+}
+
+impl fmt::Debug for Transaction {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Transaction")
+            .field("inner", &self.inner)
+            .finish()
+    }
+}
+
 /// Converts Rust `bool` into `fdb_sys::fdb_bool_t`
 #[inline]
-fn fdb_bool(v: bool) -> fdb_sys::fdb_bool_t {
+pub(crate) fn fdb_bool(v: bool) -> fdb_sys::fdb_bool_t {
     if v {
         1
     } else {
@@ -177,7 +418,7 @@ fn fdb_bool(v: bool) -> fdb_sys::fdb_bool_t {
     }
 }
 #[inline]
-fn fdb_len(len: usize, context: &'static str) -> std::os::raw::c_int {
+pub(crate) fn fdb_len(len: usize, context: &'static str) -> std::os::raw::c_int {
     assert!(
         len <= i32::max_value() as usize,
         "{}.len() > i32::max_value()",
@@ -244,6 +485,10 @@ impl<'a> RangeOption<'a> {
         self
     }
 
+    /// Returns the range option to use to read the batch following `kvs`, or `None` if there is
+    /// nothing left to read.
+    ///
+    /// Reading stops once `limit` rows were read, even if the database reports more rows.
     pub fn next_range(mut self, kvs: &FdbValues) -> Option<Self> {
         if !kvs.more() {
             return None;
@@ -264,8 +509,38 @@ impl<'a> RangeOption<'a> {
         } else {
             self.begin.make_first_greater_than(last_key);
         }
+
+        // the remaining range can be known to be empty without asking the database
+        if self.is_trivially_empty() {
+            return None;
+        }
         Some(self)
     }
+
+    /// Returns `true` if the range is known to be empty without reading the database.
+    ///
+    /// This is the case when both selectors are `first_greater_or_equal` or `first_greater_than`
+    /// selectors and `begin` doesn't resolve before `end`, for example when both selectors are
+    /// the same `first_greater_or_equal` key. Other ranges might still be empty.
+    pub fn is_trivially_empty(&self) -> bool {
+        match (self.begin.lower_bound(), self.end.lower_bound()) {
+            (Some(begin), Some(end)) => begin.ge(end),
+            _ => false,
+        }
+    }
+
+    /// Returns this range with owned selectors, e.g. to keep it after the keys it borrows.
+    pub fn into_owned(self) -> RangeOption<'static> {
+        RangeOption {
+            begin: self.begin.into_owned(),
+            end: self.end.into_owned(),
+            limit: self.limit,
+            target_bytes: self.target_bytes,
+            mode: self.mode,
+            reverse: self.reverse,
+            __non_exhaustive: std::marker::PhantomData,
+        }
+    }
 }
 
 impl<'a> Default for RangeOption<'a> {
@@ -340,12 +615,207 @@ impl Into<RangeOption<'static>> for std::ops::RangeInclusive<Vec<u8>> {
 
 impl Transaction {
     pub(crate) fn new(inner: NonNull<fdb_sys::FDBTransaction>) -> Self {
-        Self { inner }
+        Self {
+            inner,
+            calls: Mutex::new(()),
+            mutation_bytes: AtomicUsize::new(0),
+            size_violation: None,
+            remembered_options: None,
+            #[cfg(feature = "fdb-7_1")]
+            tags: Mutex::new(Vec::new()),
+            #[cfg(feature = "metrics")]
+            instrumentation: metrics::global_instrumentation(),
+            read_version_cache: None,
+        }
+    }
+
+    /// Locks the transaction for a call to the C API, until the returned guard is dropped.
+    ///
+    /// The C API doesn't allow concurrent calls on a transaction, so each call is made with the
+    /// lock held. It is only held while the call is made, not while its future is pending.
+    fn lock(&self) -> RawTransaction {
+        RawTransaction {
+            _guard: self.calls.lock().unwrap_or_else(|err| err.into_inner()),
+            inner: self.inner,
+        }
+    }
+
+    /// Forgets the read version cached by the database of the transaction if `err` reports it
+    /// as a future version, so that the retry gets a fresh one.
+    fn invalidate_read_version_cache(&self, err: &FdbError) {
+        if err.code() == FUTURE_VERSION {
+            if let Some(cache) = &self.read_version_cache {
+                cache.invalidate();
+            }
+        }
+    }
+
+    /// Returns the number of bytes of the keys, values and parameters given to `set`, `clear`,
+    /// `clear_range` and `atomic_op` since the transaction was created or reset.
+    ///
+    /// Unlike `get_approximate_size`, this is tracked by the bindings, so it is available with
+    /// every API version and doesn't need a round trip. The conflict ranges are not counted, so
+    /// the size checked against the transaction size limit at commit is usually larger.
+    pub fn estimated_mutation_bytes(&self) -> usize {
+        self.mutation_bytes.load(Ordering::Relaxed)
+    }
+
+    fn add_mutation_bytes(&self, bytes: usize) {
+        self.mutation_bytes.fetch_add(bytes, Ordering::Relaxed);
+    }
+
+    /// Enables or disables the validation of the sizes of keys and values.
+    ///
+    /// FoundationDB refuses keys larger than 10,000 bytes and values larger than 100,000 bytes,
+    /// but only reports it when the transaction is committed. When enabled, `set`, `clear_range`
+    /// and `atomic_op` check the sizes of their parameters, and `commit` fails with the first
+    /// violation, see `TransactionCommitError::size_violation`, without contacting the cluster.
+    ///
+    /// Validation is disabled by default, see `Database::set_size_validation`.
+    pub fn set_size_validation(&mut self, enabled: bool) {
+        self.size_violation = if enabled {
+            Some(Box::new(Mutex::new(None)))
+        } else {
+            None
+        };
+    }
+
+    fn validate_size(&self, key: &[u8], value: Option<&[u8]>) {
+        if let Some(size_violation) = &self.size_violation {
+            if let Some(violation) = SizeViolation::check(key, value) {
+                let mut first = size_violation.lock().unwrap_or_else(|err| err.into_inner());
+                if first.is_none() {
+                    *first = Some(violation);
+                }
+            }
+        }
+    }
+
+    /// Forgets the mutations tracked by the bindings, after `reset` or `on_error`.
+    fn clear_mutations(&self) {
+        self.mutation_bytes.store(0, Ordering::Relaxed);
+        if let Some(size_violation) = &self.size_violation {
+            *size_violation.lock().unwrap_or_else(|err| err.into_inner()) = None;
+        }
     }
 
     /// Called to set an option on an FDBTransaction.
+    ///
+    /// If `remember_options` is enabled, the option is recorded once applied, so that it is
+    /// applied again after `reset` and `on_error`.
     pub fn set_option(&self, opt: options::TransactionOption) -> FdbResult<()> {
-        unsafe { opt.apply(self.inner.as_ptr()) }
+        match &self.remembered_options {
+            Some(remembered) => {
+                unsafe { opt.clone().apply(self.lock().as_ptr()) }?;
+                remember_option(
+                    &mut remembered.lock().unwrap_or_else(|err| err.into_inner()),
+                    opt,
+                );
+                Ok(())
+            }
+            None => unsafe { opt.apply(self.lock().as_ptr()) },
+        }
+    }
+
+    /// Enables or disables the re-application of the options set with `set_option` after `reset`
+    /// and `on_error`.
+    ///
+    /// The C client clears all the options of a transaction when it is reset, which silently
+    /// drops options like `TransactionOption::AccessSystemKeys` on the retried attempt. When
+    /// enabled, the options set from now on are recorded, see `applied_options`, and applied
+    /// again to the reset transaction. An option set several times is only applied once, with
+    /// its last value, and `NextWriteNoWriteConflictRange`, which only applies to the next write,
+    /// is not recorded.
+    ///
+    /// Disabled by default, `Database::transact_boxed` enables it.
+    pub fn remember_options(&mut self, enabled: bool) {
+        match (enabled, &self.remembered_options) {
+            (true, None) => self.remembered_options = Some(Box::new(Mutex::new(Vec::new()))),
+            (false, Some(_)) => self.remembered_options = None,
+            _ => {}
+        }
+    }
+
+    /// Returns the options recorded since `remember_options` was enabled, in the order they are
+    /// applied again after `reset` and `on_error`.
+    ///
+    /// This is empty if `remember_options` is disabled.
+    pub fn applied_options(&self) -> Vec<options::TransactionOption> {
+        match &self.remembered_options {
+            Some(remembered) => remembered
+                .lock()
+                .unwrap_or_else(|err| err.into_inner())
+                .clone(),
+            None => Vec::new(),
+        }
+    }
+
+    /// Applies the options recorded by `set_option` again, after `reset` or `on_error`.
+    fn reapply_options(&self) -> FdbResult<()> {
+        if let Some(remembered) = &self.remembered_options {
+            let remembered = remembered.lock().unwrap_or_else(|err| err.into_inner());
+            for opt in remembered.iter() {
+                unsafe { opt.clone().apply(self.lock().as_ptr()) }?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Adds a tag to the transaction, which can be used to throttle the transactions having it
+    /// with `fdbcli`, see `TransactionOption::Tag`.
+    ///
+    /// The tag is checked before being set: a tag longer than 16 bytes fails with `tag_too_long`
+    /// (2110), a tag that is not valid UTF-8 fails with `invalid_option_value` (2006), and adding
+    /// more than 5 distinct tags fails with `too_many_tags` (2109). Like the other options, the
+    /// tags are cleared by `reset` and `on_error`.
+    #[cfg(feature = "fdb-7_1")]
+    pub fn set_tag(&self, tag: &[u8]) -> FdbResult<()> {
+        self.add_tag(tag, options::TransactionOption::Tag)
+    }
+
+    /// Adds a tag to the transaction, also used by the cluster to throttle the transactions
+    /// having it automatically when they are too busy, see `TransactionOption::AutoThrottleTag`.
+    ///
+    /// The tag is checked like in `set_tag`, both kinds of tags count in the limit of 5 tags.
+    #[cfg(feature = "fdb-7_1")]
+    pub fn set_auto_throttle_tag(&self, tag: &[u8]) -> FdbResult<()> {
+        self.add_tag(tag, options::TransactionOption::AutoThrottleTag)
+    }
+
+    /// Forgets the tags added by `set_tag` and `set_auto_throttle_tag`, after `reset` or
+    /// `on_error`, unless they are set again as remembered options.
+    #[cfg(feature = "fdb-7_1")]
+    fn clear_tags(&self) {
+        if self.remembered_options.is_none() {
+            self.tags
+                .lock()
+                .unwrap_or_else(|err| err.into_inner())
+                .clear();
+        }
+    }
+
+    #[cfg(feature = "fdb-7_1")]
+    fn add_tag(
+        &self,
+        tag: &[u8],
+        option: fn(String) -> options::TransactionOption,
+    ) -> FdbResult<()> {
+        if tag.len() > TAG_LENGTH_LIMIT {
+            return Err(FdbError::from_code(TAG_TOO_LONG));
+        }
+        let tag_str =
+            std::str::from_utf8(tag).map_err(|_| FdbError::from_code(INVALID_OPTION_VALUE))?;
+
+        let mut tags = self.tags.lock().unwrap_or_else(|err| err.into_inner());
+        let is_new = !tags.iter().any(|t| t.as_slice() == tag);
+        if is_new && tags.len() >= TAGS_PER_TRANSACTION_LIMIT {
+            return Err(FdbError::from_code(TOO_MANY_TAGS));
+        }
+        self.set_option(option(tag_str.to_owned()))?;
+        if is_new {
+            tags.push(tag.to_vec());
+        }
+        Ok(())
     }
 
     /// Modify the database snapshot represented by transaction to change the given
@@ -360,9 +830,17 @@ impl Transaction {
     /// * `key` - the name of the key to be inserted into the database.
     /// * `value` - the value to be inserted into the database
     pub fn set(&self, key: &[u8], value: &[u8]) {
+        self.add_mutation_bytes(key.len() + value.len());
+        self.validate_size(key, Some(value));
+        #[cfg(feature = "metrics")]
+        {
+            if let Some(instrumentation) = &self.instrumentation {
+                instrumentation.on_write(key.len() + value.len());
+            }
+        }
         unsafe {
             fdb_sys::fdb_transaction_set(
-                self.inner.as_ptr(),
+                self.lock().as_ptr(),
                 key.as_ptr(),
                 fdb_len(key.len(), "key"),
                 value.as_ptr(),
@@ -382,9 +860,10 @@ impl Transaction {
     ///
     /// * `key` - the name of the key to be removed from the database.
     pub fn clear(&self, key: &[u8]) {
+        self.add_mutation_bytes(key.len());
         unsafe {
             fdb_sys::fdb_transaction_clear(
-                self.inner.as_ptr(),
+                self.lock().as_ptr(),
                 key.as_ptr(),
                 fdb_len(key.len(), "key"),
             )
@@ -404,14 +883,60 @@ impl Transaction {
         key: &[u8],
         snapshot: bool,
     ) -> impl Future<Output = FdbResult<Option<FdbSlice>>> + Send + Sync + Unpin {
-        FdbFuture::new(unsafe {
+        let fut = FdbFuture::<Option<FdbSlice>>::new(unsafe {
             fdb_sys::fdb_transaction_get(
-                self.inner.as_ptr(),
+                self.lock().as_ptr(),
                 key.as_ptr(),
                 fdb_len(key.len(), "key"),
                 fdb_bool(snapshot),
             )
-        })
+        });
+        #[cfg(feature = "metrics")]
+        let fut = metrics::timed(
+            &self.instrumentation,
+            fut,
+            |instrumentation, duration, r| {
+                let bytes = match r {
+                    Ok(Some(value)) => value.len(),
+                    _ => 0,
+                };
+                instrumentation.on_get(duration, bytes)
+            },
+        );
+        fut
+    }
+
+    /// Reads the values of `keys`, returned in the order of `keys`.
+    ///
+    /// At most `GET_MANY_DEFAULT_CONCURRENCY` reads are in flight at the same time, see
+    /// `get_many_with_concurrency`.
+    pub fn get_many<'a>(
+        &'a self,
+        keys: impl IntoIterator<Item = &'a [u8]> + 'a,
+        snapshot: bool,
+    ) -> impl Stream<Item = FdbResult<Option<FdbSlice>>> + 'a {
+        self.get_many_with_concurrency(keys, snapshot, GET_MANY_DEFAULT_CONCURRENCY)
+    }
+
+    /// Reads the values of `keys` with at most `concurrency` reads in flight at the same time.
+    ///
+    /// The values are returned in the order of `keys`. The next reads are only started as the
+    /// stream is polled.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `concurrency` is 0.
+    pub fn get_many_with_concurrency<'a>(
+        &'a self,
+        keys: impl IntoIterator<Item = &'a [u8]> + 'a,
+        snapshot: bool,
+        concurrency: usize,
+    ) -> impl Stream<Item = FdbResult<Option<FdbSlice>>> + 'a {
+        assert!(concurrency > 0, "concurrency must be greater than 0");
+        // `buffered` keeps the reads in a `FuturesOrdered`
+        stream::iter(keys)
+            .map(move |key| self.get(key, snapshot))
+            .buffered(concurrency)
     }
 
     /// Modify the database snapshot represented by transaction to perform the operation indicated
@@ -439,9 +964,17 @@ impl Transaction {
     /// key, the benefits of using the atomic operation (for both conflict checking and performance)
     /// are lost.
     pub fn atomic_op(&self, key: &[u8], param: &[u8], op_type: options::MutationType) {
+        self.add_mutation_bytes(key.len() + param.len());
+        self.validate_size(key, Some(param));
+        #[cfg(feature = "metrics")]
+        {
+            if let Some(instrumentation) = &self.instrumentation {
+                instrumentation.on_write(key.len() + param.len());
+            }
+        }
         unsafe {
             fdb_sys::fdb_transaction_atomic_op(
-                self.inner.as_ptr(),
+                self.lock().as_ptr(),
                 key.as_ptr(),
                 fdb_len(key.len(), "key"),
                 param.as_ptr(),
@@ -451,6 +984,34 @@ impl Transaction {
         }
     }
 
+    /// Reads the current value of a 64-bit little-endian counter without adding a read conflict
+    /// range.
+    ///
+    /// The value is read with a snapshot read, which reflects the atomic operations already
+    /// issued by this transaction on the key, so atomic additions keep their benefits. Because
+    /// other transactions can modify the counter before this one commits, the returned value is
+    /// only advisory.
+    ///
+    /// This relies on the read-your-writes of snapshot reads: if
+    /// `TransactionOption::SnapshotRywDisable` or `TransactionOption::ReadYourWritesDisable` is
+    /// set, the value doesn't include the atomic operations of this transaction.
+    ///
+    /// A missing key is read as `0`, a value that is not 8 bytes long fails with
+    /// `client_invalid_operation` (2000).
+    pub async fn read_counter_dirty(&self, key: &[u8]) -> FdbResult<i64> {
+        match self.get(key, true).await? {
+            Some(value) if value.len() == 8 => {
+                let mut buf = [0u8; 8];
+                buf.copy_from_slice(&value);
+                Ok(i64::from_le_bytes(buf))
+            }
+            Some(value) => Err(FdbError::from_code(CLIENT_INVALID_OPERATION).with_context(
+                format!("counter value of {} bytes instead of 8", value.len()),
+            )),
+            None => Ok(0),
+        }
+    }
+
     /// Resolves a key selector against the keys in the database snapshot represented by
     /// transaction.
     ///
@@ -469,7 +1030,7 @@ impl Transaction {
         let key = selector.key();
         FdbFuture::new(unsafe {
             fdb_sys::fdb_transaction_get_key(
-                self.inner.as_ptr(),
+                self.lock().as_ptr(),
                 key.as_ptr(),
                 fdb_len(key.len(), "key"),
                 fdb_bool(selector.or_equal()),
@@ -479,6 +1040,18 @@ impl Transaction {
         })
     }
 
+    /// Returns the key `selector` resolves to, the same as `get_key`.
+    ///
+    /// Useful to check what a selector built with offsets designates, e.g. when debugging a
+    /// range read; its `Display` shows how it was built.
+    pub fn resolve_selector(
+        &self,
+        selector: &KeySelector,
+        snapshot: bool,
+    ) -> impl Future<Output = FdbResult<FdbSlice>> + Send + Sync + Unpin {
+        self.get_key(selector, snapshot)
+    }
+
     /// Reads all key-value pairs in the database snapshot represented by transaction (potentially
     /// limited by limit, target_bytes, or mode) which have a key lexicographically greater than or
     /// equal to the key resolved by the begin key selector and lexicographically less than the key
@@ -489,6 +1062,16 @@ impl Transaction {
     /// This method is a little more efficient than `get_ranges_keyvalues` but a little harder to
     /// use.
     ///
+    /// No request is made if the range is trivially empty (see `RangeOption::is_trivially_empty`),
+    /// and the stream ends as soon as the remaining range is known to be empty.
+    ///
+    /// # Cancellation
+    ///
+    /// The next batch is only requested when the stream is polled, and the pending request is
+    /// owned by the stream: dropping the stream, or an adapter that stops polling it like
+    /// `try_take_while`, cancels the request and frees its C future right away, without waiting
+    /// for the transaction to be dropped. Batches already returned stay valid.
+    ///
     /// # Arguments
     ///
     /// * `opt`: the range, limit, target_bytes and mode
@@ -498,7 +1081,14 @@ impl Transaction {
         opt: RangeOption<'a>,
         snapshot: bool,
     ) -> impl Stream<Item = FdbResult<FdbValues>> + Send + Sync + Unpin + 'a {
-        stream::unfold((1, Some(opt)), move |(iteration, maybe_opt)| {
+        let opt = if opt.is_trivially_empty() {
+            None
+        } else {
+            Some(opt)
+        };
+        // `unfold` only holds the pending `get_range` future while the stream is polled for the
+        // next batch, dropping it destroys (and cancels) the C future
+        stream::unfold((1, opt), move |(iteration, maybe_opt)| {
             if let Some(opt) = maybe_opt {
                 Either::Left(self.get_range(&opt, iteration as usize, snapshot).map(
                     move |maybe_values| {
@@ -536,6 +1126,32 @@ impl Transaction {
             .try_flatten()
     }
 
+    /// Reads all key-value pairs of the range like `get_ranges`, and appends copies of them to
+    /// `buffer`.
+    ///
+    /// Each batch is copied and freed as soon as it is read, and the buffer can be cleared and
+    /// reused for the next scan: reading many small keyvalues this way is cheaper than owning
+    /// them one by one with `get_ranges_keyvalues`. On error, the buffer holds the keyvalues of
+    /// the batches read before it.
+    ///
+    /// # Arguments
+    ///
+    /// * `opt`: the range, limit, target_bytes and mode
+    /// * `snapshot`: `true` if this is a [snapshot read](https://apple.github.io/foundationdb/api-c.html#snapshots)
+    /// * `buffer`: the buffer the keyvalues are appended to, it is not cleared first
+    pub async fn get_range_into(
+        &self,
+        opt: RangeOption<'_>,
+        snapshot: bool,
+        buffer: &mut KeyValueBuffer,
+    ) -> FdbResult<()> {
+        let mut ranges = self.get_ranges(opt, snapshot);
+        while let Some(values) = ranges.try_next().await? {
+            buffer.extend_from(&values);
+        }
+        Ok(())
+    }
+
     /// Reads all key-value pairs in the database snapshot represented by transaction (potentially
     /// limited by limit, target_bytes, or mode) which have a key lexicographically greater than or
     /// equal to the key resolved by the begin key selector and lexicographically less than the key
@@ -558,9 +1174,9 @@ impl Transaction {
         let key_begin = begin.key();
         let key_end = end.key();
 
-        FdbFuture::new(unsafe {
+        let fut = FdbFuture::<FdbValues>::new(unsafe {
             fdb_sys::fdb_transaction_get_range(
-                self.inner.as_ptr(),
+                self.lock().as_ptr(),
                 key_begin.as_ptr(),
                 fdb_len(key_begin.len(), "key_begin"),
                 fdb_bool(begin.or_equal()),
@@ -576,7 +1192,26 @@ impl Transaction {
                 fdb_bool(snapshot),
                 fdb_bool(opt.reverse),
             )
-        })
+        });
+        #[cfg(feature = "metrics")]
+        let fut = metrics::timed(
+            &self.instrumentation,
+            fut,
+            |instrumentation, duration, r| {
+                let (rows, bytes) = match r {
+                    Ok(values) => (
+                        values.len(),
+                        values
+                            .iter()
+                            .map(|kv| kv.key().len() + kv.value().len())
+                            .sum(),
+                    ),
+                    Err(..) => (0, 0),
+                };
+                instrumentation.on_get_range(duration, rows, bytes)
+            },
+        );
+        trace::get_range(fut, iteration)
     }
     /// Modify the database snapshot represented by transaction to remove all keys (if any) which
     /// are lexicographically greater than or equal to the given begin key and lexicographically
@@ -585,9 +1220,12 @@ impl Transaction {
     /// The modification affects the actual database only if transaction is later committed with
     /// `Transaction::commit`.
     pub fn clear_range(&self, begin: &[u8], end: &[u8]) {
+        self.add_mutation_bytes(begin.len() + end.len());
+        self.validate_size(begin, None);
+        self.validate_size(end, None);
         unsafe {
             fdb_sys::fdb_transaction_clear_range(
-                self.inner.as_ptr(),
+                self.lock().as_ptr(),
                 begin.as_ptr(),
                 fdb_len(begin.len(), "begin"),
                 end.as_ptr(),
@@ -620,12 +1258,83 @@ impl Transaction {
     /// snapshot reads or the transaction option for disabling “read-your-writes” has been invoked,
     /// any outstanding reads will immediately return errors.
     pub fn commit(self) -> impl Future<Output = TransactionResult> + Send + Sync + Unpin {
-        FdbFuture::<()>::new(unsafe { fdb_sys::fdb_transaction_commit(self.inner.as_ptr()) }).map(
-            move |r| match r {
-                Ok(()) => Ok(TransactionCommitted { tr: self }),
-                Err(err) => Err(TransactionCommitError { tr: self, err }),
+        let size_violation = self.size_violation.as_ref().and_then(|size_violation| {
+            size_violation
+                .lock()
+                .unwrap_or_else(|err| err.into_inner())
+                .clone()
+        });
+        if let Some(violation) = size_violation {
+            // the commit would fail anyway
+            return Either::Left(future::err(TransactionCommitError {
+                tr: self,
+                err: FdbError::from_code(violation.code()),
+                size_violation: Some(violation),
+            }));
+        }
+
+        let started = Instant::now();
+        let fut =
+            FdbFuture::<()>::new(unsafe { fdb_sys::fdb_transaction_commit(self.lock().as_ptr()) });
+        #[cfg(feature = "metrics")]
+        let fut = metrics::timed(
+            &self.instrumentation,
+            fut,
+            |instrumentation, duration, r| {
+                instrumentation.on_commit(duration, r.as_ref().map(|_| ()))
             },
-        )
+        );
+        let fut = trace::commit(fut);
+        Either::Right(fut.map(move |r| match r {
+            Ok(()) => {
+                let committed = TransactionCommitted { tr: self };
+                if let Some(cache) = &committed.tr.read_version_cache {
+                    // read-only transactions have a committed version of -1
+                    match committed.committed_version() {
+                        Ok(version) if version >= 0 => cache.observe(version, started),
+                        _ => {}
+                    }
+                }
+                Ok(committed)
+            }
+            Err(err) => Err(TransactionCommitError {
+                tr: self,
+                err,
+                size_violation: None,
+            }),
+        }))
+    }
+
+    /// Commits the transaction, like `commit`, and returns the versionstamp used by its
+    /// versionstamp operations, like `get_versionstamp_typed`.
+    ///
+    /// If the commit succeeds but the transaction was read-only, it has no versionstamp: the
+    /// returned error is not retryable and `TransactionCommitError::is_read_only` returns `true`.
+    pub fn commit_with_versionstamp(
+        self,
+    ) -> impl Future<Output = Result<(TransactionCommitted, Versionstamp), TransactionCommitError>>
+           + Send
+           + Sync
+           + Unpin {
+        // the versionstamp future must be created before the commit
+        let versionstamp = self.get_versionstamp_typed();
+        self.commit().and_then(move |committed| {
+            versionstamp.map(move |r| match r {
+                Ok(versionstamp) => Ok((committed, versionstamp)),
+                Err(err) => {
+                    let err = if err.code() == NO_COMMIT_VERSION {
+                        err.with_context("the read-only transaction has no versionstamp")
+                    } else {
+                        err
+                    };
+                    Err(TransactionCommitError {
+                        tr: committed.tr,
+                        err,
+                        size_violation: None,
+                    })
+                }
+            })
+        })
     }
 
     /// Implements the recommended retry and backoff behavior for a transaction. This function knows
@@ -644,16 +1353,23 @@ impl Transaction {
         self,
         err: FdbError,
     ) -> impl Future<Output = FdbResult<Transaction>> + Send + Sync + Unpin {
+        self.invalidate_read_version_cache(&err);
         FdbFuture::<()>::new(unsafe {
-            fdb_sys::fdb_transaction_on_error(self.inner.as_ptr(), err.code())
+            fdb_sys::fdb_transaction_on_error(self.lock().as_ptr(), err.code())
+        })
+        .and_then(|()| {
+            // `on_error` resets the transaction
+            self.clear_mutations();
+            #[cfg(feature = "fdb-7_1")]
+            self.clear_tags();
+            future::ready(self.reapply_options().map(|()| self))
         })
-        .map_ok(|()| self)
     }
 
     /// Cancels the transaction. All pending or future uses of the transaction will return a
     /// transaction_cancelled error. The transaction can be used again after it is reset.
     pub fn cancel(self) -> TransactionCancelled {
-        unsafe { fdb_sys::fdb_transaction_cancel(self.inner.as_ptr()) };
+        unsafe { fdb_sys::fdb_transaction_cancel(self.lock().as_ptr()) };
         TransactionCancelled { tr: self }
     }
 
@@ -665,7 +1381,7 @@ impl Transaction {
     ) -> impl Future<Output = FdbResult<FdbAddresses>> + Send + Sync + Unpin {
         FdbFuture::new(unsafe {
             fdb_sys::fdb_transaction_get_addresses_for_key(
-                self.inner.as_ptr(),
+                self.lock().as_ptr(),
                 key.as_ptr(),
                 fdb_len(key.len(), "key"),
             )
@@ -700,7 +1416,7 @@ impl Transaction {
     pub fn watch(&self, key: &[u8]) -> impl Future<Output = FdbResult<()>> + Send + Sync + Unpin {
         FdbFuture::new(unsafe {
             fdb_sys::fdb_transaction_watch(
-                self.inner.as_ptr(),
+                self.lock().as_ptr(),
                 key.as_ptr(),
                 fdb_len(key.len(), "key"),
             )
@@ -712,12 +1428,57 @@ impl Transaction {
     /// ranges, and write conflict ranges.
     ///
     /// This can be called multiple times before the transaction is committed.
-    #[cfg(feature = "fdb-6_2")]
+    #[cfg(any(feature = "fdb-6_2", feature = "fdb-7_1"))]
     pub fn get_approximate_size(
         &self,
     ) -> impl Future<Output = FdbResult<i64>> + Send + Sync + Unpin {
         FdbFuture::new(unsafe {
-            fdb_sys::fdb_transaction_get_approximate_size(self.inner.as_ptr())
+            fdb_sys::fdb_transaction_get_approximate_size(self.lock().as_ptr())
+        })
+    }
+
+    /// Returns an estimation of the number of bytes stored in the database in the range
+    /// `[begin, end)`.
+    ///
+    /// The estimation is computed from a sample of the data and can be far from the actual size
+    /// for small ranges.
+    #[cfg(feature = "fdb-7_1")]
+    pub fn get_estimated_range_size_bytes(
+        &self,
+        begin: &[u8],
+        end: &[u8],
+    ) -> impl Future<Output = FdbResult<i64>> + Send + Sync + Unpin {
+        FdbFuture::new(unsafe {
+            fdb_sys::fdb_transaction_get_estimated_range_size_bytes(
+                self.lock().as_ptr(),
+                begin.as_ptr(),
+                fdb_len(begin.len(), "begin"),
+                end.as_ptr(),
+                fdb_len(end.len(), "end"),
+            )
+        })
+    }
+
+    /// Returns keys splitting the range `[begin, end)` into chunks of about `chunk_size` bytes,
+    /// including `begin` and `end`.
+    ///
+    /// The chunks can then be read in parallel, e.g. by distinct transactions.
+    #[cfg(feature = "fdb-7_1")]
+    pub fn get_range_split_points(
+        &self,
+        begin: &[u8],
+        end: &[u8],
+        chunk_size: i64,
+    ) -> impl Future<Output = FdbResult<FdbKeys>> + Send + Sync + Unpin {
+        FdbFuture::new(unsafe {
+            fdb_sys::fdb_transaction_get_range_split_points(
+                self.lock().as_ptr(),
+                begin.as_ptr(),
+                fdb_len(begin.len(), "begin"),
+                end.as_ptr(),
+                fdb_len(end.len(), "end"),
+                chunk_size,
+            )
         })
     }
 
@@ -733,7 +1494,26 @@ impl Transaction {
     pub fn get_versionstamp(
         &self,
     ) -> impl Future<Output = FdbResult<FdbSlice>> + Send + Sync + Unpin {
-        FdbFuture::new(unsafe { fdb_sys::fdb_transaction_get_versionstamp(self.inner.as_ptr()) })
+        FdbFuture::new(unsafe { fdb_sys::fdb_transaction_get_versionstamp(self.lock().as_ptr()) })
+    }
+
+    /// Same as `get_versionstamp`, but the versionstamp is returned as a complete `Versionstamp`
+    /// with a user version of `0`.
+    ///
+    /// Fails with an `internal_error` (4100) if FoundationDB doesn't return a 10 bytes
+    /// transaction version.
+    pub fn get_versionstamp_typed(
+        &self,
+    ) -> impl Future<Output = FdbResult<Versionstamp>> + Send + Sync + Unpin {
+        self.get_versionstamp().map(|r| {
+            let slice = r?;
+            let mut tr_version = [0u8; 10];
+            if slice.len() != tr_version.len() {
+                return Err(FdbError::from_code(4100));
+            }
+            tr_version.copy_from_slice(&slice);
+            Ok(Versionstamp::complete(tr_version, 0))
+        })
     }
 
     /// The transaction obtains a snapshot read version automatically at the time of the first call
@@ -741,7 +1521,7 @@ impl Transaction {
     /// compromised by transaction options) is guaranteed to represent all transactions which were
     /// reported committed before that call.
     pub fn get_read_version(&self) -> impl Future<Output = FdbResult<i64>> + Send + Sync + Unpin {
-        FdbFuture::new(unsafe { fdb_sys::fdb_transaction_get_read_version(self.inner.as_ptr()) })
+        FdbFuture::new(unsafe { fdb_sys::fdb_transaction_get_read_version(self.lock().as_ptr()) })
     }
 
     /// Sets the snapshot read version used by a transaction.
@@ -752,7 +1532,34 @@ impl Transaction {
     /// error_code_future_version. If any of get_*() have been called on this transaction already,
     /// the result is undefined.
     pub fn set_read_version(&self, version: i64) {
-        unsafe { fdb_sys::fdb_transaction_set_read_version(self.inner.as_ptr(), version) }
+        unsafe { fdb_sys::fdb_transaction_set_read_version(self.lock().as_ptr(), version) }
+    }
+
+    /// Sets the read version of the transaction to the one cached by its database, if it is
+    /// younger than the staleness bound given to `Database::set_read_version_cache`, saving the
+    /// round trip to get a read version. Otherwise, a fresh read version is requested and cached.
+    ///
+    /// Returns the read version of the transaction. This must be called before any read. Without
+    /// a cache, this is `get_read_version`.
+    ///
+    /// # Consistency
+    ///
+    /// The reads of the transaction may not see the transactions committed by other clients
+    /// during the staleness bound. They are still consistent with each other, and the commit
+    /// still fails if one of the read keys was modified since the read version.
+    pub async fn use_cached_read_version(&self) -> FdbResult<i64> {
+        let cache = match &self.read_version_cache {
+            Some(cache) => cache,
+            None => return self.get_read_version().await,
+        };
+        if let Some(version) = cache.get() {
+            self.set_read_version(version);
+            return Ok(version);
+        }
+        let requested = Instant::now();
+        let version = self.get_read_version().await?;
+        cache.observe(version, requested);
+        Ok(version)
     }
 
     /// Reset transaction to its initial state.
@@ -764,8 +1571,15 @@ impl Transaction {
     ///
     /// It is not necessary to call `reset()` when handling an error with `on_error()` since the
     /// transaction has already been reset.
+    ///
+    /// The options are cleared, unless `remember_options` is enabled.
     pub fn reset(&mut self) {
-        unsafe { fdb_sys::fdb_transaction_reset(self.inner.as_ptr()) }
+        unsafe { fdb_sys::fdb_transaction_reset(self.lock().as_ptr()) }
+        self.clear_mutations();
+        #[cfg(feature = "fdb-7_1")]
+        self.clear_tags();
+        // these options were all accepted when they were first set
+        let _ = self.reapply_options();
     }
 
     /// Adds a conflict range to a transaction without performing the associated read or write.
@@ -782,7 +1596,7 @@ impl Transaction {
     ) -> FdbResult<()> {
         error::eval(unsafe {
             fdb_sys::fdb_transaction_add_conflict_range(
-                self.inner.as_ptr(),
+                self.lock().as_ptr(),
                 begin.as_ptr(),
                 fdb_len(begin.len(), "begin"),
                 end.as_ptr(),
@@ -791,6 +1605,83 @@ impl Transaction {
             )
         })
     }
+
+    /// Reads `key` with a snapshot read, and adds a read conflict range on `key` only.
+    ///
+    /// The transaction conflicts if `key` is modified before it commits, like with a regular
+    /// `get`, but this spells out the pattern of reading at snapshot isolation and declaring
+    /// conflicts only on the keys used to take decisions.
+    pub fn get_and_add_read_conflict(
+        &self,
+        key: &[u8],
+    ) -> impl Future<Output = FdbResult<Option<FdbSlice>>> + Send + Sync + Unpin {
+        let end = crate::key::key_after(key);
+        let conflict = self.add_conflict_range(key, &end, options::ConflictRangeType::Read);
+        let value = self.get(key, true);
+        future::ready(conflict).and_then(move |()| value)
+    }
+
+    /// Adds a read conflict range on `[begin, end)` if `snapshot` is `true`.
+    ///
+    /// Reads without `snapshot` already add their read conflict range, this is for code reading
+    /// a wide range at either isolation, e.g. depending on a parameter, and declaring conflicts
+    /// only on the part of it used to take decisions.
+    pub fn add_read_conflict_if_snapshot(
+        &self,
+        begin: &[u8],
+        end: &[u8],
+        snapshot: bool,
+    ) -> FdbResult<()> {
+        if snapshot {
+            self.add_conflict_range(begin, end, options::ConflictRangeType::Read)
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Sets `key` to `value` without adding a write conflict range, so that the transactions
+    /// which read `key` don't conflict with this one.
+    ///
+    /// This sets `TransactionOption::NextWriteNoWriteConflictRange` just before the write. The
+    /// option applies to the next write of the transaction whichever it is: if the transaction
+    /// is shared between threads, the other writes must be synchronized with this call.
+    pub fn set_without_conflict(&self, key: &[u8], value: &[u8]) -> FdbResult<()> {
+        self.set_option(options::TransactionOption::NextWriteNoWriteConflictRange)?;
+        self.set(key, value);
+        Ok(())
+    }
+}
+
+/// Records `opt` in `remembered`, replacing a previous value of the same option.
+fn remember_option(
+    remembered: &mut Vec<options::TransactionOption>,
+    opt: options::TransactionOption,
+) {
+    use options::TransactionOption;
+
+    match opt {
+        // only applies to the next write of the attempt
+        TransactionOption::NextWriteNoWriteConflictRange => {}
+        // each tag is added to the previous ones
+        #[cfg(feature = "fdb-7_1")]
+        TransactionOption::Tag(ref tag) | TransactionOption::AutoThrottleTag(ref tag) => {
+            let code = opt.code_i32();
+            let known = remembered.iter().any(|o| match o {
+                TransactionOption::Tag(t) | TransactionOption::AutoThrottleTag(t) => {
+                    o.code_i32() == code && t == tag
+                }
+                _ => false,
+            });
+            if !known {
+                remembered.push(opt);
+            }
+        }
+        _ => {
+            let code = opt.code_i32();
+            remembered.retain(|o| o.code_i32() != code);
+            remembered.push(opt);
+        }
+    }
 }
 
 impl Drop for Transaction {
@@ -0,0 +1,168 @@
+// Copyright 2018 foundationdb-rs developers, https://github.com/Clikengo/foundationdb-rs/graphs/contributors
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! Serde support for `Bytes`
+//!
+//! Human-readable formats, like JSON, represent the bytes as a base64 string (standard alphabet,
+//! with padding). The other formats use their native byte string representation.
+
+use std::fmt;
+
+use serde::de::{self, Deserialize, Deserializer, SeqAccess, Visitor};
+use serde::ser::{Serialize, Serializer};
+
+use super::Bytes;
+
+const BASE64_ALPHABET: &[u8; 64] =
+    b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+const BASE64_PAD: u8 = b'=';
+
+impl<'a> Serialize for Bytes<'a> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        if serializer.is_human_readable() {
+            serializer.serialize_str(&encode_base64(&self.0))
+        } else {
+            serializer.serialize_bytes(&self.0)
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for Bytes<'static> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        if deserializer.is_human_readable() {
+            deserializer.deserialize_str(BytesVisitor)
+        } else {
+            deserializer.deserialize_byte_buf(BytesVisitor)
+        }
+    }
+}
+
+struct BytesVisitor;
+
+impl<'de> Visitor<'de> for BytesVisitor {
+    type Value = Bytes<'static>;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "a byte string or a base64 string")
+    }
+
+    fn visit_str<E: de::Error>(self, v: &str) -> Result<Self::Value, E> {
+        decode_base64(v)
+            .map(Bytes::from)
+            .ok_or_else(|| E::invalid_value(de::Unexpected::Str(v), &self))
+    }
+
+    fn visit_bytes<E: de::Error>(self, v: &[u8]) -> Result<Self::Value, E> {
+        Ok(v.to_vec().into())
+    }
+
+    fn visit_byte_buf<E: de::Error>(self, v: Vec<u8>) -> Result<Self::Value, E> {
+        Ok(v.into())
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+        let mut bytes = Vec::with_capacity(seq.size_hint().unwrap_or(0));
+        while let Some(byte) = seq.next_element()? {
+            bytes.push(byte);
+        }
+        Ok(bytes.into())
+    }
+}
+
+fn encode_base64(bytes: &[u8]) -> String {
+    let chunks = bytes.chunks(3);
+    let mut encoded = String::with_capacity(chunks.len() * 4);
+    for chunk in chunks {
+        let b = [
+            chunk[0],
+            chunk.get(1).copied().unwrap_or(0),
+            chunk.get(2).copied().unwrap_or(0),
+        ];
+        let n = u32::from(b[0]) << 16 | u32::from(b[1]) << 8 | u32::from(b[2]);
+        for i in 0..4 {
+            if i <= chunk.len() {
+                let index = (n >> (18 - 6 * i)) & 0x3f;
+                encoded.push(BASE64_ALPHABET[index as usize] as char);
+            } else {
+                encoded.push(BASE64_PAD as char);
+            }
+        }
+    }
+    encoded
+}
+
+fn decode_base64(encoded: &str) -> Option<Vec<u8>> {
+    let chunks = encoded.as_bytes().chunks_exact(4);
+    if !chunks.remainder().is_empty() {
+        return None;
+    }
+    let count = chunks.len();
+    let mut bytes = Vec::with_capacity(count * 3);
+    for (c, chunk) in chunks.enumerate() {
+        let padding = if c + 1 == count {
+            chunk.iter().rev().take_while(|&&b| b == BASE64_PAD).count()
+        } else {
+            0
+        };
+        if padding > 2 {
+            return None;
+        }
+        let mut n = 0u32;
+        for &digit in &chunk[..4 - padding] {
+            let value = BASE64_ALPHABET.iter().position(|&b| b == digit)?;
+            n = n << 6 | value as u32;
+        }
+        n <<= 6 * padding as u32;
+        let decoded = [(n >> 16) as u8, (n >> 8) as u8, n as u8];
+        bytes.extend_from_slice(&decoded[..3 - padding]);
+    }
+    Some(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_base64() {
+        let cases: &[(&[u8], &str)] = &[
+            (b"", ""),
+            (b"f", "Zg=="),
+            (b"fo", "Zm8="),
+            (b"foo", "Zm9v"),
+            (b"foob", "Zm9vYg=="),
+            (b"\x00\xff\xfe", "AP/+"),
+        ];
+        for &(bytes, encoded) in cases {
+            assert_eq!(encode_base64(bytes), encoded);
+            assert_eq!(decode_base64(encoded).as_deref(), Some(bytes));
+        }
+        for invalid in &["Zg=", "Z===", "Zg==Zg==", "Zm9*", "Zm=v"] {
+            assert_eq!(decode_base64(invalid), None, "{}", invalid);
+        }
+    }
+
+    #[test]
+    fn test_bytes_json() {
+        let bytes = Bytes::from(&b"\x00key\xff"[..]);
+        let json = serde_json::to_string(&bytes).unwrap();
+        assert_eq!(json, "\"AGtlef8=\"");
+        assert_eq!(serde_json::from_str::<Bytes>(&json).unwrap(), bytes);
+
+        assert!(serde_json::from_str::<Bytes>("\"not base64\"").is_err());
+        assert!(serde_json::from_str::<Bytes>("[0, 107]").is_err());
+    }
+
+    #[test]
+    fn test_bytes_binary() {
+        let bytes = Bytes::from(&b"\x00key\xff"[..]);
+        let cbor = serde_cbor::to_vec(&bytes).unwrap();
+        // a byte string of 5 bytes
+        assert_eq!(cbor, b"\x45\x00key\xff");
+        assert_eq!(serde_cbor::from_slice::<Bytes>(&cbor).unwrap(), bytes);
+    }
+}
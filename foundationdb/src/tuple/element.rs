@@ -91,6 +91,13 @@ impl<'a> PartialOrd for Element<'a> {
         Some(self.cmp(other))
     }
 }
+
+/// Elements are ordered as their packed representation.
+///
+/// `a.cmp(&b)` is the same as `pack(&a).cmp(&pack(&b))`, so sorting elements sorts the keys they
+/// pack to. Integers are compared by value, whatever their variant, floats are compared by
+/// their packed bits: `-0.0 < 0.0`, and NaNs are equal to themselves, ordered by their sign bit
+/// around the other values.
 impl<'a> Ord for Element<'a> {
     fn cmp(&self, other: &Self) -> cmp::Ordering {
         self.cmp_at_root(other)
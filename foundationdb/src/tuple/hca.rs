@@ -23,6 +23,11 @@
 //!      If the write succeeds, the candidate is returned as the allocated value. Success!
 //!      If the write fails because the window has been advanced, it repeats (1).
 //!      If the write fails because the value was already set, it repeats (2).
+//!
+//! As the window is advanced once it is half-full, a candidate is expected to be found in about
+//! 2 tries. The allocation fails with `HcaError::Contention` after `MAX_CANDIDATE_ATTEMPTS`
+//! candidates were already taken, which can only happen if many clients allocate in the same
+//! window at the same time: the transaction can be retried.
 
 use std::fmt;
 use std::sync::{Mutex, PoisonError};
@@ -30,18 +35,26 @@ use std::sync::{Mutex, PoisonError};
 use futures::future;
 use rand::{self, rngs::SmallRng, Error as RandError, Rng, SeedableRng};
 
+use crate::future::FdbValues;
 use crate::options::{ConflictRangeType, MutationType, TransactionOption};
 use crate::tuple::{PackError, Subspace};
 use crate::*;
 
 const ONE_BYTES: &[u8] = &[0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00];
 
+/// The number of candidates tried by `HighContentionAllocator::allocate` before failing with
+/// `HcaError::Contention`
+pub const MAX_CANDIDATE_ATTEMPTS: usize = 1000;
+
 pub enum HcaError {
     FdbError(FdbError),
     PackError(PackError),
     InvalidDirectoryLayerMetadata,
     PoisonError,
     RandError(RandError),
+    /// `MAX_CANDIDATE_ATTEMPTS` candidates were tried without finding a free one, because of
+    /// concurrent allocations. Unlike `InvalidDirectoryLayerMetadata`, retrying may succeed.
+    Contention,
 }
 
 impl fmt::Debug for HcaError {
@@ -54,6 +67,33 @@ impl fmt::Debug for HcaError {
             }
             HcaError::PoisonError => write!(f, "mutex poisoned"),
             HcaError::RandError(err) => err.fmt(f),
+            HcaError::Contention => write!(
+                f,
+                "no free candidate found in {} attempts",
+                MAX_CANDIDATE_ATTEMPTS
+            ),
+        }
+    }
+}
+
+impl fmt::Display for HcaError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            HcaError::FdbError(err) => err.fmt(f),
+            HcaError::PackError(err) => err.fmt(f),
+            HcaError::RandError(err) => err.fmt(f),
+            _ => fmt::Debug::fmt(self, f),
+        }
+    }
+}
+
+impl std::error::Error for HcaError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            HcaError::FdbError(err) => Some(err),
+            HcaError::PackError(err) => Some(err),
+            HcaError::RandError(err) => Some(err),
+            _ => None,
         }
     }
 }
@@ -110,27 +150,17 @@ impl HighContentionAllocator {
     /// Returns a byte string that
     ///   1) has never and will never be returned by another call to this method on the same subspace
     ///   2) is nearly as short as possible given the above
+    ///
+    /// Fails with `HcaError::Contention` if no free candidate is found after
+    /// `MAX_CANDIDATE_ATTEMPTS` tries.
     pub async fn allocate(&self, trx: &Transaction) -> Result<i64, HcaError> {
-        let (begin, end) = self.counters.range();
-        let begin = KeySelector::first_greater_or_equal(begin);
-        let end = KeySelector::first_greater_than(end);
-        let counters_range = RangeOption {
-            begin,
-            end,
-            limit: Some(1),
-            reverse: true,
-            ..RangeOption::default()
-        };
+        let counters_range = self.counters_range();
         let mut rng = SmallRng::from_rng(&mut rand::thread_rng())?;
+        let mut attempts = 0;
 
         loop {
             let kvs = trx.get_range(&counters_range, 1, true).await?;
-
-            let mut start: i64 = if let Some(first) = kvs.first() {
-                self.counters.unpack(first.key())?
-            } else {
-                0
-            };
+            let mut start = self.window_start(&kvs)?;
 
             let mut window_advanced = false;
 
@@ -150,17 +180,7 @@ impl HighContentionAllocator {
                     trx.get(counters_start.bytes(), true)
                 };
 
-                let count_value = count_future.await?;
-                let count = if let Some(count_value) = count_value {
-                    if count_value.len() != 8 {
-                        return Err(HcaError::InvalidDirectoryLayerMetadata);
-                    }
-                    let mut bytes = [0u8; 8];
-                    bytes.copy_from_slice(&count_value);
-                    i64::from_le_bytes(bytes)
-                } else {
-                    0
-                };
+                let count = parse_count(count_future.await?.as_deref())?;
 
                 let window = Self::window_size(start);
                 if count * 2 < window {
@@ -172,6 +192,11 @@ impl HighContentionAllocator {
             };
 
             loop {
+                if attempts == MAX_CANDIDATE_ATTEMPTS {
+                    return Err(HcaError::Contention);
+                }
+                attempts += 1;
+
                 // As of the snapshot being read from, the window is less than half
                 // full, so this should be expected to take 2 tries.  Under high
                 // contention (and when the window advances), there is an additional
@@ -191,13 +216,8 @@ impl HighContentionAllocator {
                 let (latest_counter, candidate_value) =
                     future::try_join(latest_counter, candidate_value).await?;
 
-                let current_window_start: i64 = if let Some(first) = latest_counter.first() {
-                    self.counters.unpack(first.key())?
-                } else {
-                    0
-                };
-
-                if current_window_start > start {
+                // The window was advanced by a concurrent allocation
+                if self.window_start(&latest_counter)? > start {
                     break;
                 }
 
@@ -215,6 +235,40 @@ impl HighContentionAllocator {
         }
     }
 
+    /// Returns the current window of the allocator, as read with snapshot reads by `trx`.
+    pub async fn stats(&self, trx: &Transaction) -> Result<HcaStats, HcaError> {
+        let kvs = trx.get_range(&self.counters_range(), 1, true).await?;
+        let window_start = self.window_start(&kvs)?;
+        let count = match kvs.first() {
+            Some(kv) => parse_count(Some(kv.value()))?,
+            None => 0,
+        };
+        Ok(HcaStats {
+            window_start,
+            window_size: Self::window_size(window_start),
+            count,
+        })
+    }
+
+    /// The range option reading the counter of the current window, which is the last one.
+    fn counters_range(&self) -> RangeOption<'static> {
+        let (begin, end) = self.counters.range();
+        RangeOption {
+            begin: KeySelector::first_greater_or_equal(begin),
+            end: KeySelector::first_greater_than(end),
+            limit: Some(1),
+            reverse: true,
+            ..RangeOption::default()
+        }
+    }
+
+    fn window_start(&self, counters: &FdbValues) -> Result<i64, HcaError> {
+        Ok(match counters.first() {
+            Some(first) => self.counters.unpack(first.key())?,
+            None => 0,
+        })
+    }
+
     fn window_size(start: i64) -> i64 {
         // Larger window sizes are better for high contention, smaller sizes for
         // keeping the keys small.  But if there are many allocations, the keys
@@ -228,3 +282,28 @@ impl HighContentionAllocator {
         }
     }
 }
+
+/// The current window of a `HighContentionAllocator`, see `HighContentionAllocator::stats`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct HcaStats {
+    /// The lower bound of the values allocated from the current window
+    pub window_start: i64,
+    /// The number of values in the current window, which is advanced once half of them are
+    /// allocated
+    pub window_size: i64,
+    /// The number of allocations counted in the current window, including the ones of
+    /// transactions that failed to commit
+    pub count: i64,
+}
+
+fn parse_count(value: Option<&[u8]>) -> Result<i64, HcaError> {
+    match value {
+        Some(value) if value.len() == 8 => {
+            let mut bytes = [0u8; 8];
+            bytes.copy_from_slice(value);
+            Ok(i64::from_le_bytes(bytes))
+        }
+        Some(_) => Err(HcaError::InvalidDirectoryLayerMetadata),
+        None => Ok(0),
+    }
+}
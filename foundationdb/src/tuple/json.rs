@@ -0,0 +1,203 @@
+//! Conversions between `Element` and `serde_json::Value`
+//!
+//! The mapping is:
+//!
+//! | JSON                                   | Element                                  |
+//! |----------------------------------------|------------------------------------------|
+//! | `null`                                 | `Nil`                                    |
+//! | `true`, `false`                        | `Bool`                                   |
+//! | integer in the range of `i64`          | `Int`                                    |
+//! | integer above `i64::MAX`               | `BigInt` (feature `num-bigint`), error otherwise |
+//! | number with a fraction or an exponent  | `Double`                                 |
+//! | string                                 | `String`                                 |
+//! | array                                  | `Tuple`                                  |
+//! | object                                 | error                                    |
+//!
+//! Numbers are classified as serde_json parses them: `2` is an integer while `2.0` is not.
+//! Objects are rejected rather than flattened, because a tuple has no way to tell a flattened
+//! object from an array of pairs.
+//!
+//! The other way around, `Float` is converted to a number too, `BigInt` is converted if it fits
+//! in an `i64` or an `u64`, and `Bytes`, `Uuid`, `Versionstamp` and non-finite floats are
+//! rejected.
+
+use std::borrow::Cow;
+use std::convert::TryFrom;
+use std::fmt;
+
+use serde_json::{Number, Value};
+
+use super::Element;
+
+/// The errors of the conversions between `Element` and `serde_json::Value`
+#[derive(Debug, Clone, PartialEq)]
+pub enum JsonError {
+    /// JSON objects can't be converted to elements
+    Object,
+    /// The integer doesn't fit in an `i64`, and the `num-bigint` feature is disabled
+    IntegerOutOfRange(Number),
+    /// The element has no JSON representation, its name is given
+    UnsupportedElement(&'static str),
+}
+
+impl fmt::Display for JsonError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            JsonError::Object => write!(f, "JSON objects can't be converted to tuple elements"),
+            JsonError::IntegerOutOfRange(n) => write!(f, "integer {} is out of range", n),
+            JsonError::UnsupportedElement(name) => {
+                write!(f, "{} elements can't be converted to JSON", name)
+            }
+        }
+    }
+}
+
+impl std::error::Error for JsonError {}
+
+impl<'a> TryFrom<&'a Value> for Element<'static> {
+    type Error = JsonError;
+
+    fn try_from(value: &'a Value) -> Result<Self, JsonError> {
+        Ok(match value {
+            Value::Null => Element::Nil,
+            Value::Bool(b) => Element::Bool(*b),
+            Value::Number(n) => number_to_element(n)?,
+            Value::String(s) => Element::String(Cow::Owned(s.clone())),
+            Value::Array(values) => Element::Tuple(
+                values
+                    .iter()
+                    .map(Element::try_from)
+                    .collect::<Result<_, _>>()?,
+            ),
+            Value::Object(_) => return Err(JsonError::Object),
+        })
+    }
+}
+
+fn number_to_element(n: &Number) -> Result<Element<'static>, JsonError> {
+    if let Some(i) = n.as_i64() {
+        return Ok(Element::Int(i));
+    }
+    if let Some(u) = n.as_u64() {
+        #[cfg(feature = "num-bigint")]
+        {
+            return Ok(Element::BigInt(u.into()));
+        }
+        #[cfg(not(feature = "num-bigint"))]
+        {
+            let _ = u;
+            return Err(JsonError::IntegerOutOfRange(n.clone()));
+        }
+    }
+    match n.as_f64() {
+        Some(f) => Ok(Element::Double(f)),
+        None => Err(JsonError::IntegerOutOfRange(n.clone())),
+    }
+}
+
+fn float_to_json(f: f64) -> Result<Value, JsonError> {
+    Number::from_f64(f)
+        .map(Value::Number)
+        .ok_or(JsonError::UnsupportedElement("non-finite float"))
+}
+
+impl<'a> Element<'a> {
+    /// Converts this element to JSON, see the `tuple::json` module for the mapping.
+    pub fn to_json(&self) -> Result<Value, JsonError> {
+        Ok(match self {
+            Element::Nil => Value::Null,
+            Element::Bool(b) => Value::Bool(*b),
+            Element::Int(i) => Value::Number((*i).into()),
+            #[cfg(feature = "num-bigint")]
+            Element::BigInt(i) => {
+                if let Ok(i) = i64::try_from(i) {
+                    Value::Number(i.into())
+                } else if let Ok(u) = u64::try_from(i) {
+                    Value::Number(u.into())
+                } else {
+                    return Err(JsonError::UnsupportedElement("out of range BigInt"));
+                }
+            }
+            Element::Float(f) => float_to_json(f64::from(*f))?,
+            Element::Double(f) => float_to_json(*f)?,
+            Element::String(s) => Value::String(s.to_string()),
+            Element::Tuple(elements) => Value::Array(
+                elements
+                    .iter()
+                    .map(Element::to_json)
+                    .collect::<Result<_, _>>()?,
+            ),
+            Element::Bytes(_) => return Err(JsonError::UnsupportedElement("Bytes")),
+            #[cfg(feature = "uuid")]
+            Element::Uuid(_) => return Err(JsonError::UnsupportedElement("Uuid")),
+            Element::Versionstamp(_) => return Err(JsonError::UnsupportedElement("Versionstamp")),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn round_trip(value: Value) -> Element<'static> {
+        let element = Element::try_from(&value).expect("failed to convert from JSON");
+        assert_eq!(element.to_json().expect("failed to convert to JSON"), value);
+        element
+    }
+
+    #[test]
+    fn test_json_round_trip() {
+        assert_eq!(round_trip(json!(null)), Element::Nil);
+        assert_eq!(round_trip(json!(true)), Element::Bool(true));
+        assert_eq!(round_trip(json!(-42)), Element::Int(-42));
+        assert_eq!(round_trip(json!(i64::MAX)), Element::Int(i64::MAX));
+        assert_eq!(round_trip(json!(i64::MIN)), Element::Int(i64::MIN));
+        assert_eq!(round_trip(json!(1.5)), Element::Double(1.5));
+        assert_eq!(round_trip(json!(2.0)), Element::Double(2.0));
+        assert_eq!(
+            round_trip(json!("hello")),
+            Element::String(Cow::Borrowed("hello"))
+        );
+        assert_eq!(
+            round_trip(json!(["user", 42, [null, false]])),
+            Element::Tuple(vec![
+                Element::String(Cow::Borrowed("user")),
+                Element::Int(42),
+                Element::Tuple(vec![Element::Nil, Element::Bool(false)]),
+            ])
+        );
+    }
+
+    #[test]
+    fn test_json_rejected() {
+        assert_eq!(Element::try_from(&json!({"a": 1})), Err(JsonError::Object));
+        assert_eq!(Element::try_from(&json!([1, {}])), Err(JsonError::Object));
+
+        assert_eq!(
+            Element::Bytes(b"\x00"[..].into()).to_json(),
+            Err(JsonError::UnsupportedElement("Bytes"))
+        );
+        assert_eq!(
+            Element::Double(f64::NAN).to_json(),
+            Err(JsonError::UnsupportedElement("non-finite float"))
+        );
+        assert_eq!(Element::Float(0.5).to_json(), Ok(json!(0.5)));
+    }
+
+    #[test]
+    fn test_json_u64_above_i64() {
+        let value = json!(u64::MAX);
+        #[cfg(feature = "num-bigint")]
+        {
+            assert_eq!(round_trip(value), Element::BigInt(u64::MAX.into()));
+        }
+        #[cfg(not(feature = "num-bigint"))]
+        {
+            assert_eq!(
+                Element::try_from(&value),
+                Err(JsonError::IntegerOutOfRange(u64::MAX.into()))
+            );
+        }
+    }
+}
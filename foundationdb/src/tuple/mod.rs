@@ -2,8 +2,12 @@
 //!
 //! The official specification can be found [here](https://github.com/apple/foundationdb/blob/master/design/tuple.md).
 
+#[cfg(feature = "serde")]
+mod bytes_serde;
 mod element;
 pub mod hca;
+#[cfg(feature = "serde-json")]
+pub mod json;
 mod pack;
 mod subspace;
 mod versionstamp;
@@ -14,12 +18,14 @@ use std::io;
 use std::ops::Deref;
 use std::result;
 
+use crate::RedactedBytes;
+
 #[cfg(feature = "uuid")]
 pub use uuid::Uuid;
 
 pub use element::Element;
 pub use pack::{TuplePack, TupleUnpack, VersionstampOffset};
-pub use subspace::Subspace;
+pub use subspace::{decode_subspace_stream, KeyspaceOps, Subspace};
 pub use versionstamp::Versionstamp;
 
 const NIL: u8 = 0x00;
@@ -74,9 +80,19 @@ pub enum PackError {
         expected: Option<u8>,
     },
     BadPrefix,
+    /// The key doesn't start with the prefix of the subspace it was decoded from
+    KeyOutsideSubspace {
+        key: Vec<u8>,
+    },
     #[cfg(feature = "uuid")]
     BadUuid,
     UnsupportedIntLength,
+    BufferTooSmall {
+        required: usize,
+        available: usize,
+    },
+    NoIncompleteVersionstamp,
+    MultipleIncompleteVersionstamps,
 }
 
 impl From<io::Error> for PackError {
@@ -95,9 +111,24 @@ impl Display for PackError {
             PackError::BadStringFormat => write!(f, "not an utf8 string"),
             PackError::BadCode { found, .. } => write!(f, "bad code, found {}", found),
             PackError::BadPrefix => write!(f, "bad prefix"),
+            PackError::KeyOutsideSubspace { key } => {
+                write!(f, "key {} is outside of the subspace", RedactedBytes(key))
+            }
             #[cfg(feature = "uuid")]
             PackError::BadUuid => write!(f, "bad uuid"),
             PackError::UnsupportedIntLength => write!(f, "integer length was to large"),
+            PackError::BufferTooSmall {
+                required,
+                available,
+            } => write!(
+                f,
+                "buffer too small, {} bytes required but only {} available",
+                required, available
+            ),
+            PackError::NoIncompleteVersionstamp => write!(f, "no incomplete versionstamp"),
+            PackError::MultipleIncompleteVersionstamps => {
+                write!(f, "multiple incomplete versionstamps")
+            }
         }
     }
 }
@@ -121,17 +152,16 @@ impl<'a> fmt::Debug for Bytes<'a> {
 
 impl<'a> fmt::Display for Bytes<'a> {
     fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
-        write!(fmt, "b\"")?;
-        for &byte in self.0.iter() {
-            if byte == b'\\' {
-                write!(fmt, r"\\")?;
-            } else if byte.is_ascii_alphanumeric() {
-                write!(fmt, "{}", byte as char)?;
-            } else {
-                write!(fmt, "\\x{:02x}", byte)?;
-            }
+        fmt::Display::fmt(&RedactedBytes(&self.0), fmt)
+    }
+}
+
+impl<'a> fmt::LowerHex for Bytes<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for byte in self.0.iter() {
+            write!(f, "{:02x}", byte)?;
         }
-        write!(fmt, "\"")
+        Ok(())
     }
 }
 
@@ -139,8 +169,55 @@ impl<'a> Bytes<'a> {
     pub fn into_owned(self) -> Vec<u8> {
         self.0.into_owned()
     }
+
+    /// Returns the bytes as a lowercase hexadecimal string, two digits per byte.
+    pub fn to_hex(&self) -> String {
+        format!("{:x}", self)
+    }
+
+    /// Parses a hexadecimal string, as returned by `to_hex`. Both lowercase and uppercase digits
+    /// are accepted.
+    pub fn from_hex(hex: &str) -> Result<Bytes<'static>, FromHexError> {
+        let digits = hex.as_bytes().chunks_exact(2);
+        if !digits.remainder().is_empty() {
+            return Err(FromHexError::OddLength);
+        }
+        let digit = |index: usize, digit: u8| {
+            (digit as char)
+                .to_digit(16)
+                .map(|d| d as u8)
+                .ok_or(FromHexError::InvalidCharacter(index))
+        };
+        let mut bytes = Vec::with_capacity(digits.len());
+        for (i, pair) in digits.enumerate() {
+            bytes.push(digit(2 * i, pair[0])? << 4 | digit(2 * i + 1, pair[1])?);
+        }
+        Ok(bytes.into())
+    }
 }
 
+/// The error returned by `Bytes::from_hex`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FromHexError {
+    /// The string has an odd number of digits
+    OddLength,
+    /// The byte at the given index of the string is not a hexadecimal digit
+    InvalidCharacter(usize),
+}
+
+impl Display for FromHexError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            FromHexError::OddLength => write!(f, "odd number of hexadecimal digits"),
+            FromHexError::InvalidCharacter(index) => {
+                write!(f, "invalid hexadecimal digit at index {}", index)
+            }
+        }
+    }
+}
+
+impl std::error::Error for FromHexError {}
+
 impl<'a> Deref for Bytes<'a> {
     type Target = [u8];
     fn deref(&self) -> &Self::Target {
@@ -219,6 +296,42 @@ pub fn unpack<'de, T: TupleUnpack<'de>>(input: &'de [u8]) -> PackResult<T> {
     T::unpack_root(input)
 }
 
+/// Unpack input as an `Element`, without failing on strings that are not valid UTF-8
+///
+/// `unpack::<Element>` fails with `PackError::BadStringFormat` on such strings. Here they are
+/// unpacked as `Element::Bytes` instead, with their bytes as is, so that no data is lost (which
+/// would not be the case with `String::from_utf8_lossy`). Keys written by other bindings, that
+/// don't check the strings they pack, can then still be inspected.
+pub fn unpack_lossy(input: &[u8]) -> PackResult<Element> {
+    pack::LossyElement::unpack_root(input).map(|e| e.0)
+}
+
+/// Unpack the first elements of input, returning them with the remaining bytes
+///
+/// Unlike `unpack`, `input` can contain more elements than `T`, the bytes of these elements are
+/// returned as is and can be unpacked later.
+pub fn unpack_partial<'de, T: TupleUnpack<'de>>(input: &'de [u8]) -> PackResult<(T, &'de [u8])> {
+    T::unpack_partial_root(input)
+}
+
+/// Returns the range `[begin, end)` of all the keys starting with `prefix`.
+///
+/// `end` is the first key that does not start with `prefix`. The empty prefix selects the
+/// normal keyspace, up to `\xff`, and the keys starting with only `\xff` bytes select the system
+/// keyspace, up to `\xff\xff`. Returns `None` for prefixes of the special keyspace, starting
+/// with `\xff\xff`, which have no end.
+pub fn prefix_range(prefix: &[u8]) -> Option<(Vec<u8>, Vec<u8>)> {
+    let end = match crate::key::strinc(prefix) {
+        Some(end) => end,
+        None => match prefix.len() {
+            0 => vec![0xff],
+            1 => vec![0xff, 0xff],
+            _ => return None,
+        },
+    };
+    Some((prefix.to_vec(), end))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -233,6 +346,29 @@ mod tests {
         assert_eq!(unpack::<'de, T>(buf).unwrap(), val);
     }
 
+    #[test]
+    fn test_prefix_range() {
+        assert_eq!(
+            prefix_range(b"foo"),
+            Some((b"foo".to_vec(), b"fop".to_vec()))
+        );
+        assert_eq!(
+            prefix_range(b"fo\xff"),
+            Some((b"fo\xff".to_vec(), b"fp".to_vec()))
+        );
+        assert_eq!(
+            prefix_range(b"\x00\xff\xff"),
+            Some((b"\x00\xff\xff".to_vec(), b"\x01".to_vec()))
+        );
+        assert_eq!(prefix_range(b""), Some((Vec::new(), b"\xff".to_vec())));
+        assert_eq!(
+            prefix_range(b"\xff"),
+            Some((b"\xff".to_vec(), b"\xff\xff".to_vec()))
+        );
+        assert_eq!(prefix_range(b"\xff\xff"), None);
+        assert_eq!(prefix_range(b"\xff\xff\xff"), None);
+    }
+
     #[test]
     fn test_spec() {
         test_serde(NIL_VAL, &[NIL]);
@@ -249,6 +385,57 @@ mod tests {
         test_serde(-42f32, b"\x20\x3d\xd7\xff\xff");
     }
 
+    #[test]
+    fn test_option() {
+        fn assert_packed<T: TuplePack>(val: T, buf: &[u8]) {
+            assert_eq!(Bytes::from(pack(&val)), Bytes::from(buf));
+        }
+
+        // the bytes packed by the python binding, `None` being nested as `\x00\xff`
+        assert_packed((None::<&str>,), b"\x00");
+        assert_packed((Some("a"),), b"\x02a\x00");
+        assert_packed((("a", None::<&str>),), b"\x05\x02a\x00\x00\xff\x00");
+        assert_packed((Some(&b"a\x00"[..]),), b"\x01a\x00\xff\x00");
+        assert_packed((((None::<&[u8]>,),),), b"\x05\x05\x00\xff\x00\x00");
+        assert_packed(
+            ("k", vec![Some("a"), None]),
+            b"\x02k\x00\x05\x02a\x00\x00\xff\x00",
+        );
+
+        // borrowed values pack like owned ones
+        assert_packed((None::<&String>, Some(&"a".to_string())), b"\x00\x02a\x00");
+        assert_packed(
+            ((None::<&Vec<u8>>, Some(&vec![1u8])),),
+            b"\x05\x00\xff\x01\x01\x00\x00",
+        );
+        assert_packed(
+            ((Some(&Some(1i64)), Some(&None::<i64>)),),
+            b"\x05\x15\x01\x00\xff\x00",
+        );
+
+        // a single null element: `Some(None)` is packed and unpacked as `None`
+        test_serde((None::<Option<i64>>,), b"\x00");
+        test_serde((Some(Some(1i64)),), b"\x15\x01");
+        test_serde(((None::<Option<i64>>,),), b"\x05\x00\xff\x00");
+        assert_packed(Some(None::<i64>), b"\x00");
+        assert_packed((Some(None::<i64>),), b"\x00");
+        assert_packed(((Some(None::<i64>),),), b"\x05\x00\xff\x00");
+        assert_eq!(
+            unpack::<((Option<Option<i64>>,),)>(b"\x05\x00\xff\x00").unwrap(),
+            ((None,),)
+        );
+
+        // borrowed strings and bytes are unpacked into owned values
+        test_serde(
+            ((Some("a".to_string()), None::<String>),),
+            b"\x05\x02a\x00\x00\xff\x00",
+        );
+        assert_eq!(
+            unpack::<(Option<Bytes>, Option<Cow<str>>)>(b"\x01a\x00\xff\x00\x00").unwrap(),
+            (Some(Bytes::from(&b"a\x00"[..])), None)
+        );
+    }
+
     #[test]
     fn test_simple() {
         // bool
@@ -412,6 +599,50 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_small_ints_and_char() {
+        // byte vectors from `fdb.tuple.pack` of the Python binding
+        test_serde(0u8, b"\x14");
+        test_serde(5u8, b"\x15\x05");
+        test_serde(255u8, b"\x15\xff");
+        test_serde(0i8, b"\x14");
+        test_serde(127i8, b"\x15\x7f");
+        test_serde(-1i8, b"\x13\xfe");
+        test_serde(-128i8, b"\x13\x7f");
+        test_serde((1u8, -2i8), b"\x15\x01\x13\xfd");
+        assert_eq!(pack(&(200u8,)), pack(&(200i64,)));
+        assert_eq!(pack(&(-100i8,)), pack(&(-100i64,)));
+
+        // values that don't fit are rejected
+        match unpack::<u8>(b"\x16\x01\x00").unwrap_err() {
+            PackError::BadCode { found: 0x16, .. } => {}
+            err => panic!("unexpected error {:?}", err),
+        }
+        match unpack::<i8>(b"\x15\x80").unwrap_err() {
+            PackError::UnsupportedIntLength => {}
+            err => panic!("unexpected error {:?}", err),
+        }
+        match unpack::<i8>(b"\x13\x7e").unwrap_err() {
+            PackError::UnsupportedIntLength => {}
+            err => panic!("unexpected error {:?}", err),
+        }
+
+        // `Vec<u8>` and `&[u8]` are byte strings, other vectors are nested tuples
+        test_serde(vec![1u8, 0, 2], b"\x01\x01\x00\xff\x02\x00");
+        assert_eq!(pack(&&b"foo"[..]), b"\x01foo\x00");
+        test_serde((vec![1u8, 2],), b"\x01\x01\x02\x00");
+        test_serde((vec![1i8, -1],), b"\x05\x15\x01\x13\xfe\x00");
+        test_serde((vec![1u16, 2],), b"\x05\x15\x01\x15\x02\x00");
+
+        // a `char` is a string of one character
+        test_serde('a', b"\x02a\x00");
+        test_serde('\0', b"\x02\x00\xff\x00");
+        test_serde(('é', 'a'), b"\x02\xc3\xa9\x00\x02a\x00");
+        assert_eq!(pack(&'Ô'), pack(&"Ô"));
+        assert!(unpack::<char>(b"\x02ab\x00").is_err());
+        assert!(unpack::<char>(b"\x02\x00").is_err());
+    }
+
     #[cfg(feature = "num-bigint")]
     #[test]
     fn test_bigint() {
@@ -678,4 +909,420 @@ mod tests {
             )
         );
     }
+
+    fn random_f64(rng: &mut impl rand::Rng) -> f64 {
+        match rng.gen_range(0, 8) {
+            0 => 0.0,
+            1 => -0.0,
+            2 => f64::NAN,
+            3 => -f64::NAN,
+            4 => f64::INFINITY,
+            5 => f64::NEG_INFINITY,
+            6 => f64::from_bits(rng.gen()),
+            _ => rng.gen::<f64>() * 2.0 - 1.0,
+        }
+    }
+
+    #[cfg(feature = "num-bigint")]
+    fn random_bigint(rng: &mut impl rand::Rng) -> Element<'static> {
+        // values out of the i64 range once scaled
+        let value = num_bigint::BigInt::from(rng.gen::<i64>() >> rng.gen_range(0, 64));
+        let scale = num_bigint::BigInt::from(i64::MAX) * rng.gen_range(0u32, 3) + 1u32;
+        Element::BigInt(value * scale)
+    }
+
+    #[cfg(not(feature = "num-bigint"))]
+    fn random_bigint(rng: &mut impl rand::Rng) -> Element<'static> {
+        Element::Int(rng.gen_range(-2, 3))
+    }
+
+    #[cfg(feature = "uuid")]
+    fn random_uuid(rng: &mut impl rand::Rng) -> Element<'static> {
+        Element::Uuid(uuid::Uuid::from_bytes(rng.gen()))
+    }
+
+    #[cfg(not(feature = "uuid"))]
+    fn random_uuid(_rng: &mut impl rand::Rng) -> Element<'static> {
+        Element::Nil
+    }
+
+    fn random_element(rng: &mut impl rand::Rng, depth: usize) -> Element<'static> {
+        let max_kind = if depth < 3 { 12 } else { 11 };
+        match rng.gen_range(0, max_kind) {
+            0 => Element::Nil,
+            1 => Element::Bytes(
+                (0..rng.gen_range(0, 16))
+                    .map(|_| rng.gen_range(0, 3) as u8 * 0x7f)
+                    .collect::<Vec<u8>>()
+                    .into(),
+            ),
+            2 => Element::String(
+                (0..rng.gen_range(0, 16))
+                    .map(|_| ['a', '\0', 'é', '\u{10FFFF}'][rng.gen_range(0, 4)])
+                    .collect::<String>()
+                    .into(),
+            ),
+            3 => Element::Int(rng.gen::<i64>() >> rng.gen_range(0, 64)),
+            4 => Element::Float(random_f64(rng) as f32),
+            5 => Element::Double(random_f64(rng)),
+            6 => Element::Bool(rng.gen()),
+            7 => Element::Versionstamp(Versionstamp::complete(rng.gen(), rng.gen())),
+            8 => Element::Versionstamp(Versionstamp::incomplete(rng.gen())),
+            9 => random_bigint(rng),
+            10 => random_uuid(rng),
+            _ => Element::Tuple(
+                (0..rng.gen_range(0, 4))
+                    .map(|_| random_element(rng, depth + 1))
+                    .collect(),
+            ),
+        }
+    }
+
+    #[test]
+    fn test_element_ordering() {
+        use rand::SeedableRng;
+
+        fn check(a: &Element, b: &Element) {
+            assert_eq!(
+                a.cmp(b),
+                pack(a).cmp(&pack(b)),
+                "{:?} cmp {:?} doesn't match the packed ordering",
+                a,
+                b
+            );
+        }
+
+        assert!(Element::Double(-0.0) < Element::Double(0.0));
+        assert!(Element::Double(f64::INFINITY) < Element::Double(f64::NAN));
+        assert!(Element::Double(-f64::NAN) < Element::Double(f64::NEG_INFINITY));
+        assert_eq!(Element::Double(f64::NAN), Element::Double(f64::NAN));
+        assert!(Element::Int(i64::MAX) < Element::Float(f32::NEG_INFINITY));
+        assert!(Element::Tuple(vec![]) < Element::Tuple(vec![Element::Nil]));
+
+        let mut rng = rand::rngs::SmallRng::seed_from_u64(0x0bde5);
+        let mut elements = Vec::new();
+        for _ in 0..10_000 {
+            let a = random_element(&mut rng, 0);
+            let b = random_element(&mut rng, 0);
+            check(&a, &b);
+            check(&a, &a.clone());
+
+            // tuples sharing a prefix
+            let mut c = vec![a.clone()];
+            c.push(b.clone());
+            check(&Element::Tuple(vec![a.clone()]), &Element::Tuple(c.clone()));
+            check(
+                &Element::Tuple(vec![Element::Tuple(c)]),
+                &Element::Tuple(vec![b.clone()]),
+            );
+            elements.push(a);
+        }
+
+        elements.sort();
+        let packed: Vec<Vec<u8>> = elements.iter().map(pack).collect();
+        assert!(packed.windows(2).all(|w| w[0] <= w[1]));
+    }
+
+    #[test]
+    fn test_unpack_partial() {
+        let packed = pack(&("prefix", 1, (2, "three"), Bytes::from(b"four".as_ref())));
+
+        let (prefix, rest): (String, _) = unpack_partial(&packed).unwrap();
+        assert_eq!(prefix, "prefix");
+        assert_eq!(
+            rest,
+            &pack(&(1, (2, "three"), Bytes::from(b"four".as_ref())))[..]
+        );
+
+        let ((prefix, one), rest): ((String, i64), _) = unpack_partial(&packed).unwrap();
+        assert_eq!((prefix.as_str(), one), ("prefix", 1));
+        let ((nested, four), rest): (((i64, String), Bytes), _) = unpack_partial(rest).unwrap();
+        assert_eq!(nested, (2, "three".to_string()));
+        assert_eq!(four, Bytes::from(b"four".as_ref()));
+        assert!(rest.is_empty());
+
+        let (all, rest): (Vec<Element>, _) = unpack_partial(&packed).unwrap();
+        assert_eq!(all.len(), 4);
+        assert!(rest.is_empty());
+
+        assert!(unpack_partial::<i64>(&packed).is_err());
+        assert!(unpack_partial::<(String, String)>(&packed).is_err());
+        assert!(unpack::<(String, i64)>(&packed).is_err());
+    }
+
+    #[test]
+    fn test_maps() {
+        use std::collections::{BTreeMap, HashMap};
+
+        let mut map = BTreeMap::new();
+        map.insert("b".to_owned(), 2i64);
+        map.insert("a".to_owned(), 1);
+        assert_eq!(pack(&map), pack(&("a", 1, "b", 2)));
+        assert_eq!(pack(&(&map,)), pack(&(("a", 1, "b", 2),)));
+        assert_eq!(unpack::<BTreeMap<String, i64>>(&pack(&map)).unwrap(), map);
+        assert_eq!(
+            unpack::<(BTreeMap<String, i64>, i64)>(&pack(&(&map, 3))).unwrap(),
+            (map.clone(), 3)
+        );
+        assert_eq!(
+            unpack::<(BTreeMap<String, i64>,)>(&pack(&(BTreeMap::<String, i64>::new(),))).unwrap(),
+            (BTreeMap::new(),)
+        );
+        match unpack::<BTreeMap<String, i64>>(&pack(&("a", 1, "b"))) {
+            Err(PackError::MissingBytes) => {}
+            r => panic!("expected MissingBytes, got {:?}", r),
+        }
+
+        // the packed bytes don't depend on the insertion order nor on the capacity
+        let mut map1 = HashMap::new();
+        let mut map2 = HashMap::with_capacity(1000);
+        for i in 0..100i64 {
+            map1.insert(i, Some(i.to_string()));
+            map2.insert(99 - i, Some((99 - i).to_string()));
+        }
+        map1.insert(100, None);
+        map2.insert(100, None);
+        assert_eq!(pack(&(&map1,)), pack(&(&map2,)));
+        let btree = map1.clone().into_iter().collect::<BTreeMap<_, _>>();
+        assert_eq!(pack(&(&map1,)), pack(&(&btree,)));
+        assert_eq!(
+            unpack::<(BTreeMap<i64, Option<String>>,)>(&pack(&(&map2,))).unwrap(),
+            (btree,)
+        );
+    }
+
+    #[test]
+    fn test_time() {
+        use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+        let duration = Duration::new(5, 7);
+        assert_eq!(pack(&duration), pack(&(5, 7)));
+        assert_eq!(pack(&(duration,)), pack(&((5, 7),)));
+        assert_eq!(unpack::<Duration>(&pack(&duration)).unwrap(), duration);
+        assert!(unpack::<Duration>(&pack(&(5, 1_000_000_000))).is_err());
+        assert!(unpack::<Duration>(&pack(&(-1, 0))).is_err());
+
+        let time = UNIX_EPOCH + Duration::new(1_600_000_000, 500);
+        assert_eq!(pack(&time), pack(&(1_600_000_000, 500)));
+        assert_eq!(unpack::<SystemTime>(&pack(&time)).unwrap(), time);
+
+        let before = UNIX_EPOCH - Duration::from_nanos(1);
+        assert_eq!(pack(&before), pack(&(-1, 999_999_999)));
+        assert_eq!(unpack::<SystemTime>(&pack(&before)).unwrap(), before);
+        let before = UNIX_EPOCH - Duration::from_secs(2);
+        assert_eq!(pack(&before), pack(&(-2, 0)));
+        assert_eq!(unpack::<SystemTime>(&pack(&before)).unwrap(), before);
+
+        let now = SystemTime::now();
+        assert_eq!(unpack::<(SystemTime,)>(&pack(&(now,))).unwrap(), (now,));
+        assert!(unpack::<SystemTime>(&pack(&(0, 1_000_000_000))).is_err());
+    }
+
+    #[test]
+    fn test_bytes_hex() {
+        let bytes = Bytes::from(&b"\x00\x01key\xff"[..]);
+        assert_eq!(bytes.to_hex(), "00016b6579ff");
+        assert_eq!(format!("{:x}", bytes), "00016b6579ff");
+        assert_eq!(Bytes::from_hex("00016b6579ff").unwrap(), bytes);
+        assert_eq!(Bytes::from_hex("00016B6579FF").unwrap(), bytes);
+        assert_eq!(Bytes::from_hex("").unwrap(), Bytes::from(Vec::new()));
+
+        assert_eq!(Bytes::from_hex("abc"), Err(FromHexError::OddLength));
+        assert_eq!(
+            Bytes::from_hex("0g"),
+            Err(FromHexError::InvalidCharacter(1))
+        );
+        assert_eq!(
+            Bytes::from_hex("00é"),
+            Err(FromHexError::InvalidCharacter(2))
+        );
+    }
+
+    #[test]
+    fn test_pack_size_hint() {
+        use rand::SeedableRng;
+
+        let mut rng = rand::rngs::SmallRng::seed_from_u64(0x5eed);
+        for _ in 0..10_000 {
+            let element = random_element(&mut rng, 0);
+            let packed = pack(&element);
+            let hint = element.pack_size_hint();
+            assert!(
+                hint >= packed.len(),
+                "size hint {} < {} for {:?}",
+                hint,
+                packed.len(),
+                element
+            );
+
+            let mut buf = vec![0u8; hint];
+            let size = element.pack_into_slice(&mut buf).unwrap();
+            assert_eq!(Bytes::from(&buf[..size]), Bytes::from(packed.as_slice()));
+
+            if !packed.is_empty() {
+                let mut buf = vec![0u8; packed.len() - 1];
+                match element.pack_into_slice(&mut buf) {
+                    Err(PackError::BufferTooSmall {
+                        required,
+                        available,
+                    }) => {
+                        assert_eq!(required, hint);
+                        assert_eq!(available, packed.len() - 1);
+                    }
+                    r => panic!("expected BufferTooSmall, got {:?}", r),
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_unpack_lossy() {
+        // valid strings are unpacked the same in both modes
+        let packed = pack(&("abc", (1, "d\x00e")));
+        assert_eq!(
+            unpack_lossy(&packed).unwrap(),
+            unpack::<Element>(&packed).unwrap()
+        );
+
+        // a lone continuation byte and an encoded surrogate
+        let invalid: &[&[u8]] = &[b"a\xffb", b"\xed\xa0\x80"];
+        for &bytes in invalid {
+            let mut packed = vec![STRING];
+            packed.extend_from_slice(bytes);
+            packed.push(NIL);
+            match unpack::<Element>(&packed) {
+                Err(PackError::BadStringFormat) => {}
+                r => panic!("expected BadStringFormat, got {:?}", r),
+            }
+            assert_eq!(unpack_lossy(&packed).unwrap(), Element::Bytes(bytes.into()));
+
+            // nested, after a valid element
+            let mut nested = pack(&(1, "x"));
+            nested.push(NESTED);
+            nested.extend_from_slice(&packed);
+            nested.push(NIL);
+            match unpack::<Element>(&nested) {
+                Err(PackError::BadStringFormat) => {}
+                r => panic!("expected BadStringFormat, got {:?}", r),
+            }
+            assert_eq!(
+                unpack_lossy(&nested).unwrap(),
+                Element::Tuple(vec![
+                    Element::Int(1),
+                    Element::String("x".into()),
+                    Element::Tuple(vec![Element::Bytes(bytes.into())]),
+                ])
+            );
+        }
+
+        // an escaped NIL in an invalid string
+        let packed = b"\x02\xff\x00\xff\x00";
+        assert_eq!(
+            unpack_lossy(packed).unwrap(),
+            Element::Bytes(b"\xff\x00"[..].into())
+        );
+    }
+
+    /// Checks the offset returned by `pack_into_vec_with_versionstamp` for `v`, packed after
+    /// `prefix`, against the number of incomplete versionstamps of `element`, its value.
+    fn check_versionstamp_offset<T: TuplePack>(v: &T, element: &Element, prefix: &[u8]) {
+        let mut packed = prefix.to_vec();
+        let offset = v.pack_into_vec_with_versionstamp(&mut packed);
+        match element.count_incomplete_versionstamp() {
+            0 => {
+                assert_eq!(
+                    offset,
+                    VersionstampOffset::None {
+                        size: packed.len() as u32
+                    },
+                    "{:?}",
+                    element
+                );
+            }
+            1 => {
+                let offset = match offset {
+                    VersionstampOffset::OneIncomplete { offset } => offset as usize,
+                    offset => panic!(
+                        "expected one incomplete, got {:?} for {:?}",
+                        offset, element
+                    ),
+                };
+                let len = packed.len();
+                let (packed, offset_bytes) = packed.split_at_mut(len - 4);
+                assert_eq!(offset_bytes, &(offset as u32).to_le_bytes()[..]);
+                assert_eq!(packed[offset - 1], VERSIONSTAMP, "{:?}", element);
+                assert_eq!(&packed[offset..offset + 10], &[0xff; 10], "{:?}", element);
+
+                // the offset points exactly at the transaction version of the stamp
+                packed[offset..offset + 10].copy_from_slice(&[0x42; 10]);
+                let stamped: Element = unpack(&packed[prefix.len()..]).unwrap();
+                assert_eq!(stamped.count_incomplete_versionstamp(), 0, "{:?}", element);
+                let mut expected = pack(element);
+                let start = offset - prefix.len();
+                expected[start..start + 10].copy_from_slice(&[0x42; 10]);
+                assert_eq!(pack(&stamped), expected, "{:?}", element);
+            }
+            _ => assert_eq!(
+                offset,
+                VersionstampOffset::MultipleIncomplete,
+                "{:?}",
+                element
+            ),
+        }
+    }
+
+    #[test]
+    fn test_versionstamp_offsets() {
+        use rand::{Rng, SeedableRng};
+
+        let mut rng = rand::rngs::SmallRng::seed_from_u64(0x057a);
+        for _ in 0..10_000 {
+            let mut element = random_element(&mut rng, 0);
+            // nest it deeper, among other elements
+            for _ in 0..rng.gen_range(0, 6) {
+                let mut tuple = (0..rng.gen_range(0, 3))
+                    .map(|_| random_element(&mut rng, 3))
+                    .collect::<Vec<_>>();
+                let at = rng.gen_range(0, tuple.len() + 1);
+                tuple.insert(at, element);
+                element = Element::Tuple(tuple);
+            }
+            let prefix = (0..rng.gen_range(0, 4))
+                .map(|_| rng.gen())
+                .collect::<Vec<u8>>();
+            check_versionstamp_offset(&element, &element, &prefix);
+            check_versionstamp_offset(
+                &(&element,),
+                &Element::Tuple(vec![element.clone()]),
+                &prefix,
+            );
+        }
+
+        // typed nested tuples
+        fn check_typed<T: TuplePack>(v: &T, prefix: &[u8]) {
+            let packed = pack(v);
+            check_versionstamp_offset(v, &unpack(&packed).unwrap(), prefix);
+        }
+        let stamp = Versionstamp::incomplete(7);
+        check_typed(
+            &(
+                1,
+                ("a", (Some(stamp.clone()), None::<i64>), vec![Element::Nil]),
+            ),
+            b"prefix",
+        );
+        check_typed(&vec![vec![vec![Element::Versionstamp(stamp.clone())]]], b"");
+        check_typed(
+            &vec![(Bytes::from(&b"\x00\xff"[..]), vec![Some(stamp.clone())])],
+            b"\x00",
+        );
+        let mut map = std::collections::HashMap::new();
+        map.insert((1, Versionstamp::incomplete(1)), vec![Element::Nil]);
+        check_typed(&(0, map), b"");
+        let mut map = std::collections::BTreeMap::new();
+        map.insert("a", (Element::Nil, Some(stamp.clone())));
+        map.insert("b", (Element::Nil, None));
+        check_typed(&vec![map], b"");
+        check_typed(&(stamp.clone(), (1, stamp)), b"");
+    }
 }
@@ -1,8 +1,11 @@
 use super::*;
 use memchr::memchr_iter;
+use std::collections::{BTreeMap, HashMap};
 use std::convert::TryFrom;
+use std::hash::BuildHasher;
 use std::io;
 use std::mem;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
 #[derive(Debug, Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Hash)]
 pub enum VersionstampOffset {
@@ -43,6 +46,20 @@ impl std::ops::AddAssign for VersionstampOffset {
 
 const PACK_ERR_MSG: &str = "pack io error on Vec, data size didn't fit in `u32`?";
 
+/// A writer that only counts the bytes written to it
+struct ByteCounter(usize);
+
+impl io::Write for ByteCounter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0 += buf.len();
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
 /// A type that can be packed
 pub trait TuplePack {
     fn pack<W: io::Write>(
@@ -104,12 +121,103 @@ pub trait TuplePack {
         }
         offset
     }
+
+    /// Returns the number of bytes needed to pack this value
+    ///
+    /// The returned size is never smaller than the actual encoding. The default implementation
+    /// computes the exact size without allocating.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the encoded data size doesn't fit in `u32`.
+    fn pack_size_hint(&self) -> usize {
+        let mut counter = ByteCounter(0);
+        self.pack_root(&mut counter).expect(PACK_ERR_MSG);
+        counter.0
+    }
+
+    /// Pack value into the given slice and returns the number of bytes written
+    ///
+    /// Returns `PackError::BufferTooSmall` if the encoded value doesn't fit in `output`, the
+    /// content of `output` is then unspecified.
+    fn pack_into_slice(&self, output: &mut [u8]) -> PackResult<usize> {
+        let available = output.len();
+        let mut w = &mut output[..];
+        match self.pack_root(&mut w) {
+            Ok(..) => Ok(available - w.len()),
+            Err(ref err) if err.kind() == io::ErrorKind::WriteZero => {
+                Err(PackError::BufferTooSmall {
+                    required: self.pack_size_hint(),
+                    available,
+                })
+            }
+            Err(err) => Err(err.into()),
+        }
+    }
+
+    /// Packs a slice of values, this is how `&[Self]` and `Vec<Self>` are packed
+    ///
+    /// The default implementation packs the values as a nested tuple. `u8` overrides it so that
+    /// `&[u8]` and `Vec<u8>` are packed as a byte string, like in the other bindings.
+    fn pack_slice<W: io::Write>(
+        slice: &[Self],
+        w: &mut W,
+        tuple_depth: TupleDepth,
+    ) -> io::Result<VersionstampOffset>
+    where
+        Self: Sized,
+    {
+        let mut offset = VersionstampOffset::None { size: 0 };
+
+        if tuple_depth.depth() > 0 {
+            w.write_all(&[NESTED])?;
+            offset += 1;
+        }
+
+        for v in slice.iter() {
+            offset += v.pack(w, tuple_depth.increment())?;
+        }
+
+        if tuple_depth.depth() > 0 {
+            w.write_all(&[NIL])?;
+            offset += 1;
+        }
+        Ok(offset)
+    }
 }
 
 /// A type that can be unpacked
 pub trait TupleUnpack<'de>: Sized {
     fn unpack(input: &'de [u8], tuple_depth: TupleDepth) -> PackResult<(&'de [u8], Self)>;
 
+    /// Unpacks a list of values, this is how `Vec<Self>` is unpacked
+    ///
+    /// The default implementation unpacks a nested tuple. `u8` overrides it so that `Vec<u8>` is
+    /// unpacked from a byte string, matching `TuplePack::pack_slice`.
+    fn unpack_vec(
+        mut input: &'de [u8],
+        tuple_depth: TupleDepth,
+    ) -> PackResult<(&'de [u8], Vec<Self>)> {
+        let nested = tuple_depth.depth() > 0;
+        if nested {
+            input = parse_code(input, NESTED)?;
+        }
+
+        let mut vec = Vec::new();
+
+        while !is_end_of_tuple(input, nested) {
+            let (rem, v) = Self::unpack(input, tuple_depth.increment())?;
+            input = rem;
+            vec.push(v);
+        }
+
+        if nested {
+            input = parse_code(input, NIL)?;
+        }
+
+        Ok((input, vec))
+    }
+
     fn unpack_root(input: &'de [u8]) -> PackResult<Self> {
         let (input, this) = Self::unpack(input, TupleDepth::new())?;
         if !input.is_empty() {
@@ -117,6 +225,13 @@ pub trait TupleUnpack<'de>: Sized {
         }
         Ok(this)
     }
+
+    /// Unpacks the first elements of `input`, returning them with the bytes that were not
+    /// unpacked.
+    fn unpack_partial_root(input: &'de [u8]) -> PackResult<(Self, &'de [u8])> {
+        let (input, this) = Self::unpack(input, TupleDepth::new())?;
+        Ok((this, input))
+    }
 }
 
 impl<'a, T> TuplePack for &'a T
@@ -354,7 +469,12 @@ macro_rules! impl_ux {
         impl_ux!($ux, mem::size_of::<$ux>());
     };
     ($ux: ident, $max_sz:expr) => {
+        impl_ux!($ux, $max_sz, {}, {});
+    };
+    ($ux: ident, $max_sz:expr, { $($pack_items:tt)* }, { $($unpack_items:tt)* }) => {
         impl TuplePack for $ux {
+            $($pack_items)*
+
             fn pack<W: io::Write>(
                 &self,
                 w: &mut W,
@@ -377,6 +497,8 @@ macro_rules! impl_ux {
         }
 
         impl<'de> TupleUnpack<'de> for $ux {
+            $($unpack_items)*
+
             fn unpack(input: &[u8], _tuple_depth: TupleDepth) -> PackResult<(&[u8], Self)> {
                 const SZ: usize = mem::size_of::<$ux>();
                 let (input, found) = parse_byte(input)?;
@@ -524,14 +646,36 @@ macro_rules! impl_fx {
     };
 }
 
-//impl_ux!(u8);
+// `&[u8]` and `Vec<u8>` are byte strings, not nested tuples of integers
+impl_ux!(
+    u8,
+    mem::size_of::<u8>(),
+    {
+        fn pack_slice<W: io::Write>(
+            slice: &[Self],
+            w: &mut W,
+            tuple_depth: TupleDepth,
+        ) -> io::Result<VersionstampOffset> {
+            Bytes::from(slice).pack(w, tuple_depth)
+        }
+    },
+    {
+        fn unpack_vec(
+            input: &'de [u8],
+            tuple_depth: TupleDepth,
+        ) -> PackResult<(&'de [u8], Vec<Self>)> {
+            let (input, bytes) = Bytes::unpack(input, tuple_depth)?;
+            Ok((input, bytes.into_owned()))
+        }
+    }
+);
 impl_ux!(u16);
 impl_ux!(u32);
 impl_ux!(u64);
 impl_ux!(u128, MAX_SZ);
 impl_ux!(usize);
 
-//impl_ix!(i8, u8);
+impl_ix!(i8, u8);
 impl_ix!(i16, u16);
 impl_ix!(i32, u32);
 impl_ix!(i64, u64);
@@ -723,22 +867,7 @@ where
         w: &mut W,
         tuple_depth: TupleDepth,
     ) -> io::Result<VersionstampOffset> {
-        let mut offset = VersionstampOffset::None { size: 0 };
-
-        if tuple_depth.depth() > 0 {
-            w.write_all(&[NESTED])?;
-            offset += 1;
-        }
-
-        for v in self.iter() {
-            offset += v.pack(w, tuple_depth.increment())?;
-        }
-
-        if tuple_depth.depth() > 0 {
-            w.write_all(&[NIL])?;
-            offset += 1;
-        }
-        Ok(offset)
+        T::pack_slice(self, w, tuple_depth)
     }
 }
 
@@ -751,7 +880,7 @@ where
         w: &mut W,
         tuple_depth: TupleDepth,
     ) -> io::Result<VersionstampOffset> {
-        self.as_slice().pack(w, tuple_depth)
+        T::pack_slice(self, w, tuple_depth)
     }
 }
 
@@ -767,6 +896,36 @@ fn is_end_of_tuple(input: &[u8], nested: bool) -> bool {
 impl<'de, T> TupleUnpack<'de> for Vec<T>
 where
     T: TupleUnpack<'de>,
+{
+    fn unpack(input: &'de [u8], tuple_depth: TupleDepth) -> PackResult<(&'de [u8], Self)> {
+        T::unpack_vec(input, tuple_depth)
+    }
+}
+
+/// Packs the entries as a nested tuple of alternating keys and values, in the order of the keys.
+///
+/// This encoding is specific to this crate, the other bindings have no representation for maps.
+impl<K, V> TuplePack for BTreeMap<K, V>
+where
+    K: TuplePack,
+    V: TuplePack,
+{
+    fn pack<W: io::Write>(
+        &self,
+        w: &mut W,
+        tuple_depth: TupleDepth,
+    ) -> io::Result<VersionstampOffset> {
+        pack_map(self.iter(), w, tuple_depth)
+    }
+}
+
+/// Unpacks a nested tuple of alternating keys and values, as packed by `BTreeMap::pack`.
+///
+/// If a key is repeated, the last value is kept.
+impl<'de, K, V> TupleUnpack<'de> for BTreeMap<K, V>
+where
+    K: TupleUnpack<'de> + Ord,
+    V: TupleUnpack<'de>,
 {
     fn unpack(mut input: &'de [u8], tuple_depth: TupleDepth) -> PackResult<(&'de [u8], Self)> {
         let nested = tuple_depth.depth() > 0;
@@ -774,65 +933,195 @@ where
             input = parse_code(input, NESTED)?;
         }
 
-        let mut vec = Vec::new();
-
+        let mut map = BTreeMap::new();
         while !is_end_of_tuple(input, nested) {
-            let (rem, v) = T::unpack(input, tuple_depth.increment())?;
+            let (rem, k) = K::unpack(input, tuple_depth.increment())?;
+            if is_end_of_tuple(rem, nested) {
+                return Err(PackError::MissingBytes);
+            }
+            let (rem, v) = V::unpack(rem, tuple_depth.increment())?;
             input = rem;
-            vec.push(v);
+            map.insert(k, v);
         }
 
         if nested {
             input = parse_code(input, NIL)?;
         }
 
-        Ok((input, vec))
+        Ok((input, map))
     }
 }
 
-impl<'a> TuplePack for Bytes<'a> {
+/// Packs the entries like `BTreeMap`, the entries being sorted by their packed keys so that
+/// equal maps are packed to the same bytes.
+///
+/// There is no `TupleUnpack` implementation, the entries can be unpacked into a `BTreeMap`.
+impl<K, V, S> TuplePack for HashMap<K, V, S>
+where
+    K: TuplePack,
+    V: TuplePack,
+    S: BuildHasher,
+{
     fn pack<W: io::Write>(
         &self,
         w: &mut W,
-        _tuple_depth: TupleDepth,
+        tuple_depth: TupleDepth,
     ) -> io::Result<VersionstampOffset> {
-        w.write_all(&[BYTES])?;
-        write_bytes(w, self.as_ref())
+        let mut entries = self
+            .iter()
+            .map(|(k, v)| {
+                let mut key = Vec::new();
+                let offset = k.pack(&mut key, tuple_depth.increment())?;
+                Ok((key, offset, v))
+            })
+            .collect::<io::Result<Vec<_>>>()?;
+        entries.sort_by(|a, b| a.0.cmp(&b.0));
+
+        let mut offset = VersionstampOffset::None { size: 0 };
+        if tuple_depth.depth() > 0 {
+            w.write_all(&[NESTED])?;
+            offset += 1;
+        }
+
+        for (key, key_offset, v) in entries {
+            w.write_all(&key)?;
+            offset += key_offset;
+            offset += v.pack(w, tuple_depth.increment())?;
+        }
+
+        if tuple_depth.depth() > 0 {
+            w.write_all(&[NIL])?;
+            offset += 1;
+        }
+        Ok(offset)
     }
 }
 
-impl<'de> TupleUnpack<'de> for Bytes<'de> {
-    fn unpack(input: &'de [u8], _tuple_depth: TupleDepth) -> PackResult<(&'de [u8], Self)> {
-        let input = parse_code(input, BYTES)?;
-        let (input, v) = parse_slice(input)?;
-        Ok((input, Bytes(v)))
+fn pack_map<'a, K, V, W>(
+    entries: impl Iterator<Item = (&'a K, &'a V)>,
+    w: &mut W,
+    tuple_depth: TupleDepth,
+) -> io::Result<VersionstampOffset>
+where
+    K: TuplePack + 'a,
+    V: TuplePack + 'a,
+    W: io::Write,
+{
+    let mut offset = VersionstampOffset::None { size: 0 };
+    if tuple_depth.depth() > 0 {
+        w.write_all(&[NESTED])?;
+        offset += 1;
+    }
+
+    for (k, v) in entries {
+        offset += k.pack(w, tuple_depth.increment())?;
+        offset += v.pack(w, tuple_depth.increment())?;
+    }
+
+    if tuple_depth.depth() > 0 {
+        w.write_all(&[NIL])?;
+        offset += 1;
+    }
+    Ok(offset)
+}
+
+const NANOS_PER_SEC: u32 = 1_000_000_000;
+
+fn check_nanos(nanos: u32) -> PackResult<u32> {
+    if nanos < NANOS_PER_SEC {
+        Ok(nanos)
+    } else {
+        Err(PackError::Message(
+            format!("{} is not a valid number of nanoseconds", nanos).into_boxed_str(),
+        ))
     }
 }
 
-impl<'a> TuplePack for &'a [u8] {
+/// Packs the duration as a `(secs, nanos)` tuple, `nanos` being lower than 1,000,000,000.
+///
+/// This encoding is specific to this crate.
+impl TuplePack for Duration {
     fn pack<W: io::Write>(
         &self,
         w: &mut W,
         tuple_depth: TupleDepth,
     ) -> io::Result<VersionstampOffset> {
-        Bytes::from(*self).pack(w, tuple_depth)
+        (self.as_secs(), self.subsec_nanos()).pack(w, tuple_depth)
     }
 }
 
-impl TuplePack for Vec<u8> {
+impl<'de> TupleUnpack<'de> for Duration {
+    fn unpack(input: &'de [u8], tuple_depth: TupleDepth) -> PackResult<(&'de [u8], Self)> {
+        let (input, (secs, nanos)) = <(u64, u32)>::unpack(input, tuple_depth)?;
+        Ok((input, Duration::new(secs, check_nanos(nanos)?)))
+    }
+}
+
+/// Packs the time as a `(secs, nanos)` tuple relative to the Unix epoch, `nanos` being lower
+/// than 1,000,000,000. The times before the epoch have negative seconds: one nanosecond before
+/// the epoch is `(-1, 999_999_999)`.
+///
+/// This encoding is specific to this crate.
+impl TuplePack for SystemTime {
     fn pack<W: io::Write>(
         &self,
         w: &mut W,
         tuple_depth: TupleDepth,
     ) -> io::Result<VersionstampOffset> {
-        Bytes::from(self.as_slice()).pack(w, tuple_depth)
+        let (secs, nanos) = match self.duration_since(UNIX_EPOCH) {
+            Ok(since) => (i128::from(since.as_secs()), since.subsec_nanos()),
+            Err(err) => {
+                let before = err.duration();
+                let secs = -i128::from(before.as_secs());
+                match before.subsec_nanos() {
+                    0 => (secs, 0),
+                    nanos => (secs - 1, NANOS_PER_SEC - nanos),
+                }
+            }
+        };
+        (secs, nanos).pack(w, tuple_depth)
     }
 }
 
-impl<'de> TupleUnpack<'de> for Vec<u8> {
+impl<'de> TupleUnpack<'de> for SystemTime {
     fn unpack(input: &'de [u8], tuple_depth: TupleDepth) -> PackResult<(&'de [u8], Self)> {
-        let (input, bytes) = Bytes::unpack(input, tuple_depth)?;
-        Ok((input, bytes.into_owned()))
+        let (input, (secs, nanos)) = <(i128, u32)>::unpack(input, tuple_depth)?;
+        let nanos = Duration::from_nanos(u64::from(check_nanos(nanos)?));
+        let out_of_range =
+            || PackError::Message(format!("{} seconds are out of range", secs).into_boxed_str());
+        let whole_secs = secs
+            .checked_abs()
+            .and_then(|secs| u64::try_from(secs).ok())
+            .map(Duration::from_secs)
+            .ok_or_else(out_of_range)?;
+        let time = if secs >= 0 {
+            UNIX_EPOCH.checked_add(whole_secs)
+        } else {
+            UNIX_EPOCH.checked_sub(whole_secs)
+        };
+        let time = time
+            .and_then(|time| time.checked_add(nanos))
+            .ok_or_else(out_of_range)?;
+        Ok((input, time))
+    }
+}
+
+impl<'a> TuplePack for Bytes<'a> {
+    fn pack<W: io::Write>(
+        &self,
+        w: &mut W,
+        _tuple_depth: TupleDepth,
+    ) -> io::Result<VersionstampOffset> {
+        w.write_all(&[BYTES])?;
+        write_bytes(w, self.as_ref())
+    }
+}
+
+impl<'de> TupleUnpack<'de> for Bytes<'de> {
+    fn unpack(input: &'de [u8], _tuple_depth: TupleDepth) -> PackResult<(&'de [u8], Self)> {
+        let input = parse_code(input, BYTES)?;
+        let (input, v) = parse_slice(input)?;
+        Ok((input, Bytes(v)))
     }
 }
 
@@ -883,6 +1172,37 @@ impl<'de> TupleUnpack<'de> for String {
     }
 }
 
+impl TuplePack for char {
+    fn pack<W: io::Write>(
+        &self,
+        w: &mut W,
+        tuple_depth: TupleDepth,
+    ) -> io::Result<VersionstampOffset> {
+        let mut buf = [0u8; 4];
+        (&*self.encode_utf8(&mut buf)).pack(w, tuple_depth)
+    }
+}
+
+impl<'de> TupleUnpack<'de> for char {
+    fn unpack(input: &[u8], _tuple_depth: TupleDepth) -> PackResult<(&[u8], Self)> {
+        let input = parse_code(input, STRING)?;
+        let (input, v) = parse_string(input)?;
+        let mut chars = v.chars();
+        match (chars.next(), chars.next()) {
+            (Some(c), None) => Ok((input, c)),
+            _ => Err(PackError::Message(
+                format!("{:?} is not a single character", v).into_boxed_str(),
+            )),
+        }
+    }
+}
+
+/// Packs `None` as the null element, `\x00`, or `\x00\xff` inside a nested tuple, and `Some(v)`
+/// as `v`.
+///
+/// The tuple encoding has a single null element, so `Some(None)` packs like `None` and is
+/// unpacked as `None`. `Option<&T>` packs to the same bytes as `Option<T>`, e.g. `Option<&str>`
+/// like `Option<String>`; borrowed values are unpacked into their owned or `Cow` counterparts.
 impl<T> TuplePack for Option<T>
 where
     T: TuplePack,
@@ -1053,6 +1373,58 @@ impl<'de> TupleUnpack<'de> for Element<'de> {
     }
 }
 
+/// An `Element` unpacked by `unpack_lossy`, whose strings that are not valid UTF-8 are unpacked
+/// as `Element::Bytes`.
+pub(crate) struct LossyElement<'de>(pub(crate) Element<'de>);
+
+impl<'de> TupleUnpack<'de> for LossyElement<'de> {
+    fn unpack(input: &'de [u8], tuple_depth: TupleDepth) -> PackResult<(&'de [u8], Self)> {
+        let first = match input.first() {
+            None if tuple_depth.depth() == 0 => {
+                return Ok((input, LossyElement(Element::Tuple(Vec::new()))))
+            }
+            None => return Err(PackError::MissingBytes),
+            Some(byte) => byte,
+        };
+
+        let (mut input, mut v) = match *first {
+            STRING => {
+                let (input, slice) = parse_slice(parse_code(input, STRING)?)?;
+                let v = match slice {
+                    Cow::Borrowed(slice) => match std::str::from_utf8(slice) {
+                        Ok(s) => Element::String(Cow::Borrowed(s)),
+                        Err(_) => Element::Bytes(Bytes(Cow::Borrowed(slice))),
+                    },
+                    Cow::Owned(vec) => match String::from_utf8(vec) {
+                        Ok(s) => Element::String(Cow::Owned(s)),
+                        Err(err) => Element::Bytes(Bytes(Cow::Owned(err.into_bytes()))),
+                    },
+                };
+                (input, v)
+            }
+            NESTED => {
+                let (input, v) = Vec::<Self>::unpack(input, tuple_depth)?;
+                (input, Element::Tuple(v.into_iter().map(|e| e.0).collect()))
+            }
+            _ if tuple_depth.depth() == 0 => Element::unpack(input, tuple_depth.increment())?,
+            _ => Element::unpack(input, tuple_depth)?,
+        };
+
+        if tuple_depth.depth() == 0 && !input.is_empty() {
+            let mut tuple = Vec::new();
+            tuple.push(v);
+            while !input.is_empty() {
+                let (rem, v) = Self::unpack(input, tuple_depth.increment())?;
+                tuple.push(v.0);
+                input = rem;
+            }
+            v = Element::Tuple(tuple);
+        }
+
+        Ok((input, LossyElement(v)))
+    }
+}
+
 impl TuplePack for Versionstamp {
     fn pack<W: io::Write>(
         &self,
@@ -7,8 +7,18 @@
 // copied, modified, or distributed except according to those terms.
 
 use super::*;
-use crate::{KeySelector, RangeOption, Transaction};
-use std::borrow::Cow;
+use crate::future::{FdbValue, FdbValues};
+use crate::options::{ConflictRangeType, MutationType};
+use crate::{
+    FdbBindingError, FdbError, FdbResult, KeySelector, RangeOption, RedactedBytes, Transaction,
+};
+use futures::{Stream, StreamExt};
+use std::borrow::{Borrow, Cow};
+use std::ops::{Bound, RangeBounds};
+use std::sync::Arc;
+
+/// `key_outside_legal_range` error code
+const KEY_OUTSIDE_LEGAL_RANGE: i32 = 2004;
 
 /// Represents a well-defined region of keyspace in a FoundationDB database
 ///
@@ -21,50 +31,111 @@ use std::borrow::Cow;
 /// general guidance on subspace usage, see the Subspaces section of the [Developer Guide].
 ///
 /// [Developer Guide]: https://apple.github.io/foundationdb/developer-guide.html#subspaces
-#[derive(Debug, Clone)]
+///
+/// The prefix is immutable and shared between clones, cloning a Subspace doesn't copy it.
+/// Subspaces are compared, ordered and hashed by their prefix bytes, and can be looked up by
+/// prefix in maps thanks to `Borrow<[u8]>`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
 pub struct Subspace {
-    prefix: Vec<u8>,
+    prefix: Arc<[u8]>,
 }
 
 impl<E: TuplePack> From<E> for Subspace {
     fn from(e: E) -> Self {
-        Self { prefix: pack(&e) }
+        Self {
+            prefix: pack(&e).into(),
+        }
+    }
+}
+
+impl Borrow<[u8]> for Subspace {
+    fn borrow(&self) -> &[u8] {
+        &self.prefix
     }
 }
 
 impl Subspace {
     /// `all` returns the Subspace corresponding to all keys in a FoundationDB database.
     pub fn all() -> Subspace {
-        Self { prefix: Vec::new() }
+        Self {
+            prefix: Arc::new([]),
+        }
     }
 
     /// `from_bytes` returns a new Subspace from the provided bytes.
     pub fn from_bytes(bytes: &[u8]) -> Self {
         Self {
-            prefix: bytes.to_vec(),
+            prefix: bytes.into(),
         }
     }
 
     /// Returns a new Subspace whose prefix extends this Subspace with a given tuple encodable.
     pub fn subspace<T: TuplePack>(&self, t: &T) -> Self {
         Self {
-            prefix: self.pack(t),
+            prefix: self.pack(t).into(),
         }
     }
 
     /// `bytes` returns the literal bytes of the prefix of this Subspace.
     pub fn bytes(&self) -> &[u8] {
-        self.prefix.as_slice()
+        &self.prefix
     }
 
     /// Returns the key encoding the specified Tuple with the prefix of this Subspace
     /// prepended.
     pub fn pack<T: TuplePack>(&self, t: &T) -> Vec<u8> {
-        let mut out = self.prefix.clone();
+        let mut out = self.prefix.to_vec();
         pack_into(t, &mut out);
         out
     }
 
+    /// Returns the key encoding the specified Tuple with the prefix of this Subspace prepended,
+    /// followed by the 4 bytes little-endian offset of its incomplete versionstamp.
+    ///
+    /// The returned key is meant to be written with `MutationType::SetVersionstampedKey`, which
+    /// replaces the incomplete versionstamp and removes the offset. Returns an error if `t`
+    /// doesn't contain exactly one incomplete versionstamp.
+    pub fn pack_with_versionstamp<T: TuplePack>(&self, t: &T) -> PackResult<Vec<u8>> {
+        let mut out = self.prefix.to_vec();
+        match t.pack_into_vec_with_versionstamp(&mut out) {
+            VersionstampOffset::OneIncomplete { .. } => Ok(out),
+            VersionstampOffset::None { .. } => Err(PackError::NoIncompleteVersionstamp),
+            VersionstampOffset::MultipleIncomplete => {
+                Err(PackError::MultipleIncompleteVersionstamps)
+            }
+        }
+    }
+
+    /// Returns the number of bytes needed by `pack` for the specified Tuple, which is never
+    /// smaller than the actual key size.
+    pub fn pack_size_hint<T: TuplePack>(&self, t: &T) -> usize {
+        self.prefix.len() + t.pack_size_hint()
+    }
+
+    /// Writes the key encoding the specified Tuple with the prefix of this Subspace prepended
+    /// into `output`, and returns the size of the key.
+    ///
+    /// Returns `PackError::BufferTooSmall` if the key doesn't fit in `output`.
+    pub fn pack_into_slice<T: TuplePack>(&self, t: &T, output: &mut [u8]) -> PackResult<usize> {
+        let prefix_len = self.prefix.len();
+        let available = output.len();
+        if available < prefix_len {
+            return Err(PackError::BufferTooSmall {
+                required: self.pack_size_hint(t),
+                available,
+            });
+        }
+        output[..prefix_len].copy_from_slice(&self.prefix);
+        match t.pack_into_slice(&mut output[prefix_len..]) {
+            Ok(size) => Ok(prefix_len + size),
+            Err(PackError::BufferTooSmall { required, .. }) => Err(PackError::BufferTooSmall {
+                required: prefix_len + required,
+                available,
+            }),
+            Err(err) => Err(err),
+        }
+    }
+
     /// `unpack` returns the Tuple encoded by the given key with the prefix of this Subspace
     /// removed.  `unpack` will return an error if the key is not in this Subspace or does not
     /// encode a well-formed Tuple.
@@ -76,24 +147,145 @@ impl Subspace {
         unpack(key)
     }
 
+    /// `unpack_partial` returns the first elements of the Tuple encoded by the given key with the
+    /// prefix of this Subspace removed, and the bytes of the elements that were not unpacked.
+    ///
+    /// This allows to decode the common elements of heterogeneous keys, and to decide how to
+    /// decode the remaining bytes from them. `unpack_partial` will return an error if the key is
+    /// not in this Subspace or does not start with a well-formed `T`.
+    pub fn unpack_partial<'de, T: TupleUnpack<'de>>(
+        &self,
+        key: &'de [u8],
+    ) -> PackResult<(T, &'de [u8])> {
+        if !self.is_start_of(key) {
+            return Err(PackError::BadPrefix);
+        }
+        let key = &key[self.prefix.len()..];
+        unpack_partial(key)
+    }
+
+    /// `decode_kv` unpacks the key of a key-value pair of this Subspace into `K`, the prefix of
+    /// this Subspace removed, and its value into `V`.
+    ///
+    /// Unlike `unpack`, a key outside of this Subspace is reported by
+    /// `PackError::KeyOutsideSubspace`, which carries the key.
+    pub fn decode_kv<'de, K: TupleUnpack<'de>, V: TupleUnpack<'de>>(
+        &self,
+        key: &'de [u8],
+        value: &'de [u8],
+    ) -> PackResult<(K, V)> {
+        if !self.is_start_of(key) {
+            return Err(PackError::KeyOutsideSubspace { key: key.to_vec() });
+        }
+        let k = unpack(&key[self.prefix.len()..])?;
+        let v = unpack(value)?;
+        Ok((k, v))
+    }
+
     /// `is_start_of` returns true if the provided key starts with the prefix of this Subspace,
     /// indicating that the Subspace logically contains the key.
     pub fn is_start_of(&self, key: &[u8]) -> bool {
-        key.starts_with(&self.prefix)
+        key.starts_with(self.bytes())
+    }
+
+    /// `is_root` returns true if this Subspace has an empty prefix, like `Subspace::all`.
+    pub fn is_root(&self) -> bool {
+        self.prefix.is_empty()
     }
 
     /// `range` returns first and last key of given Subspace
+    ///
+    /// The range of the root Subspace is `[\x00, \xff)`: it contains all the keys packed by
+    /// the root Subspace, but neither the empty key nor the system keys starting with `\xff`.
     pub fn range(&self) -> (Vec<u8>, Vec<u8>) {
-        let mut begin = Vec::with_capacity(self.prefix.len() + 1);
-        begin.extend_from_slice(&self.prefix);
-        begin.push(0x00);
+        if self.is_root() {
+            // never include the system keys, whatever the helper the range is given to
+            return (vec![0x00], vec![0xff]);
+        }
+        tuple_range(self.prefix.to_vec())
+    }
 
-        let mut end = Vec::with_capacity(self.prefix.len() + 1);
-        end.extend_from_slice(&self.prefix);
-        end.push(0xff);
+    /// Returns the range of the keys packed by this Subspace from tuples starting with the
+    /// elements of `t`, i.e. `self.subspace(t).range()`.
+    ///
+    /// The key packing `t` itself is not part of the range.
+    pub fn range_with_prefix<T: TuplePack>(&self, t: &T) -> (Vec<u8>, Vec<u8>) {
+        tuple_range(self.pack(t))
+    }
 
+    /// Returns the range of the keys packed by this Subspace from tuples starting with the
+    /// elements of `t`, followed by an element within `bounds`.
+    ///
+    /// The bounds apply to the element following `t`, whatever the elements after it: with an
+    /// excluded start `u`, neither the key packing `(t..., u)` nor the keys of longer tuples
+    /// starting with `(t..., u)` are part of the range. An unbounded side extends to the end of
+    /// `range_with_prefix(t)`, which doesn't contain the key packing `t` itself. As for
+    /// `RangeOption`, the end of the returned range is exclusive.
+    ///
+    /// ```
+    /// use foundationdb::tuple::Subspace;
+    /// use std::ops::Bound;
+    ///
+    /// let events = Subspace::from("events");
+    /// // the events of user 42 at times in (100, 200]
+    /// let (begin, end) = events.range_bounds(&42, (Bound::Excluded(100), Bound::Included(200)));
+    /// assert!(events.pack(&(42, 100, "a")) < begin);
+    /// assert!(begin <= events.pack(&(42, 101)) && events.pack(&(42, 200, "a")) < end);
+    /// assert!(events.pack(&(42, 201)) >= end);
+    ///
+    /// let (begin, end) = events.range_bounds(&42, 100..200);
+    /// assert_eq!(begin, events.pack(&(42, 100)));
+    /// assert_eq!(end, events.pack(&(42, 200)));
+    /// ```
+    pub fn range_bounds<T, B, U>(&self, t: &T, bounds: B) -> (Vec<u8>, Vec<u8>)
+    where
+        T: TuplePack,
+        B: RangeBounds<U>,
+        U: TuplePack,
+    {
+        let prefix = self.pack(t);
+        let bound = |element: &U, after: bool| {
+            let mut key = prefix.clone();
+            pack_into(element, &mut key);
+            if after {
+                // after the keys of all the tuples starting with `element`
+                key.push(0xff);
+            }
+            key
+        };
+        let (unbounded_begin, unbounded_end) = tuple_range(prefix.clone());
+        let begin = match bounds.start_bound() {
+            Bound::Included(element) => bound(element, false),
+            Bound::Excluded(element) => bound(element, true),
+            Bound::Unbounded => unbounded_begin,
+        };
+        let end = match bounds.end_bound() {
+            Bound::Included(element) => bound(element, true),
+            Bound::Excluded(element) => bound(element, false),
+            Bound::Unbounded => unbounded_end,
+        };
         (begin, end)
     }
+
+    /// Returns the `RangeOption` selecting all the keys packed by this Subspace.
+    ///
+    /// Keys of adjacent subspaces, including subspaces whose prefix starts with this Subspace
+    /// prefix, and the prefix itself are not part of the range.
+    pub fn range_option(&self) -> RangeOption<'static> {
+        RangeOption::from(self)
+    }
+}
+
+/// Returns the range of the tuples packed after `prefix`.
+fn tuple_range(prefix: Vec<u8>) -> (Vec<u8>, Vec<u8>) {
+    let mut end = Vec::with_capacity(prefix.len() + 1);
+    end.extend_from_slice(&prefix);
+    end.push(0xff);
+
+    let mut begin = prefix;
+    begin.push(0x00);
+
+    (begin, end)
 }
 
 impl<'a> From<&'a Subspace> for RangeOption<'static> {
@@ -108,17 +300,271 @@ impl<'a> From<&'a Subspace> for RangeOption<'static> {
     }
 }
 
+/// The operations of a region of the keyspace, packing keys under a prefix
+///
+/// It is implemented by `Subspace`, `DirectorySubspace` and `DirectoryOutput`, so that layers
+/// can be written once for all of them. Unlike the inherent methods of `DirectoryOutput`, which
+/// panic for a partition, whose prefix cannot be used directly, these methods return an error.
+///
+/// # Example
+///
+/// ```
+/// use foundationdb::options::MutationType;
+/// use foundationdb::tuple::{KeyspaceOps, PackResult};
+/// use foundationdb::{FdbError, Transaction};
+///
+/// /// Counters stored under any keyspace
+/// struct Counters<K> {
+///     space: K,
+/// }
+///
+/// impl<K: KeyspaceOps> Counters<K> {
+///     fn new(space: K) -> Self {
+///         Counters { space }
+///     }
+///
+///     fn increment(&self, trx: &Transaction, name: &str) -> PackResult<()> {
+///         let key = self.space.pack(&name)?;
+///         trx.atomic_op(&key, &1i64.to_le_bytes(), MutationType::Add);
+///         Ok(())
+///     }
+///
+///     async fn get(&self, trx: &Transaction, name: &str) -> Result<i64, FdbError> {
+///         let key = self.space.pack(&name).expect("counters in a partition");
+///         let mut value = [0; 8];
+///         if let Some(bytes) = trx.get(&key, false).await? {
+///             value.copy_from_slice(&bytes);
+///         }
+///         Ok(i64::from_le_bytes(value))
+///     }
+/// }
+/// ```
+pub trait KeyspaceOps {
+    /// Returns the prefix of the keyspace.
+    fn bytes(&self) -> PackResult<&[u8]>;
+
+    /// Returns the key encoding the specified Tuple with the prefix of the keyspace prepended.
+    fn pack<T: TuplePack>(&self, t: &T) -> PackResult<Vec<u8>>;
+
+    /// Returns the Tuple encoded by the given key with the prefix of the keyspace removed.
+    fn unpack<'de, T: TupleUnpack<'de>>(&self, key: &'de [u8]) -> PackResult<T>;
+
+    /// Returns the first and last key of the keyspace.
+    fn range(&self) -> PackResult<(Vec<u8>, Vec<u8>)>;
+
+    /// Returns a new Subspace whose prefix extends the keyspace with a given tuple encodable.
+    fn subspace<T: TuplePack>(&self, t: &T) -> PackResult<Subspace>;
+}
+
+impl KeyspaceOps for Subspace {
+    fn bytes(&self) -> PackResult<&[u8]> {
+        Ok(Subspace::bytes(self))
+    }
+
+    fn pack<T: TuplePack>(&self, t: &T) -> PackResult<Vec<u8>> {
+        Ok(Subspace::pack(self, t))
+    }
+
+    fn unpack<'de, T: TupleUnpack<'de>>(&self, key: &'de [u8]) -> PackResult<T> {
+        Subspace::unpack(self, key)
+    }
+
+    fn range(&self) -> PackResult<(Vec<u8>, Vec<u8>)> {
+        Ok(Subspace::range(self))
+    }
+
+    fn subspace<T: TuplePack>(&self, t: &T) -> PackResult<Subspace> {
+        Ok(Subspace::subspace(self, t))
+    }
+}
+
+impl<'a, K: KeyspaceOps + ?Sized> KeyspaceOps for &'a K {
+    fn bytes(&self) -> PackResult<&[u8]> {
+        (**self).bytes()
+    }
+
+    fn pack<T: TuplePack>(&self, t: &T) -> PackResult<Vec<u8>> {
+        (**self).pack(t)
+    }
+
+    fn unpack<'de, T: TupleUnpack<'de>>(&self, key: &'de [u8]) -> PackResult<T> {
+        (**self).unpack(key)
+    }
+
+    fn range(&self) -> PackResult<(Vec<u8>, Vec<u8>)> {
+        (**self).range()
+    }
+
+    fn subspace<T: TuplePack>(&self, t: &T) -> PackResult<Subspace> {
+        (**self).subspace(t)
+    }
+}
+
+/// Decodes the key-values of `stream`, usually returned by `Transaction::get_ranges_keyvalues`,
+/// with `Subspace::decode_kv`.
+///
+/// The errors of `stream` and the errors decoding a key-value, e.g.
+/// `PackError::KeyOutsideSubspace` for a key outside of `subspace`, are returned in place of
+/// the key-value, so that `try_collect` or `try_for_each` stop at the first of them.
+///
+/// ```
+/// use foundationdb::tuple::{decode_subspace_stream, Subspace};
+/// use foundationdb::{FdbBindingError, Transaction};
+/// use futures::prelude::*;
+///
+/// async fn scores(
+///     trx: &Transaction,
+///     players: &Subspace,
+/// ) -> Result<Vec<((String,), (i64,))>, FdbBindingError> {
+///     let kvs = trx.get_ranges_keyvalues(players.range_option(), false);
+///     decode_subspace_stream(players, kvs).try_collect().await
+/// }
+/// ```
+pub fn decode_subspace_stream<S, K, V>(
+    subspace: &Subspace,
+    stream: S,
+) -> impl Stream<Item = Result<(K, V), FdbBindingError>>
+where
+    S: Stream<Item = FdbResult<FdbValue>>,
+    K: for<'de> TupleUnpack<'de>,
+    V: for<'de> TupleUnpack<'de>,
+{
+    let subspace = subspace.clone();
+    stream.map(move |kv| -> Result<(K, V), FdbBindingError> {
+        let kv = kv?;
+        Ok(subspace.decode_kv(kv.key(), kv.value())?)
+    })
+}
+
 impl Transaction {
+    /// Returns a stream of KeyValue slices of all the keys in `subspace`.
+    ///
+    /// See `get_ranges` and `Subspace::range_option` for details.
+    pub fn get_subspace_ranges<'a>(
+        &'a self,
+        subspace: &Subspace,
+        snapshot: bool,
+    ) -> impl Stream<Item = FdbResult<FdbValues>> + Send + Sync + Unpin + 'a {
+        self.get_ranges(subspace.range_option(), snapshot)
+    }
+
+    /// Clears all the keys in `subspace`.
+    ///
+    /// Like `Subspace::range_option`, the prefix itself and the keys starting with the prefix
+    /// followed by `\xff` are not cleared, use `clear_prefix` to clear them too. In particular,
+    /// clearing the root Subspace clears the whole normal keyspace but never the system keys.
     pub fn clear_subspace_range(&self, subspace: &Subspace) {
         let (begin, end) = subspace.range();
+        debug_assert!(!subspace.is_root() || end.as_slice() == b"\xff");
         self.clear_range(&begin, &end)
     }
+
+    /// Adds a conflict range of type `ty` on all the keys in `subspace`, the range of
+    /// `Subspace::range`.
+    pub fn add_conflict_subspace(
+        &self,
+        subspace: &Subspace,
+        ty: ConflictRangeType,
+    ) -> FdbResult<()> {
+        let (begin, end) = subspace.range();
+        self.add_conflict_range(&begin, &end, ty)
+    }
+
+    /// Adds a conflict range of type `ty` on the key encoding `t` in `subspace` only.
+    ///
+    /// The keys starting with this key, e.g. the keys of `subspace.subspace(t)`, are not in the
+    /// range.
+    pub fn add_conflict_key<T: TuplePack>(
+        &self,
+        subspace: &Subspace,
+        t: &T,
+        ty: ConflictRangeType,
+    ) -> FdbResult<()> {
+        let key = subspace.pack(t);
+        self.add_conflict_range(&key, &crate::key::key_after(&key), ty)
+    }
+
+    /// Clears all the keys starting with `prefix`, including `prefix` itself.
+    ///
+    /// The cleared range is computed by `tuple::prefix_range`: the empty prefix clears the normal
+    /// keyspace and a prefix made of a single `\xff` the system keyspace.
+    ///
+    /// The prefixes of the special keyspace, starting with `\xff\xff`, have no range: nothing is
+    /// cleared and `key_outside_legal_range` (2004) is returned.
+    pub fn clear_prefix(&self, prefix: &[u8]) -> FdbResult<()> {
+        match prefix_range(prefix) {
+            Some((begin, end)) => {
+                self.clear_range(&begin, &end);
+                Ok(())
+            }
+            None => Err(
+                FdbError::from_code(KEY_OUTSIDE_LEGAL_RANGE).with_context(format!(
+                    "clear_prefix: no range for the prefix {}",
+                    RedactedBytes(prefix)
+                )),
+            ),
+        }
+    }
+
+    /// Sets the key encoding `t` in `subspace` to `value`, the incomplete versionstamp of `t`
+    /// being replaced by the versionstamp of this transaction at commit time.
+    ///
+    /// Use `get_versionstamp_typed` to retrieve the versionstamp of the written key.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `t` doesn't contain exactly one incomplete versionstamp.
+    pub fn set_versionstamped_key<T: TuplePack>(&self, subspace: &Subspace, t: &T, value: &[u8]) {
+        let key = subspace
+            .pack_with_versionstamp(t)
+            .expect("set_versionstamped_key requires exactly one incomplete versionstamp");
+        self.atomic_op(&key, value, MutationType::SetVersionstampedKey);
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    #[test]
+    fn keyspace_ops() {
+        fn pack_in<K: KeyspaceOps>(space: K) -> PackResult<Vec<u8>> {
+            space.pack(&("key",))
+        }
+
+        let ss: Subspace = 1.into();
+        assert_eq!(pack_in(&ss).unwrap(), ss.pack(&("key",)));
+        assert_eq!(KeyspaceOps::bytes(&ss).unwrap(), ss.bytes());
+        assert_eq!(KeyspaceOps::range(&ss).unwrap(), ss.range());
+        assert_eq!(
+            KeyspaceOps::subspace(&ss, &2).unwrap().bytes(),
+            ss.subspace(&2).bytes()
+        );
+        let key = ss.pack(&(2, "three"));
+        let t: (i64, String) = KeyspaceOps::unpack(&ss, &key).unwrap();
+        assert_eq!(t, (2, "three".to_string()));
+    }
+
+    #[test]
+    fn decode_kv() {
+        let ss: Subspace = ("players",).into();
+        let key = ss.pack(&("alice", 3));
+        let value = pack(&(42u64,));
+        let (k, v): ((String, i64), (u64,)) = ss.decode_kv(&key, &value).unwrap();
+        assert_eq!(k, ("alice".to_string(), 3));
+        assert_eq!(v, (42,));
+
+        let foreign = pack(&("teams", "alice"));
+        match ss.decode_kv::<(String, i64), (u64,)>(&foreign, &value) {
+            Err(PackError::KeyOutsideSubspace { key }) => assert_eq!(key, foreign),
+            r => panic!("expected KeyOutsideSubspace, got {:?}", r),
+        }
+        match ss.decode_kv::<(String, i64), (u64,)>(&key, b"not a tuple") {
+            Err(PackError::KeyOutsideSubspace { .. }) | Ok(..) => panic!("expected a bad value"),
+            Err(..) => {}
+        }
+    }
+
     #[test]
     fn sub() {
         let ss0: Subspace = 1.into();
@@ -129,6 +575,27 @@ mod tests {
         assert_eq!(ss1.bytes(), ss2.bytes());
     }
 
+    #[test]
+    fn pack_into_slice() {
+        let ss0: Subspace = 1.into();
+        let tup = (2, "three");
+
+        let packed = ss0.pack(&tup);
+        assert!(ss0.pack_size_hint(&tup) >= packed.len());
+
+        let mut buf = [0u8; 32];
+        let size = ss0.pack_into_slice(&tup, &mut buf).unwrap();
+        assert_eq!(&buf[..size], packed.as_slice());
+
+        match ss0.pack_into_slice(&tup, &mut buf[..1]) {
+            Err(PackError::BufferTooSmall {
+                required,
+                available: 1,
+            }) => assert_eq!(required, packed.len()),
+            r => panic!("expected BufferTooSmall, got {:?}", r),
+        }
+    }
+
     #[test]
     fn pack_unpack() {
         let ss0: Subspace = 1.into();
@@ -144,6 +611,60 @@ mod tests {
         assert!(ss0.unpack::<(i64, i64, i64)>(&packed).is_err());
     }
 
+    #[test]
+    fn pack_with_versionstamp() {
+        let ss0: Subspace = 1.into();
+        let tup = ("a", Versionstamp::incomplete(7));
+
+        let key = ss0.pack_with_versionstamp(&tup).unwrap();
+        let packed = ss0.pack(&tup);
+        let (body, trailer) = key.split_at(key.len() - 4);
+        assert_eq!(body, packed.as_slice());
+        let mut offset = [0u8; 4];
+        offset.copy_from_slice(trailer);
+        let offset = u32::from_le_bytes(offset) as usize;
+        // the offset points at the 10 bytes transaction version, after the versionstamp code
+        assert_eq!(offset, packed.len() - 12);
+        assert_eq!(&packed[offset..offset + 10], &[0xff; 10]);
+
+        match ss0.pack_with_versionstamp(&("a", 2)) {
+            Err(PackError::NoIncompleteVersionstamp) => {}
+            r => panic!("expected NoIncompleteVersionstamp, got {:?}", r),
+        }
+        let complete = Versionstamp::complete([1; 10], 0);
+        match ss0.pack_with_versionstamp(&("a", complete)) {
+            Err(PackError::NoIncompleteVersionstamp) => {}
+            r => panic!("expected NoIncompleteVersionstamp, got {:?}", r),
+        }
+        let two = (Versionstamp::incomplete(0), Versionstamp::incomplete(1));
+        match ss0.pack_with_versionstamp(&two) {
+            Err(PackError::MultipleIncompleteVersionstamps) => {}
+            r => panic!("expected MultipleIncompleteVersionstamps, got {:?}", r),
+        }
+    }
+
+    #[test]
+    fn unpack_partial() {
+        let ss0: Subspace = 1.into();
+        let packed = ss0.pack(&("a", 2, "b"));
+
+        let (all, rest): ((String, i64, String), _) = ss0.unpack_partial(&packed).unwrap();
+        assert_eq!(all, ("a".to_string(), 2, "b".to_string()));
+        assert!(rest.is_empty());
+
+        let (first, rest): (String, _) = ss0.unpack_partial(&packed).unwrap();
+        assert_eq!(first, "a");
+        assert_eq!(rest, &pack(&(2, "b"))[..]);
+        assert!(ss0.unpack::<String>(&packed).is_err());
+
+        let ss1: Subspace = 2.into();
+        match ss1.unpack_partial::<String>(&packed) {
+            Err(PackError::BadPrefix) => {}
+            r => panic!("expected BadPrefix, got {:?}", r),
+        }
+        assert!(ss0.unpack_partial::<i64>(&packed).is_err());
+    }
+
     #[test]
     fn is_start_of() {
         let ss0: Subspace = 1.into();
@@ -159,6 +680,33 @@ mod tests {
         assert!(Subspace::from(("start", 42)).is_start_of(&pack(&("start", 42, "end"))));
     }
 
+    #[test]
+    fn range_option() {
+        fn contains(range: &RangeOption, key: &[u8]) -> bool {
+            key >= range.begin.key() && key < range.end.key()
+        }
+
+        let ss: Subspace = 1.into();
+        let range = ss.range_option();
+        assert!(contains(&range, &ss.pack(&(2, 3))));
+        assert!(contains(&range, &ss.pack(&"")));
+        assert!(!contains(&range, ss.bytes()));
+        assert!(!contains(&range, &pack(&0)));
+        assert!(!contains(&range, &pack(&2)));
+
+        // prefix ending with 0xff
+        let ss = Subspace::from_bytes(b"a\xff");
+        let range = ss.range_option();
+        assert!(contains(&range, &ss.pack(&(2, 3))));
+        assert!(contains(&range, &ss.pack(&Bytes::from(&b"\xff"[..]))));
+        assert!(!contains(&range, ss.bytes()));
+        assert!(!contains(&range, b"a\xfe\xff"));
+        assert!(!contains(&range, b"a\xff\xff"));
+        assert!(!contains(&range, b"a\xff\xff\x00"));
+        assert!(!contains(&range, b"b"));
+        assert!(!contains(&range, b"b\x00"));
+    }
+
     #[test]
     fn range() {
         let ss: Subspace = 1.into();
@@ -168,4 +716,175 @@ mod tests {
         let (begin, end) = ss.range();
         assert!(packed >= begin && packed <= end);
     }
+
+    #[test]
+    fn root() {
+        assert!(Subspace::all().is_root());
+        assert!(Subspace::from_bytes(b"").is_root());
+        assert!(!Subspace::from(1).is_root());
+        assert!(!Subspace::all().subspace(&1).is_root());
+
+        let (begin, end) = Subspace::all().range();
+        assert_eq!(begin, b"\x00");
+        assert_eq!(end, b"\xff");
+        assert!(Subspace::all().pack(&("a", 1)) < end);
+        assert!(b"\xff/system".to_vec() >= end);
+        assert!(b"".to_vec() < begin);
+
+        let range = Subspace::all().range_option();
+        assert_eq!(range.begin.key(), b"\x00");
+        assert_eq!(range.end.key(), b"\xff");
+    }
+
+    #[test]
+    fn range_with_prefix() {
+        let ss: Subspace = 1.into();
+        let (begin, end) = ss.range_with_prefix(&("a", 2));
+        assert_eq!((begin.clone(), end.clone()), ss.subspace(&("a", 2)).range());
+
+        let contains = |key: &[u8]| key >= begin.as_slice() && key < end.as_slice();
+        assert!(contains(&ss.pack(&("a", 2, 3))));
+        assert!(contains(&ss.pack(&("a", 2, "b", 4))));
+        assert!(!contains(&ss.pack(&("a", 2))));
+        assert!(!contains(&ss.pack(&("a", 3, 3))));
+        assert!(!contains(&ss.pack(&("b", 2, 3))));
+        assert!(!contains(&pack(&("a", 2, 3))));
+
+        // from the root Subspace, the range only depends on the tuple
+        let (begin, end) = Subspace::all().range_with_prefix(&"a");
+        assert_eq!(begin, [&pack(&"a")[..], b"\x00"].concat());
+        assert_eq!(end, [&pack(&"a")[..], b"\xff"].concat());
+    }
+
+    #[test]
+    fn range_bounds() {
+        use std::ops::Bound::*;
+
+        let ss: Subspace = "events".into();
+        let ints: Vec<i64> = vec![-300, -2, -1, 0, 1, 2, 255, 256];
+        let strings = vec!["", "a", "a\x00", "ab", "b"];
+        // the keys of the users 1 and 2, whose second element is the checked one, and of the
+        // longer tuples starting with the same elements
+        let mut keys = Vec::new();
+        for &user in &[1, 2] {
+            keys.push((ss.pack(&user), None, None));
+            for &i in &ints {
+                keys.push((ss.pack(&(user, i)), Some(i), None));
+                keys.push((ss.pack(&(user, i, "x")), Some(i), None));
+                keys.push((ss.pack(&(user, i, -1)), Some(i), None));
+            }
+            for &string in &strings {
+                keys.push((ss.pack(&(user, string)), None, Some(string)));
+                keys.push((ss.pack(&(user, string, 0)), None, Some(string)));
+            }
+        }
+        keys.push((pack(&(1, 0)), Some(0), None));
+        keys.sort();
+
+        // checks the keys of the range against `contains`, for the elements of user 1
+        fn check<F: Fn(&[u8], Option<i64>, Option<&str>) -> bool>(
+            keys: &[(Vec<u8>, Option<i64>, Option<&str>)],
+            ss: &Subspace,
+            (begin, end): (Vec<u8>, Vec<u8>),
+            contains: F,
+        ) {
+            for (key, int, string) in keys {
+                let in_range = key >= &begin && key < &end;
+                let expected = ss.unpack_partial::<i64>(key).ok().map(|(user, _)| user) == Some(1)
+                    && (int.is_some() || string.is_some())
+                    && contains(key, *int, *string);
+                assert_eq!(in_range, expected, "{:?} {:?} {:?}", key, int, string);
+            }
+        }
+
+        let int_bounds: Vec<(Bound<i64>, Bound<i64>)> = vec![
+            (Included(-2), Included(1)),
+            (Excluded(-2), Excluded(1)),
+            (Included(-300), Excluded(256)),
+            (Excluded(-300), Included(256)),
+            (Unbounded, Included(0)),
+            (Excluded(0), Unbounded),
+            (Included(1), Excluded(1)),
+            (Included(1), Included(1)),
+            (Excluded(255), Included(255)),
+        ];
+        for bounds in int_bounds {
+            let range = ss.range_bounds(&1, bounds);
+            check(&keys, &ss, range, |_, int, _| match int {
+                Some(i) => bounds.contains(&i),
+                // strings sort before integers
+                None => bounds.0 == Unbounded,
+            });
+        }
+
+        let string_bounds: Vec<(Bound<&str>, Bound<&str>)> = vec![
+            (Included("a"), Included("ab")),
+            (Excluded("a"), Excluded("b")),
+            (Excluded(""), Unbounded),
+            (Unbounded, Excluded("a")),
+        ];
+        for bounds in string_bounds {
+            let range = ss.range_bounds::<_, _, &str>(&1, bounds);
+            check(&keys, &ss, range, |_, int, string| match string {
+                Some(string) => RangeBounds::<&str>::contains(&bounds, &string),
+                None => int.is_some() && bounds.1 == Unbounded,
+            });
+        }
+
+        // full range syntax, and the root Subspace
+        assert_eq!(
+            ss.range_bounds::<_, _, i64>(&1, ..),
+            ss.range_with_prefix(&1)
+        );
+        assert_eq!(ss.range_bounds(&1, 2..3).0, ss.pack(&(1, 2)));
+        assert_eq!(ss.range_bounds(&1, 2..3).1, ss.pack(&(1, 3)));
+        assert_eq!(
+            Subspace::all().range_bounds(&(), ..=-1),
+            (b"\x00".to_vec(), [&pack(&-1)[..], b"\xff"].concat())
+        );
+    }
+
+    #[test]
+    fn map_key() {
+        use std::collections::{BTreeMap, HashMap};
+
+        let a: Subspace = "a".into();
+        let b: Subspace = "b".into();
+        assert_eq!(a, Subspace::from_bytes(&pack(&"a")));
+        assert_ne!(a, b);
+        assert!(a < b);
+        assert_eq!(
+            a.cmp(&a.subspace(&1)),
+            a.bytes().cmp(a.subspace(&1).bytes())
+        );
+
+        let mut map = HashMap::new();
+        map.insert(a.clone(), 1);
+        map.insert(b.clone(), 2);
+        assert_eq!(map.get(&Subspace::from("a")), Some(&1));
+        assert_eq!(map.get(b.bytes()), Some(&2));
+        assert_eq!(map.get(&pack(&"c")[..]), None);
+
+        let mut map = BTreeMap::new();
+        map.insert(b.clone(), 2);
+        map.insert(a.clone(), 1);
+        assert_eq!(map.keys().cloned().collect::<Vec<_>>(), vec![a.clone(), b]);
+        assert_eq!(map.get(a.bytes()), Some(&1));
+    }
+
+    #[test]
+    fn clone_shares_prefix() {
+        let ss: Subspace = ("a", 1).into();
+        assert_eq!(Arc::strong_count(&ss.prefix), 1);
+        let clone = ss.clone();
+        assert_eq!(Arc::strong_count(&ss.prefix), 2);
+        assert!(Arc::ptr_eq(&ss.prefix, &clone.prefix));
+        assert_eq!(clone.bytes().as_ptr(), ss.bytes().as_ptr());
+        drop(clone);
+        assert_eq!(Arc::strong_count(&ss.prefix), 1);
+
+        // sub-subspaces have their own prefix
+        let sub = ss.subspace(&2);
+        assert!(!Arc::ptr_eq(&ss.prefix, &sub.prefix));
+    }
 }
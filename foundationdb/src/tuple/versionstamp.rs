@@ -13,6 +13,10 @@ impl<'a> fmt::Debug for Versionstamp {
 }
 
 impl Versionstamp {
+    /// Creates a versionstamp whose transaction version is replaced at commit time, when
+    /// written with a `SetVersionstampedKey` or `SetVersionstampedValue` atomic operation.
+    ///
+    /// `user_version` orders the versionstamps written by a single transaction.
     pub fn incomplete(user_version: u16) -> Self {
         let mut bytes = [0xff; 12];
         bytes[10..].copy_from_slice(&user_version.to_be_bytes());
@@ -26,10 +30,13 @@ impl Versionstamp {
         Versionstamp { bytes }
     }
 
+    /// Returns the 10 bytes transaction version, the first 8 bytes being the big-endian commit
+    /// version of the transaction.
     pub fn transaction_version(&self) -> &[u8] {
         &self.bytes[0..10]
     }
 
+    /// Returns the user version used to order the versionstamps of a single transaction.
     pub fn user_version(&self) -> u16 {
         let mut user_version = [0; 2];
         user_version.copy_from_slice(&self.bytes[10..12]);
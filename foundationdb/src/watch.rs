@@ -0,0 +1,316 @@
+// Copyright 2018 foundationdb-rs developers, https://github.com/Clikengo/foundationdb-rs/graphs/contributors
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! Repeated watches over a set of keys, and polling of the changes of a range
+
+use std::collections::hash_map::DefaultHasher;
+use std::collections::BTreeMap;
+use std::hash::{Hash, Hasher};
+use std::time::Duration;
+
+use futures::future::BoxFuture;
+use futures::prelude::*;
+use futures::stream::FuturesUnordered;
+
+use crate::options::StreamingMode;
+use crate::timer::delay;
+use crate::tuple::Subspace;
+use crate::{Database, FdbResult, RangeOption, TransactOption, Transaction};
+
+/// `too_many_watches` error code
+const TOO_MANY_WATCHES: i32 = 1032;
+const WATCH_BACKOFF_INITIAL: Duration = Duration::from_millis(10);
+const WATCH_BACKOFF_MAX: Duration = Duration::from_secs(1);
+/// The number of keys of a range above which `Database::poll_range_changes` stops telling which
+/// keys changed.
+const POLL_MAX_KEYS: usize = 10_000;
+
+type WatchFuture = BoxFuture<'static, (usize, FdbResult<()>)>;
+
+struct WatchState<'a> {
+    db: &'a Database,
+    keys: Vec<Vec<u8>>,
+    watches: FuturesUnordered<WatchFuture>,
+    backoff: Duration,
+    armed: bool,
+    done: bool,
+}
+
+impl Database {
+    /// Returns a stream that yields a key of `keys` each time its value changes.
+    ///
+    /// Watches on all keys are set by a committed transaction. When a watch fires, it is set again
+    /// by a new transaction before the key is yielded, so reading the key after it was yielded
+    /// never misses a later change. Multiple changes of a key before its watch is set again are
+    /// reported once.
+    ///
+    /// Watches that fail with `too_many_watches` are set again after an exponential backoff.
+    /// Watches that fail with a retryable error are set again and their key is yielded, as the
+    /// key might have changed. Any other error is yielded and ends the stream.
+    ///
+    /// Dropping the stream cancels all its watches.
+    pub fn watch_stream(
+        &self,
+        keys: Vec<Vec<u8>>,
+    ) -> impl Stream<Item = FdbResult<Vec<u8>>> + Send + Unpin + '_ {
+        let state = WatchState {
+            db: self,
+            keys,
+            watches: FuturesUnordered::new(),
+            backoff: WATCH_BACKOFF_INITIAL,
+            armed: false,
+            done: false,
+        };
+
+        stream::unfold(state, |mut state| async move {
+            if state.done {
+                return None;
+            }
+            let item = state.next().await;
+            if let Some(Err(..)) = item {
+                state.done = true;
+            }
+            item.map(|item| (item, state))
+        })
+        .boxed()
+    }
+
+    /// Returns a stream that yields the changes of the keys of `subspace`, polling its content
+    /// every `interval`.
+    ///
+    /// This is a polling fallback for the watches, which only work on single keys: each poll
+    /// reads the whole range in a read-only transaction, and compares it with the previous poll.
+    /// The first poll only takes the initial snapshot. A change is yielded for each poll that
+    /// saw a difference, several writes between two polls being reported as one change, and a
+    /// key written back to its previous value between two polls not being reported.
+    ///
+    /// Up to 10,000 keys, the hash of the value of each key is kept to tell which keys were
+    /// added, removed or modified. Above, see `poll_range_changes_with_limit`. The first error
+    /// is yielded and ends the stream.
+    pub fn poll_range_changes(
+        &self,
+        subspace: Subspace,
+        interval: Duration,
+    ) -> impl Stream<Item = FdbResult<RangeChange>> + Send + '_ {
+        self.poll_range_changes_with_limit(subspace, interval, POLL_MAX_KEYS)
+    }
+
+    /// Like `poll_range_changes`, keeping the hashes of the values of at most `max_keys` keys.
+    ///
+    /// When the range holds more than `max_keys` keys, only a hash of its whole content is kept,
+    /// and the changes are reported as `RangeChange::Unknown`.
+    pub fn poll_range_changes_with_limit(
+        &self,
+        subspace: Subspace,
+        interval: Duration,
+        max_keys: usize,
+    ) -> impl Stream<Item = FdbResult<RangeChange>> + Send + '_ {
+        let (begin, end) = subspace.range();
+        stream::unfold(Some(None), move |state: Option<Option<RangeSnapshot>>| {
+            let range = (begin.clone(), end.clone());
+            async move {
+                let mut last = state?;
+                loop {
+                    if last.is_some() {
+                        delay(interval).await;
+                    }
+                    let current = match self.range_snapshot(range.clone(), max_keys).await {
+                        Ok(current) => current,
+                        Err(err) => return Some((Err(err), None)),
+                    };
+                    let change = last
+                        .as_ref()
+                        .and_then(|last| RangeSnapshot::diff(last, &current));
+                    last = Some(current);
+                    if let Some(change) = change {
+                        return Some((Ok(change), Some(last)));
+                    }
+                }
+            }
+        })
+    }
+
+    /// Reads the content of `range` in a read-only transaction.
+    async fn range_snapshot(
+        &self,
+        range: (Vec<u8>, Vec<u8>),
+        max_keys: usize,
+    ) -> FdbResult<RangeSnapshot> {
+        self.transact_boxed(
+            range,
+            move |trx, range| read_range_snapshot(trx, range, max_keys).boxed(),
+            TransactOption::read_only(),
+        )
+        .await
+    }
+}
+
+/// A change of the keys of a range, yielded by `Database::poll_range_changes`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RangeChange {
+    /// The keys that changed, in key order, between the previous poll and the read version
+    /// `version`
+    Keys {
+        version: i64,
+        added: Vec<Vec<u8>>,
+        removed: Vec<Vec<u8>>,
+        modified: Vec<Vec<u8>>,
+    },
+    /// The range changed between the previous poll and the read version `version`, but it
+    /// holds too many keys to tell which ones
+    Unknown { version: i64 },
+}
+
+impl RangeChange {
+    /// The read version of the poll that saw the change
+    pub fn version(&self) -> i64 {
+        match self {
+            RangeChange::Keys { version, .. } | RangeChange::Unknown { version } => *version,
+        }
+    }
+}
+
+/// The content of a range at a read version
+struct RangeSnapshot {
+    version: i64,
+    /// A hash of all the keys and values of the range
+    hash: u64,
+    /// The hash of the value of each key, if there are not more than the maximum number of keys
+    values: Option<BTreeMap<Vec<u8>, u64>>,
+}
+
+impl RangeSnapshot {
+    /// Returns the change from `old` to `new`, if any.
+    fn diff(old: &RangeSnapshot, new: &RangeSnapshot) -> Option<RangeChange> {
+        if old.hash == new.hash {
+            return None;
+        }
+        let version = new.version;
+        let (old_values, new_values) = match (&old.values, &new.values) {
+            (Some(old_values), Some(new_values)) => (old_values, new_values),
+            _ => return Some(RangeChange::Unknown { version }),
+        };
+        let mut added = Vec::new();
+        let mut removed = Vec::new();
+        let mut modified = Vec::new();
+        for (key, value) in new_values {
+            match old_values.get(key) {
+                None => added.push(key.clone()),
+                Some(old_value) if old_value != value => modified.push(key.clone()),
+                Some(..) => {}
+            }
+        }
+        for key in old_values.keys() {
+            if !new_values.contains_key(key) {
+                removed.push(key.clone());
+            }
+        }
+        Some(RangeChange::Keys {
+            version,
+            added,
+            removed,
+            modified,
+        })
+    }
+}
+
+/// Reads the content of `range` at the read version of `trx`.
+async fn read_range_snapshot(
+    trx: &Transaction,
+    range: &(Vec<u8>, Vec<u8>),
+    max_keys: usize,
+) -> FdbResult<RangeSnapshot> {
+    let version = trx.get_read_version().await?;
+    let opt = RangeOption {
+        mode: StreamingMode::WantAll,
+        ..RangeOption::from((range.0.as_slice(), range.1.as_slice()))
+    };
+    let mut hasher = DefaultHasher::new();
+    let mut values = Some(BTreeMap::new());
+    let mut ranges = trx.get_ranges(opt, true);
+    while let Some(kvs) = ranges.try_next().await? {
+        for kv in kvs.iter() {
+            kv.key().hash(&mut hasher);
+            kv.value().hash(&mut hasher);
+            if let Some(map) = &mut values {
+                if map.len() < max_keys {
+                    let mut value_hasher = DefaultHasher::new();
+                    kv.value().hash(&mut value_hasher);
+                    map.insert(kv.key().to_vec(), value_hasher.finish());
+                } else {
+                    // too many keys, only the hash of the whole range is kept
+                    values = None;
+                }
+            }
+        }
+    }
+    Ok(RangeSnapshot {
+        version,
+        hash: hasher.finish(),
+        values,
+    })
+}
+
+impl<'a> WatchState<'a> {
+    async fn next(&mut self) -> Option<FdbResult<Vec<u8>>> {
+        if !self.armed {
+            self.armed = true;
+            let all: Vec<usize> = (0..self.keys.len()).collect();
+            if let Err(err) = self.arm(&all).await {
+                return Some(Err(err));
+            }
+        }
+
+        loop {
+            let (index, res) = self.watches.next().await?;
+            match res {
+                Ok(()) => {
+                    self.backoff = WATCH_BACKOFF_INITIAL;
+                }
+                Err(err) if err.code() == TOO_MANY_WATCHES => {
+                    delay(self.backoff).await;
+                    self.backoff = std::cmp::min(self.backoff * 2, WATCH_BACKOFF_MAX);
+                    if let Err(err) = self.arm(&[index]).await {
+                        return Some(Err(err));
+                    }
+                    continue;
+                }
+                Err(err) if err.is_retryable() => {}
+                Err(err) => return Some(Err(err)),
+            }
+
+            if let Err(err) = self.arm(&[index]).await {
+                return Some(Err(err));
+            }
+            return Some(Ok(self.keys[index].clone()));
+        }
+    }
+
+    /// Sets the watches of the given keys in a committed transaction.
+    async fn arm(&mut self, indexes: &[usize]) -> FdbResult<()> {
+        let mut trx = self.db.create_trx()?;
+        loop {
+            let watches: Vec<WatchFuture> = indexes
+                .iter()
+                .map(|&index| {
+                    trx.watch(&self.keys[index])
+                        .map(move |res| (index, res))
+                        .boxed()
+                })
+                .collect();
+
+            match trx.commit().await {
+                Ok(..) => {
+                    self.watches.extend(watches);
+                    return Ok(());
+                }
+                // watches of the failed transaction are cancelled by the retry
+                Err(err) => trx = err.on_error().await?,
+            }
+        }
+    }
+}
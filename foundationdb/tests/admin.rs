@@ -0,0 +1,40 @@
+// Copyright 2018 foundationdb-rs developers, https://github.com/Clikengo/foundationdb-rs/graphs/contributors
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+#![cfg(feature = "fdb-7_1")]
+
+use foundationdb::FdbResult;
+
+mod common;
+
+#[test]
+fn test_admin() {
+    let _guard = unsafe { foundationdb::boot() };
+    futures::executor::block_on(test_reboot_unknown_worker_async()).expect("failed to run");
+    futures::executor::block_on(test_create_snapshot_async()).expect("failed to run");
+}
+
+async fn test_reboot_unknown_worker_async() -> FdbResult<()> {
+    let db = common::database().await?;
+
+    // no worker listens on this address, so nothing is rebooted
+    assert_eq!(db.reboot_worker("127.0.0.1:1", true, 0).await?, 0);
+
+    Ok(())
+}
+
+async fn test_create_snapshot_async() -> FdbResult<()> {
+    let db = common::database().await?;
+
+    // the test cluster is not configured to allow snapshots
+    assert!(db
+        .create_snapshot("00000000000000000000000000000000", "/bin/false")
+        .await
+        .is_err());
+
+    Ok(())
+}
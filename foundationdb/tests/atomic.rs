@@ -14,17 +14,19 @@ mod common;
 fn test_atomic() {
     let _guard = unsafe { foundationdb::boot() };
     futures::executor::block_on(test_atomic_async()).expect("failed to run");
+    futures::executor::block_on(test_read_counter_dirty_async()).expect("failed to run");
+}
+
+fn le_i64(value: i64) -> [u8; 8] {
+    let mut buf = [0u8; 8];
+    byteorder::LE::write_i64(&mut buf, value);
+    buf
 }
 
 async fn atomic_add(db: &Database, key: &[u8], value: i64) -> FdbResult<()> {
     let trx = db.create_trx()?;
 
-    let val = {
-        let mut buf = [0u8; 8];
-        byteorder::LE::write_i64(&mut buf, value);
-        buf
-    };
-    trx.atomic_op(key, &val, options::MutationType::Add);
+    trx.atomic_op(key, &le_i64(value), options::MutationType::Add);
 
     trx.commit().await?;
     Ok(())
@@ -64,3 +66,47 @@ async fn test_atomic_async() -> FdbResult<()> {
     }
     Ok(())
 }
+
+async fn test_read_counter_dirty_async() -> FdbResult<()> {
+    const KEY: &[u8] = b"test-atomic-dirty";
+
+    let db = common::database().await?;
+
+    {
+        let trx = db.create_trx()?;
+        trx.set(KEY, &le_i64(10));
+        trx.commit().await?;
+    }
+
+    let trx = db.create_trx()?;
+    trx.atomic_op(KEY, &le_i64(5), options::MutationType::Add);
+    trx.atomic_op(KEY, &le_i64(-2), options::MutationType::Add);
+    assert_eq!(trx.read_counter_dirty(KEY).await?, 13);
+
+    // a concurrent writer must not make this transaction conflict
+    atomic_add(&db, KEY, 100).await?;
+    trx.commit().await?;
+
+    let trx = db.create_trx()?;
+    assert_eq!(trx.read_counter_dirty(KEY).await?, 113);
+    let value = trx.get(KEY, false).await?.expect("value should exists");
+    assert_eq!(byteorder::LE::read_i64(&value), 113);
+
+    // without the read-your-writes of snapshot reads, the additions of the transaction are missed
+    let trx = db.create_trx()?;
+    trx.set_option(options::TransactionOption::SnapshotRywDisable)?;
+    trx.atomic_op(KEY, &le_i64(5), options::MutationType::Add);
+    assert_eq!(trx.read_counter_dirty(KEY).await?, 113);
+
+    // only 64-bit counters can be read
+    let trx = db.create_trx()?;
+    for value in &[&b""[..], b"\x01\x00\x00\x00", &[1u8; 9]] {
+        trx.set(KEY, value);
+        match trx.read_counter_dirty(KEY).await {
+            Err(err) => assert_eq!(err.code(), 2000),
+            Ok(counter) => panic!("{:?} read as the counter {}", value, counter),
+        }
+    }
+
+    Ok(())
+}
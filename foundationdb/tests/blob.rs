@@ -0,0 +1,143 @@
+// Copyright 2018 foundationdb-rs developers, https://github.com/Clikengo/foundationdb-rs/graphs/contributors
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+#![cfg(feature = "blob")]
+
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use foundationdb::layers::{BlobSubspace, LayerError};
+use foundationdb::tuple::Subspace;
+use foundationdb::{Database, FdbError, TransactOption};
+use futures::prelude::*;
+use rand::Rng;
+
+mod common;
+
+#[test]
+fn test_blob() {
+    let _guard = unsafe { foundationdb::boot() };
+    futures::executor::block_on(test_blob_small_async()).expect("failed to run");
+    futures::executor::block_on(test_blob_large_async()).expect("failed to run");
+    futures::executor::block_on(test_blob_concurrent_rewrite_async()).expect("failed to run");
+}
+
+async fn read_blob(db: &Database, blob: &BlobSubspace, key: &[u8]) -> Result<Vec<u8>, LayerError> {
+    db.transact_boxed(
+        (blob, key),
+        |trx, (blob, key)| blob.read(trx, key, false).try_concat().boxed(),
+        TransactOption::default(),
+    )
+    .await
+}
+
+async fn test_blob_small_async() -> Result<(), LayerError> {
+    let db = common::database().await?;
+    let blob = BlobSubspace::new(Subspace::from("test-blob-small"));
+
+    let trx = db.create_trx()?;
+    blob.delete(&trx, b"empty");
+    blob.delete(&trx, b"small");
+    trx.commit().await.map_err(FdbError::from)?;
+
+    let trx = db.create_trx()?;
+    assert_eq!(blob.len(&trx, b"small", false).await?, None);
+    assert!(blob
+        .read(&trx, b"small", false)
+        .try_concat()
+        .await?
+        .is_empty());
+    blob.write_trx(&trx, b"empty", b"").await?;
+    blob.write_trx(&trx, b"small", b"hello world").await?;
+    trx.commit().await.map_err(FdbError::from)?;
+
+    let trx = db.create_trx()?;
+    assert_eq!(blob.len(&trx, b"empty", false).await?, Some(0));
+    assert_eq!(blob.len(&trx, b"small", false).await?, Some(11));
+    assert_eq!(
+        blob.read(&trx, b"small", false).try_concat().await?,
+        b"hello world"
+    );
+
+    // rewriting a blob with the same transaction replaces it
+    blob.write_trx(&trx, b"small", b"bye").await?;
+    assert_eq!(blob.read(&trx, b"small", false).try_concat().await?, b"bye");
+
+    Ok(())
+}
+
+async fn test_blob_large_async() -> Result<(), LayerError> {
+    let db = common::database().await?;
+    let blob = BlobSubspace::new(Subspace::from("test-blob-large"));
+
+    let mut payload = vec![0u8; 25 * 1024 * 1024];
+    rand::thread_rng().fill(&mut payload[..]);
+
+    blob.write(&db, b"large", &payload).await?;
+
+    let trx = db.create_trx()?;
+    assert_eq!(
+        blob.len(&trx, b"large", false).await?,
+        Some(payload.len() as u64)
+    );
+    let read = read_blob(&db, &blob, b"large").await?;
+    assert_eq!(read.len(), payload.len());
+    assert!(read == payload, "blob content differs");
+
+    let trx = db.create_trx()?;
+    blob.delete(&trx, b"large");
+    trx.commit().await.map_err(FdbError::from)?;
+
+    let trx = db.create_trx()?;
+    assert_eq!(blob.len(&trx, b"large", false).await?, None);
+    let remaining = trx
+        .get_range(&blob.subspace().range_option(), 1, false)
+        .await?;
+    assert!(remaining.is_empty());
+
+    Ok(())
+}
+
+async fn test_blob_concurrent_rewrite_async() -> Result<(), LayerError> {
+    let db = common::database().await?;
+    let blob = BlobSubspace::new(Subspace::from("test-blob-concurrent"));
+
+    // large enough for the writes to span multiple transactions
+    let old = vec![1u8; 12 * 1024 * 1024];
+    let new = vec![2u8; 12 * 1024 * 1024 + 1];
+    blob.write(&db, b"blob", &old).await?;
+
+    let done = AtomicBool::new(false);
+    let writer = async {
+        let res = blob.write(&db, b"blob", &new).await;
+        done.store(true, Ordering::SeqCst);
+        res
+    };
+    let reader = async {
+        let mut reads = 0;
+        loop {
+            let finished = done.load(Ordering::SeqCst);
+            let read = read_blob(&db, &blob, b"blob").await?;
+            assert!(
+                read == old || read == new,
+                "read a torn blob of {} bytes",
+                read.len()
+            );
+            reads += 1;
+            if finished {
+                // the write was done before this read started
+                assert!(read == new, "read the old blob after the rewrite");
+                return Ok::<_, LayerError>(reads);
+            }
+        }
+    };
+
+    let (written, reads) = future::join(writer, reader).await;
+    written?;
+    assert!(reads? > 0);
+
+    Ok(())
+}
@@ -0,0 +1,271 @@
+// Copyright 2018 foundationdb-rs developers, https://github.com/Clikengo/foundationdb-rs/graphs/contributors
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! The network can only be booted once per process, so every test runs in its own subprocess.
+
+use foundationdb::api;
+use foundationdb::options::NetworkOption;
+use std::process::Command;
+
+const CHILD_ENV: &str = "FDB_BOOT_TEST_CHILD";
+const TRACE_DIR_ENV: &str = "FDB_BOOT_TEST_TRACE_DIR";
+
+/// Runs the ignored `child` test in a subprocess and returns whether it succeeded along with
+/// its output.
+fn run_child(mode: &str) -> (bool, String) {
+    run_child_with_env(mode, &[])
+}
+
+/// Same as `run_child`, with additional environment variables.
+fn run_child_with_env(mode: &str, envs: &[(&str, &str)]) -> (bool, String) {
+    let output = Command::new(std::env::current_exe().expect("no test executable"))
+        .args(["--ignored", "--exact", "child", "--nocapture"].iter())
+        .env(CHILD_ENV, mode)
+        .envs(envs.iter().cloned())
+        .output()
+        .expect("failed to spawn child test");
+    let mut text = String::from_utf8_lossy(&output.stdout).into_owned();
+    text.push_str(&String::from_utf8_lossy(&output.stderr));
+    (output.status.success(), text)
+}
+
+#[test]
+#[ignore]
+fn child() {
+    let mode = match std::env::var(CHILD_ENV) {
+        Ok(mode) => mode,
+        Err(_) => return,
+    };
+
+    match mode.as_str() {
+        "stop" => {
+            assert!(!api::is_network_running());
+            let network = unsafe { foundationdb::boot() };
+            assert!(api::is_network_running());
+            network.stop().expect("failed to stop network");
+            assert!(!api::is_network_running());
+        }
+        "twice" => {
+            let _network = unsafe { foundationdb::boot() };
+            let _network = unsafe { foundationdb::boot() };
+        }
+        "reboot" => {
+            let network = unsafe { foundationdb::boot() };
+            network.stop().expect("failed to stop network");
+            let _network = unsafe { foundationdb::boot() };
+        }
+        "run" => {
+            let value = foundationdb::run(|| {
+                assert!(api::is_network_running());
+                42
+            });
+            assert_eq!(value, 42);
+            assert!(!api::is_network_running());
+            // the network cannot be restarted, booting again panics
+            let _network = unsafe { foundationdb::boot() };
+        }
+        "run_panic" => {
+            let result = std::panic::catch_unwind(|| {
+                foundationdb::run(|| panic!("panic inside run"));
+            });
+            assert!(result.is_err());
+            assert!(!api::is_network_running());
+        }
+        "run_async" => {
+            let value = foundationdb::run_async(async {
+                assert!(api::is_network_running());
+                42
+            });
+            assert_eq!(value, 42);
+            assert!(!api::is_network_running());
+        }
+        "trace" => {
+            let dir = std::env::var(TRACE_DIR_ENV).expect("no trace directory");
+            let builder = api::FdbApiBuilder::default().trace_enable(&dir);
+            #[cfg(any(feature = "fdb-6_1", feature = "fdb-6_2", feature = "fdb-7_1"))]
+            let builder = builder.trace_format(api::TraceFormat::Json);
+            let network_builder = builder.build().expect("failed to set the network options");
+            let network = unsafe { network_builder.boot() }.expect("failed to boot");
+            let db = foundationdb::Database::default().expect("failed to open the database");
+            futures::executor::block_on(async { db.create_trx()?.get_read_version().await })
+                .expect("failed to get a read version");
+            drop(db);
+            network.stop().expect("failed to stop network");
+        }
+        "trace_option" => {
+            let dir = std::env::var(TRACE_DIR_ENV).expect("no trace directory");
+            let network_builder = api::FdbApiBuilder::default()
+                .build()
+                .expect("failed to initialize the api")
+                .set_option(NetworkOption::TraceEnable(dir))
+                .expect("failed to enable the traces");
+            let network = unsafe { network_builder.boot() }.expect("failed to boot");
+            let db = foundationdb::Database::default().expect("failed to open the database");
+            futures::executor::block_on(async { db.create_trx()?.get_read_version().await })
+                .expect("failed to get a read version");
+            drop(db);
+            network.stop().expect("failed to stop network");
+        }
+        "tls_bytes" => {
+            let err = api::FdbApiBuilder::default()
+                .set_network_option(NetworkOption::TLSCertBytes(b"not a certificate".to_vec()))
+                .build()
+                .err()
+                .expect("the certificate is not PEM");
+            assert_eq!(err.code(), 2106);
+        }
+        "tls_path" => {
+            let network_builder = api::FdbApiBuilder::default()
+                .build()
+                .expect("failed to initialize the api");
+            let path = std::env::temp_dir().join("fdb-boot-test-missing-key.pem");
+            let err = network_builder
+                .set_option(NetworkOption::TLSKeyPath(
+                    path.to_string_lossy().into_owned(),
+                ))
+                .err()
+                .expect("the key file is missing");
+            assert_eq!(err.code(), 2106);
+        }
+        "trace_after_boot" => {
+            let _network = unsafe { foundationdb::boot() };
+            let err = api::FdbApiBuilder::default()
+                .trace_enable(std::env::temp_dir())
+                .build()
+                .err()
+                .expect("the network options were set after boot");
+            assert_eq!(err.code(), 2009);
+        }
+        mode => panic!("unknown child mode {}", mode),
+    }
+}
+
+#[test]
+fn test_stop() {
+    let (success, output) = run_child("stop");
+    assert!(success, "{}", output);
+}
+
+#[test]
+fn test_boot_twice() {
+    let (success, output) = run_child("twice");
+    assert!(!success);
+    assert!(
+        output.contains("the FoundationDB network is already running"),
+        "{}",
+        output
+    );
+}
+
+#[test]
+fn test_reboot() {
+    let (success, output) = run_child("reboot");
+    assert!(!success);
+    assert!(
+        output.contains("the FoundationDB network was stopped, it cannot be restarted"),
+        "{}",
+        output
+    );
+}
+
+#[test]
+fn test_run() {
+    let (success, output) = run_child("run");
+    assert!(!success);
+    assert!(
+        output.contains("the FoundationDB network was stopped, it cannot be restarted"),
+        "{}",
+        output
+    );
+}
+
+#[test]
+fn test_run_panic() {
+    let (success, output) = run_child("run_panic");
+    assert!(success, "{}", output);
+}
+
+#[test]
+fn test_run_async() {
+    let (success, output) = run_child("run_async");
+    assert!(success, "{}", output);
+}
+
+/// Runs `mode` with a fresh trace directory, and returns the names of the trace files.
+fn run_child_with_traces(mode: &str) -> Vec<String> {
+    let dir = std::env::temp_dir().join(format!(
+        "fdb-boot-test-{}-{}",
+        mode.replace('_', "-"),
+        std::process::id()
+    ));
+    std::fs::create_dir_all(&dir).expect("failed to create the trace directory");
+
+    let (success, output) = run_child_with_env(
+        mode,
+        &[(
+            TRACE_DIR_ENV,
+            dir.to_str().expect("non UTF-8 temp directory"),
+        )],
+    );
+    let files: Vec<String> = std::fs::read_dir(&dir)
+        .expect("failed to list the trace directory")
+        .map(|entry| {
+            entry
+                .expect("failed to list the trace directory")
+                .file_name()
+                .to_string_lossy()
+                .into_owned()
+        })
+        .collect();
+    std::fs::remove_dir_all(&dir).expect("failed to remove the trace directory");
+
+    assert!(success, "{}", output);
+    files
+}
+
+#[test]
+fn test_trace() {
+    let files = run_child_with_traces("trace");
+    let extension = if cfg!(any(
+        feature = "fdb-6_1",
+        feature = "fdb-6_2",
+        feature = "fdb-7_1"
+    )) {
+        ".json"
+    } else {
+        ".xml"
+    };
+    assert!(
+        files.iter().any(|file| file.ends_with(extension)),
+        "{:?}",
+        files
+    );
+}
+
+#[test]
+fn test_trace_option() {
+    let files = run_child_with_traces("trace_option");
+    assert!(!files.is_empty());
+}
+
+#[test]
+fn test_tls_bytes() {
+    let (success, output) = run_child("tls_bytes");
+    assert!(success, "{}", output);
+}
+
+#[test]
+fn test_tls_path() {
+    let (success, output) = run_child("tls_path");
+    assert!(success, "{}", output);
+}
+
+#[test]
+fn test_trace_after_boot() {
+    let (success, output) = run_child("trace_after_boot");
+    assert!(success, "{}", output);
+}
@@ -0,0 +1,108 @@
+// Copyright 2018 foundationdb-rs developers, https://github.com/Clikengo/foundationdb-rs/graphs/contributors
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+use foundationdb::options::StreamingMode;
+use foundationdb::tuple::Subspace;
+use foundationdb::*;
+use futures::prelude::*;
+
+mod common;
+
+#[test]
+fn test_bulk() {
+    let _guard = unsafe { foundationdb::boot() };
+    futures::executor::block_on(test_bulk_insert_async()).expect("failed to run");
+    futures::executor::block_on(test_bulk_insert_last_write_wins_async()).expect("failed to run");
+}
+
+async fn clear(db: &Database, subspace: &Subspace) -> FdbResult<()> {
+    let trx = db.create_trx()?;
+    trx.clear_subspace_range(subspace);
+    trx.commit().await?;
+    Ok(())
+}
+
+async fn count_keys(db: &Database, subspace: &Subspace) -> FdbResult<usize> {
+    let trx = db.create_trx()?;
+    let opt = RangeOption {
+        mode: StreamingMode::WantAll,
+        ..RangeOption::from(subspace.range())
+    };
+    trx.get_ranges(opt, true)
+        .try_fold(0, |count, values| future::ok(count + values.len()))
+        .await
+}
+
+async fn test_bulk_insert_async() -> FdbResult<()> {
+    const N: usize = 200_000;
+
+    let db = common::database().await?;
+    let subspace = Subspace::all().subspace(&"test-bulk-insert");
+    clear(&db, &subspace).await?;
+
+    let items = stream::iter(0..N).map(|i| (subspace.pack(&(i as i64)), b"value".to_vec()));
+    let stats = db
+        .bulk_insert(
+            items,
+            BulkOptions {
+                concurrency: 4,
+                ..BulkOptions::default()
+            },
+        )
+        .await?;
+    assert_eq!(stats.keys, N);
+    assert!(stats.transactions >= N / BulkOptions::default().max_batch_count);
+    assert!(stats.bytes > N * 5);
+
+    assert_eq!(count_keys(&db, &subspace).await?, N);
+    clear(&db, &subspace).await?;
+
+    Ok(())
+}
+
+async fn test_bulk_insert_last_write_wins_async() -> FdbResult<()> {
+    const N: usize = 10_000;
+
+    let db = common::database().await?;
+    let subspace = Subspace::all().subspace(&"test-bulk-insert-lww");
+    clear(&db, &subspace).await?;
+
+    // every batch writes the same key, and the other keys are written twice in distinct batches
+    let items = stream::iter(0..N).flat_map(|i| {
+        stream::iter(vec![
+            (subspace.pack(&"last"), i.to_string().into_bytes()),
+            (
+                subspace.pack(&((i % (N / 2)) as i64)),
+                i.to_string().into_bytes(),
+            ),
+        ])
+    });
+    let stats = db
+        .bulk_insert(
+            items,
+            BulkOptions {
+                max_batch_count: 100,
+                concurrency: 8,
+                ..BulkOptions::default()
+            },
+        )
+        .await?;
+    assert_eq!(stats.keys, 2 * N);
+    assert_eq!(stats.transactions, 2 * N / 100);
+
+    let trx = db.create_trx()?;
+    let last = trx.get(&subspace.pack(&"last"), false).await?;
+    assert_eq!(last.as_deref(), Some((N - 1).to_string().as_bytes()));
+    for i in 0..N / 2 {
+        let value = trx.get(&subspace.pack(&(i as i64)), false).await?;
+        assert_eq!(value.as_deref(), Some((i + N / 2).to_string().as_bytes()));
+    }
+    assert_eq!(count_keys(&db, &subspace).await?, N / 2 + 1);
+    clear(&db, &subspace).await?;
+
+    Ok(())
+}
@@ -0,0 +1,72 @@
+// Copyright 2018 foundationdb-rs developers, https://github.com/Clikengo/foundationdb-rs/graphs/contributors
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+use foundationdb::tuple::Subspace;
+
+mod common;
+
+#[allow(dead_code)]
+#[path = "../examples/class_scheduling.rs"]
+mod class_scheduling;
+
+use class_scheduling::*;
+
+const SEATS: i64 = 4;
+const STUDENTS: usize = 10;
+const OPS_PER_STUDENT: usize = 300;
+
+#[test]
+fn test_class_scheduling() {
+    let _guard = unsafe { foundationdb::boot() };
+    let db = futures::executor::block_on(common::database()).expect("failed to get database");
+
+    let schema = futures::executor::block_on(Schema::open(&db, &["test-class-scheduling"]))
+        .expect("failed to open the schema");
+    // few classes and seats so that the workload hits the capacity checks
+    let classes: Vec<String> = all_classes().into_iter().take(20).collect();
+
+    futures::executor::block_on(init(&db, &schema, &classes, SEATS)).expect("failed to init");
+    let students = run_sim(db.clone(), &schema, &classes, STUDENTS, OPS_PER_STUDENT);
+
+    futures::executor::block_on(check_invariants(&db, &schema, &classes, &students, SEATS))
+        .expect("failed to check invariants");
+
+    futures::executor::block_on(test_switch_full_class_async(&db)).expect("failed to run");
+}
+
+async fn test_switch_full_class_async(db: &foundationdb::Database) -> Result<()> {
+    let schema = Schema::new(&Subspace::from("test-class-scheduling-switch"));
+    let classes = vec!["a".to_string(), "b".to_string()];
+    init(db, &schema, &classes, 1).await?;
+
+    signup(db, &schema, "s1", "a").await?;
+    signup(db, &schema, "s2", "b").await?;
+    match switch_classes(db, &schema, "s2", "b", "a").await {
+        Err(Error::NoRemainingSeats) => {}
+        r => panic!("expected NoRemainingSeats, got {:?}", r),
+    }
+
+    // the failed switch must not have dropped the old class
+    let trx = db.create_trx()?;
+    assert_eq!(
+        student_classes(&trx, &schema, "s2").await?,
+        vec!["b".to_string()]
+    );
+    assert_eq!(seats_left(&trx, &schema, "b").await?, Some(0));
+
+    drop_class(db, &schema, "s1", "a").await?;
+    switch_classes(db, &schema, "s2", "b", "a").await?;
+    let trx = db.create_trx()?;
+    assert_eq!(
+        student_classes(&trx, &schema, "s2").await?,
+        vec!["a".to_string()]
+    );
+    assert_eq!(seats_left(&trx, &schema, "a").await?, Some(0));
+    assert_eq!(seats_left(&trx, &schema, "b").await?, Some(1));
+
+    Ok(())
+}
@@ -0,0 +1,168 @@
+// Copyright 2018 foundationdb-rs developers, https://github.com/Clikengo/foundationdb-rs/graphs/contributors
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+use foundationdb::cluster_file;
+use foundationdb::options::DatabaseOption;
+use foundationdb::tuple::Subspace;
+use foundationdb::{FdbError, FdbResult, RangeOption};
+use futures::prelude::*;
+use std::thread;
+use std::time::Duration;
+
+mod common;
+
+const THREADS: usize = 16;
+const TRANSACTIONS_PER_THREAD: usize = 1000;
+
+#[test]
+fn test_database() {
+    let _guard = unsafe { foundationdb::boot() };
+    futures::executor::block_on(test_concurrent_create_trx_async()).expect("failed to run");
+    futures::executor::block_on(test_set_options_async()).expect("failed to run");
+    futures::executor::block_on(test_read_version_cache_async()).expect("failed to run");
+    futures::executor::block_on(test_connection_string_async()).expect("failed to run");
+}
+
+async fn test_concurrent_create_trx_async() -> FdbResult<()> {
+    let db = common::database().await?;
+    let subspace = Subspace::all().subspace(&"test-database-clone");
+
+    let trx = db.create_trx()?;
+    trx.clear_subspace_range(&subspace);
+    trx.commit().await?;
+
+    // every thread creates and commits its transactions from its own clone of the database
+    let threads = (0..THREADS)
+        .map(|t| {
+            let db = db.clone();
+            let subspace = subspace.clone();
+            thread::spawn(move || {
+                futures::executor::block_on(async {
+                    for i in 0..TRANSACTIONS_PER_THREAD {
+                        let trx = db.create_trx()?;
+                        trx.set(&subspace.pack(&(t, i)), b"");
+                        trx.commit().await?;
+                    }
+                    Ok::<_, FdbError>(())
+                })
+            })
+        })
+        .collect::<Vec<_>>();
+    for thread in threads {
+        thread.join().expect("failed to join thread")?;
+    }
+
+    // the handles of the threads are dropped, the original one is still usable
+    let trx = db.create_trx()?;
+    let count = trx
+        .get_ranges(RangeOption::from(subspace.range()), false)
+        .try_fold(0usize, |count, kvs| future::ok(count + kvs.len()))
+        .await?;
+    assert_eq!(count, THREADS * TRANSACTIONS_PER_THREAD);
+
+    Ok(())
+}
+
+async fn test_set_options_async() -> FdbResult<()> {
+    let db = common::database().await?;
+
+    db.set_option(DatabaseOption::MaxWatches(10_000))?;
+    assert!(DatabaseOption::MaxWatches(10_000).is_settable_after_use());
+
+    let err = db
+        .set_option(DatabaseOption::MaxWatches(-1))
+        .expect_err("a negative number of watches should be invalid");
+    assert_eq!(err.code(), 2006);
+    assert!(err.to_string().contains("MaxWatches(-1)"), "{}", err);
+
+    // the first failing option is reported
+    let err = db
+        .set_options(vec![
+            DatabaseOption::LocationCacheSize(100_000),
+            DatabaseOption::MaxWatches(2_000_000),
+            DatabaseOption::MaxWatches(-1),
+        ])
+        .expect_err("too many watches should be invalid");
+    assert!(err.to_string().contains("MaxWatches(2000000)"), "{}", err);
+
+    db.set_options(vec![
+        DatabaseOption::LocationCacheSize(100_000),
+        DatabaseOption::MaxWatches(10_000),
+    ])?;
+
+    Ok(())
+}
+
+async fn test_read_version_cache_async() -> FdbResult<()> {
+    const MAX_STALENESS: Duration = Duration::from_millis(500);
+
+    let mut db = common::database().await?;
+    db.set_read_version_cache(MAX_STALENESS);
+
+    // the second transaction reuses the read version of the first one
+    let trx = db.create_trx()?;
+    let version = trx.use_cached_read_version().await?;
+    let trx = db.create_trx()?;
+    assert_eq!(trx.use_cached_read_version().await?, version);
+    assert_eq!(trx.get_read_version().await?, version);
+    db.get(b"test-database-read-version-cache").await?;
+
+    // a committed version is newer, it replaces the cached one
+    let trx = db.create_trx()?;
+    trx.set(b"test-database-read-version-cache", b"1");
+    let committed = trx.commit().await?.committed_version()?;
+    assert!(committed > version);
+    let trx = db.create_trx()?;
+    assert_eq!(trx.use_cached_read_version().await?, committed);
+    assert_eq!(
+        db.get(b"test-database-read-version-cache").await?,
+        Some(b"1".to_vec())
+    );
+
+    // once stale, a fresh read version is used
+    thread::sleep(MAX_STALENESS * 2);
+    let trx = db.create_trx()?;
+    let fresh = trx.use_cached_read_version().await?;
+    assert!(fresh > committed);
+    let trx = db.create_trx()?;
+    assert_eq!(trx.use_cached_read_version().await?, fresh);
+
+    // without a cache, every transaction gets its own read version
+    let db = common::database().await?;
+    let trx = db.create_trx()?;
+    let version = trx.use_cached_read_version().await?;
+    thread::sleep(Duration::from_millis(10));
+    let trx = db.create_trx()?;
+    assert!(trx.use_cached_read_version().await? > version);
+
+    let trx = db.create_trx()?;
+    trx.clear(b"test-database-read-version-cache");
+    trx.commit().await?;
+
+    Ok(())
+}
+
+async fn test_connection_string_async() -> FdbResult<()> {
+    let db = common::database().await?;
+
+    let connection_string = db.connection_string().await?;
+    let content =
+        std::fs::read_to_string(cluster_file::resolve()).expect("failed to read the cluster file");
+    let expected = cluster_file::parse(&content).expect("invalid cluster file");
+    let actual = cluster_file::parse(&connection_string).expect("invalid connection string");
+    assert_eq!(actual.description, expected.description);
+    assert_eq!(actual.id, expected.id);
+    assert_eq!(actual.coordinators.len(), expected.coordinators.len());
+
+    // the current connection string is yielded at once
+    let mut watch = db
+        .watch_connection_string(Duration::from_millis(10))
+        .boxed();
+    assert_eq!(watch.try_next().await?, Some(connection_string));
+
+    Ok(())
+}
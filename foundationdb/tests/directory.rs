@@ -0,0 +1,817 @@
+// Copyright 2018 foundationdb-rs developers, https://github.com/Clikengo/foundationdb-rs/graphs/contributors
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+use foundationdb::directory::{
+    Directory, DirectoryError, DirectoryLayer, DirectoryOutput, DirectoryStat, DirectoryTree,
+    TreeLimits,
+};
+use foundationdb::options::{MutationType, TransactionOption};
+use foundationdb::tuple::{KeyspaceOps, PackResult, Subspace};
+use foundationdb::{Database, FdbError, RangeOption, Transaction};
+use std::convert::TryFrom;
+
+mod common;
+
+#[test]
+fn test_directory() {
+    let _guard = unsafe { foundationdb::boot() };
+    futures::executor::block_on(test_directory_async()).expect("failed to run");
+    futures::executor::block_on(test_directory_partition_async()).expect("failed to run");
+    futures::executor::block_on(test_directory_partition_layout_async()).expect("failed to run");
+    futures::executor::block_on(test_directory_move_async()).expect("failed to run");
+    futures::executor::block_on(test_directory_db_helpers_async()).expect("failed to run");
+    futures::executor::block_on(test_directory_relative_paths_async()).expect("failed to run");
+    futures::executor::block_on(test_directory_tree_async()).expect("failed to run");
+    futures::executor::block_on(test_directory_stat_async()).expect("failed to run");
+    futures::executor::block_on(test_directory_remove_if_empty_async()).expect("failed to run");
+    futures::executor::block_on(test_directory_system_prefix_async()).expect("failed to run");
+    futures::executor::block_on(test_directory_open_cached_async()).expect("failed to run");
+    futures::executor::block_on(test_directory_keyspace_ops_async()).expect("failed to run");
+}
+
+fn path(names: &[&str]) -> Vec<String> {
+    names.iter().map(|name| name.to_string()).collect()
+}
+
+async fn directory_layer(db: &Database, name: &str) -> Result<DirectoryLayer, DirectoryError> {
+    let node_subspace = Subspace::from_bytes(format!("{}-node", name).as_bytes());
+    let content_subspace = Subspace::from_bytes(format!("{}-content", name).as_bytes());
+
+    let trx = db.create_trx()?;
+    trx.clear_subspace_range(&node_subspace);
+    trx.clear_subspace_range(&content_subspace);
+    trx.commit().await.map_err(FdbError::from)?;
+
+    Ok(DirectoryLayer::new(node_subspace, content_subspace, false))
+}
+
+async fn test_directory_async() -> Result<(), DirectoryError> {
+    let db = common::database().await?;
+    let directory = directory_layer(&db, "test-directory").await?;
+
+    let trx = db.create_trx()?;
+    let a = directory
+        .create_or_open(&trx, &path(&["a"]), None, None)
+        .await?;
+    let b = directory
+        .create(&trx, &path(&["a", "b"]), None, Some(b"layer"))
+        .await?;
+    assert_eq!(a.get_path(), path(&["a"]).as_slice());
+    assert_eq!(b.get_path(), path(&["a", "b"]).as_slice());
+    assert_eq!(b.get_layer(), b"layer");
+    trx.commit().await.map_err(FdbError::from)?;
+
+    let trx = db.create_trx()?;
+    match directory.create(&trx, &path(&["a"]), None, None).await {
+        Err(DirectoryError::DirAlreadyExists) => {}
+        r => panic!("expected DirAlreadyExists, got {:?}", r),
+    }
+    match directory
+        .open(&trx, &path(&["a", "b"]), Some(b"other"))
+        .await
+    {
+        Err(DirectoryError::IncompatibleLayer) => {}
+        r => panic!("expected IncompatibleLayer, got {:?}", r),
+    }
+    let opened = directory.open(&trx, &path(&["a", "b"]), None).await?;
+    assert_eq!(opened.bytes(), b.bytes());
+    assert_eq!(directory.list(&trx, &path(&["a"])).await?, path(&["b"]));
+    assert!(directory.exists(&trx, &path(&["a", "b"])).await?);
+    assert!(!directory.exists(&trx, &path(&["z"])).await?);
+
+    let moved = directory
+        .move_to(&trx, &path(&["a"]), &path(&["c"]))
+        .await?;
+    assert_eq!(moved.bytes(), a.bytes());
+    assert!(!directory.exists(&trx, &path(&["a"])).await?);
+    assert_eq!(directory.list(&trx, &[]).await?, path(&["c"]));
+    let opened = directory.open(&trx, &path(&["c", "b"]), None).await?;
+    assert_eq!(opened.bytes(), b.bytes());
+
+    assert!(directory.remove(&trx, &path(&["c"])).await?);
+    assert!(!directory.remove_if_exists(&trx, &path(&["c"])).await?);
+    assert!(directory.list(&trx, &[]).await?.is_empty());
+    trx.commit().await.map_err(FdbError::from)?;
+
+    Ok(())
+}
+
+async fn test_directory_partition_async() -> Result<(), DirectoryError> {
+    let db = common::database().await?;
+    let directory = directory_layer(&db, "test-directory-partition").await?;
+
+    let trx = db.create_trx()?;
+    let partition = directory
+        .create(&trx, &path(&["p"]), None, Some(b"partition"))
+        .await?;
+    match &partition {
+        DirectoryOutput::DirectoryPartition(_) => {}
+        output => panic!("expected a partition, got {:?}", output),
+    }
+
+    let inner = partition
+        .create_or_open(&trx, &path(&["inner"]), None, None)
+        .await?;
+    assert_eq!(inner.get_path(), path(&["p", "inner"]).as_slice());
+    assert_eq!(partition.list(&trx, &[]).await?, path(&["inner"]));
+    assert!(directory.exists(&trx, &path(&["p", "inner"])).await?);
+
+    match directory
+        .move_to(&trx, &path(&["p", "inner"]), &path(&["outer"]))
+        .await
+    {
+        Err(DirectoryError::CannotMoveBetweenPartition) => {}
+        r => panic!("expected CannotMoveBetweenPartition, got {:?}", r),
+    }
+
+    assert!(partition.remove(&trx, &[]).await?);
+    assert!(!directory.exists(&trx, &path(&["p"])).await?);
+    trx.commit().await.map_err(FdbError::from)?;
+
+    Ok(())
+}
+
+async fn is_empty(trx: &Transaction, begin: &[u8], end: &[u8]) -> Result<bool, FdbError> {
+    let range = trx
+        .get_range(&RangeOption::from((begin, end)), 1, false)
+        .await?;
+    Ok(range.is_empty())
+}
+
+async fn test_directory_partition_layout_async() -> Result<(), DirectoryError> {
+    let db = common::database().await?;
+    let directory = directory_layer(&db, "test-directory-partition-layout").await?;
+    let node_subspace = Subspace::from_bytes(b"test-directory-partition-layout-tenant-node");
+
+    let trx = db.create_trx()?;
+    trx.clear_subspace_range(&node_subspace);
+    let partition = match directory
+        .create(&trx, &path(&["tenant"]), None, Some(b"partition"))
+        .await?
+    {
+        DirectoryOutput::DirectoryPartition(partition) => partition,
+        output => panic!("expected a partition, got {:?}", output),
+    };
+    let content_subspace = Subspace::from_bytes(partition.prefix());
+    let partition = partition.with_layout(node_subspace.clone(), content_subspace);
+
+    let a = partition.create(&trx, &path(&["a"]), None, None).await?;
+    let b = partition
+        .create(&trx, &path(&["a", "b"]), None, None)
+        .await?;
+    assert_eq!(b.get_path(), path(&["tenant", "a", "b"]).as_slice());
+    assert!(a.bytes().starts_with(partition.prefix()));
+    assert_eq!(partition.list(&trx, &[]).await?, path(&["a"]));
+
+    let moved = partition
+        .move_to(&trx, &path(&["a"]), &path(&["c"]))
+        .await?;
+    assert_eq!(moved.get_path(), path(&["tenant", "c"]).as_slice());
+    assert_eq!(moved.bytes(), a.bytes());
+    let opened = partition.open(&trx, &path(&["c", "b"]), None).await?;
+    assert_eq!(opened.bytes(), b.bytes());
+    let moved = opened.move_directory(&trx, &path(&["tenant", "d"])).await?;
+    assert_eq!(moved.get_path(), path(&["tenant", "d"]).as_slice());
+    assert!(partition.exists(&trx, &path(&["d"])).await?);
+
+    // the metadata is in the given node subspace, not in the default `\xFE` one
+    let mut default_node_prefix = partition.prefix().to_vec();
+    default_node_prefix.push(0xFE);
+    let mut default_node_end = partition.prefix().to_vec();
+    default_node_end.push(0xFF);
+    assert!(is_empty(&trx, &default_node_prefix, &default_node_end).await?);
+    let (begin, end) = node_subspace.range();
+    assert!(!is_empty(&trx, &begin, &end).await?);
+
+    assert!(partition.remove(&trx, &path(&["c"])).await?);
+    assert!(!partition.exists(&trx, &path(&["c"])).await?);
+    assert!(partition.exists(&trx, &path(&["d"])).await?);
+
+    // removing the partition clears its metadata too
+    assert!(partition.remove(&trx, &[]).await?);
+    assert!(!directory.exists(&trx, &path(&["tenant"])).await?);
+    assert!(is_empty(&trx, &begin, &end).await?);
+    trx.commit().await.map_err(FdbError::from)?;
+
+    Ok(())
+}
+
+async fn test_directory_move_async() -> Result<(), DirectoryError> {
+    let db = common::database().await?;
+    let directory = directory_layer(&db, "test-directory-move").await?;
+
+    let trx = db.create_trx()?;
+    let a = directory.create(&trx, &path(&["a"]), None, None).await?;
+    let c = directory
+        .create(&trx, &path(&["a", "b", "c"]), None, None)
+        .await?;
+    trx.set(&c.pack(&("key",)), b"value");
+    trx.commit().await.map_err(FdbError::from)?;
+
+    // moving a directory moves its children and keeps its content
+    let trx = db.create_trx()?;
+    let moved = a.move_directory(&trx, &path(&["x"])).await?;
+    assert_eq!(moved.get_path(), path(&["x"]).as_slice());
+    assert_eq!(moved.bytes(), a.bytes());
+    assert!(!directory.exists(&trx, &path(&["a"])).await?);
+    assert_eq!(directory.list(&trx, &[]).await?, path(&["x"]));
+    assert_eq!(moved.list(&trx, &[]).await?, path(&["b"]));
+    let opened = directory.open(&trx, &path(&["x", "b", "c"]), None).await?;
+    assert_eq!(opened.bytes(), c.bytes());
+    assert_eq!(
+        trx.get(&opened.pack(&("key",)), false).await?.as_deref(),
+        Some(&b"value"[..])
+    );
+    trx.commit().await.map_err(FdbError::from)?;
+
+    let trx = db.create_trx()?;
+    for new_path in &[path(&["x"]), path(&["x", "b"]), path(&["x", "y", "z"])] {
+        match moved.move_directory(&trx, new_path).await {
+            Err(DirectoryError::BadDestinationDirectory) => {}
+            r => panic!("expected BadDestinationDirectory, got {:?}", r),
+        }
+    }
+    // empty segments are accepted, as by the other bindings
+    let empty = moved.move_directory(&trx, &path(&[""])).await?;
+    assert_eq!(empty.get_path(), path(&[""]).as_slice());
+    let moved = empty.move_directory(&trx, &path(&["x"])).await?;
+    match directory.move_directory(&trx, &path(&["y"])).await {
+        Err(DirectoryError::CannotMoveRootDirectory) => {}
+        r => panic!("expected CannotMoveRootDirectory, got {:?}", r),
+    }
+    // a prefix of a segment is not a parent
+    let xy = moved.move_directory(&trx, &path(&["xy"])).await?;
+    assert_eq!(xy.get_path(), path(&["xy"]).as_slice());
+
+    // across partition boundaries
+    let partition = directory
+        .create(&trx, &path(&["p"]), None, Some(b"partition"))
+        .await?;
+    let inner = partition
+        .create(&trx, &path(&["inner"]), None, None)
+        .await?;
+    match inner.move_directory(&trx, &path(&["outer"])).await {
+        Err(DirectoryError::CannotMoveBetweenPartition) => {}
+        r => panic!("expected CannotMoveBetweenPartition, got {:?}", r),
+    }
+    match xy.move_directory(&trx, &path(&["p", "xy"])).await {
+        Err(DirectoryError::CannotMoveBetweenPartition) => {}
+        r => panic!("expected CannotMoveBetweenPartition, got {:?}", r),
+    }
+    let renamed = inner.move_directory(&trx, &path(&["p", "renamed"])).await?;
+    assert_eq!(renamed.get_path(), path(&["p", "renamed"]).as_slice());
+    assert_eq!(renamed.bytes(), inner.bytes());
+
+    // the partition itself is moved by its parent directory layer
+    let moved_partition = partition.move_directory(&trx, &path(&["q"])).await?;
+    assert_eq!(moved_partition.get_path(), path(&["q"]).as_slice());
+    assert_eq!(moved_partition.list(&trx, &[]).await?, path(&["renamed"]));
+    trx.commit().await.map_err(FdbError::from)?;
+
+    Ok(())
+}
+
+async fn test_directory_db_helpers_async() -> Result<(), DirectoryError> {
+    let db = common::database().await?;
+    let directory = directory_layer(&db, "test-directory-db").await?;
+
+    let a = directory
+        .db_create_or_open(&db, &path(&["a"]), None, None)
+        .await?;
+    let b = a
+        .db_create_or_open(&db, &path(&["b"]), None, Some(b"layer"))
+        .await?;
+    assert_eq!(b.get_path(), path(&["a", "b"]).as_slice());
+
+    // the helpers committed their transactions
+    let trx = db.create_trx()?;
+    assert!(directory.exists(&trx, &path(&["a", "b"])).await?);
+    let opened = directory.open(&trx, &path(&["a", "b"]), None).await?;
+    assert_eq!(opened.bytes(), b.bytes());
+    assert_eq!(opened.get_layer(), b"layer");
+    drop(trx);
+
+    let opened = directory.db_open(&db, &path(&["a", "b"]), None).await?;
+    assert_eq!(opened.bytes(), b.bytes());
+    match directory
+        .db_open(&db, &path(&["a", "b"]), Some(b"other"))
+        .await
+    {
+        Err(DirectoryError::IncompatibleLayer) => {}
+        r => panic!("expected IncompatibleLayer, got {:?}", r),
+    }
+    assert!(directory.db_exists(&db, &path(&["a"])).await?);
+    assert_eq!(directory.db_list(&db, &path(&["a"])).await?, path(&["b"]));
+
+    assert!(directory.db_remove(&db, &path(&["a"])).await?);
+    let trx = db.create_trx()?;
+    assert!(!directory.exists(&trx, &path(&["a"])).await?);
+    assert!(!directory.db_exists(&db, &path(&["a"])).await?);
+    match directory.db_remove(&db, &path(&["a"])).await {
+        Err(DirectoryError::DirectoryDoesNotExists) => {}
+        r => panic!("expected DirectoryDoesNotExists, got {:?}", r),
+    }
+
+    Ok(())
+}
+
+async fn test_directory_relative_paths_async() -> Result<(), DirectoryError> {
+    let db = common::database().await?;
+    let directory = directory_layer(&db, "test-directory-relative").await?;
+    let trx = db.create_trx()?;
+
+    let a = match directory.create(&trx, &path(&["a"]), None, None).await? {
+        DirectoryOutput::DirectorySubspace(a) => a,
+        output => panic!("expected a directory subspace, got {:?}", output),
+    };
+    assert!(a.parent_path().is_empty());
+
+    // paths given to a directory are relative to it
+    let b = a.create(&trx, &path(&["b"]), None, None).await?;
+    assert_eq!(b.get_path(), path(&["a", "b"]).as_slice());
+    let b = match b {
+        DirectoryOutput::DirectorySubspace(b) => b,
+        output => panic!("expected a directory subspace, got {:?}", output),
+    };
+    assert_eq!(b.parent_path(), path(&["a"]).as_slice());
+    assert!(directory.exists(&trx, &path(&["a", "b"])).await?);
+    assert!(!directory.exists(&trx, &path(&["b"])).await?);
+
+    let opened = a.relative_open(&trx, &path(&["b"]), None).await?;
+    assert_eq!(opened.bytes(), b.bytes());
+    assert_eq!(a.open(&trx, &path(&["b"]), None).await?.bytes(), b.bytes());
+    assert_eq!(a.list(&trx, &[]).await?, path(&["b"]));
+
+    // paths given to the directory layer are absolute
+    let layer = a.directory_layer();
+    let opened = layer.open(&trx, &path(&["a", "b"]), None).await?;
+    assert_eq!(opened.bytes(), b.bytes());
+    assert_eq!(layer.list(&trx, a.parent_path()).await?, path(&["a"]));
+    match a.relative_open(&trx, &path(&["a", "b"]), None).await {
+        Err(DirectoryError::DirectoryDoesNotExists) => {}
+        r => panic!("expected DirectoryDoesNotExists, got {:?}", r),
+    }
+
+    // only regular directories can be converted into a subspace
+    let subspace = opened.into_subspace().expect("not a partition");
+    assert_eq!(subspace.bytes(), b.bytes());
+    assert_eq!(b.clone().into_subspace().bytes(), b.bytes());
+    let partition = a
+        .create(&trx, &path(&["partition"]), None, Some(b"partition"))
+        .await?;
+    assert!(partition.into_subspace().is_none());
+
+    Ok(())
+}
+
+async fn test_directory_tree_async() -> Result<(), DirectoryError> {
+    let db = common::database().await?;
+    let directory = directory_layer(&db, "test-directory-tree").await?;
+
+    let trx = db.create_trx()?;
+    for dir in &[&["a", "b", "c"][..], &["a", "d"], &["e"]] {
+        directory.create(&trx, &path(dir), None, None).await?;
+    }
+    directory
+        .create(&trx, &path(&["a", "b", "l"]), None, Some(b"layer"))
+        .await?;
+    let partition = directory
+        .create(&trx, &path(&["p"]), None, Some(b"partition"))
+        .await?;
+    partition
+        .create(&trx, &path(&["inner"]), None, None)
+        .await?;
+
+    fn node(name: &str, layer: &[u8], children: Vec<DirectoryTree>) -> DirectoryTree {
+        DirectoryTree {
+            name: name.to_string(),
+            layer: layer.to_vec(),
+            partition: layer == b"partition",
+            children,
+        }
+    }
+
+    let tree = directory.tree(&trx, &[]).await?;
+    let expected = node(
+        "",
+        b"",
+        vec![
+            node(
+                "a",
+                b"",
+                vec![
+                    node(
+                        "b",
+                        b"",
+                        vec![node("c", b"", vec![]), node("l", b"layer", vec![])],
+                    ),
+                    node("d", b"", vec![]),
+                ],
+            ),
+            node("e", b"", vec![]),
+            // partitions are descended into
+            node("p", b"partition", vec![node("inner", b"", vec![])]),
+        ],
+    );
+    assert_eq!(tree, expected);
+    assert_eq!(tree.len(), 9);
+
+    let subtree = directory.tree(&trx, &path(&["a", "b"])).await?;
+    assert_eq!(subtree, expected.children[0].children[0]);
+    match directory.tree(&trx, &path(&["z"])).await {
+        Err(DirectoryError::DirectoryDoesNotExists) => {}
+        r => panic!("expected DirectoryDoesNotExists, got {:?}", r),
+    }
+
+    let limits = TreeLimits {
+        max_nodes: 9,
+        ..TreeLimits::default()
+    };
+    assert_eq!(directory.tree_with_limits(&trx, &[], limits).await?, tree);
+    let limits = TreeLimits {
+        max_nodes: 8,
+        ..TreeLimits::default()
+    };
+    match directory.tree_with_limits(&trx, &[], limits).await {
+        Err(DirectoryError::TreeLimitExceeded) => {}
+        r => panic!("expected TreeLimitExceeded, got {:?}", r),
+    }
+    let limits = TreeLimits {
+        max_depth: 2,
+        ..TreeLimits::default()
+    };
+    match directory.tree_with_limits(&trx, &[], limits).await {
+        Err(DirectoryError::TreeLimitExceeded) => {}
+        r => panic!("expected TreeLimitExceeded, got {:?}", r),
+    }
+
+    Ok(())
+}
+
+async fn test_directory_stat_async() -> Result<(), DirectoryError> {
+    let db = common::database().await?;
+    let directory = directory_layer(&db, "test-directory-stat").await?;
+
+    let trx = db.create_trx()?;
+    let a = directory
+        .create(&trx, &path(&["a"]), None, Some(b"layer"))
+        .await?;
+    let b = directory
+        .create(&trx, &path(&["a", "b"]), None, None)
+        .await?;
+    let p = directory
+        .create(&trx, &path(&["p"]), None, Some(b"partition"))
+        .await?;
+    let empty = directory
+        .create(&trx, &path(&["empty"]), None, Some(b"partition"))
+        .await?;
+    let inner = p.create(&trx, &path(&["inner"]), None, None).await?;
+
+    assert_eq!(
+        directory.stat(&trx, &path(&["a"])).await?,
+        Some(DirectoryStat {
+            prefix: a.bytes().to_vec(),
+            layer: b"layer".to_vec(),
+            has_children: true,
+            is_partition: false,
+        })
+    );
+    assert_eq!(
+        directory.stat(&trx, &path(&["a", "b"])).await?,
+        Some(DirectoryStat {
+            prefix: b.bytes().to_vec(),
+            layer: Vec::new(),
+            has_children: false,
+            is_partition: false,
+        })
+    );
+
+    // missing directories, including below an existing one
+    assert_eq!(directory.stat(&trx, &path(&["z"])).await?, None);
+    assert_eq!(directory.stat(&trx, &path(&["a", "z"])).await?, None);
+    assert_eq!(directory.stat(&trx, &path(&["p", "z"])).await?, None);
+
+    // partitions, and the directories inside them
+    fn partition_prefix(dir: &DirectoryOutput) -> Vec<u8> {
+        match dir {
+            DirectoryOutput::DirectoryPartition(partition) => partition.prefix().to_vec(),
+            DirectoryOutput::DirectorySubspace(_) => panic!("expected a partition"),
+        }
+    }
+    assert_eq!(
+        directory.stat(&trx, &path(&["p"])).await?,
+        Some(DirectoryStat {
+            prefix: partition_prefix(&p),
+            layer: b"partition".to_vec(),
+            has_children: true,
+            is_partition: true,
+        })
+    );
+    assert_eq!(
+        directory.stat(&trx, &path(&["empty"])).await?,
+        Some(DirectoryStat {
+            prefix: partition_prefix(&empty),
+            layer: b"partition".to_vec(),
+            has_children: false,
+            is_partition: true,
+        })
+    );
+    assert_eq!(
+        directory.stat(&trx, &path(&["p", "inner"])).await?,
+        Some(DirectoryStat {
+            prefix: inner.bytes().to_vec(),
+            layer: Vec::new(),
+            has_children: false,
+            is_partition: false,
+        })
+    );
+
+    match directory.stat(&trx, &[]).await {
+        Err(DirectoryError::CannotOpenRoot) => {}
+        r => panic!("expected CannotOpenRoot, got {:?}", r),
+    }
+
+    Ok(())
+}
+
+async fn test_directory_remove_if_empty_async() -> Result<(), DirectoryError> {
+    let db = common::database().await?;
+    let directory = directory_layer(&db, "test-directory-remove-if-empty").await?;
+
+    let trx = db.create_trx()?;
+    directory
+        .create(&trx, &path(&["empty"]), None, None)
+        .await?;
+    let content = directory
+        .create(&trx, &path(&["content"]), None, None)
+        .await?;
+    trx.set(&content.pack(&("key", 1)), b"value");
+    directory
+        .create(&trx, &path(&["children", "child"]), None, None)
+        .await?;
+    let empty_partition = directory
+        .create(&trx, &path(&["empty-partition"]), None, Some(b"partition"))
+        .await?;
+    // creating and removing a directory writes the metadata of the partition layer
+    empty_partition
+        .create(&trx, &path(&["removed"]), None, None)
+        .await?;
+    empty_partition.remove(&trx, &path(&["removed"])).await?;
+    directory
+        .create(&trx, &path(&["partition"]), None, Some(b"partition"))
+        .await?;
+    let inner = directory
+        .create(&trx, &path(&["partition", "inner"]), None, None)
+        .await?;
+    trx.set(&inner.pack(&"key"), b"value");
+
+    // an empty directory is removed
+    assert!(directory.is_empty(&trx, &path(&["empty"])).await?);
+    assert!(directory.remove_if_empty(&trx, &path(&["empty"])).await?);
+    assert!(!directory.exists(&trx, &path(&["empty"])).await?);
+    assert!(!directory.remove_if_empty(&trx, &path(&["empty"])).await?);
+    match directory.is_empty(&trx, &path(&["empty"])).await {
+        Err(DirectoryError::DirectoryDoesNotExists) => {}
+        r => panic!("expected DirectoryDoesNotExists, got {:?}", r),
+    }
+
+    // a directory with content keys only, or with sub-directories only, is kept
+    for name in &["content", "children"] {
+        assert!(!directory.is_empty(&trx, &path(&[*name])).await?);
+        match directory.remove_if_empty(&trx, &path(&[*name])).await {
+            Err(DirectoryError::NotEmpty) => {}
+            r => panic!("expected NotEmpty for {}, got {:?}", name, r),
+        }
+        assert!(directory.exists(&trx, &path(&[*name])).await?);
+    }
+    assert_eq!(
+        trx.get(&content.pack(&("key", 1)), false).await?.as_deref(),
+        Some(&b"value"[..])
+    );
+    assert!(
+        directory
+            .is_empty(&trx, &path(&["children", "child"]))
+            .await?
+    );
+
+    // partitions, whose metadata is not content
+    assert!(
+        directory
+            .is_empty(&trx, &path(&["empty-partition"]))
+            .await?
+    );
+    assert!(!directory.is_empty(&trx, &path(&["partition"])).await?);
+    assert!(
+        !directory
+            .is_empty(&trx, &path(&["partition", "inner"]))
+            .await?
+    );
+    match directory
+        .remove_if_empty(&trx, &path(&["partition", "inner"]))
+        .await
+    {
+        Err(DirectoryError::NotEmpty) => {}
+        r => panic!("expected NotEmpty, got {:?}", r),
+    }
+    assert!(
+        directory
+            .remove_if_empty(&trx, &path(&["empty-partition"]))
+            .await?
+    );
+
+    match directory.remove_if_empty(&trx, &[]).await {
+        Err(DirectoryError::CannotModifyRootDirectory) => {}
+        r => panic!("expected CannotModifyRootDirectory, got {:?}", r),
+    }
+
+    Ok(())
+}
+
+async fn test_directory_open_cached_async() -> Result<(), DirectoryError> {
+    let db = common::database().await?;
+    let directory = directory_layer(&db, "test-directory-open-cached").await?;
+
+    let trx = db.create_trx()?;
+    let users = directory
+        .create(&trx, &path(&["users"]), None, Some(b"layer"))
+        .await?;
+    trx.commit().await.map_err(FdbError::from)?;
+    // the prefix persisted, e.g. in a configuration
+    let prefix = users.bytes().to_vec();
+
+    let trx = db.create_trx()?;
+    let opened = directory
+        .open_cached(&trx, &path(&["users"]), &prefix)
+        .await?;
+    assert_eq!(opened.bytes(), prefix.as_slice());
+    assert!(
+        directory
+            .verify_prefix(&trx, &path(&["users"]), &prefix)
+            .await?
+    );
+
+    // the directory is moved, and another one is created at its path
+    directory
+        .move_to(&trx, &path(&["users"]), &path(&["archived-users"]))
+        .await?;
+    let created = directory
+        .create(&trx, &path(&["users"]), None, None)
+        .await?;
+    assert_ne!(created.bytes(), prefix.as_slice());
+    trx.commit().await.map_err(FdbError::from)?;
+
+    let trx = db.create_trx()?;
+    match directory
+        .open_cached(&trx, &path(&["users"]), &prefix)
+        .await
+    {
+        Err(DirectoryError::PrefixMismatch) => {}
+        r => panic!("expected PrefixMismatch, got {:?}", r),
+    }
+    assert!(
+        !directory
+            .verify_prefix(&trx, &path(&["users"]), &prefix)
+            .await?
+    );
+    // the moved directory keeps its prefix
+    let moved = directory
+        .open_cached(&trx, &path(&["archived-users"]), &prefix)
+        .await?;
+    assert_eq!(moved.bytes(), prefix.as_slice());
+
+    // a removed directory
+    directory.remove(&trx, &path(&["archived-users"])).await?;
+    match directory
+        .open_cached(&trx, &path(&["archived-users"]), &prefix)
+        .await
+    {
+        Err(DirectoryError::DirectoryDoesNotExists) => {}
+        r => panic!("expected DirectoryDoesNotExists, got {:?}", r),
+    }
+    assert!(
+        !directory
+            .verify_prefix(&trx, &path(&["archived-users"]), &prefix)
+            .await?
+    );
+
+    Ok(())
+}
+
+/// A layer written once for every `KeyspaceOps`
+struct Counters<K> {
+    space: K,
+}
+
+impl<K: KeyspaceOps> Counters<K> {
+    fn new(space: K) -> Self {
+        Counters { space }
+    }
+
+    fn increment(&self, trx: &Transaction, name: &str) -> PackResult<()> {
+        let key = self.space.pack(&name)?;
+        trx.atomic_op(&key, &1i64.to_le_bytes(), MutationType::Add);
+        Ok(())
+    }
+
+    async fn get(&self, trx: &Transaction, name: &str) -> Result<i64, DirectoryError> {
+        let key = self.space.pack(&name)?;
+        let mut value = [0; 8];
+        if let Some(bytes) = trx.get(&key, false).await? {
+            value.copy_from_slice(&bytes);
+        }
+        Ok(i64::from_le_bytes(value))
+    }
+}
+
+async fn test_directory_keyspace_ops_async() -> Result<(), DirectoryError> {
+    let db = common::database().await?;
+    let directory = directory_layer(&db, "test-directory-keyspace-ops").await?;
+
+    let trx = db.create_trx()?;
+    let output = directory
+        .create(&trx, &path(&["counters"]), None, None)
+        .await?;
+    let directory_subspace = match &output {
+        DirectoryOutput::DirectorySubspace(d) => d.clone(),
+        output => panic!("expected a directory, got {:?}", output),
+    };
+    let subspace = Subspace::try_from(output.clone()).expect("not a partition");
+    assert_eq!(subspace.bytes(), output.bytes());
+    assert_eq!(
+        Subspace::from(directory_subspace.clone()).bytes(),
+        output.bytes()
+    );
+
+    // the three keyspaces share the prefix of the directory
+    let by_subspace = Counters::new(subspace);
+    let by_directory = Counters::new(directory_subspace);
+    let by_output = Counters::new(&output);
+    by_subspace.increment(&trx, "a")?;
+    by_directory.increment(&trx, "a")?;
+    by_output.increment(&trx, "b")?;
+    assert_eq!(by_output.get(&trx, "a").await?, 2);
+    assert_eq!(by_subspace.get(&trx, "b").await?, 1);
+    assert_eq!(by_directory.get(&trx, "c").await?, 0);
+    assert_eq!(
+        KeyspaceOps::range(&output)?,
+        KeyspaceOps::range(&by_subspace.space)?
+    );
+    let (name,): (String,) = by_output.space.unpack(&output.pack(&("a",)))?;
+    assert_eq!(name, "a");
+
+    // the prefix of a partition can't be used, which is an error rather than a panic
+    let partition = directory
+        .create(&trx, &path(&["partition"]), None, Some(b"partition"))
+        .await?;
+    let by_partition = Counters::new(partition.clone());
+    assert!(by_partition.increment(&trx, "a").is_err());
+    assert!(KeyspaceOps::bytes(&partition).is_err());
+    assert!(KeyspaceOps::subspace(&partition, &"a").is_err());
+    assert!(Subspace::try_from(partition).is_err());
+
+    trx.commit().await.map_err(FdbError::from)?;
+
+    Ok(())
+}
+
+async fn test_directory_system_prefix_async() -> Result<(), DirectoryError> {
+    let db = common::database().await?;
+    let node_subspace = Subspace::from_bytes(b"test-directory-system-prefix-node");
+    let content_subspace = Subspace::from_bytes(b"test-directory-system-prefix-content");
+    let trx = db.create_trx()?;
+    trx.clear_subspace_range(&node_subspace);
+    trx.commit().await.map_err(FdbError::from)?;
+    let directory = DirectoryLayer::new(node_subspace, content_subspace, true);
+
+    // the transaction is never committed: removing the directory clears the system keyspace
+    let trx = db.create_trx()?;
+    trx.set_option(TransactionOption::AccessSystemKeys)?;
+    let system = directory
+        .create(&trx, &path(&["system"]), Some(&b"\xff"[..]), None)
+        .await?;
+    assert_eq!(system.bytes(), b"\xff");
+
+    // the system keys are the content of the directory
+    assert!(!directory.is_empty(&trx, &path(&["system"])).await?);
+    match directory.remove_if_empty(&trx, &path(&["system"])).await {
+        Err(DirectoryError::NotEmpty) => {}
+        r => panic!("expected NotEmpty, got {:?}", r),
+    }
+
+    directory.remove(&trx, &path(&["system"])).await?;
+    assert!(!directory.exists(&trx, &path(&["system"])).await?);
+    assert!(trx
+        .get_range(
+            &RangeOption::from((&b"\xff"[..], &b"\xff\xff"[..])),
+            1,
+            false
+        )
+        .await?
+        .is_empty());
+
+    Ok(())
+}
@@ -0,0 +1,161 @@
+// Copyright 2018 foundationdb-rs developers, https://github.com/Clikengo/foundationdb-rs/graphs/contributors
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+use foundationdb::tuple::Bytes;
+use foundationdb::*;
+
+mod common;
+
+#[allow(dead_code)]
+#[path = "../examples/fdbrs.rs"]
+mod fdbrs;
+
+use fdbrs::*;
+
+#[test]
+fn test_fdbrs() {
+    test_parse_tuple();
+    test_parse_bytes();
+    test_parse_args();
+
+    let _guard = unsafe { foundationdb::boot() };
+    futures::executor::block_on(test_execute_async()).expect("failed to run");
+}
+
+fn bytes(literal: &str) -> String {
+    match parse_bytes(literal) {
+        Ok(bytes) => Bytes::from(bytes).to_string(),
+        Err(err) => format!("error: {}", err),
+    }
+}
+
+fn test_parse_tuple() {
+    let tuple = parse_tuple(r#"("a", 1, -2, 2.5, b"\x00\xff", nil, true, false, ("b",), ())"#)
+        .expect("failed to parse");
+    assert_eq!(
+        format!("{:?}", tuple),
+        r#"Tuple([String("a"), Int(1), Int(-2), Double(2.5), Bytes(b"\x00\xff"), Nil, Bool(true), Bool(false), Tuple([String("b")]), Tuple([])])"#
+    );
+    assert_eq!(
+        format!("{:?}", parse_tuple(r#" ( "é\"\\" , 1e3 ) "#)),
+        r#"Ok(Tuple([String("é\"\\"), Double(1000.0)]))"#
+    );
+
+    let error = |literal| parse_tuple(literal).unwrap_err().to_string();
+    assert_eq!(error(""), "expected `(` at position 0");
+    assert_eq!(error("(1"), "expected `,` or `)` at position 2");
+    assert_eq!(error("(1,,)"), "unexpected character at position 3");
+    assert_eq!(error("(\"a)"), "unterminated string at position 1");
+    assert_eq!(error("(b\"\\x0)"), "invalid `\\x` escape at position 3");
+    assert_eq!(
+        error("(1) 2"),
+        "unexpected trailing characters at position 4"
+    );
+    assert_eq!(error("(maybe)"), "unknown identifier at position 1");
+    assert_eq!(error("(1.2.3)"), "invalid number at position 1");
+}
+
+fn test_parse_bytes() {
+    assert_eq!(bytes("key"), r#"b"key""#);
+    assert_eq!(bytes("0x00ff"), r#"b"\x00\xff""#);
+    assert_eq!(bytes(r#"("user", 42)"#), r#"b"\x02user\x00\x15\x2a""#);
+    assert_eq!(
+        bytes(r#"("a", ("b", nil))"#),
+        r#"b"\x02a\x00\x05\x02b\x00\x00\xff\x00""#
+    );
+    assert_eq!(
+        bytes("0x0"),
+        "error: odd number of hexadecimal digits at position 2"
+    );
+    assert_eq!(
+        bytes("0x0g"),
+        "error: invalid hexadecimal digit at position 3"
+    );
+}
+
+fn test_parse_args() {
+    let args = |args: &[&str]| {
+        let args: Vec<String> = args.iter().map(|s| s.to_string()).collect();
+        parse_args(&args)
+    };
+
+    assert_eq!(
+        args(&["set", "(1,)", "0x01"]).unwrap(),
+        Command::Set(b"\x15\x01".to_vec(), b"\x01".to_vec())
+    );
+    assert_eq!(
+        args(&["scan", "--limit", "3", "a", "--reverse"]).unwrap(),
+        Command::Scan {
+            prefix: b"a".to_vec(),
+            limit: Some(3),
+            reverse: true,
+        }
+    );
+    assert_eq!(
+        args(&["dir", "list", "/a/b/"]).unwrap(),
+        Command::DirList(vec!["a".to_string(), "b".to_string()])
+    );
+    assert_eq!(args(&[]).unwrap_err().exit_code(), 2);
+    assert_eq!(args(&["get"]).unwrap_err().exit_code(), 2);
+    assert_eq!(args(&["get", "(1"]).unwrap_err().exit_code(), 2);
+    assert_eq!(args(&["scan", "--limit", "x"]).unwrap_err().exit_code(), 2);
+    assert_eq!(args(&["drop"]).unwrap_err().exit_code(), 2);
+}
+
+async fn run(db: &Database, args: &[&str]) -> Result<String, fdbrs::Error> {
+    let args: Vec<String> = args.iter().map(|s| s.to_string()).collect();
+    let mut out = Vec::new();
+    execute(db, parse_args(&args)?, &mut out).await?;
+    Ok(String::from_utf8(out).expect("invalid output"))
+}
+
+async fn test_execute_async() -> FdbResult<()> {
+    let db = common::database().await?;
+    let prefix = format!("(\"{}\",", common::random_str(10));
+    let key = |i: i64| format!("{} {})", prefix, i);
+
+    for i in 0..3 {
+        run(&db, &["set", &key(i), &format!("value{}", i)])
+            .await
+            .expect("failed to set");
+    }
+    assert_eq!(
+        run(&db, &["get", &key(1)]).await.expect("failed to get"),
+        "b\"value1\"\n"
+    );
+
+    let scan = run(
+        &db,
+        &["scan", &format!("{})", prefix), "--reverse", "--limit", "2"],
+    )
+    .await
+    .expect("failed to scan");
+    let values: Vec<&str> = scan
+        .lines()
+        .map(|l| l.rsplit(" = ").next().unwrap())
+        .collect();
+    assert_eq!(values, vec!["b\"value2\"", "b\"value1\""]);
+
+    run(&db, &["clear", &key(0)])
+        .await
+        .expect("failed to clear");
+    let err = run(&db, &["get", &key(0)]).await.unwrap_err();
+    assert_eq!(err.exit_code(), 1);
+
+    run(&db, &["clearrange", &key(1), &key(3)])
+        .await
+        .expect("failed to clear range");
+    let scan = run(&db, &["scan", &format!("{})", prefix)])
+        .await
+        .expect("failed to scan");
+    assert_eq!(scan, "");
+
+    let list = run(&db, &["dir", "list"]).await.expect("failed to list");
+    assert!(list.lines().all(|name| !name.is_empty()));
+
+    Ok(())
+}
@@ -6,8 +6,10 @@
 // copied, modified, or distributed except according to those terms.
 use foundationdb::*;
 use futures::future::*;
+use futures::stream::TryStreamExt;
 use std::ops::Deref;
 use std::sync::{atomic::*, Arc};
+use std::time::{Duration, Instant};
 
 mod common;
 
@@ -16,14 +18,32 @@ fn test_get() {
     let _guard = unsafe { foundationdb::boot() };
     futures::executor::block_on(test_set_get_async()).expect("failed to run");
     futures::executor::block_on(test_get_multi_async()).expect("failed to run");
+    futures::executor::block_on(test_get_many_async()).expect("failed to run");
     futures::executor::block_on(test_set_conflict_async()).expect("failed to run");
     futures::executor::block_on(test_set_conflict_snapshot_async()).expect("failed to run");
+    futures::executor::block_on(test_conflict_helpers_async()).expect("failed to run");
     futures::executor::block_on(test_transact_async()).expect("failed to run");
     futures::executor::block_on(test_transact_limit()).expect("failed to run");
     futures::executor::block_on(test_transact_timeout()).expect("failed to run");
+    futures::executor::block_on(test_transact_deadline()).expect("failed to run");
+    futures::executor::block_on(test_transact_retry_fresh()).expect("failed to run");
+    futures::executor::block_on(test_default_transaction_options()).expect("failed to run");
+    futures::executor::block_on(test_remember_options()).expect("failed to run");
+    futures::executor::block_on(test_option_ranges()).expect("failed to run");
+    futures::executor::block_on(test_transact_classifier_abort()).expect("failed to run");
+    futures::executor::block_on(test_transact_backoff()).expect("failed to run");
+    futures::executor::block_on(test_transact_read_only()).expect("failed to run");
+    futures::executor::block_on(test_transact_max_size()).expect("failed to run");
+    futures::executor::block_on(test_estimated_mutation_bytes()).expect("failed to run");
+    futures::executor::block_on(test_size_validation()).expect("failed to run");
     futures::executor::block_on(test_versionstamp_async()).expect("failed to run");
+    futures::executor::block_on(test_versionstamp_typed_async()).expect("failed to run");
+    futures::executor::block_on(test_commit_with_versionstamp_async()).expect("failed to run");
+    futures::executor::block_on(test_versionstamped_key_async()).expect("failed to run");
     futures::executor::block_on(test_read_version_async()).expect("failed to run");
     futures::executor::block_on(test_set_read_version_async()).expect("failed to run");
+    futures::executor::block_on(test_read_as_of_async()).expect("failed to run");
+    futures::executor::block_on(test_database_reads_async()).expect("failed to run");
     futures::executor::block_on(test_get_addresses_for_key_async()).expect("failed to run");
 }
 
@@ -57,6 +77,46 @@ async fn test_get_multi_async() -> FdbResult<()> {
     Ok(())
 }
 
+async fn test_get_many_async() -> FdbResult<()> {
+    const N: usize = 10_000;
+    let db = common::database().await?;
+
+    let keys = (0..N)
+        .map(|i| format!("test_get_many/{:05}", i).into_bytes())
+        .collect::<Vec<_>>();
+
+    // only the even keys exist
+    let trx = db.create_trx()?;
+    trx.clear_range(b"test_get_many/", b"test_get_many0");
+    for (i, key) in keys.iter().enumerate().step_by(2) {
+        trx.set(key, i.to_string().as_bytes());
+    }
+    trx.commit().await?;
+
+    let trx = db.create_trx()?;
+    let values = trx
+        .get_many(keys.iter().map(Vec::as_slice), false)
+        .try_collect::<Vec<_>>()
+        .await?;
+    assert_eq!(values.len(), N);
+    for (i, value) in values.iter().enumerate() {
+        if i % 2 == 0 {
+            assert_eq!(value.as_deref(), Some(i.to_string().as_bytes()));
+        } else {
+            assert!(value.is_none(), "key {} should not exist", i);
+        }
+    }
+
+    let values = trx
+        .get_many_with_concurrency(keys[..3].iter().map(Vec::as_slice), true, 1)
+        .try_collect::<Vec<_>>()
+        .await?;
+    assert_eq!(values.len(), 3);
+    assert!(values[0].is_some() && values[1].is_none() && values[2].is_some());
+
+    Ok(())
+}
+
 async fn test_set_conflict_async() -> FdbResult<()> {
     let key = b"test_set_conflict";
     let db = common::database().await?;
@@ -115,6 +175,55 @@ async fn test_set_conflict_snapshot_async() -> FdbResult<()> {
     Ok(())
 }
 
+async fn test_conflict_helpers_async() -> FdbResult<()> {
+    const KEY: &[u8] = b"test_conflict_helpers";
+    const READ_KEY: &[u8] = b"test_conflict_helpers_read";
+    let db = common::database().await?;
+
+    // both transactions read the key the other one writes, but without write conflict ranges
+    let trx1 = db.create_trx()?;
+    let trx2 = db.create_trx()?;
+    trx1.get(KEY, false).await?;
+    trx2.get(KEY, false).await?;
+    trx1.set_without_conflict(KEY, b"1")?;
+    trx2.set_without_conflict(KEY, b"2")?;
+    trx1.commit().await?;
+    trx2.commit().await?;
+
+    make_dirty(&db, READ_KEY).await?;
+
+    // the key read by `get_and_add_read_conflict` conflicts
+    let trx = db.create_trx()?;
+    assert_eq!(
+        trx.get_and_add_read_conflict(READ_KEY).await?.as_deref(),
+        Some(&b""[..])
+    );
+    make_dirty(&db, READ_KEY).await?;
+    trx.set(KEY, b"3");
+    let err = trx.commit().await.unwrap_err();
+    assert_eq!(err.code(), 1020);
+
+    // but not the keys next to it
+    let trx = db.create_trx()?;
+    trx.get_and_add_read_conflict(READ_KEY).await?;
+    make_dirty(&db, b"test_conflict_helpers_read\x00").await?;
+    trx.set(KEY, b"4");
+    trx.commit().await?;
+
+    // both isolations conflict on the key read
+    for &snapshot in &[false, true] {
+        let trx = db.create_trx()?;
+        trx.get(READ_KEY, snapshot).await?;
+        trx.add_read_conflict_if_snapshot(READ_KEY, b"test_conflict_helpers_read\x00", snapshot)?;
+        make_dirty(&db, READ_KEY).await?;
+        trx.set(KEY, b"5");
+        let err = trx.commit().await.unwrap_err();
+        assert_eq!(err.code(), 1020);
+    }
+
+    Ok(())
+}
+
 // Makes the key dirty. It will abort transactions which performs non-snapshot read on the `key`.
 async fn make_dirty(db: &Database, key: &[u8]) -> FdbResult<()> {
     let trx = db.create_trx()?;
@@ -135,7 +244,7 @@ async fn test_transact_async() -> FdbResult<()> {
         // increment try counter
         try_count0.fetch_add(1, Ordering::SeqCst);
 
-        trx.set_option(options::TransactionOption::RetryLimit(RETRY_COUNT as i32))
+        trx.set_option(options::TransactionOption::RetryLimit(RETRY_COUNT as i64))
             .expect("failed to set retry limit");
 
         // update conflict range
@@ -193,9 +302,16 @@ async fn test_transact_limit() -> FdbResult<()> {
     let try_count = Arc::new(AtomicUsize::new(0));
     let db = common::database().await?;
     let res = db
-        .transact_boxed(
-            &db,
-            |trx, db| async_body(db, trx, try_count.clone()).boxed(),
+        .transact_owned(
+            |trx| {
+                let (db, try_count) = (&db, try_count.clone());
+                async move {
+                    match async_body(db, &trx, try_count).await {
+                        Ok(()) => Ok((trx, ())),
+                        Err(err) => Err((trx, err)),
+                    }
+                }
+            },
             TransactOption {
                 retry_limit: Some(5),
                 ..TransactOption::default()
@@ -248,6 +364,529 @@ async fn test_transact_timeout() -> FdbResult<()> {
     Ok(())
 }
 
+async fn test_transact_deadline() -> FdbResult<()> {
+    const KEY: &[u8] = b"test_transact_deadline";
+    const DEADLINE: Duration = Duration::from_millis(300);
+    async fn async_body(
+        trx: &Transaction,
+        try_count0: Arc<AtomicUsize>,
+        read: bool,
+    ) -> FdbResult<()> {
+        try_count0.fetch_add(1, Ordering::SeqCst);
+
+        std::thread::sleep(Duration::from_millis(100));
+        if read {
+            // fails once the timeout set from the deadline elapsed
+            trx.get(KEY, false).await?;
+        }
+
+        // retryable, `transact` would retry forever without the deadline
+        Err(FdbError::from_code(1020))
+    }
+
+    let db = common::database().await?;
+    for &read in &[false, true] {
+        let try_count = Arc::new(AtomicUsize::new(0));
+        let start = Instant::now();
+        let res = db
+            .transact_boxed(
+                (),
+                |trx, _| async_body(trx, try_count.clone(), read).boxed(),
+                TransactOption::deadline(DEADLINE),
+            )
+            .await;
+        match res {
+            Err(err) => assert_eq!(err.code(), 1031),
+            Ok(()) => panic!("should not be able to commit"),
+        }
+        assert!(start.elapsed() >= DEADLINE);
+        assert!(start.elapsed() < DEADLINE * 10, "retried past the deadline");
+        assert!(try_count.load(Ordering::SeqCst) >= 2);
+    }
+
+    // the closure sleeps past the deadline, the commit fails
+    let try_count = Arc::new(AtomicUsize::new(0));
+    let res = db
+        .transact_boxed(
+            (),
+            |trx, _| {
+                let try_count = try_count.clone();
+                async move {
+                    try_count.fetch_add(1, Ordering::SeqCst);
+                    std::thread::sleep(DEADLINE * 2);
+                    trx.set(KEY, b"late");
+                    Ok::<_, FdbError>(())
+                }
+                .boxed()
+            },
+            TransactOption::deadline(DEADLINE),
+        )
+        .await;
+    match res {
+        Err(err) => assert_eq!(err.code(), 1031),
+        Ok(()) => panic!("should not be able to commit"),
+    }
+    assert_eq!(try_count.load(Ordering::SeqCst), 1);
+
+    Ok(())
+}
+
+async fn test_default_transaction_options() -> FdbResult<()> {
+    const KEY: &[u8] = b"test_default_transaction_options";
+
+    let mut db = common::database().await?;
+    let before = db.create_trx()?;
+    db.set_default_transaction_options(vec![options::TransactionOption::Timeout(1)]);
+
+    let trx = db.create_trx()?;
+    std::thread::sleep(Duration::from_millis(50));
+    match trx.get(KEY, false).await {
+        Err(err) => assert_eq!(err.code(), 1031),
+        Ok(_) => panic!("the default timeout should have been applied"),
+    }
+
+    // `transact` gets its transactions from `create_trx`
+    let res = db
+        .transact_boxed(
+            (),
+            |trx, _| {
+                async move {
+                    std::thread::sleep(Duration::from_millis(50));
+                    trx.get(KEY, false).await?;
+                    Ok::<_, FdbError>(())
+                }
+                .boxed()
+            },
+            TransactOption::default(),
+        )
+        .await;
+    match res {
+        Err(err) => assert_eq!(err.code(), 1031),
+        Ok(()) => panic!("the default timeout should have been applied"),
+    }
+
+    // the transactions created before are not affected
+    std::thread::sleep(Duration::from_millis(50));
+    before.get(KEY, false).await?;
+
+    Ok(())
+}
+
+async fn test_transact_max_size() -> FdbResult<()> {
+    const KEY: &[u8] = b"test_transact_max_size";
+    // 11MB of mutations, above the 10MB transaction size limit
+    fn write_too_much(trx: &Transaction) {
+        let value = vec![0u8; 100_000];
+        for i in 0..110u32 {
+            let mut key = KEY.to_vec();
+            key.extend_from_slice(&i.to_be_bytes());
+            trx.set(&key, &value);
+        }
+    }
+
+    let db = common::database().await?;
+    let try_count = Arc::new(AtomicUsize::new(0));
+    let res = db
+        .transact_boxed(
+            try_count.clone(),
+            |trx, try_count| {
+                async move {
+                    try_count.fetch_add(1, Ordering::SeqCst);
+                    write_too_much(trx);
+                    Ok::<_, FdbError>(())
+                }
+                .boxed()
+            },
+            TransactOption::max_transaction_size(10_000_000),
+        )
+        .await;
+    // only the guard sets the sizes, the commit was not sent
+    match res {
+        Err(err) => {
+            assert_eq!(err.code(), 2101);
+            let too_large = err
+                .transaction_too_large()
+                .expect("the size guard should have tripped");
+            assert_eq!(too_large.limit, 10_000_000);
+            assert_eq!(too_large.size, 110 * (KEY.len() + 4 + 100_000));
+        }
+        Ok(()) => panic!("the size guard should have tripped"),
+    }
+    assert_eq!(try_count.load(Ordering::SeqCst), 1);
+
+    // the same error returned by the cluster on commit is not marked
+    let trx = db.create_trx()?;
+    write_too_much(&trx);
+    let err = trx.commit().await.unwrap_err();
+    assert_eq!(err.code(), 2101);
+    assert!(err.transaction_too_large().is_none());
+
+    Ok(())
+}
+
+async fn test_estimated_mutation_bytes() -> FdbResult<()> {
+    let db = common::database().await?;
+    let mut trx = db.create_trx()?;
+    assert_eq!(trx.estimated_mutation_bytes(), 0);
+
+    trx.set(b"key", b"value");
+    assert_eq!(trx.estimated_mutation_bytes(), 8);
+    trx.clear(b"key");
+    assert_eq!(trx.estimated_mutation_bytes(), 11);
+    trx.clear_range(b"a", b"b");
+    assert_eq!(trx.estimated_mutation_bytes(), 13);
+    trx.atomic_op(b"counter", &1i64.to_le_bytes(), options::MutationType::Add);
+    assert_eq!(trx.estimated_mutation_bytes(), 28);
+
+    trx.reset();
+    assert_eq!(trx.estimated_mutation_bytes(), 0);
+
+    Ok(())
+}
+
+async fn test_size_validation() -> FdbResult<()> {
+    let key = vec![b'k'; 11_000];
+
+    let mut db = common::database().await?;
+    db.set_size_validation(true);
+
+    let trx = db.create_trx()?;
+    trx.set(&key, b"value");
+    trx.set(b"test_size_validation", &vec![0u8; 110_000]);
+    let err = trx.commit().await.unwrap_err();
+    assert_eq!(err.code(), 2102);
+    let violation = err.size_violation().expect("missing size violation");
+    assert_eq!(violation.code(), 2102);
+    assert_eq!(violation.key(), &key[..64]);
+    assert_eq!(violation.size(), 11_000);
+    assert_eq!(violation.limit(), 10_000);
+    assert!(err
+        .to_string()
+        .ends_with("is 11000 bytes, larger than the limit of 10000 bytes"));
+
+    let trx = db.create_trx()?;
+    trx.set(b"test_size_validation", &vec![0u8; 110_000]);
+    let err = trx.commit().await.unwrap_err();
+    assert_eq!(err.code(), 2103);
+    assert_eq!(err.size_violation().map(|v| v.size()), Some(110_000));
+
+    // the violation is forgotten by `reset`
+    let trx = err.reset();
+    trx.set(b"test_size_validation", b"value");
+    trx.commit().await?;
+
+    // without validation, the commit fails without telling which key is too large
+    db.set_size_validation(false);
+    let trx = db.create_trx()?;
+    trx.set(&key, b"value");
+    let err = trx.commit().await.unwrap_err();
+    assert_eq!(err.code(), 2102);
+    assert!(err.size_violation().is_none());
+
+    Ok(())
+}
+
+async fn test_transact_retry_fresh() -> FdbResult<()> {
+    const KEY: &[u8] = b"test_transact_retry_fresh";
+    async fn async_body(trx: &Transaction, try_count0: Arc<AtomicUsize>) -> FdbResult<()> {
+        // increment try counter
+        let try_count = try_count0.fetch_add(1, Ordering::SeqCst);
+
+        if try_count == 0 {
+            // make the first attempt time out, `transaction_timed_out` is not retryable by
+            // `Transaction::on_error`
+            trx.set_option(options::TransactionOption::Timeout(1))
+                .expect("failed to set timeout");
+            std::thread::sleep(std::time::Duration::from_millis(50));
+        } else {
+            // the fresh transaction got the timeout of the failed one
+            let timeouts: Vec<_> = trx
+                .applied_options()
+                .into_iter()
+                .filter_map(|opt| match opt {
+                    options::TransactionOption::Timeout(timeout) => Some(timeout),
+                    _ => None,
+                })
+                .collect();
+            assert_eq!(timeouts, vec![1]);
+            trx.set_option(options::TransactionOption::Timeout(0))
+                .expect("failed to clear timeout");
+        }
+
+        trx.get(KEY, false).await?;
+        trx.set(KEY, common::random_str(10).as_bytes());
+
+        // `Database::transact` will handle commit by itself, so returns without commit
+        Ok(())
+    }
+
+    let try_count = Arc::new(AtomicUsize::new(0));
+    let db = common::database().await?;
+    db.transact_boxed(
+        (),
+        |trx, _| async_body(trx, try_count.clone()).boxed(),
+        TransactOption::retry_classifier(Arc::new(|err, _attempt| {
+            if err.code() == 1031 {
+                RetryDecision::RetryFresh
+            } else {
+                RetryDecision::default_for(err, false)
+            }
+        })),
+    )
+    .await?;
+
+    assert_eq!(try_count.load(Ordering::SeqCst), 2);
+
+    Ok(())
+}
+
+async fn test_option_ranges() -> FdbResult<()> {
+    let db = common::database().await?;
+
+    // -1 means unlimited retries
+    let trx = db.create_trx()?;
+    trx.set_option(options::TransactionOption::RetryLimit(-1))?;
+    trx.set_option(options::TransactionOption::Timeout(i64::from(i32::MAX)))?;
+    trx.get(b"test_option_ranges", false).await?;
+
+    match trx.set_option(options::TransactionOption::Timeout(i64::from(i32::MAX) + 1)) {
+        Err(err) => assert_eq!(err.code(), 2006),
+        Ok(()) => panic!("the timeout should be out of range"),
+    }
+
+    // the value is checked before reaching the client
+    match unsafe { options::NetworkOption::BuggifySectionActivatedProbability(150).apply() } {
+        Err(err) => {
+            assert_eq!(err.code(), 2006);
+            assert!(err.to_string().contains("out of range"));
+        }
+        Ok(()) => panic!("a probability of 150% should be out of range"),
+    }
+
+    Ok(())
+}
+
+async fn test_remember_options() -> FdbResult<()> {
+    const KEY: &[u8] = b"\xff/coordinators";
+
+    let db = common::database().await?;
+
+    // the options are cleared by `reset` by default
+    let mut trx = db.create_trx()?;
+    trx.set_option(options::TransactionOption::AccessSystemKeys)?;
+    trx.get(KEY, true).await?;
+    assert!(trx.applied_options().is_empty());
+    trx.reset();
+    match trx.get(KEY, true).await {
+        Err(err) => assert_eq!(err.code(), 2004),
+        Ok(_) => panic!("the option should have been cleared"),
+    }
+
+    let mut trx = db.create_trx()?;
+    trx.remember_options(true);
+    trx.set_option(options::TransactionOption::AccessSystemKeys)?;
+    trx.set_option(options::TransactionOption::Timeout(10_000))?;
+    trx.set_option(options::TransactionOption::Timeout(20_000))?;
+    assert_eq!(trx.applied_options().len(), 2);
+    trx.reset();
+    trx.get(KEY, true).await?;
+    let trx = trx.on_error(FdbError::from_code(1020)).await?;
+    trx.get(KEY, true).await?;
+
+    // `transact_boxed` remembers the options set by the first attempt
+    let try_count = Arc::new(AtomicUsize::new(0));
+    db.transact_boxed(
+        try_count.clone(),
+        |trx, try_count| {
+            async move {
+                if try_count.fetch_add(1, Ordering::SeqCst) == 0 {
+                    trx.set_option(options::TransactionOption::AccessSystemKeys)?;
+                    trx.get(KEY, true).await?;
+                    // retryable
+                    return Err(FdbError::from_code(1020));
+                }
+                trx.get(KEY, true).await?;
+                Ok::<_, FdbError>(())
+            }
+            .boxed()
+        },
+        TransactOption::default(),
+    )
+    .await?;
+    assert_eq!(try_count.load(Ordering::SeqCst), 2);
+
+    // and gives them to the fresh transactions of `RetryDecision::RetryFresh`
+    let try_count = Arc::new(AtomicUsize::new(0));
+    db.transact_boxed(
+        try_count.clone(),
+        |trx, try_count| {
+            async move {
+                if try_count.fetch_add(1, Ordering::SeqCst) == 0 {
+                    trx.set_option(options::TransactionOption::AccessSystemKeys)?;
+                    trx.get(KEY, true).await?;
+                    return Err(FdbError::from_code(1020));
+                }
+                trx.get(KEY, true).await?;
+                Ok::<_, FdbError>(())
+            }
+            .boxed()
+        },
+        TransactOption::retry_classifier(Arc::new(|_err, _attempt| RetryDecision::RetryFresh)),
+    )
+    .await?;
+    assert_eq!(try_count.load(Ordering::SeqCst), 2);
+
+    Ok(())
+}
+
+async fn test_transact_classifier_abort() -> FdbResult<()> {
+    const KEY: &[u8] = b"test_transact_classifier_abort";
+    async fn async_body(
+        db: &Database,
+        trx: &Transaction,
+        try_count0: Arc<AtomicUsize>,
+    ) -> FdbResult<()> {
+        // increment try counter
+        try_count0.fetch_add(1, Ordering::SeqCst);
+
+        // update conflict range
+        trx.get(KEY, false).await?;
+
+        // make current transaction invalid by making conflict
+        make_dirty(db, KEY).await?;
+
+        trx.set(KEY, common::random_str(10).as_bytes());
+
+        // `Database::transact` will handle commit by itself, so returns without commit
+        Ok(())
+    }
+
+    let try_count = Arc::new(AtomicUsize::new(0));
+    let db = common::database().await?;
+    let res = db
+        .transact_boxed(
+            &db,
+            |trx, db| async_body(db, trx, try_count.clone()).boxed(),
+            TransactOption::retry_classifier(Arc::new(|err, attempt| {
+                if err.code() == 1020 && attempt >= 2 {
+                    RetryDecision::Abort
+                } else {
+                    RetryDecision::default_for(err, false)
+                }
+            })),
+        )
+        .await;
+    assert_eq!(res.map_err(|err| err.code()), Err(1020));
+
+    assert_eq!(try_count.load(Ordering::SeqCst), 2);
+
+    Ok(())
+}
+
+async fn test_transact_backoff() -> FdbResult<()> {
+    let try_count = Arc::new(AtomicUsize::new(0));
+    let backoff_count = Arc::new(AtomicUsize::new(0));
+    let db = common::database().await?;
+
+    let backoff_count0 = backoff_count.clone();
+    let res = db
+        .transact_boxed(
+            try_count.clone(),
+            |_trx, try_count| {
+                try_count.fetch_add(1, Ordering::SeqCst);
+                // not_committed is retryable, only the backoff policy stops the retries
+                future::err::<(), _>(FdbError::from_code(1020)).boxed()
+            },
+            TransactOption::backoff(BackoffPolicy::Custom(Arc::new(move |retry, err| {
+                let count = backoff_count0.fetch_add(1, Ordering::SeqCst) + 1;
+                assert_eq!(retry as usize, count);
+                assert_eq!(err.code(), 1020);
+                if retry > 3 {
+                    None
+                } else {
+                    Some(Duration::from_millis(1))
+                }
+            }))),
+        )
+        .await;
+    assert_eq!(res.map_err(|err| err.code()), Err(1020));
+    assert_eq!(try_count.load(Ordering::SeqCst), 4);
+    assert_eq!(backoff_count.load(Ordering::SeqCst), 4);
+
+    // the delay of the jitter policy never exceeds its maximum
+    let policy = BackoffPolicy::ExponentialJitter {
+        base: Duration::from_millis(10),
+        max: Duration::from_millis(50),
+        multiplier: 2.,
+    };
+    let err = FdbError::from_code(1020);
+    for retry in 1..100 {
+        let delay = policy
+            .delay(retry, &err)
+            .expect("jitter never stops retrying");
+        assert!(delay <= Duration::from_millis(50));
+    }
+    assert!(policy.delay(1, &err).unwrap() <= Duration::from_millis(10));
+    // even when the maximum can't be represented as a f64
+    let policy = BackoffPolicy::ExponentialJitter {
+        base: Duration::new(u64::MAX, 999_999_999),
+        max: Duration::new(u64::MAX, 999_999_999),
+        multiplier: 2.,
+    };
+    for retry in 1..100 {
+        policy
+            .delay(retry, &err)
+            .expect("jitter never stops retrying");
+    }
+    assert_eq!(
+        BackoffPolicy::FdbDefault.delay(1, &err),
+        Some(Duration::from_secs(0))
+    );
+
+    Ok(())
+}
+
+async fn test_transact_read_only() -> FdbResult<()> {
+    const KEY: &[u8] = b"test_transact_read_only";
+    async fn async_body(trx: &Transaction, try_count0: Arc<AtomicUsize>) -> FdbResult<usize> {
+        // increment try counter
+        let try_count = try_count0.fetch_add(1, Ordering::SeqCst);
+
+        let value = trx.get(KEY, false).await?;
+        if try_count == 0 {
+            // transaction_too_old is retried through `Transaction::on_error`
+            return Err(FdbError::from_code(1007));
+        }
+
+        // discarded, as the transaction is not committed
+        trx.set(KEY, b"written");
+
+        Ok(value.map(|v| v.len()).unwrap_or(0))
+    }
+
+    let db = common::database().await?;
+    let trx = db.create_trx()?;
+    trx.clear(KEY);
+    trx.commit().await?;
+
+    let try_count = Arc::new(AtomicUsize::new(0));
+    let len = db
+        .transact_boxed(
+            (),
+            |trx, _| async_body(trx, try_count.clone()).boxed(),
+            TransactOption::read_only(),
+        )
+        .await?;
+    assert_eq!(len, 0);
+    assert_eq!(try_count.load(Ordering::SeqCst), 2);
+
+    // nothing was committed
+    let trx = db.create_trx()?;
+    assert!(trx.get(KEY, false).await?.is_none());
+
+    Ok(())
+}
+
 async fn test_versionstamp_async() -> FdbResult<()> {
     const KEY: &[u8] = b"test_versionstamp";
     let db = common::database().await?;
@@ -261,6 +900,123 @@ async fn test_versionstamp_async() -> FdbResult<()> {
     Ok(())
 }
 
+async fn test_versionstamp_typed_async() -> FdbResult<()> {
+    use foundationdb::tuple::{Subspace, Versionstamp};
+
+    let subspace = Subspace::from("test_versionstamp_typed");
+    let db = common::database().await?;
+
+    let trx = db.create_trx()?;
+    trx.clear_subspace_range(&subspace);
+    trx.commit().await?;
+
+    let trx = db.create_trx()?;
+    trx.set_versionstamped_key(&subspace, &(Versionstamp::incomplete(0), "key"), b"value");
+    let f_version = trx.get_versionstamp_typed();
+    let committed_version = trx.commit().await?.committed_version()?;
+    let versionstamp = f_version.await?;
+
+    assert!(versionstamp.is_complete());
+    assert_eq!(versionstamp.user_version(), 0);
+    let mut version = [0u8; 8];
+    version.copy_from_slice(&versionstamp.transaction_version()[..8]);
+    assert_eq!(i64::from_be_bytes(version), committed_version);
+
+    let trx = db.create_trx()?;
+    let range = trx.get_range(&subspace.range_option(), 1, false).await?;
+    assert_eq!(range.len(), 1);
+    let (key_versionstamp, name): (Versionstamp, String) = subspace
+        .unpack(range[0].key())
+        .expect("failed to unpack key");
+    assert_eq!(key_versionstamp, versionstamp);
+    assert_eq!(name, "key");
+    assert_eq!(range[0].value(), b"value");
+
+    Ok(())
+}
+
+async fn test_commit_with_versionstamp_async() -> FdbResult<()> {
+    use foundationdb::tuple::{Subspace, Versionstamp};
+
+    let subspace = Subspace::from("test_commit_with_versionstamp");
+    let db = common::database().await?;
+
+    let trx = db.create_trx()?;
+    trx.clear_subspace_range(&subspace);
+    trx.commit().await?;
+
+    let trx = db.create_trx()?;
+    trx.set_versionstamped_key(&subspace, &(Versionstamp::incomplete(3), "key"), b"value");
+    let (committed, versionstamp) = trx.commit_with_versionstamp().await?;
+
+    assert!(versionstamp.is_complete());
+    let mut version = [0u8; 8];
+    version.copy_from_slice(&versionstamp.transaction_version()[..8]);
+    assert_eq!(i64::from_be_bytes(version), committed.committed_version()?);
+
+    let trx = db.create_trx()?;
+    let range = trx.get_range(&subspace.range_option(), 1, false).await?;
+    assert_eq!(range.len(), 1);
+    let (key_versionstamp, _): (Versionstamp, String) = subspace
+        .unpack(range[0].key())
+        .expect("failed to unpack key");
+    assert_eq!(
+        key_versionstamp.transaction_version(),
+        versionstamp.transaction_version()
+    );
+    assert_eq!(key_versionstamp.user_version(), 3);
+
+    // a read-only transaction has no versionstamp
+    let trx = db.create_trx()?;
+    trx.get(b"test_commit_with_versionstamp", false).await?;
+    match trx.commit_with_versionstamp().await {
+        Err(err) => {
+            assert!(err.is_read_only());
+            assert!(!err.is_retryable());
+        }
+        Ok(_) => panic!("a read-only transaction should have no versionstamp"),
+    }
+
+    Ok(())
+}
+
+async fn test_versionstamped_key_async() -> FdbResult<()> {
+    use foundationdb::options::MutationType;
+    use foundationdb::tuple::{Subspace, Versionstamp};
+
+    let subspace = Subspace::from("test_versionstamped_key");
+    let db = common::database().await?;
+
+    let trx = db.create_trx()?;
+    trx.clear_subspace_range(&subspace);
+    trx.commit().await?;
+
+    let trx = db.create_trx()?;
+    let key = subspace
+        .pack_with_versionstamp(&("events", Versionstamp::incomplete(7)))
+        .expect("failed to pack key");
+    trx.atomic_op(&key, b"value", MutationType::SetVersionstampedKey);
+    let f_version = trx.get_versionstamp_typed();
+    trx.commit().await?;
+    let versionstamp = f_version.await?;
+
+    let trx = db.create_trx()?;
+    let events = subspace.subspace(&"events");
+    let range = trx.get_range(&events.range_option(), 1, false).await?;
+    assert_eq!(range.len(), 1);
+    let key_versionstamp: Versionstamp =
+        events.unpack(range[0].key()).expect("failed to unpack key");
+    assert!(key_versionstamp.is_complete());
+    assert_eq!(key_versionstamp.user_version(), 7);
+    assert_eq!(
+        key_versionstamp.transaction_version(),
+        versionstamp.transaction_version()
+    );
+    assert_eq!(range[0].value(), b"value");
+
+    Ok(())
+}
+
 async fn test_read_version_async() -> FdbResult<()> {
     let db = common::database().await?;
 
@@ -281,6 +1037,106 @@ async fn test_set_read_version_async() -> FdbResult<()> {
     Ok(())
 }
 
+async fn test_read_as_of_async() -> FdbResult<()> {
+    const KEY: &[u8] = b"test_read_as_of";
+    let db = common::database().await?;
+
+    let trx = db.create_trx()?;
+    trx.set(KEY, b"old");
+    let version = trx.commit().await?.committed_version()?;
+
+    let trx = db.create_trx()?;
+    trx.set(KEY, b"new");
+    trx.commit().await?;
+
+    let res = db
+        .read_as_of(version, &[KEY, b"test_read_as_of_missing"])
+        .await
+        .expect("failed to read as of version");
+    assert_eq!(res.version, version);
+    assert_eq!(res.values.len(), 2);
+    assert_eq!(res.values[0].as_deref(), Some(&b"old"[..]));
+    assert!(res.values[1].is_none());
+
+    match db.read_as_of(1, &[KEY]).await {
+        Err(AsOfError::VersionTooOld) => {}
+        res => panic!("expected VersionTooOld, got {:?}", res.err()),
+    }
+    match db.read_as_of(i64::MAX, &[KEY]).await {
+        Err(AsOfError::VersionInFuture) => {}
+        res => panic!("expected VersionInFuture, got {:?}", res.err()),
+    }
+
+    // usable as a standard error
+    let err: Box<dyn std::error::Error> = Box::new(AsOfError::VersionTooOld);
+    assert_eq!(err.to_string(), "version is too old to be read");
+    assert!(err.source().is_none());
+    let err = AsOfError::from(FdbError::from_code(1031));
+    assert_eq!(
+        std::error::Error::source(&err).map(|source| source.to_string()),
+        Some(FdbError::from_code(1031).to_string())
+    );
+
+    let oldest = db.oldest_readable_version_hint().await?;
+    let current = db.create_trx()?.get_read_version().await?;
+    assert!(oldest > 1);
+    assert!(oldest <= current);
+
+    Ok(())
+}
+
+async fn test_database_reads_async() -> FdbResult<()> {
+    let db = common::database().await?;
+
+    let trx = db.create_trx()?;
+    trx.clear_range(b"test_database_reads", b"test_database_reads\xff");
+    trx.set(b"test_database_reads/a", b"1");
+    trx.set(b"test_database_reads/b", b"2");
+    trx.commit().await?;
+
+    assert_eq!(db.get(b"test_database_reads/a").await?, Some(b"1".to_vec()));
+    assert_eq!(db.get(b"test_database_reads/c").await?, None);
+    assert_eq!(
+        db.get_range(&RangeOption::from((
+            &b"test_database_reads/"[..],
+            &b"test_database_reads0"[..]
+        )))
+        .await?,
+        vec![
+            (b"test_database_reads/a".to_vec(), b"1".to_vec()),
+            (b"test_database_reads/b".to_vec(), b"2".to_vec()),
+        ]
+    );
+    assert_eq!(
+        db.get_key(&KeySelector::first_greater_than(
+            &b"test_database_reads/a"[..]
+        ))
+        .await?,
+        b"test_database_reads/b".to_vec()
+    );
+
+    // the first attempts fail with `future_version`, which is retried
+    let mut db = db;
+    let attempts = Arc::new(AtomicUsize::new(0));
+    let injector_attempts = attempts.clone();
+    db.set_read_fault_injector(Arc::new(move || {
+        if injector_attempts.fetch_add(1, Ordering::SeqCst) < 2 {
+            Some(FdbError::from_code(1009))
+        } else {
+            None
+        }
+    }));
+    assert_eq!(db.get(b"test_database_reads/b").await?, Some(b"2".to_vec()));
+    assert_eq!(attempts.load(Ordering::SeqCst), 3);
+
+    // non retryable errors are returned
+    db.set_read_fault_injector(Arc::new(|| Some(FdbError::from_code(2000))));
+    let err = db.get(b"test_database_reads/b").await.unwrap_err();
+    assert_eq!(err.code(), 2000);
+
+    Ok(())
+}
+
 async fn test_get_addresses_for_key_async() -> FdbResult<()> {
     const KEY: &[u8] = b"test_get_addresses_for_key";
 
@@ -297,5 +1153,12 @@ async fn test_get_addresses_for_key_async() -> FdbResult<()> {
     eprintln!("{}", addr0.to_str().unwrap());
     assert!(it.next().is_none());
 
+    let parsed = addr0.parse().expect("failed to parse address");
+    assert_eq!(parsed.to_string(), addr0.to_str().unwrap());
+    assert_eq!(
+        addrs.parsed().collect::<Vec<_>>(),
+        db.storage_servers_for_key(KEY).await?
+    );
+
     Ok(())
 }
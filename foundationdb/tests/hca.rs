@@ -8,7 +8,8 @@
 use std::collections::HashSet;
 use std::iter::FromIterator;
 
-use foundationdb::tuple::{hca::HighContentionAllocator, Subspace};
+use foundationdb::tuple::hca::{HcaError, HcaStats, HighContentionAllocator};
+use foundationdb::tuple::Subspace;
 use foundationdb::{FdbResult, TransactOption};
 use futures::prelude::*;
 
@@ -20,6 +21,8 @@ fn test_hca_many_sequential_allocations() {
     futures::executor::block_on(test_hca_many_sequential_allocations_async())
         .expect("failed to run");
     futures::executor::block_on(test_hca_concurrent_allocations_async()).expect("failed to run");
+    futures::executor::block_on(test_hca_window_advance_async()).expect("failed to run");
+    futures::executor::block_on(test_hca_contention_async()).expect("failed to run");
 }
 
 async fn test_hca_many_sequential_allocations_async() -> FdbResult<()> {
@@ -84,6 +87,84 @@ async fn test_hca_concurrent_allocations_async() -> FdbResult<()> {
     Ok(())
 }
 
+async fn test_hca_window_advance_async() -> FdbResult<()> {
+    const KEY: &[u8] = b"test-hca-window-advance";
+
+    let db = common::database().await?;
+    let subspace = Subspace::from_bytes(KEY);
+    let counters = subspace.subspace(&0i64);
+    let recent = subspace.subspace(&1i64);
+
+    // the next allocation fills the first window [0, 64) up to half of its size
+    let trx = db.create_trx()?;
+    trx.clear_subspace_range(&subspace);
+    trx.set(&counters.pack(&0i64), &31i64.to_le_bytes());
+    for candidate in 0..31i64 {
+        trx.set(&recent.pack(&candidate), &[]);
+    }
+    trx.commit().await?;
+
+    let hca = HighContentionAllocator::new(subspace.clone());
+    let trx = db.create_trx()?;
+    assert_eq!(
+        hca.stats(&trx).await.unwrap(),
+        HcaStats {
+            window_start: 0,
+            window_size: 64,
+            count: 31,
+        }
+    );
+
+    let allocated = hca.allocate(&trx).await.unwrap();
+    assert!((64..128).contains(&allocated), "allocated {}", allocated);
+    trx.commit().await?;
+
+    let trx = db.create_trx()?;
+    assert_eq!(
+        hca.stats(&trx).await.unwrap(),
+        HcaStats {
+            window_start: 64,
+            window_size: 64,
+            count: 1,
+        }
+    );
+    // the previous window is forgotten
+    let previous = trx
+        .get_range(&recent.range().into(), 1, false)
+        .await?
+        .iter()
+        .map(|kv| recent.unpack::<i64>(kv.key()).unwrap())
+        .collect::<Vec<_>>();
+    assert_eq!(previous, vec![allocated]);
+
+    Ok(())
+}
+
+async fn test_hca_contention_async() -> FdbResult<()> {
+    const KEY: &[u8] = b"test-hca-contention";
+
+    let db = common::database().await?;
+    let subspace = Subspace::from_bytes(KEY);
+    let recent = subspace.subspace(&1i64);
+
+    // every candidate of the first window is taken, but the window is not advanced
+    let trx = db.create_trx()?;
+    trx.clear_subspace_range(&subspace);
+    for candidate in 0..64i64 {
+        trx.set(&recent.pack(&candidate), &[]);
+    }
+    trx.commit().await?;
+
+    let hca = HighContentionAllocator::new(subspace);
+    let trx = db.create_trx()?;
+    match hca.allocate(&trx).await {
+        Err(HcaError::Contention) => {}
+        r => panic!("expected HcaError::Contention, got {:?}", r),
+    }
+
+    Ok(())
+}
+
 fn check_hca_result_uniqueness(results: &Vec<i64>) {
     let result_set: HashSet<i64> = HashSet::from_iter(results.clone());
 
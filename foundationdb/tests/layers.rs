@@ -0,0 +1,195 @@
+// Copyright 2018 foundationdb-rs developers, https://github.com/Clikengo/foundationdb-rs/graphs/contributors
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+use foundationdb::layers::{FieldedValue, Index, LayerError};
+use foundationdb::tuple::{unpack, Subspace};
+use foundationdb::{Database, FdbError, FdbResult, Transaction};
+use futures::prelude::*;
+
+mod common;
+
+#[test]
+fn test_layers() {
+    let _guard = unsafe { foundationdb::boot() };
+    futures::executor::block_on(test_fielded_value_disjoint_fields_async()).expect("failed to run");
+    futures::executor::block_on(test_fielded_value_same_field_async()).expect("failed to run");
+    futures::executor::block_on(test_fielded_value_read_all_async()).expect("failed to run");
+    futures::executor::block_on(test_index_async()).expect("failed to run");
+}
+
+async fn setup_record(db: &Database, name: &str) -> Result<FieldedValue, LayerError> {
+    let record = FieldedValue::new(Subspace::from_bytes(name.as_bytes()));
+
+    let trx = db.create_trx()?;
+    record.clear(&trx);
+    record.write_fields(&trx, vec![("a", b"1"), ("b", b"2")]);
+    trx.commit().await.map_err(FdbError::from)?;
+
+    Ok(record)
+}
+
+async fn test_fielded_value_disjoint_fields_async() -> Result<(), LayerError> {
+    let db = common::database().await?;
+    let record = setup_record(&db, "test-fielded-value-disjoint").await?;
+
+    let trx1 = db.create_trx()?;
+    let trx2 = db.create_trx()?;
+
+    assert_eq!(
+        record.read_field(&trx1, "a", false).await?,
+        Some(b"1".to_vec())
+    );
+    assert_eq!(
+        record.read_field(&trx2, "b", false).await?,
+        Some(b"2".to_vec())
+    );
+    record.write_fields(&trx1, vec![("a", b"3")]);
+    record.write_fields(&trx2, vec![("b", b"4")]);
+
+    trx1.commit().await.map_err(FdbError::from)?;
+    trx2.commit().await.map_err(FdbError::from)?;
+
+    let trx = db.create_trx()?;
+    let all = record.read_all(&trx, false).await?;
+    assert_eq!(all.get("a"), Some(&b"3".to_vec()));
+    assert_eq!(all.get("b"), Some(&b"4".to_vec()));
+
+    Ok(())
+}
+
+async fn test_fielded_value_same_field_async() -> Result<(), LayerError> {
+    let db = common::database().await?;
+    let record = setup_record(&db, "test-fielded-value-same").await?;
+
+    let trx1 = db.create_trx()?;
+    let trx2 = db.create_trx()?;
+
+    record.read_field(&trx1, "a", false).await?;
+    record.read_field(&trx2, "a", false).await?;
+    record.write_fields(&trx1, vec![("a", b"3")]);
+    record.write_fields(&trx2, vec![("a", b"4")]);
+
+    trx1.commit().await.map_err(FdbError::from)?;
+    let err = trx2
+        .commit()
+        .await
+        .expect_err("updating the same field must conflict");
+    assert_eq!(err.code(), 1020);
+
+    Ok(())
+}
+
+async fn test_fielded_value_read_all_async() -> Result<(), LayerError> {
+    let db = common::database().await?;
+    let record = setup_record(&db, "test-fielded-value-read-all").await?;
+
+    let trx = db.create_trx()?;
+    record.write_fields(&trx, vec![("c", b"5")]);
+    record.compact(&trx, &["a", "b"]).await?;
+    trx.commit().await.map_err(FdbError::from)?;
+
+    let trx = db.create_trx()?;
+    record.write_fields(&trx, vec![("b", b"6")]);
+    trx.commit().await.map_err(FdbError::from)?;
+
+    let trx = db.create_trx()?;
+    let all = record.read_all(&trx, false).await?;
+    assert_eq!(all.len(), 3);
+    assert_eq!(all.get("a"), Some(&b"1".to_vec()));
+    assert_eq!(all.get("b"), Some(&b"6".to_vec()));
+    assert_eq!(all.get("c"), Some(&b"5".to_vec()));
+    assert_eq!(
+        record.read_field(&trx, "a", false).await?,
+        Some(b"1".to_vec())
+    );
+    assert_eq!(
+        record.read_field(&trx, "b", false).await?,
+        Some(b"6".to_vec())
+    );
+
+    Ok(())
+}
+
+async fn scan_index(
+    index: &Index,
+    trx: &Transaction,
+    value_prefix: &(&str,),
+) -> FdbResult<Vec<i64>> {
+    index
+        .scan(trx, value_prefix, false)
+        .map_ok(|pk| unpack::<(i64,)>(&pk).expect("invalid primary key").0)
+        .try_collect()
+        .await
+}
+
+async fn test_index_async() -> Result<(), LayerError> {
+    let db = common::database().await?;
+    let records = Subspace::from_bytes(b"test-index-records");
+    let index = Index::new(Subspace::from_bytes(b"test-index-by-city"));
+
+    let trx = db.create_trx()?;
+    trx.clear_subspace_range(&records);
+    index.clear(&trx);
+    trx.commit().await.map_err(FdbError::from)?;
+
+    // writes the city of the user `id`, and its index entry
+    let set_city = |trx: &Transaction, id: i64, old: Option<&str>, new: Option<&str>| {
+        match new {
+            Some(city) => trx.set(&records.pack(&id), city.as_bytes()),
+            None => trx.clear(&records.pack(&id)),
+        }
+        index.update(
+            trx,
+            &(id,),
+            old.map(|c| (c,)).as_ref(),
+            new.map(|c| (c,)).as_ref(),
+        );
+    };
+
+    // insert
+    let trx = db.create_trx()?;
+    set_city(&trx, 1, None, Some("paris"));
+    set_city(&trx, 2, None, Some("lyon"));
+    set_city(&trx, 3, None, Some("paris"));
+    trx.commit().await.map_err(FdbError::from)?;
+
+    let trx = db.create_trx()?;
+    assert_eq!(scan_index(&index, &trx, &("paris",)).await?, vec![1, 3]);
+    assert_eq!(scan_index(&index, &trx, &("lyon",)).await?, vec![2]);
+    assert_eq!(
+        scan_index(&index, &trx, &("nice",)).await?,
+        Vec::<i64>::new()
+    );
+    let all: Vec<Vec<u8>> = index.scan(&trx, &(), false).try_collect().await?;
+    assert_eq!(all.len(), 3);
+
+    // update, the entry moves to the new value
+    let trx = db.create_trx()?;
+    set_city(&trx, 1, Some("paris"), Some("lyon"));
+    // unchanged, nothing is written
+    set_city(&trx, 2, Some("lyon"), Some("lyon"));
+    trx.commit().await.map_err(FdbError::from)?;
+
+    let trx = db.create_trx()?;
+    assert_eq!(scan_index(&index, &trx, &("paris",)).await?, vec![3]);
+    assert_eq!(scan_index(&index, &trx, &("lyon",)).await?, vec![1, 2]);
+
+    // delete
+    let trx = db.create_trx()?;
+    set_city(&trx, 3, Some("paris"), None);
+    trx.commit().await.map_err(FdbError::from)?;
+
+    let trx = db.create_trx()?;
+    assert_eq!(
+        scan_index(&index, &trx, &("paris",)).await?,
+        Vec::<i64>::new()
+    );
+    assert_eq!(scan_index(&index, &trx, &("lyon",)).await?, vec![1, 2]);
+    assert!(trx.get(&records.pack(&3), false).await?.is_none());
+
+    Ok(())
+}
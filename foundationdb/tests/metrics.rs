@@ -0,0 +1,159 @@
+// Copyright 2018 foundationdb-rs developers, https://github.com/Clikengo/foundationdb-rs/graphs/contributors
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+#![cfg(feature = "metrics")]
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use foundationdb::*;
+use futures::prelude::*;
+
+mod common;
+
+#[derive(Default)]
+struct Counters {
+    gets: AtomicUsize,
+    range_rows: AtomicUsize,
+    commits: AtomicUsize,
+    failed_commits: AtomicUsize,
+    written_bytes: AtomicUsize,
+    retries: Mutex<Vec<i32>>,
+    conflicts: Mutex<Vec<(Vec<u8>, Vec<u8>)>>,
+}
+
+impl Instrumentation for Counters {
+    fn on_get(&self, _duration: Duration, _bytes: usize) {
+        self.gets.fetch_add(1, Ordering::SeqCst);
+    }
+
+    fn on_get_range(&self, _duration: Duration, rows: usize, _bytes: usize) {
+        self.range_rows.fetch_add(rows, Ordering::SeqCst);
+    }
+
+    fn on_commit(&self, _duration: Duration, result: Result<(), &FdbError>) {
+        self.commits.fetch_add(1, Ordering::SeqCst);
+        if result.is_err() {
+            self.failed_commits.fetch_add(1, Ordering::SeqCst);
+        }
+    }
+
+    fn on_retry(&self, code: i32) {
+        self.retries.lock().unwrap().push(code);
+    }
+
+    fn on_write(&self, bytes: usize) {
+        self.written_bytes.fetch_add(bytes, Ordering::SeqCst);
+    }
+
+    fn on_conflict(&self, ranges: &[(Vec<u8>, Vec<u8>)]) {
+        self.conflicts.lock().unwrap().extend_from_slice(ranges);
+    }
+}
+
+#[test]
+fn test_metrics() {
+    let _guard = unsafe { foundationdb::boot() };
+    futures::executor::block_on(test_metrics_async()).expect("failed to run");
+    #[cfg(feature = "fdb-7_1")]
+    futures::executor::block_on(test_conflict_metrics_async()).expect("failed to run");
+}
+
+async fn test_metrics_async() -> FdbResult<()> {
+    const KEY: &[u8] = b"test-metrics";
+
+    // the database making the conflicting write is not instrumented
+    let other_db = common::database().await?;
+    let trx = other_db.create_trx()?;
+    trx.clear_range(KEY, b"test-metrics\xff");
+    trx.commit().await?;
+
+    let counters = Arc::new(Counters::default());
+    let mut db = common::database().await?;
+    db.set_instrumentation(counters.clone());
+
+    let tries = AtomicUsize::new(0);
+    db.transact_boxed(
+        (&other_db, &tries),
+        |trx, (other_db, tries)| {
+            async move {
+                let _ = trx.get(KEY, false).await?;
+                if tries.fetch_add(1, Ordering::SeqCst) == 0 {
+                    // conflicts with the read of the first try
+                    let other = other_db.create_trx()?;
+                    other.set(KEY, b"conflict");
+                    other.commit().await?;
+                }
+                trx.set(KEY, b"value");
+                Ok::<_, FdbError>(())
+            }
+            .boxed()
+        },
+        TransactOption::default(),
+    )
+    .await?;
+
+    assert_eq!(tries.load(Ordering::SeqCst), 2);
+    assert_eq!(counters.commits.load(Ordering::SeqCst), 2);
+    assert_eq!(counters.failed_commits.load(Ordering::SeqCst), 1);
+    assert_eq!(*counters.retries.lock().unwrap(), vec![1020]);
+    // not reported without `TransactOption::report_conflicting_keys`
+    assert!(counters.conflicts.lock().unwrap().is_empty());
+    assert_eq!(counters.gets.load(Ordering::SeqCst), 2);
+    assert_eq!(
+        counters.written_bytes.load(Ordering::SeqCst),
+        2 * (KEY.len() + b"value".len())
+    );
+
+    let trx = db.create_trx()?;
+    let range = RangeOption::from((KEY, b"test-metrics\xff".as_ref()));
+    let kvs = trx.get_range(&range, 1, false).await?;
+    assert_eq!(kvs.len(), 1);
+    assert_eq!(counters.range_rows.load(Ordering::SeqCst), 1);
+
+    Ok(())
+}
+
+#[cfg(feature = "fdb-7_1")]
+async fn test_conflict_metrics_async() -> FdbResult<()> {
+    const KEY: &[u8] = b"test-conflict-metrics";
+
+    let other_db = common::database().await?;
+    let counters = Arc::new(Counters::default());
+    let mut db = common::database().await?;
+    db.set_instrumentation(counters.clone());
+
+    let tries = AtomicUsize::new(0);
+    db.transact_boxed(
+        (&other_db, &tries),
+        |trx, (other_db, tries)| {
+            async move {
+                let _ = trx.get(KEY, false).await?;
+                if tries.fetch_add(1, Ordering::SeqCst) == 0 {
+                    let other = other_db.create_trx()?;
+                    other.set(KEY, b"conflict");
+                    other.commit().await?;
+                }
+                trx.set(KEY, b"value");
+                Ok::<_, FdbError>(())
+            }
+            .boxed()
+        },
+        TransactOption::report_conflicting_keys(true),
+    )
+    .await?;
+
+    assert_eq!(tries.load(Ordering::SeqCst), 2);
+    assert_eq!(*counters.retries.lock().unwrap(), vec![1020]);
+    let conflicts = counters.conflicts.lock().unwrap();
+    assert!(conflicts
+        .iter()
+        .any(|(begin, end)| begin.as_slice() <= KEY && KEY < end.as_slice()));
+
+    Ok(())
+}
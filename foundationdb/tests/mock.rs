@@ -0,0 +1,157 @@
+// Copyright 2018 foundationdb-rs developers, https://github.com/Clikengo/foundationdb-rs/graphs/contributors
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+#![cfg(feature = "mock")]
+
+use std::convert::TryInto;
+
+use foundationdb::options::MutationType;
+use foundationdb::testing::MockDatabase;
+use foundationdb::tuple::Subspace;
+use foundationdb::*;
+use futures::prelude::*;
+
+mod common;
+
+/// A small layer keeping account balances and a counter of transfers, written against the
+/// `FdbRead` and `FdbWrite` traits.
+struct Accounts {
+    subspace: Subspace,
+}
+
+impl Accounts {
+    fn balances(&self) -> Subspace {
+        self.subspace.subspace(&"balance")
+    }
+
+    fn transfers_key(&self) -> Vec<u8> {
+        self.subspace.pack(&"transfers")
+    }
+
+    async fn balance<T: FdbRead>(&self, trx: &T, account: &str) -> FdbResult<i64> {
+        let value = trx.get(&self.balances().pack(&account), false).await?;
+        Ok(value
+            .map(|value| i64::from_le_bytes(value[..].try_into().expect("8 bytes balance")))
+            .unwrap_or(0))
+    }
+
+    async fn transfer<T: FdbRead + FdbWrite>(
+        &self,
+        trx: &T,
+        from: &str,
+        to: &str,
+        amount: i64,
+    ) -> FdbResult<bool> {
+        let from_balance = self.balance(trx, from).await?;
+        if from_balance < amount {
+            return Ok(false);
+        }
+        let to_balance = self.balance(trx, to).await?;
+        trx.set(
+            &self.balances().pack(&from),
+            &(from_balance - amount).to_le_bytes(),
+        );
+        trx.set(
+            &self.balances().pack(&to),
+            &(to_balance + amount).to_le_bytes(),
+        );
+        trx.atomic_op(
+            &self.transfers_key(),
+            &1i64.to_le_bytes(),
+            MutationType::Add,
+        );
+        Ok(true)
+    }
+
+    async fn accounts<T: FdbRead>(&self, trx: &T) -> FdbResult<Vec<(String, i64)>> {
+        let balances = self.balances();
+        let kvs = trx.read_range(&balances.range().into(), false).await?;
+        Ok(kvs
+            .into_iter()
+            .filter_map(|(key, value)| {
+                let account: String = balances.unpack(&key).ok()?;
+                let balance = i64::from_le_bytes(value[..].try_into().ok()?);
+                Some((account, balance))
+            })
+            .collect())
+    }
+
+    fn clear<T: FdbWrite>(&self, trx: &T) {
+        let (begin, end) = self.subspace.range();
+        trx.clear_range(&begin, &end);
+    }
+}
+
+/// Runs the same scenario on `create_trx`, committing each transaction with `commit`.
+macro_rules! accounts_scenario {
+    ($create_trx:expr, $commit:expr) => {{
+        let accounts = Accounts {
+            subspace: Subspace::all().subspace(&"test-mock-accounts"),
+        };
+
+        let trx = $create_trx;
+        accounts.clear(&trx);
+        trx.set(&accounts.balances().pack(&"alice"), &100i64.to_le_bytes());
+        $commit(trx).await?;
+
+        let trx = $create_trx;
+        assert!(accounts.transfer(&trx, "alice", "bob", 30).await?);
+        assert!(!accounts.transfer(&trx, "bob", "carol", 31).await?);
+        assert!(accounts.transfer(&trx, "bob", "carol", 10).await?);
+        $commit(trx).await?;
+
+        let trx = $create_trx;
+        assert_eq!(
+            accounts.accounts(&trx).await?,
+            vec![
+                ("alice".to_owned(), 70),
+                ("bob".to_owned(), 20),
+                ("carol".to_owned(), 10),
+            ]
+        );
+        let transfers = FdbRead::get(&trx, &accounts.transfers_key(), false).await?;
+        assert_eq!(transfers, Some(2i64.to_le_bytes().to_vec()));
+
+        // two transfers from the same account conflict
+        let t1 = $create_trx;
+        let t2 = $create_trx;
+        assert!(accounts.transfer(&t1, "alice", "bob", 50).await?);
+        assert!(accounts.transfer(&t2, "alice", "carol", 50).await?);
+        $commit(t1).await?;
+        match $commit(t2).await {
+            Err(err) => assert_eq!(err.code(), 1020),
+            Ok(..) => panic!("the second transfer should conflict"),
+        }
+
+        let trx = $create_trx;
+        assert_eq!(accounts.balance(&trx, "alice").await?, 20);
+        accounts.clear(&trx);
+        $commit(trx).await?;
+    }};
+}
+
+#[test]
+fn test_mock() {
+    futures::executor::block_on(test_accounts_mock()).expect("failed to run");
+    let _guard = unsafe { foundationdb::boot() };
+    futures::executor::block_on(test_accounts_database()).expect("failed to run");
+}
+
+async fn test_accounts_mock() -> FdbResult<()> {
+    let db = MockDatabase::new();
+    accounts_scenario!(db.create_trx()?, |trx: testing::MockTransaction| trx
+        .commit());
+    Ok(())
+}
+
+async fn test_accounts_database() -> FdbResult<()> {
+    let db = common::database().await?;
+    accounts_scenario!(db.create_trx()?, |trx: Transaction| trx
+        .commit()
+        .map_err(FdbError::from));
+    Ok(())
+}
@@ -0,0 +1,128 @@
+// Copyright 2018 foundationdb-rs developers, https://github.com/Clikengo/foundationdb-rs/graphs/contributors
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+#![cfg(feature = "queue")]
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use foundationdb::layers::Queue;
+use foundationdb::tuple::Subspace;
+use foundationdb::{Database, FdbError, FdbResult, TransactOption};
+use futures::prelude::*;
+
+mod common;
+
+const PRODUCERS: usize = 20;
+const CONSUMERS: usize = 5;
+const ITEMS_PER_PRODUCER: usize = 10;
+
+#[test]
+fn test_queue() {
+    let _guard = unsafe { foundationdb::boot() };
+    futures::executor::block_on(test_queue_order_async()).expect("failed to run");
+    futures::executor::block_on(test_queue_concurrent_async()).expect("failed to run");
+}
+
+async fn setup_queue(db: &Database, name: &str) -> FdbResult<Queue> {
+    let queue = Queue::new(Subspace::from_bytes(name.as_bytes()));
+    let trx = db.create_trx()?;
+    queue.clear(&trx);
+    trx.commit().await?;
+    Ok(queue)
+}
+
+async fn test_queue_order_async() -> FdbResult<()> {
+    let db = common::database().await?;
+    let queue = setup_queue(&db, "test-queue-order").await?;
+
+    let trx = db.create_trx()?;
+    queue.push(&trx, b"a");
+    queue.push(&trx, b"b");
+    trx.commit().await?;
+    let trx = db.create_trx()?;
+    queue.push(&trx, b"c");
+    trx.commit().await?;
+
+    let trx = db.create_trx()?;
+    assert_eq!(queue.len(&trx, false).await?, 3);
+    assert_eq!(queue.peek(&trx, false).await?, Some(b"a".to_vec()));
+    assert!(!queue.is_empty(&trx, false).await?);
+    drop(trx);
+
+    assert_eq!(queue.pop(&db).await?, Some(b"a".to_vec()));
+    assert_eq!(queue.pop(&db).await?, Some(b"b".to_vec()));
+    assert_eq!(queue.pop(&db).await?, Some(b"c".to_vec()));
+    assert_eq!(queue.pop(&db).await?, None);
+
+    let trx = db.create_trx()?;
+    assert_eq!(queue.len(&trx, false).await?, 0);
+    assert!(queue.is_empty(&trx, false).await?);
+
+    Ok(())
+}
+
+async fn test_queue_concurrent_async() -> FdbResult<()> {
+    let db = common::database().await?;
+    let queue = setup_queue(&db, "test-queue-concurrent").await?;
+    let total = PRODUCERS * ITEMS_PER_PRODUCER;
+    let popped = AtomicUsize::new(0);
+
+    let producers = (0..PRODUCERS).map(|producer| {
+        let (db, queue) = (&db, &queue);
+        async move {
+            for item in 0..ITEMS_PER_PRODUCER {
+                let value = format!("{}-{}", producer, item).into_bytes();
+                db.transact_boxed(
+                    (queue, value),
+                    |trx, (queue, value)| {
+                        queue.push(trx, value);
+                        future::ok::<_, FdbError>(()).boxed()
+                    },
+                    TransactOption::default(),
+                )
+                .await?;
+            }
+            Ok::<_, FdbError>(())
+        }
+    });
+    let consumers = (0..CONSUMERS).map(|_| {
+        let (db, queue, popped) = (&db, &queue, &popped);
+        async move {
+            let mut items = Vec::new();
+            while popped.load(Ordering::SeqCst) < total {
+                if let Some(value) = queue.pop(db).await? {
+                    popped.fetch_add(1, Ordering::SeqCst);
+                    items.push(value);
+                }
+            }
+            Ok::<_, FdbError>(items)
+        }
+    });
+
+    let (produced, consumed) = future::join(
+        future::try_join_all(producers),
+        future::try_join_all(consumers),
+    )
+    .await;
+    produced?;
+
+    // every pushed item is popped exactly once
+    let mut items: Vec<Vec<u8>> = consumed?.into_iter().flatten().collect();
+    items.sort();
+    let mut expected: Vec<Vec<u8>> = (0..PRODUCERS)
+        .flat_map(|producer| {
+            (0..ITEMS_PER_PRODUCER).map(move |item| format!("{}-{}", producer, item).into_bytes())
+        })
+        .collect();
+    expected.sort();
+    assert_eq!(items, expected);
+
+    let trx = db.create_trx()?;
+    assert!(queue.is_empty(&trx, false).await?);
+
+    Ok(())
+}
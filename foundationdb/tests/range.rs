@@ -5,10 +5,13 @@
 // http://opensource.org/licenses/MIT>, at your option. This file may not be
 // copied, modified, or distributed except according to those terms.
 
+use foundationdb::future::KeyValueBuffer;
+use foundationdb::tuple::{decode_subspace_stream, pack, PackError, Subspace};
 use foundationdb::*;
 use futures::future;
 use futures::prelude::*;
 use std::borrow::Cow;
+use std::time::{Duration, Instant};
 
 mod common;
 
@@ -18,6 +21,17 @@ fn test_range() {
     futures::executor::block_on(test_get_range_async()).expect("failed to run");
     futures::executor::block_on(test_range_option_async()).expect("failed to run");
     futures::executor::block_on(test_get_ranges_async()).expect("failed to run");
+    futures::executor::block_on(test_get_ranges_limit_async()).expect("failed to run");
+    futures::executor::block_on(test_get_ranges_empty_async()).expect("failed to run");
+    futures::executor::block_on(test_get_ranges_cancel_async()).expect("failed to run");
+    futures::executor::block_on(test_clear_range_chunked_async()).expect("failed to run");
+    futures::executor::block_on(test_get_range_into_async()).expect("failed to run");
+    futures::executor::block_on(test_get_ranges_with_deadline_async()).expect("failed to run");
+    futures::executor::block_on(test_scan_all_async()).expect("failed to run");
+    futures::executor::block_on(test_decode_subspace_stream_async()).expect("failed to run");
+    futures::executor::block_on(test_clear_prefix_async()).expect("failed to run");
+    #[cfg(feature = "fdb-7_1")]
+    futures::executor::block_on(test_get_range_split_points_async()).expect("failed to run");
 }
 
 async fn test_get_range_async() -> FdbResult<()> {
@@ -68,6 +82,28 @@ async fn test_get_range_async() -> FdbResult<()> {
             .collect::<Vec<_>>();
         let owned_desc = range.into_iter().rev().collect::<Vec<_>>();
         assert_eq!(owned_asc, owned_desc.into_iter().rev().collect::<Vec<_>>());
+
+        // `into_bytes_vec` gives the same pairs as copying them, and outlives the transaction
+        let copied = owned_asc
+            .iter()
+            .map(|kv| (kv.key().to_vec(), kv.value().to_vec()))
+            .collect::<Vec<_>>();
+        let shared = trx.get_range(&opt, 1, false).await?.into_bytes_vec();
+        let (key, value) = &copied[0];
+        let bytes = trx
+            .get(key, false)
+            .await?
+            .expect("missing value")
+            .into_bytes();
+        drop(trx);
+        assert_eq!(&bytes[..], &value[..]);
+        assert_eq!(
+            copied,
+            shared
+                .iter()
+                .map(|(key, value)| (key.to_vec(), value.to_vec()))
+                .collect::<Vec<_>>()
+        );
     }
 
     Ok(())
@@ -203,3 +239,570 @@ async fn test_range_option_async() -> FdbResult<()> {
 
     Ok(())
 }
+
+async fn test_get_ranges_limit_async() -> FdbResult<()> {
+    use foundationdb::options::StreamingMode;
+
+    const N: usize = 1000;
+    let key_begin = "test-ranges-limit-";
+    let key_end = "test-ranges-limit.";
+    let k = |i: usize| format!("{}-{:010}", key_begin, i).into_bytes();
+
+    let db = common::database().await?;
+
+    let trx = db.create_trx()?;
+    trx.clear_range(key_begin.as_bytes(), key_end.as_bytes());
+    for i in 0..N {
+        trx.set(&k(i), common::random_str(10).as_bytes());
+    }
+    trx.commit().await?;
+
+    let trx = db.create_trx()?;
+    for &mode in &[
+        StreamingMode::Iterator,
+        StreamingMode::Small,
+        StreamingMode::Exact,
+    ] {
+        for &reverse in &[false, true] {
+            let opt = RangeOption {
+                mode,
+                reverse,
+                limit: Some(N),
+                ..RangeOption::from((key_begin.as_bytes(), key_end.as_bytes()))
+            };
+            // the size of the first batch, Exact returns everything in a single batch
+            let batch_size = match mode {
+                StreamingMode::Exact => trx.get_range(&opt, 1, false).await?.len(),
+                _ => trx
+                    .get_range(
+                        &RangeOption {
+                            limit: None,
+                            ..opt.clone()
+                        },
+                        1,
+                        false,
+                    )
+                    .await?
+                    .len(),
+            };
+            assert!(batch_size > 0);
+
+            let mut limits = vec![1, batch_size, batch_size + 1, N - 1, N, N + 1, 2 * N];
+            if batch_size > 1 {
+                limits.push(batch_size - 1);
+            }
+            for &limit in limits.iter() {
+                let opt = RangeOption {
+                    limit: Some(limit),
+                    ..opt.clone()
+                };
+
+                let mut keys = Vec::new();
+                let mut batches = trx.get_ranges(opt, false);
+                while let Some(kvs) = batches.try_next().await? {
+                    assert!(
+                        !kvs.is_empty(),
+                        "empty batch for mode {:?}, reverse {}, limit {}",
+                        mode,
+                        reverse,
+                        limit
+                    );
+                    keys.extend(kvs.iter().map(|kv| kv.key().to_vec()));
+                }
+
+                let mut expected: Vec<Vec<u8>> = (0..N).map(k).collect();
+                if reverse {
+                    expected.reverse();
+                }
+                expected.truncate(limit);
+                assert!(
+                    keys == expected,
+                    "mode {:?}, reverse {}, limit {}: got {} keys, expected {}",
+                    mode,
+                    reverse,
+                    limit,
+                    keys.len(),
+                    expected.len()
+                );
+            }
+        }
+    }
+
+    // without limit
+    for &reverse in &[false, true] {
+        let opt = RangeOption {
+            reverse,
+            ..RangeOption::from((key_begin.as_bytes(), key_end.as_bytes()))
+        };
+        let keys: Vec<Vec<u8>> = trx
+            .get_ranges_keyvalues(opt, false)
+            .map_ok(|kv| kv.key().to_vec())
+            .try_collect()
+            .await?;
+        let mut expected: Vec<Vec<u8>> = (0..N).map(k).collect();
+        if reverse {
+            expected.reverse();
+        }
+        assert!(
+            keys == expected,
+            "reverse {}: got {} keys",
+            reverse,
+            keys.len()
+        );
+    }
+
+    Ok(())
+}
+
+async fn test_get_ranges_empty_async() -> FdbResult<()> {
+    let key_begin = "test-ranges-empty-";
+    let key_end = "test-ranges-empty.";
+    let k = |i: usize| format!("{}-{:04}", key_begin, i).into_bytes();
+
+    let db = common::database().await?;
+
+    let trx = db.create_trx()?;
+    trx.clear_range(key_begin.as_bytes(), key_end.as_bytes());
+    for i in 0..100 {
+        trx.set(&k(i), b"");
+    }
+    trx.commit().await?;
+
+    let (low, high) = (k(10), k(90));
+    assert!(RangeOption::from((low.as_slice(), low.as_slice())).is_trivially_empty());
+    assert!(RangeOption::from((high.as_slice(), low.as_slice())).is_trivially_empty());
+    assert!(RangeOption::from((
+        KeySelector::first_greater_than(low.as_slice()),
+        KeySelector::first_greater_or_equal(low.as_slice()),
+    ))
+    .is_trivially_empty());
+    assert!(!RangeOption::from((low.as_slice(), high.as_slice())).is_trivially_empty());
+    assert!(!RangeOption::from((
+        KeySelector::last_less_or_equal(high.as_slice()),
+        KeySelector::first_greater_or_equal(low.as_slice()),
+    ))
+    .is_trivially_empty());
+
+    let trx = db.create_trx()?;
+    for &reverse in &[false, true] {
+        // each batch of the stream is a request to the database
+        let count_requests = |opt: RangeOption<'static>| {
+            let trx = &trx;
+            async move {
+                let mut requests = 0;
+                let mut batches = trx.get_ranges(opt, false);
+                while let Some(kvs) = batches.try_next().await? {
+                    requests += 1;
+                    assert!(kvs.is_empty(), "read keys from an inverted range");
+                }
+                Ok::<_, FdbError>(requests)
+            }
+        };
+
+        // trivially empty, no request at all
+        let opt = RangeOption {
+            reverse,
+            ..RangeOption::from((k(90), k(10)))
+        };
+        assert_eq!(count_requests(opt).await?, 0);
+
+        // inverted selectors that must be resolved by the database
+        let opt = RangeOption {
+            reverse,
+            ..RangeOption::from((
+                KeySelector::last_less_or_equal(k(90)),
+                KeySelector::first_greater_or_equal(k(10)),
+            ))
+        };
+        assert_eq!(count_requests(opt).await?, 1);
+    }
+
+    Ok(())
+}
+
+async fn test_get_ranges_cancel_async() -> FdbResult<()> {
+    use foundationdb::future::live_futures;
+
+    const N: usize = 1000;
+    let db = common::database().await?;
+    let key = |i: usize| format!("test-ranges-cancel-{:04}", i).into_bytes();
+
+    let trx = db.create_trx()?;
+    trx.clear_range(&key(0), &key(N));
+    for i in 0..N {
+        trx.set(&key(i), common::random_str(100).as_bytes());
+    }
+    trx.commit().await?;
+
+    let trx = db.create_trx()?;
+    let opt = RangeOption {
+        mode: options::StreamingMode::Small,
+        ..RangeOption::from((key(0), key(N)))
+    };
+    let baseline = live_futures();
+
+    // drop a partially consumed stream while the next batch is pending
+    {
+        let mut batches = trx.get_ranges(opt.clone(), false);
+        let first = batches.try_next().await?.expect("a first batch");
+        assert!(first.len() < N);
+        assert_eq!(live_futures(), baseline + 1);
+        drop(first);
+        if futures::poll!(batches.next()).is_pending() {
+            assert_eq!(live_futures(), baseline + 1);
+        }
+    }
+    assert_eq!(live_futures(), baseline);
+
+    // short-circuit the stream after the first batch
+    let mut count = 0;
+    let batches: Vec<_> = trx
+        .get_ranges(opt, false)
+        .try_take_while(|_| {
+            count += 1;
+            future::ok(count == 1)
+        })
+        .try_collect()
+        .await?;
+    assert_eq!(batches.len(), 1);
+    assert_eq!(live_futures(), baseline + 1);
+    drop(batches);
+    assert_eq!(live_futures(), baseline);
+
+    Ok(())
+}
+
+async fn test_clear_range_chunked_async() -> FdbResult<()> {
+    const N: usize = 50_000;
+    const CHUNK: usize = 1_000;
+
+    let db = common::database().await?;
+    let begin = b"test-clear-chunked-".to_vec();
+    let end = b"test-clear-chunked.".to_vec();
+    let key = |i: usize| format!("test-clear-chunked-{:05}", i).into_bytes();
+
+    let trx = db.create_trx()?;
+    trx.clear_range(&begin, &end);
+    trx.commit().await?;
+    for batch in 0..N / 5_000 {
+        let trx = db.create_trx()?;
+        for i in batch * 5_000..(batch + 1) * 5_000 {
+            trx.set(&key(i), b"value");
+        }
+        trx.commit().await?;
+    }
+
+    // stop after a few chunks, and resume from the continuation
+    let first: Vec<ClearProgress> = db
+        .clear_range_chunked(&begin, &end, CHUNK, Some(Duration::from_millis(1)))
+        .take(3)
+        .try_collect()
+        .await?;
+    assert_eq!(first.len(), 3);
+    assert_eq!(first[2].total, 3 * CHUNK);
+    let last = first.last().unwrap();
+    assert_eq!(last.last_key, key(3 * CHUNK - 1));
+
+    let rest: Vec<ClearProgress> = db
+        .clear_range_chunked(&last.continuation, &end, CHUNK, None)
+        .try_collect()
+        .await?;
+    let total = first.iter().chain(&rest).map(|p| p.cleared).sum::<usize>();
+    assert_eq!(total, N);
+    assert_eq!(rest.last().unwrap().total, N - 3 * CHUNK);
+    assert!(rest.iter().all(|p| p.cleared > 0 && p.cleared <= CHUNK));
+    assert_eq!(rest.last().unwrap().last_key, key(N - 1));
+
+    let trx = db.create_trx()?;
+    let remaining = trx
+        .get_range(
+            &RangeOption::from((begin.as_slice(), end.as_slice())),
+            1,
+            false,
+        )
+        .await?;
+    assert!(remaining.is_empty());
+
+    // an empty range ends the stream at once
+    let progress: Vec<ClearProgress> = db
+        .clear_range_chunked(&begin, &end, CHUNK, None)
+        .try_collect()
+        .await?;
+    assert!(progress.is_empty());
+
+    Ok(())
+}
+
+async fn test_get_range_into_async() -> FdbResult<()> {
+    const N: usize = 10_000;
+
+    let db = common::database().await?;
+    let begin = b"test-range-into-".to_vec();
+    let end = b"test-range-into.".to_vec();
+    let key = |i: usize| format!("test-range-into-{:05}", i).into_bytes();
+
+    let trx = db.create_trx()?;
+    trx.clear_range(&begin, &end);
+    for i in 0..N {
+        trx.set(&key(i), format!("value{}", i).as_bytes());
+    }
+    trx.commit().await?;
+
+    let trx = db.create_trx()?;
+    let opt = || RangeOption {
+        mode: options::StreamingMode::Small,
+        ..RangeOption::from((begin.clone(), end.clone()))
+    };
+    let mut buffer = KeyValueBuffer::new();
+    trx.get_range_into(opt(), false, &mut buffer).await?;
+    assert_eq!(buffer.len(), N);
+    for (i, (k, v)) in buffer.iter().enumerate() {
+        assert_eq!(k, key(i).as_slice());
+        assert_eq!(v, format!("value{}", i).as_bytes());
+    }
+
+    let expected: Vec<(Vec<u8>, Vec<u8>)> = trx
+        .get_ranges_keyvalues(opt(), false)
+        .map_ok(|kv| (kv.key().to_vec(), kv.value().to_vec()))
+        .try_collect()
+        .await?;
+    let copied: Vec<(Vec<u8>, Vec<u8>)> = buffer
+        .iter()
+        .map(|(k, v)| (k.to_vec(), v.to_vec()))
+        .collect();
+    assert_eq!(copied, expected);
+
+    // the buffer is appended to, until cleared
+    let limited = RangeOption {
+        limit: Some(10),
+        ..opt()
+    };
+    trx.get_range_into(limited.clone(), false, &mut buffer)
+        .await?;
+    assert_eq!(buffer.len(), N + 10);
+    assert_eq!(buffer.last().unwrap().0, key(9).as_slice());
+    buffer.clear();
+    trx.get_range_into(limited.rev(), false, &mut buffer)
+        .await?;
+    assert_eq!(buffer.len(), 10);
+    assert_eq!(buffer.get(0).unwrap().0, key(N - 1).as_slice());
+
+    Ok(())
+}
+
+#[cfg(feature = "fdb-7_1")]
+async fn test_get_range_split_points_async() -> FdbResult<()> {
+    let db = common::database().await?;
+    let begin = b"test-split-points-".to_vec();
+    let end = b"test-split-points.".to_vec();
+
+    let trx = db.create_trx()?;
+    trx.clear_range(&begin, &end);
+    for i in 0..1_000 {
+        let key = format!("test-split-points-{:04}", i);
+        trx.set(key.as_bytes(), &[0; 1_000]);
+    }
+    trx.commit().await?;
+
+    let trx = db.create_trx()?;
+    let points = trx.get_range_split_points(&begin, &end, 10_000).await?;
+    // the range bounds are always included, the keys in between depend on the sampled data
+    assert!(points.len() >= 2, "{:?}", points);
+    assert_eq!(points[0].key(), begin.as_slice());
+    assert_eq!(points[points.len() - 1].key(), end.as_slice());
+    assert!(points.windows(2).all(|w| w[0].key() < w[1].key()));
+
+    Ok(())
+}
+
+async fn test_get_ranges_with_deadline_async() -> FdbResult<()> {
+    const N: usize = 2_000;
+
+    let db = common::database().await?;
+    let begin = b"test-range-deadline-".to_vec();
+    let end = b"test-range-deadline.".to_vec();
+    let key = |i: usize| format!("test-range-deadline-{:05}", i).into_bytes();
+
+    let trx = db.create_trx()?;
+    trx.clear_range(&begin, &end);
+    for i in 0..N {
+        trx.set(&key(i), b"value");
+    }
+    trx.commit().await?;
+
+    let opt = RangeOption {
+        mode: options::StreamingMode::Small,
+        ..RangeOption::from((begin.clone(), end.clone()))
+    };
+
+    // a deadline far away reads the whole range
+    let trx = db.create_trx()?;
+    let far = Instant::now() + Duration::from_secs(60);
+    let items: Vec<ScanBatch> = trx
+        .get_ranges_with_deadline(opt.clone(), false, far)
+        .try_collect()
+        .await?;
+    let mut count = 0;
+    for item in &items {
+        match item {
+            ScanBatch::Values(values) => count += values.len(),
+            ScanBatch::Partial(..) => panic!("unexpected partial scan"),
+        }
+    }
+    assert_eq!(count, N);
+
+    // a deadline already passed reads the first batch, then yields the rest of the range
+    let trx = db.create_trx()?;
+    let items: Vec<ScanBatch> = trx
+        .get_ranges_with_deadline(opt.clone(), false, Instant::now())
+        .try_collect()
+        .await?;
+    assert_eq!(items.len(), 2);
+    let first = match &items[0] {
+        ScanBatch::Values(values) => values,
+        ScanBatch::Partial(..) => panic!("the first batch is always read"),
+    };
+    assert!(!first.is_empty() && first.len() < N);
+    let rest = match &items[1] {
+        ScanBatch::Partial(partial) => partial.continued_from.clone().into_owned(),
+        ScanBatch::Values(..) => panic!("expected a partial scan"),
+    };
+
+    let trx = db.create_trx()?;
+    let keys: Vec<Vec<u8>> = trx
+        .get_ranges_keyvalues(rest, false)
+        .map_ok(|kv| kv.key().to_vec())
+        .try_collect()
+        .await?;
+    assert_eq!(first.len() + keys.len(), N);
+    assert_eq!(keys[0], key(first.len()));
+    assert_eq!(keys[keys.len() - 1], key(N - 1));
+
+    Ok(())
+}
+
+async fn test_scan_all_async() -> FdbResult<()> {
+    const N: usize = 2_000;
+
+    let db = common::database().await?;
+    let begin = b"test-scan-all-".to_vec();
+    let end = b"test-scan-all.".to_vec();
+    let key = |i: usize| format!("test-scan-all-{:05}", i).into_bytes();
+
+    let trx = db.create_trx()?;
+    trx.clear_range(&begin, &end);
+    for i in 0..N {
+        trx.set(&key(i), b"value");
+    }
+    trx.commit().await?;
+
+    let opt = RangeOption {
+        mode: options::StreamingMode::Small,
+        ..RangeOption::from((begin.clone(), end.clone()))
+    };
+
+    // without time, each transaction reads a single batch
+    let mut keys = Vec::new();
+    let transactions = db
+        .scan_all_within(opt.clone(), Duration::from_secs(0), |kv| {
+            keys.push(kv.key().to_vec())
+        })
+        .await?;
+    assert!(transactions >= 2, "{} transactions", transactions);
+    let expected: Vec<Vec<u8>> = (0..N).map(key).collect();
+    assert_eq!(keys, expected);
+
+    // reverse and limited ranges are resumed too
+    let mut keys = Vec::new();
+    let limited = RangeOption {
+        limit: Some(N / 2),
+        ..opt.clone().rev()
+    };
+    db.scan_all_within(limited, Duration::from_secs(0), |kv| {
+        keys.push(kv.key().to_vec())
+    })
+    .await?;
+    let expected: Vec<Vec<u8>> = (N / 2..N).rev().map(key).collect();
+    assert_eq!(keys, expected);
+
+    let mut count = 0;
+    assert_eq!(db.scan_all(opt, |_| count += 1).await?, 1);
+    assert_eq!(count, N);
+
+    Ok(())
+}
+
+async fn test_decode_subspace_stream_async() -> Result<(), FdbBindingError> {
+    let db = common::database().await?;
+    let root = Subspace::from("test-decode-subspace-stream");
+    let scores = root.subspace(&"scores");
+    let foreign = root.pack(&("teams", "red"));
+
+    let trx = db.create_trx()?;
+    trx.clear_subspace_range(&root);
+    let expected: Vec<((String, i64), (u64,))> = (0..10)
+        .map(|i| ((format!("player-{}", i), i - 5), ((i * 100) as u64,)))
+        .collect();
+    for (key, value) in &expected {
+        trx.set(&scores.pack(key), &pack(value));
+    }
+    trx.set(&foreign, &pack(&(0u64,)));
+    trx.commit().await?;
+
+    let trx = db.create_trx()?;
+    let kvs = trx.get_ranges_keyvalues(scores.range_option(), false);
+    let decoded: Vec<((String, i64), (u64,))> =
+        decode_subspace_stream(&scores, kvs).try_collect().await?;
+    assert_eq!(decoded, expected);
+
+    // the range of root holds the scores, then the foreign key
+    let kvs = trx.get_ranges_keyvalues(root.range_option(), false);
+    let mut decoded = decode_subspace_stream::<_, (String, i64), (u64,)>(&scores, kvs);
+    let mut count = 0;
+    let err = loop {
+        match decoded.next().await {
+            Some(Ok(..)) => count += 1,
+            Some(Err(err)) => break err,
+            None => panic!("the foreign key must be reported"),
+        }
+    };
+    assert_eq!(count, expected.len());
+    match err {
+        FdbBindingError::PackError(PackError::KeyOutsideSubspace { key }) => {
+            assert_eq!(key, foreign)
+        }
+        err => panic!("expected KeyOutsideSubspace, got {:?}", err),
+    }
+
+    Ok(())
+}
+
+async fn test_clear_prefix_async() -> FdbResult<()> {
+    const PREFIX: &[u8] = b"test-clear-prefix";
+    let db = common::database().await?;
+
+    let trx = db.create_trx()?;
+    trx.set(PREFIX, b"prefix");
+    trx.set(b"test-clear-prefix\x00", b"inner");
+    trx.set(b"test-clear-prefix\xff\xff", b"inner");
+    trx.set(b"test-clear-prefiy", b"after");
+    trx.commit().await?;
+
+    let trx = db.create_trx()?;
+    trx.clear_prefix(PREFIX)?;
+    trx.commit().await?;
+
+    let trx = db.create_trx()?;
+    let opt = RangeOption::from((&PREFIX[..], &b"test-clear-prefiz"[..]));
+    let range = trx.get_range(&opt, 1, false).await?;
+    let keys: Vec<&[u8]> = range.iter().map(|kv| kv.key()).collect();
+    assert_eq!(keys, vec![&b"test-clear-prefiy"[..]]);
+
+    // the special keyspace has no range to clear
+    let err = trx
+        .clear_prefix(b"\xff\xff/")
+        .expect_err("the special keyspace can't be cleared");
+    assert_eq!(err.code(), 2004);
+
+    Ok(())
+}
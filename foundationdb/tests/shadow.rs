@@ -0,0 +1,137 @@
+// Copyright 2018 foundationdb-rs developers, https://github.com/Clikengo/foundationdb-rs/graphs/contributors
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+use std::sync::Arc;
+
+use foundationdb::shadow::{MismatchKind, ShadowOptions, ShadowReader};
+use foundationdb::tuple::Subspace;
+use foundationdb::FdbResult;
+
+mod common;
+
+const KEYS: usize = 100;
+const READS: usize = 1_000;
+
+#[test]
+fn test_shadow() {
+    let _guard = unsafe { foundationdb::boot() };
+    futures::executor::block_on(test_shadow_divergent_async()).expect("failed to run");
+    futures::executor::block_on(test_shadow_identical_async()).expect("failed to run");
+}
+
+async fn setup(name: &str) -> FdbResult<(Arc<foundationdb::Database>, Subspace)> {
+    let db = common::database().await?;
+    let subspace = Subspace::from(name);
+
+    let trx = db.create_trx()?;
+    trx.clear_subspace_range(&subspace);
+    for i in 0..KEYS {
+        // even keys are only known by the primary
+        if i % 2 == 1 {
+            trx.set(&subspace.pack(&(i as i64)), &i.to_le_bytes());
+        }
+    }
+    trx.commit().await?;
+
+    Ok((Arc::new(db), subspace))
+}
+
+async fn test_shadow_divergent_async() -> FdbResult<()> {
+    let (db, subspace) = setup("test-shadow-divergent").await?;
+
+    let secondary_subspace = subspace.clone();
+    let reader = ShadowReader::new(
+        db,
+        move |key: Vec<u8>| {
+            let i: i64 = secondary_subspace.unpack(&key).expect("bad key");
+            // the secondary knows even keys, and has different values for odd keys
+            let value = if i % 2 == 0 {
+                Some(vec![0])
+            } else {
+                Some((i as usize + 1).to_le_bytes().to_vec())
+            };
+            futures::future::ready(value)
+        },
+        ShadowOptions {
+            sample_rate: 0.5,
+            queue_size: READS,
+            max_mismatches: 10,
+            ..ShadowOptions::default()
+        },
+    );
+
+    for i in 0..READS {
+        let key = subspace.pack(&((i % KEYS) as i64));
+        let value = reader.get(&key, false).await?;
+        assert_eq!(value.is_some(), i % 2 == 1);
+    }
+
+    let report = reader.finish();
+    assert_eq!(report.reads, READS as u64);
+    assert_eq!(report.skipped, 0);
+    assert_eq!(report.compared, report.sampled);
+    // every read diverges, so every comparison is a mismatch
+    assert_eq!(report.mismatch_count, report.compared);
+    assert!(
+        report.sampled > 400 && report.sampled < 600,
+        "sampled {} reads out of {}",
+        report.sampled,
+        READS
+    );
+    assert_eq!(report.mismatches.len(), 10);
+    for mismatch in report.mismatches.iter() {
+        match mismatch.kind {
+            MismatchKind::MissingInPrimary => {
+                assert_eq!(mismatch.primary_len, None);
+                assert_eq!(mismatch.secondary_len, Some(1));
+            }
+            MismatchKind::ValueDiffers => {
+                assert_eq!(mismatch.primary_len, Some(8));
+                assert_eq!(mismatch.secondary_len, Some(8));
+            }
+            MismatchKind::MissingInSecondary => panic!("unexpected mismatch {:?}", mismatch),
+        }
+    }
+
+    Ok(())
+}
+
+async fn test_shadow_identical_async() -> FdbResult<()> {
+    let (db, subspace) = setup("test-shadow-identical").await?;
+
+    let secondary_subspace = subspace.clone();
+    let reader = ShadowReader::new(
+        db,
+        move |key: Vec<u8>| {
+            let i: i64 = secondary_subspace.unpack(&key).expect("bad key");
+            let value = if i % 2 == 1 {
+                Some((i as usize).to_le_bytes().to_vec())
+            } else {
+                None
+            };
+            futures::future::ready(value)
+        },
+        ShadowOptions {
+            sample_rate: 1.0,
+            queue_size: KEYS,
+            ..ShadowOptions::default()
+        },
+    );
+
+    for i in 0..KEYS {
+        reader.get(&subspace.pack(&(i as i64)), false).await?;
+    }
+
+    let report = reader.finish();
+    assert_eq!(report.reads, KEYS as u64);
+    assert_eq!(report.sampled, KEYS as u64);
+    assert_eq!(report.compared + report.skipped, KEYS as u64);
+    assert_eq!(report.mismatch_count, 0);
+    assert!(report.mismatches.is_empty());
+
+    Ok(())
+}
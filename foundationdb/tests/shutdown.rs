@@ -0,0 +1,73 @@
+// Copyright 2018 foundationdb-rs developers, https://github.com/Clikengo/foundationdb-rs/graphs/contributors
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+use foundationdb::*;
+use futures::prelude::*;
+use futures::task::{noop_waker_ref, Context, Poll};
+use std::pin::Pin;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Barrier};
+use std::thread;
+
+mod common;
+
+const WATCHES: usize = 1000;
+const THREADS: usize = 8;
+
+// Stopping the network while futures are pending on other threads must not crash, whether their
+// callbacks run late or never. The CI also runs this test under the address sanitizer.
+#[test]
+fn test_stop_network_with_pending_futures() {
+    let network = unsafe { foundationdb::boot() };
+    let db = futures::executor::block_on(common::database()).expect("failed to get database");
+
+    // watches of keys that are not modified stay pending
+    let trx = db.create_trx().expect("failed to create transaction");
+    let mut watches = (0..WATCHES)
+        .map(|i| trx.watch(format!("test-shutdown-{}", i).as_bytes()))
+        .collect::<Vec<_>>()
+        .into_iter();
+    futures::executor::block_on(trx.commit()).expect("failed to commit");
+
+    let polled = Arc::new(Barrier::new(THREADS + 1));
+    let stopped = Arc::new(AtomicBool::new(false));
+    let threads = (0..THREADS)
+        .map(|_| {
+            let mut watches = watches.by_ref().take(WATCHES / THREADS).collect::<Vec<_>>();
+            let polled = polled.clone();
+            let stopped = stopped.clone();
+            thread::spawn(move || {
+                // the first poll sets the callbacks, the next ones race with the network stop
+                let mut cx = Context::from_waker(noop_waker_ref());
+                let mut first = true;
+                while !stopped.load(Ordering::SeqCst) {
+                    for watch in watches.iter_mut() {
+                        if let Poll::Ready(r) = Pin::new(watch).poll(&mut cx) {
+                            panic!("watch of an unmodified key resolved: {:?}", r);
+                        }
+                    }
+                    if first {
+                        first = false;
+                        polled.wait();
+                    }
+                }
+                // the futures are destroyed after the network is stopped
+                drop(watches);
+            })
+        })
+        .collect::<Vec<_>>();
+
+    polled.wait();
+    drop(network);
+    stopped.store(true, Ordering::SeqCst);
+    for thread in threads {
+        thread.join().expect("failed to join thread");
+    }
+    drop(db);
+
+    assert_eq!(foundationdb::future::live_futures(), 0);
+}
@@ -0,0 +1,149 @@
+// Copyright 2018 foundationdb-rs developers, https://github.com/Clikengo/foundationdb-rs/graphs/contributors
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+#[cfg(feature = "fdb-7_1")]
+use foundationdb::key::key_after;
+#[cfg(feature = "fdb-7_1")]
+use foundationdb::options::{ConflictRangeType, TransactionOption};
+use foundationdb::special_keys::*;
+#[cfg(feature = "fdb-7_1")]
+use foundationdb::tuple::Subspace;
+use foundationdb::FdbResult;
+
+mod common;
+
+#[test]
+fn test_special_keys() {
+    let _guard = unsafe { foundationdb::boot() };
+    futures::executor::block_on(test_status_json_async()).expect("failed to run");
+    #[cfg(feature = "fdb-7_1")]
+    futures::executor::block_on(test_connection_string_async()).expect("failed to run");
+    #[cfg(feature = "fdb-7_1")]
+    futures::executor::block_on(test_conflict_ranges_async()).expect("failed to run");
+    #[cfg(feature = "fdb-7_1")]
+    futures::executor::block_on(test_conflicting_keys_async()).expect("failed to run");
+    #[cfg(feature = "fdb-7_1")]
+    futures::executor::block_on(test_subspace_conflict_ranges_async()).expect("failed to run");
+}
+
+async fn test_status_json_async() -> FdbResult<()> {
+    let db = common::database().await?;
+    let trx = db.create_trx()?;
+
+    let status = trx
+        .get_special(SpecialKey::StatusJson)
+        .await?
+        .expect("missing status");
+    assert!(!status.is_empty());
+    assert_eq!(status.first(), Some(&b'{'));
+
+    Ok(())
+}
+
+#[cfg(feature = "fdb-7_1")]
+async fn test_connection_string_async() -> FdbResult<()> {
+    let db = common::database().await?;
+    let trx = db.create_trx()?;
+
+    let connection_string = trx
+        .get_special(SpecialKey::ConnectionString)
+        .await?
+        .expect("missing connection string");
+    // description:id@address:port,...
+    assert!(!connection_string.is_empty());
+    assert!(connection_string.contains(&b'@'));
+
+    Ok(())
+}
+
+#[cfg(feature = "fdb-7_1")]
+async fn test_conflict_ranges_async() -> FdbResult<()> {
+    let db = common::database().await?;
+    let trx = db.create_trx()?;
+
+    trx.get(b"test_special_keys", false).await?;
+    trx.set(b"test_special_keys_write", b"");
+
+    let reads = trx
+        .get_special_range(SpecialKeyRange::ReadConflictRanges)
+        .await?;
+    assert_eq!(
+        reads,
+        vec![
+            (b"test_special_keys".to_vec(), b"1".to_vec()),
+            (b"test_special_keys\x00".to_vec(), b"0".to_vec()),
+        ]
+    );
+    let writes = trx
+        .get_special_range(SpecialKeyRange::WriteConflictRanges)
+        .await?;
+    assert_eq!(
+        writes,
+        vec![
+            (b"test_special_keys_write".to_vec(), b"1".to_vec()),
+            (b"test_special_keys_write\x00".to_vec(), b"0".to_vec()),
+        ]
+    );
+
+    Ok(())
+}
+
+#[cfg(feature = "fdb-7_1")]
+async fn test_conflicting_keys_async() -> FdbResult<()> {
+    const KEY: &[u8] = b"test_conflicting_keys";
+    let db = common::database().await?;
+
+    let trx = db.create_trx()?;
+    trx.set_option(TransactionOption::ReportConflictingKeys)?;
+    trx.get(KEY, false).await?;
+    // a transaction without writes doesn't conflict
+    trx.set(b"test_conflicting_keys_write", b"");
+
+    let other = db.create_trx()?;
+    other.set(KEY, b"conflict");
+    other.commit().await?;
+
+    let err = trx.commit().await.expect_err("the commit should conflict");
+    assert_eq!(err.code(), 1020);
+    let ranges = err.conflicting_keys().await?;
+    assert!(!ranges.is_empty());
+    assert!(ranges
+        .iter()
+        .any(|(begin, end)| begin.as_slice() <= KEY && KEY < end.as_slice()));
+
+    Ok(())
+}
+
+#[cfg(feature = "fdb-7_1")]
+async fn test_subspace_conflict_ranges_async() -> FdbResult<()> {
+    let db = common::database().await?;
+    let trx = db.create_trx()?;
+    let subspace = Subspace::from("test_subspace_conflict_ranges");
+
+    trx.add_conflict_subspace(&subspace.subspace(&"read"), ConflictRangeType::Read)?;
+    trx.add_conflict_key(&subspace, &("write", 1), ConflictRangeType::Write)?;
+
+    let (begin, end) = subspace.subspace(&"read").range();
+    let reads = trx
+        .get_special_range(SpecialKeyRange::ReadConflictRanges)
+        .await?;
+    assert_eq!(reads, vec![(begin, b"1".to_vec()), (end, b"0".to_vec())]);
+
+    let key = subspace.pack(&("write", 1));
+    let writes = trx
+        .get_special_range(SpecialKeyRange::WriteConflictRanges)
+        .await?;
+    assert_eq!(
+        writes,
+        vec![
+            (key.clone(), b"1".to_vec()),
+            (key_after(&key), b"0".to_vec())
+        ]
+    );
+
+    Ok(())
+}
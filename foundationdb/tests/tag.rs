@@ -0,0 +1,124 @@
+// Copyright 2018 foundationdb-rs developers, https://github.com/Clikengo/foundationdb-rs/graphs/contributors
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+#![cfg(feature = "fdb-7_1")]
+
+use foundationdb::{FdbError, FdbResult, TransactOption};
+use futures::prelude::*;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+mod common;
+
+#[test]
+fn test_tag() {
+    let _guard = unsafe { foundationdb::boot() };
+    futures::executor::block_on(test_tag_validation_async()).expect("failed to run");
+    futures::executor::block_on(test_tag_commit_async()).expect("failed to run");
+    futures::executor::block_on(test_tag_retry_async()).expect("failed to run");
+}
+
+async fn test_tag_validation_async() -> FdbResult<()> {
+    let db = common::database().await?;
+    let mut trx = db.create_trx()?;
+
+    // tag_too_long
+    let err = trx.set_tag(&[b'a'; 17]).unwrap_err();
+    assert_eq!(err.code(), 2110);
+    trx.set_tag(&[b'a'; 16])?;
+
+    // invalid_option_value
+    let err = trx.set_auto_throttle_tag(b"\xff\xfe").unwrap_err();
+    assert_eq!(err.code(), 2006);
+
+    // too_many_tags, setting a tag again doesn't count
+    for tag in &[&b"b"[..], b"c", b"d", b"e", b"b"] {
+        trx.set_tag(tag)?;
+    }
+    let err = trx.set_auto_throttle_tag(b"f").unwrap_err();
+    assert_eq!(err.code(), 2109);
+    trx.set_auto_throttle_tag(b"e")?;
+
+    // reset clears the tags
+    trx.reset();
+    trx.set_auto_throttle_tag(b"f")?;
+
+    Ok(())
+}
+
+async fn test_tag_commit_async() -> FdbResult<()> {
+    const KEY: &[u8] = b"test-tag-key";
+
+    let db = common::database().await?;
+
+    let trx = db.create_trx()?;
+    trx.set_tag(b"test-tag")?;
+    trx.set_auto_throttle_tag(b"test-auto-tag")?;
+    trx.set(KEY, b"tagged");
+    trx.commit().await?;
+
+    let trx = db.create_trx()?;
+    trx.set_tag(b"test-tag")?;
+    assert_eq!(trx.get(KEY, false).await?.as_deref(), Some(&b"tagged"[..]));
+
+    Ok(())
+}
+
+async fn test_tag_retry_async() -> FdbResult<()> {
+    const KEY: &[u8] = b"test-tag-retry-key";
+
+    let db = common::database().await?;
+
+    // `on_error` clears the tags, each attempt can set 5 new ones
+    let mut trx = db.create_trx()?;
+    for attempt in 0..3 {
+        for i in 0..5 {
+            trx.set_tag(format!("retry-{}-{}", attempt, i).as_bytes())?;
+        }
+        trx = trx.on_error(FdbError::from_code(1020)).await?;
+    }
+
+    // as does the `on_error` of a failed commit
+    for attempt in 0..3 {
+        for i in 0..5 {
+            trx.set_tag(format!("commit-{}-{}", attempt, i).as_bytes())?;
+        }
+        trx.get(KEY, false).await?;
+        let other = db.create_trx()?;
+        other.set(KEY, b"other");
+        other.commit().await?;
+        trx.set(KEY, b"conflicting");
+        let err = trx.commit().await.unwrap_err();
+        assert_eq!(err.code(), 1020);
+        trx = err.on_error().await?;
+    }
+
+    // a retry loop setting a different tag on every attempt
+    let try_count = Arc::new(AtomicUsize::new(0));
+    db.transact_boxed(
+        try_count.clone(),
+        |trx, try_count| {
+            async move {
+                let attempt = try_count.fetch_add(1, Ordering::SeqCst);
+                trx.set_tag(format!("attempt-{}", attempt).as_bytes())?;
+                if attempt < 10 {
+                    return Err(FdbError::from_code(1020));
+                }
+                Ok(())
+            }
+            .boxed()
+        },
+        TransactOption {
+            remember_options: Some(false),
+            ..TransactOption::default()
+        },
+    )
+    .await?;
+    assert_eq!(try_count.load(Ordering::SeqCst), 11);
+
+    Ok(())
+}
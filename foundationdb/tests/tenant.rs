@@ -0,0 +1,57 @@
+// Copyright 2018 foundationdb-rs developers, https://github.com/Clikengo/foundationdb-rs/graphs/contributors
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+#![cfg(feature = "fdb-7_1")]
+
+use foundationdb::{FdbResult, TenantManagement};
+
+mod common;
+
+#[test]
+fn test_tenant() {
+    let _guard = unsafe { foundationdb::boot() };
+    futures::executor::block_on(test_tenant_isolation_async()).expect("failed to run");
+}
+
+async fn test_tenant_isolation_async() -> FdbResult<()> {
+    const TENANT: &[u8] = b"test-tenant";
+    const KEY: &[u8] = b"test-tenant-key";
+
+    let db = common::database().await?;
+
+    match TenantManagement::create_tenant(&db, TENANT).await {
+        // tenant_already_exists, left over by a previous run
+        Err(err) if err.code() == 2132 => {}
+        r => r?,
+    }
+    let tenants = TenantManagement::list_tenants(&db, b"", b"\xff", 1000).await?;
+    assert!(tenants.iter().any(|name| name == TENANT));
+
+    let tenant = db.open_tenant(TENANT)?;
+    let trx = tenant.create_trx()?;
+    trx.set(KEY, b"inside");
+    trx.commit().await?;
+
+    let trx = tenant.create_trx()?;
+    assert_eq!(
+        trx.get(KEY, false).await?.as_deref(),
+        Some(b"inside".as_ref())
+    );
+
+    let trx = db.create_trx()?;
+    assert!(trx.get(KEY, false).await?.is_none());
+
+    let trx = tenant.create_trx()?;
+    trx.clear(KEY);
+    trx.commit().await?;
+    TenantManagement::delete_tenant(&db, TENANT).await?;
+
+    let tenants = TenantManagement::list_tenants(&db, b"", b"\xff", 1000).await?;
+    assert!(!tenants.iter().any(|name| name == TENANT));
+
+    Ok(())
+}
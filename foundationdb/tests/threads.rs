@@ -0,0 +1,90 @@
+// Copyright 2018 foundationdb-rs developers, https://github.com/Clikengo/foundationdb-rs/graphs/contributors
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! Shares a transaction between threads, also run with the thread sanitizer in CI.
+
+use foundationdb::options::{MutationType, StreamingMode};
+use foundationdb::tuple::Subspace;
+use foundationdb::{FdbResult, RangeOption, Transaction};
+use futures::executor::block_on;
+use std::sync::Arc;
+use std::thread;
+
+mod common;
+
+const THREADS: usize = 8;
+const KEYS_PER_THREAD: usize = 200;
+
+#[test]
+fn test_threads() {
+    let _guard = unsafe { foundationdb::boot() };
+    block_on(test_shared_transaction_async()).expect("failed to run");
+}
+
+async fn test_shared_transaction_async() -> FdbResult<()> {
+    let db = common::database().await?;
+    let subspace = Subspace::all().subspace(&"test-threads");
+
+    let trx = db.create_trx()?;
+    trx.clear_subspace_range(&subspace);
+    trx.commit().await?;
+
+    let trx = Arc::new(db.create_trx()?);
+    let threads = (0..THREADS)
+        .map(|t| {
+            let trx = trx.clone();
+            let subspace = subspace.clone();
+            thread::spawn(move || -> FdbResult<()> {
+                for i in 0..KEYS_PER_THREAD {
+                    let key = subspace.pack(&(t as i64, i as i64));
+                    trx.set(&key, &[t as u8]);
+                    trx.atomic_op(
+                        &subspace.pack(&"counter"),
+                        &1i64.to_le_bytes(),
+                        MutationType::Add,
+                    );
+                    // reads see the writes of the thread, whatever the other threads do
+                    let value = block_on(trx.get(&key, false))?;
+                    assert_eq!(value.as_deref(), Some(&[t as u8][..]));
+                }
+                Ok(())
+            })
+        })
+        .collect::<Vec<_>>();
+    for thread in threads {
+        thread.join().expect("thread panicked")?;
+    }
+
+    let trx = Arc::try_unwrap(trx).expect("transaction still shared");
+    trx.commit().await?;
+
+    let trx = db.create_trx()?;
+    let count = count_keys(&trx, &subspace).await?;
+    assert_eq!(count, THREADS * KEYS_PER_THREAD + 1);
+    let counter = trx
+        .get(&subspace.pack(&"counter"), false)
+        .await?
+        .expect("missing counter");
+    let mut bytes = [0; 8];
+    bytes.copy_from_slice(&counter);
+    assert_eq!(
+        i64::from_le_bytes(bytes),
+        (THREADS * KEYS_PER_THREAD) as i64
+    );
+
+    Ok(())
+}
+
+async fn count_keys(trx: &Transaction, subspace: &Subspace) -> FdbResult<usize> {
+    let opt = RangeOption {
+        mode: StreamingMode::WantAll,
+        ..RangeOption::from(subspace.range())
+    };
+    let kvs = trx.get_range(&opt, 1, false).await?;
+    assert!(!kvs.more());
+    Ok(kvs.len())
+}
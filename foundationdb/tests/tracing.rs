@@ -0,0 +1,147 @@
+// Copyright 2018 foundationdb-rs developers, https://github.com/Clikengo/foundationdb-rs/graphs/contributors
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+#![cfg(feature = "tracing")]
+
+use std::io;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+
+use foundationdb::*;
+use futures::prelude::*;
+use tracing_subscriber::fmt::format::FmtSpan;
+
+mod common;
+
+/// Collects the output of the subscriber, to check the recorded spans
+#[derive(Clone, Default)]
+struct Output(Arc<Mutex<Vec<u8>>>);
+
+impl io::Write for Output {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.lock().unwrap().write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl Output {
+    fn lines(&self) -> Vec<String> {
+        let output = self.0.lock().unwrap();
+        String::from_utf8_lossy(&output)
+            .lines()
+            .map(String::from)
+            .collect()
+    }
+}
+
+#[test]
+fn test_tracing() {
+    let _guard = unsafe { foundationdb::boot() };
+    futures::executor::block_on(test_tracing_async()).expect("failed to run");
+}
+
+async fn test_tracing_async() -> FdbResult<()> {
+    const KEY: &[u8] = b"test-tracing";
+
+    let db = common::database().await?;
+    let trx = db.create_trx()?;
+    trx.clear_range(KEY, b"test-tracing\xff");
+    trx.commit().await?;
+
+    let output = Output::default();
+    let writer = output.clone();
+    let subscriber = tracing_subscriber::fmt()
+        .with_writer(move || writer.clone())
+        .with_max_level(tracing::Level::DEBUG)
+        .with_span_events(FmtSpan::CLOSE)
+        .with_ansi(false)
+        .with_target(false)
+        .without_time()
+        .finish();
+    let _default = tracing::subscriber::set_default(subscriber);
+
+    let tries = AtomicUsize::new(0);
+    db.transact_boxed(
+        (&db, &tries),
+        |trx, (db, tries)| {
+            async move {
+                let _ = trx.get(KEY, false).await?;
+                let _ = trx
+                    .get_range(
+                        &RangeOption::from((KEY, b"test-tracing\xff".as_ref())),
+                        1,
+                        false,
+                    )
+                    .await?;
+                if tries.fetch_add(1, Ordering::SeqCst) == 0 {
+                    // conflicts with the read of the first try
+                    let other = db.create_trx()?;
+                    other.set(KEY, b"conflict");
+                    other.commit().await?;
+                }
+                trx.set(KEY, b"value");
+                Ok::<_, FdbError>(())
+            }
+            .boxed()
+        },
+        TransactOption::default(),
+    )
+    .await?;
+    assert_eq!(tries.load(Ordering::SeqCst), 2);
+
+    let lines = output.lines();
+    // the lines of the closed spans named `name`, the innermost span of their context
+    let closed = |name: &str| -> Vec<&str> {
+        lines
+            .iter()
+            .filter_map(|line| {
+                let context = &line[..line.find(": close")?];
+                let span = context.rsplit("}:").next()?;
+                if span.trim_start_matches("DEBUG ").starts_with(name) {
+                    Some(span)
+                } else {
+                    None
+                }
+            })
+            .collect()
+    };
+
+    let transact = closed("fdb_transact{");
+    assert_eq!(transact.len(), 1, "{:#?}", lines);
+    assert!(transact[0].contains("attempts=2"), "{}", transact[0]);
+    assert!(!transact[0].contains("error_code"), "{}", transact[0]);
+    assert!(
+        lines
+            .iter()
+            .any(|line| line.contains("retrying") && line.contains("code=1020")),
+        "{:#?}",
+        lines
+    );
+
+    // the commits of the two attempts, and the conflicting one
+    let commits = closed("fdb_commit{");
+    assert_eq!(commits.len(), 3, "{:#?}", lines);
+    assert!(commits.iter().all(|line| line.contains("latency_us=")));
+    assert_eq!(
+        commits
+            .iter()
+            .filter(|line| line.contains("error_code=1020"))
+            .count(),
+        1
+    );
+
+    let ranges = closed("fdb_get_range{");
+    assert_eq!(ranges.len(), 2, "{:#?}", lines);
+    assert!(ranges
+        .iter()
+        .all(|line| line.contains("iteration=1") && line.contains("rows=")));
+
+    Ok(())
+}
@@ -0,0 +1,51 @@
+// Copyright 2018 foundationdb-rs developers, https://github.com/Clikengo/foundationdb-rs/graphs/contributors
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+use foundationdb::api;
+use foundationdb::FdbResult;
+
+mod common;
+
+#[test]
+fn test_version() {
+    let _guard = unsafe { foundationdb::boot() };
+    test_client_version();
+    futures::executor::block_on(test_server_protocol_version_async()).expect("failed to run");
+}
+
+fn test_client_version() {
+    let version = api::get_client_version();
+    assert!(!version.is_empty());
+
+    // e.g. `6.2.29,<commit hash>,<protocol>`
+    let release = version.split(',').next().expect("empty client version");
+    let numbers = release
+        .split('.')
+        .map(|number| number.parse::<u32>())
+        .collect::<Result<Vec<_>, _>>()
+        .unwrap_or_else(|err| panic!("invalid client version {:?}: {}", version, err));
+    assert_eq!(numbers.len(), 3, "invalid client version {:?}", version);
+
+    // both come from the loaded library, e.g. 620 for 6.2.29
+    let max_api_version = api::get_max_api_version() as u32;
+    assert_eq!(max_api_version / 10, numbers[0] * 10 + numbers[1]);
+}
+
+#[cfg(feature = "fdb-7_1")]
+async fn test_server_protocol_version_async() -> FdbResult<()> {
+    let db = common::database().await?;
+    let version = db.get_server_protocol_version().await?;
+    assert!(version.is_compatible_with(version));
+    // protocol versions all start with `fdb00b`
+    assert!(version.to_string().starts_with("fdb00b"), "{}", version);
+    Ok(())
+}
+
+#[cfg(not(feature = "fdb-7_1"))]
+async fn test_server_protocol_version_async() -> FdbResult<()> {
+    Ok(())
+}
@@ -5,7 +5,14 @@
 // http://opensource.org/licenses/MIT>, at your option. This file may not be
 // copied, modified, or distributed except according to those terms.
 
+use foundationdb::tuple::Subspace;
 use foundationdb::*;
+use futures::prelude::*;
+use std::collections::HashSet;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
 
 mod common;
 
@@ -14,6 +21,8 @@ fn test_watch() {
     let _guard = unsafe { foundationdb::boot() };
     futures::executor::block_on(test_watch_async()).expect("failed to run");
     futures::executor::block_on(test_watch_without_commit_async()).expect("failed to run");
+    futures::executor::block_on(test_watch_stream_async()).expect("failed to run");
+    futures::executor::block_on(test_poll_range_changes_async()).expect("failed to run");
 }
 
 async fn test_watch_async() -> FdbResult<()> {
@@ -53,3 +62,118 @@ async fn test_watch_without_commit_async() -> FdbResult<()> {
 
     Ok(())
 }
+
+async fn test_watch_stream_async() -> FdbResult<()> {
+    const KEY1: &[u8] = b"test-watch-stream-1";
+    const KEY2: &[u8] = b"test-watch-stream-2";
+
+    let db = common::database().await?;
+    let mut stream = db.watch_stream(vec![KEY1.to_vec(), KEY2.to_vec()]);
+
+    // keep updating both keys from another thread until both notifications arrived, as the
+    // watches are only set once the stream is polled
+    let stop = Arc::new(AtomicBool::new(false));
+    let writer_stop = stop.clone();
+    let writer = thread::spawn(move || {
+        futures::executor::block_on(async {
+            let db = common::database().await?;
+            while !writer_stop.load(Ordering::SeqCst) {
+                let trx = db.create_trx()?;
+                trx.set(KEY1, common::random_str(10).as_bytes());
+                trx.set(KEY2, common::random_str(10).as_bytes());
+                trx.commit().await?;
+                thread::sleep(Duration::from_millis(50));
+            }
+            FdbResult::Ok(())
+        })
+    });
+
+    let mut fired = HashSet::new();
+    while fired.len() < 2 {
+        let key = stream.next().await.expect("watch stream ended")?;
+        fired.insert(key);
+    }
+    stop.store(true, Ordering::SeqCst);
+    writer.join().expect("failed to join writer")?;
+
+    assert!(fired.contains(KEY1));
+    assert!(fired.contains(KEY2));
+
+    Ok(())
+}
+
+/// Writes `sets` and clears `clears` under `subspace` from another thread, after the first poll.
+fn write_later(
+    subspace: &Subspace,
+    sets: &[(&str, &str)],
+    clears: &[&str],
+) -> thread::JoinHandle<FdbResult<()>> {
+    let sets: Vec<(Vec<u8>, Vec<u8>)> = sets
+        .iter()
+        .map(|(key, value)| (subspace.pack(key), value.as_bytes().to_vec()))
+        .collect();
+    let clears: Vec<Vec<u8>> = clears.iter().map(|key| subspace.pack(key)).collect();
+    thread::spawn(move || {
+        thread::sleep(Duration::from_millis(500));
+        futures::executor::block_on(async {
+            let db = common::database().await?;
+            let trx = db.create_trx()?;
+            for (key, value) in &sets {
+                trx.set(key, value);
+            }
+            for key in &clears {
+                trx.clear(key);
+            }
+            trx.commit().await?;
+            Ok(())
+        })
+    })
+}
+
+async fn test_poll_range_changes_async() -> FdbResult<()> {
+    let db = common::database().await?;
+    let subspace = Subspace::from_bytes(format!("test-poll-{}", common::random_str(10)).as_bytes());
+
+    let trx = db.create_trx()?;
+    trx.set(&subspace.pack(&"a"), b"1");
+    trx.set(&subspace.pack(&"b"), b"1");
+    trx.commit().await?;
+
+    let trx = db.create_trx()?;
+    let initial_version = trx.get_read_version().await?;
+
+    let mut changes = db.poll_range_changes(subspace.clone(), Duration::from_millis(50));
+    let writer = write_later(&subspace, &[("a", "2"), ("c", "1")], &["b"]);
+    let change = changes.next().await.expect("poll stream ended")?;
+    writer.join().expect("failed to join writer")?;
+    assert!(change.version() > initial_version);
+    assert_eq!(
+        change,
+        RangeChange::Keys {
+            version: change.version(),
+            added: vec![subspace.pack(&"c")],
+            removed: vec![subspace.pack(&"b")],
+            modified: vec![subspace.pack(&"a")],
+        }
+    );
+    drop(changes);
+
+    // with too many keys to keep, the changed keys are unknown
+    let mut changes =
+        db.poll_range_changes_with_limit(subspace.clone(), Duration::from_millis(50), 1);
+    let writer = write_later(&subspace, &[("d", "1")], &[]);
+    let change = changes.next().await.expect("poll stream ended")?;
+    writer.join().expect("failed to join writer")?;
+    assert_eq!(
+        change,
+        RangeChange::Unknown {
+            version: change.version()
+        }
+    );
+
+    let trx = db.create_trx()?;
+    trx.clear_subspace_range(&subspace);
+    trx.commit().await?;
+
+    Ok(())
+}